@@ -46,7 +46,8 @@ def main():
 
     nodes: list[Node] = []
     with open(args.nodes_file, "r") as f:
-        headers = [i.strip() for i in f.readline().split(",")]
+        # strip unit labels, e.g. "x [mm]"
+        headers = [i.split("[")[0].strip() for i in f.readline().split(",")]
 
         for line in f.readlines():
             if not line.strip():
@@ -65,7 +66,8 @@ def main():
 
     elements: list[Element] = []
     with open(args.elements_file, "r") as f:
-        headers = [i.strip() for i in f.readline().split(",")]
+        # strip unit labels, e.g. "x [mm]"
+        headers = [i.split("[")[0].strip() for i in f.readline().split(",")]
 
         for line in f.readlines():
             if not line.strip():
@@ -183,9 +185,6 @@ def main():
     initial_plot.set_aspect("equal", adjustable="box")
     plt.show()
 
-    os.remove(args.nodes_file)
-    os.remove(args.elements_file)
-
 
 if __name__ == "__main__":
     main()
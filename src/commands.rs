@@ -0,0 +1,652 @@
+use std::path::Path;
+
+use crate::{
+    contact,
+    datatypes::{MeshOptions, ModelMetadata, Results},
+    error::MagnetiteError,
+    harmonic,
+    input::{self, ModelInput},
+    mesher::{self, MeshQuality},
+    model::Model,
+    msh, post_processor,
+    renderer::{self, Colormap, DeformationScale, PlotField, PlotOptions},
+    results_file::ResultsFile,
+    sweep, topology, transient,
+    units::Units,
+};
+use tracing::{info, warn};
+
+/// Where commands write their output files
+#[derive(Debug, Clone, Default)]
+pub struct OutputPaths {
+    /// The directory outputs are written to; the working directory if unset
+    pub dir: Option<String>,
+    /// Prefix added to the name of every output file
+    pub prefix: String,
+}
+
+impl OutputPaths {
+    /// Creates the output directory if it doesn't exist
+    pub fn create_dir(&self) -> Result<(), MagnetiteError> {
+        if let Some(dir) = &self.dir {
+            if let Err(err) = std::fs::create_dir_all(dir) {
+                return Err(MagnetiteError::Input(format!(
+                    "Failed to create output directory {dir}: {err}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Places an output file in the output directory, with the output prefix
+    /// added to its name and a load case name inserted before its extension,
+    /// e.g. `out/run1_nodes_dead.csv`
+    ///
+    /// # Arguments
+    /// * `output` - The output file
+    /// * `case` - The load case or combination name, if any
+    pub fn path(&self, output: &str, case: Option<&str>) -> String {
+        let output = case_path(output, case);
+        let path = Path::new(&output);
+        let path = match path.file_name() {
+            Some(name) if !self.prefix.is_empty() => {
+                path.with_file_name(format!("{}{}", self.prefix, name.to_string_lossy()))
+            }
+            _ => path.to_path_buf(),
+        };
+        match &self.dir {
+            Some(dir) => Path::new(dir).join(path),
+            None => path,
+        }
+        .to_string_lossy()
+        .into_owned()
+    }
+}
+
+/// Inserts a load case name into a filepath, before its extension, e.g.
+/// `nodes_dead.csv`
+fn case_path(output: &str, case: Option<&str>) -> String {
+    let Some(case) = case else {
+        return output.to_string();
+    };
+    match output.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}_{case}.{extension}"),
+        None => format!("{output}_{case}"),
+    }
+}
+
+/// How results are plotted and animated
+#[derive(Debug, Clone)]
+pub struct PlotSettings {
+    pub colormap: Colormap,
+    pub field: PlotField,
+    pub scale: DeformationScale,
+    /// The plot width in pixels
+    pub width: u32,
+    /// Plot output files; .svg or .png
+    pub plots: Vec<String>,
+    /// Animation output file; .gif, or .png/.svg for numbered frames
+    pub animation: Option<String>,
+    /// The number of animation frames
+    pub frames: usize,
+}
+
+/// The output files of a solve
+#[derive(Debug, Clone)]
+pub struct SolveOutputs {
+    /// Contact force output file, written when the model has contact
+    pub contact: String,
+    /// Results file output, which [`post`] can read
+    pub results: String,
+}
+
+/// Loads an input file, with Gmsh settings that override its mesh options
+///
+/// # Arguments
+/// * `input_file` - The path to the input file
+/// * `settings` - The Gmsh executable, Gmsh log and whether to keep
+///   intermediate files
+fn load_input(input_file: &str, settings: &MeshOptions) -> Result<ModelInput, MagnetiteError> {
+    let mut model_input = input::load(input_file)?;
+    let mesh_options = &mut model_input.metadata.mesh_options;
+    mesh_options.gmsh_path = settings.gmsh_path.clone();
+    mesh_options.gmsh_log = settings.gmsh_log.clone();
+    mesh_options.keep_intermediates = settings.keep_intermediates;
+    Ok(model_input)
+}
+
+/// Loads a model from an input file and its geometry files
+///
+/// # Arguments
+/// * `model_input` - The loaded input file; its analysis sections other than
+///   load cases, nonlinear and contact are left to the caller
+/// * `geometry_files` - The geometry files
+fn load_model(model_input: ModelInput, geometry_files: &[&str]) -> Result<Model, MagnetiteError> {
+    let geometry = mesher::load_geometry(geometry_files, &model_input.metadata)?;
+    Ok(Model::from_input(model_input, geometry))
+}
+
+/// Reports the size and element shapes of a mesh, warning about poorly
+/// shaped elements
+fn report_mesh_quality(quality: &MeshQuality) {
+    info!(
+        "mesh has {} nodes and {} elements",
+        quality.nodes, quality.elements
+    );
+    info!(
+        "smallest angle is {:.1} degrees, in element {}; the mean smallest angle is {:.1} degrees",
+        quality.min_angle, quality.worst_element, quality.mean_min_angle
+    );
+    info!("largest aspect ratio is {:.2}", quality.max_aspect_ratio);
+    if quality.poor_elements > 0 {
+        warn!(
+            "{} elements have an angle below {} degrees; refine the mesh or simplify the geometry around them",
+            quality.poor_elements,
+            mesher::POOR_ELEMENT_ANGLE
+        );
+    }
+    if quality.inverted_elements > 0 {
+        warn!(
+            "{} elements have zero or negative area",
+            quality.inverted_elements
+        );
+    }
+}
+
+/// Meshes the geometry, saves the mesh and reports its quality
+///
+/// # Arguments
+/// * `input_file` - The path to the input file with the mesh options
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+/// * `outputs` - Where to write outputs
+/// * `output` - The mesh output file
+pub fn mesh(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    outputs: &OutputPaths,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let mut model = load_model(load_input(input_file, settings)?, geometry_files)?;
+    let units = model.metadata.units;
+    let mesh = model.mesh()?;
+    let output = outputs.path(output, None);
+    msh::write_msh(mesh, units.as_ref(), &output)?;
+    info!("wrote mesh to {output}");
+    report_mesh_quality(&mesher::mesh_quality(mesh));
+    Ok(())
+}
+
+/// Checks the input file, geometry and boundary rules without solving
+///
+/// # Arguments
+/// * `input_file` - The path to the input file
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+pub fn check(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+) -> Result<(), MagnetiteError> {
+    let mut model = load_model(load_input(input_file, settings)?, geometry_files)?;
+    for (rule, nodes) in model.check()? {
+        info!("boundary '{rule}' applies to {nodes} nodes");
+    }
+    if let Some(mesh) = model.current_mesh() {
+        report_mesh_quality(&mesher::mesh_quality(mesh));
+    }
+    info!("{input_file} and its geometry are ready to solve");
+    Ok(())
+}
+
+/// Solves a model, and writes its results file, csv files, plots and
+/// animation. Models with load cases write each load case and combination
+/// separately.
+///
+/// # Arguments
+/// * `input_file` - The path to the input file
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+/// * `plot` - The plot settings
+/// * `outputs` - Where to write outputs
+/// * `files` - The contact force and results file outputs
+///
+/// # Returns
+/// A DesignCheck error if a factor of safety check fails, once every
+/// output is written
+pub fn solve(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    plot: &PlotSettings,
+    outputs: &OutputPaths,
+    files: &SolveOutputs,
+) -> Result<(), MagnetiteError> {
+    let input_document: serde_json::Value = input::read(input_file)?;
+    let mut model = load_model(load_input(input_file, settings)?, geometry_files)?;
+
+    // Large displacement results are plotted as they are, unless a scale is
+    // asked for
+    let plot = &PlotSettings {
+        scale: match plot.scale {
+            DeformationScale::Auto if model.nonlinear.is_some() => DeformationScale::Factor(1.0),
+            scale => scale,
+        },
+        ..plot.clone()
+    };
+
+    // Without load cases, solve the single scenario described by the
+    // boundary conditions
+    if model.load_cases.is_empty() {
+        model.solve()?;
+        let (Some(results), Some(mesh)) = (model.results(), model.current_mesh()) else {
+            return Err(MagnetiteError::Solver(
+                "The solve finished without results".to_owned(),
+            ));
+        };
+        if model.contact.is_some() {
+            contact::write_contact_forces(
+                model.contact_forces(),
+                &mesh.nodes,
+                model.metadata.units.as_ref(),
+                &outputs.path(&files.contact, None),
+            )?;
+        }
+        ResultsFile::new(&model, results, None, input_document)?
+            .write(&outputs.path(&files.results, None))?;
+        return write_outputs(results, &model.metadata, plot, outputs, None);
+    }
+
+    // Otherwise write each load case and combination separately, reporting
+    // the first failed design check once all outputs are written
+    model.solve_load_cases()?;
+    let mut design_check = Ok(());
+    for case in model.case_results() {
+        info!("writing outputs for '{}'", case.name);
+        ResultsFile::new(
+            &model,
+            &case.results,
+            Some(&case.name),
+            input_document.clone(),
+        )?
+        .write(&outputs.path(&files.results, Some(&case.name)))?;
+        match write_outputs(
+            &case.results,
+            &model.metadata,
+            plot,
+            outputs,
+            Some(&case.name),
+        ) {
+            Err(err @ MagnetiteError::DesignCheck(_)) if design_check.is_ok() => {
+                design_check = Err(err)
+            }
+            Err(MagnetiteError::DesignCheck(_)) => (),
+            other => other?,
+        }
+    }
+    design_check
+}
+
+/// Writes the csv files, plots and animation for a set of results, and
+/// reports their factor of safety
+///
+/// # Arguments
+/// * `results` - The results to write
+/// * `model_metadata` - The model metadata
+/// * `plot` - The plot settings
+/// * `outputs` - Where to write outputs
+/// * `case` - The load case or combination name, appended to output files
+///
+/// # Returns
+/// A DesignCheck error if the factor of safety check fails
+fn write_outputs(
+    results: &Results,
+    model_metadata: &ModelMetadata,
+    plot: &PlotSettings,
+    outputs: &OutputPaths,
+    case: Option<&str>,
+) -> Result<(), MagnetiteError> {
+    post_processor::csv_output(
+        results,
+        model_metadata.units.as_ref(),
+        &outputs.path("nodes.csv", case),
+        &outputs.path("elements.csv", case),
+    )?;
+    post_processor::plasticity_report(results);
+    let design_check = post_processor::safety_report(results, model_metadata);
+
+    write_plots(results, model_metadata.units, plot, outputs, case)?;
+
+    // Report a failed design check only once all outputs are written
+    design_check
+}
+
+/// Writes the plots and animation for a set of results
+///
+/// # Arguments
+/// * `results` - The results to plot
+/// * `units` - The units to label plots with, if declared
+/// * `plot` - The plot settings
+/// * `outputs` - Where to write outputs
+/// * `case` - The load case or combination name, appended to output files
+fn write_plots(
+    results: &Results,
+    units: Option<Units>,
+    plot: &PlotSettings,
+    outputs: &OutputPaths,
+    case: Option<&str>,
+) -> Result<(), MagnetiteError> {
+    let plot_options = PlotOptions {
+        colormap: plot.colormap.clone(),
+        field: plot.field,
+        displacement_scale: plot.scale.resolve(results),
+        width: plot.width,
+        units,
+    };
+
+    for output in &plot.plots {
+        renderer::render(results, &outputs.path(output, case), &plot_options)?;
+    }
+
+    if let Some(animation) = &plot.animation {
+        renderer::animate(
+            results,
+            &outputs.path(animation, case),
+            &plot_options,
+            plot.frames,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Plots or exports results again, from a results file or from the nodes
+/// and elements csv files of a solve
+///
+/// # Arguments
+/// * `files` - A results file, or the nodes and elements csv files
+/// * `plot` - The plot settings
+/// * `outputs` - Where to write outputs
+/// * `csv` - Whether to write the nodes and elements csv files again
+pub fn post(
+    files: &[&str],
+    plot: &PlotSettings,
+    outputs: &OutputPaths,
+    csv: bool,
+) -> Result<(), MagnetiteError> {
+    let (results, units, nonlinear) = match files {
+        [nodes, elements] => {
+            let (results, units) = post_processor::read_csv_output(nodes, elements)?;
+            (results, units, false)
+        }
+        [results_file] => {
+            let file = ResultsFile::read(results_file)?;
+            info!(
+                "read {} nodes and {} elements from {results_file}",
+                file.nodes.len(),
+                file.elements.len()
+            );
+            (
+                file.results(),
+                file.units,
+                file.solver.analysis == "nonlinear",
+            )
+        }
+        _ => {
+            return Err(MagnetiteError::Input(
+                "Post takes a results file, or a nodes and an elements csv file".to_owned(),
+            ))
+        }
+    };
+
+    if csv {
+        post_processor::csv_output(
+            &results,
+            units.as_ref(),
+            &outputs.path("nodes.csv", None),
+            &outputs.path("elements.csv", None),
+        )?;
+    }
+    post_processor::plasticity_report(&results);
+
+    // Large displacement results are plotted as they are, as in a solve
+    let plot = &PlotSettings {
+        scale: match plot.scale {
+            DeformationScale::Auto if nonlinear => DeformationScale::Factor(1.0),
+            scale => scale,
+        },
+        ..plot.clone()
+    };
+    write_plots(&results, units, plot, outputs, None)
+}
+
+/// Runs every case of an input file's sweep section and writes their
+/// summary table
+///
+/// # Arguments
+/// * `input_file` - The path to the input file with a sweep section
+/// * `geometry_files` - The geometry files, with `${parameter}` placeholders
+/// * `settings` - The Gmsh settings of every case
+/// * `jobs` - The number of cases to run at once
+/// * `outputs` - Where to write outputs
+/// * `summary` - The summary table output file
+///
+/// # Returns
+/// A Solver error if any case failed, or a DesignCheck error if any case is
+/// below the minimum factor of safety
+pub fn sweep(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    jobs: usize,
+    outputs: &OutputPaths,
+    summary: &str,
+) -> Result<(), MagnetiteError> {
+    let results = sweep::run(input_file, geometry_files, jobs, settings)?;
+    let summary = &outputs.path(summary, None);
+    sweep::write_summary(&results, summary)?;
+
+    let errors = results.iter().filter(|r| r.outcome.is_err()).count();
+    let failures = results
+        .iter()
+        .filter(|r| matches!(r.outcome, Ok(ref s) if s.passed == Some(false)))
+        .count();
+    if errors > 0 {
+        return Err(MagnetiteError::Solver(format!(
+            "{errors} of {} sweep cases failed; see {summary}",
+            results.len()
+        )));
+    }
+    if failures > 0 {
+        return Err(MagnetiteError::DesignCheck(format!(
+            "{failures} of {} sweep cases are below the minimum factor of safety; see {summary}",
+            results.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Optimises the material layout with the input file's topology section,
+/// and writes the element densities and the optimised boundary
+///
+/// # Arguments
+/// * `input_file` - The path to the input file with a topology section
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+/// * `outputs` - Where to write outputs
+/// * `density` - The element density output file
+/// * `boundary` - The optimised boundary output file, an svg
+/// * `threshold` - The density at the optimised boundary
+pub fn topology(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    outputs: &OutputPaths,
+    density: &str,
+    boundary: &str,
+    threshold: f64,
+) -> Result<(), MagnetiteError> {
+    if !(threshold > 0.0 && threshold < 1.0) {
+        return Err(MagnetiteError::Input(format!(
+            "Density threshold must be between 0 and 1, not {threshold}"
+        )));
+    }
+
+    let mut model_input = load_input(input_file, settings)?;
+    let Some(options) = model_input.topology.take() else {
+        return Err(MagnetiteError::Input(format!(
+            "{input_file} has no topology section"
+        )));
+    };
+    let mut model = load_model(model_input, geometry_files)?;
+    let result = model.optimize_topology(&options)?;
+    let units = model.metadata.units.as_ref();
+    info!(
+        "final compliance {:.4e} at volume fraction {:.3}",
+        result.compliance, result.volume_fraction
+    );
+
+    if let Some(mesh) = model.current_mesh() {
+        topology::write_densities(mesh, &result.densities, units, &outputs.path(density, None))?;
+        let loops = topology::extract_boundary(mesh, &result.densities, threshold);
+        topology::write_boundary_svg(&loops, units, &outputs.path(boundary, None))?;
+    }
+    Ok(())
+}
+
+/// Integrates the model through time with the input file's transient
+/// section, reports the peak displacement and stress, and writes their
+/// histories
+///
+/// # Arguments
+/// * `input_file` - The path to the input file with a transient section
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+/// * `outputs` - Where to write outputs
+/// * `displacements` - The nodal displacement history output file
+/// * `stresses` - The element stress history output file
+pub fn transient(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    outputs: &OutputPaths,
+    displacements: &str,
+    stresses: &str,
+) -> Result<(), MagnetiteError> {
+    let mut model_input = load_input(input_file, settings)?;
+    let Some(options) = model_input.transient.take() else {
+        return Err(MagnetiteError::Input(format!(
+            "{input_file} has no transient section"
+        )));
+    };
+    if model_input.nonlinear.is_some() {
+        warn!("transient solves are linear; ignoring the nonlinear and plasticity settings");
+    }
+    let mut model = load_model(model_input, geometry_files)?;
+    let frames = model.solve_transient(&options)?;
+    let units = model.metadata.units.as_ref();
+
+    let peak = |value: &dyn Fn(&Results) -> f64| {
+        frames
+            .iter()
+            .map(|frame| (frame.time, value(&frame.results)))
+            .fold(
+                (0.0, 0.0),
+                |best, next| if next.1 > best.1 { next } else { best },
+            )
+    };
+    let (displacement_time, peak_displacement) = peak(&|results| {
+        results
+            .nodes
+            .iter()
+            .map(|n| n.ux.hypot(n.uy))
+            .fold(0.0, f64::max)
+    });
+    let (stress_time, peak_stress) = peak(&|results| {
+        results
+            .elements
+            .iter()
+            .map(|e| e.von_mises)
+            .fold(0.0, f64::max)
+    });
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress = units.map_or(1.0, |u| u.stress.factor());
+    info!(
+        "peak displacement {:.4e}{} at t = {displacement_time:.4e} s",
+        peak_displacement / length,
+        units.map_or(String::new(), |u| format!(" {}", u.length.name()))
+    );
+    info!(
+        "peak von Mises stress {:.4e}{} at t = {stress_time:.4e} s",
+        peak_stress / stress,
+        units.map_or(String::new(), |u| format!(" {}", u.stress.name()))
+    );
+
+    transient::write_history(
+        &frames,
+        units,
+        &outputs.path(displacements, None),
+        &outputs.path(stresses, None),
+    )
+}
+
+/// Finds the frequency response with the input file's harmonic section, and
+/// writes the nodal displacements and probe responses
+///
+/// # Arguments
+/// * `input_file` - The path to the input file with a harmonic section
+/// * `geometry_files` - The geometry files
+/// * `settings` - The Gmsh settings
+/// * `outputs` - Where to write outputs
+/// * `displacements` - The complex nodal displacement output file
+/// * `probes` - The probe amplitude and phase output file, written when the
+///   harmonic section has probes
+pub fn harmonic(
+    input_file: &str,
+    geometry_files: &[&str],
+    settings: &MeshOptions,
+    outputs: &OutputPaths,
+    displacements: &str,
+    probes: &str,
+) -> Result<(), MagnetiteError> {
+    let mut model_input = load_input(input_file, settings)?;
+    let Some(options) = model_input.harmonic.take() else {
+        return Err(MagnetiteError::Input(format!(
+            "{input_file} has no harmonic section"
+        )));
+    };
+    if model_input.nonlinear.is_some() {
+        warn!("harmonic solves are linear; ignoring the nonlinear and plasticity settings");
+    }
+    let mut model = load_model(model_input, geometry_files)?;
+    let result = model.solve_harmonic(&options)?;
+    let units = model.metadata.units.as_ref();
+
+    harmonic::write_fields(&result, units, &outputs.path(displacements, None))?;
+    if !result.probes.is_empty() {
+        harmonic::write_probes(&result, units, &outputs.path(probes, None))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn places_outputs_with_prefix_and_case() {
+        let outputs = OutputPaths {
+            dir: Some("out".to_string()),
+            prefix: "run1_".to_string(),
+        };
+        assert_eq!(
+            outputs.path("plots/nodes.csv", Some("dead")),
+            Path::new("out/plots/run1_nodes_dead.csv").to_string_lossy()
+        );
+        assert_eq!(
+            OutputPaths::default().path("results", Some("wind")),
+            "results_wind"
+        );
+    }
+}
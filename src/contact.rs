@@ -0,0 +1,629 @@
+use std::collections::HashMap;
+
+use nalgebra::DMatrix;
+
+use crate::{
+    datatypes::{
+        ContactMethod, ContactOptions, Element, Material, ModelMetadata, Node, RigidLine, Vertex,
+    },
+    error::MagnetiteError,
+    solver::{self, FactoredStiffness, DOF},
+    units::{label, Units},
+};
+use tracing::info;
+
+/// A node that may touch a boundary segment of another body, or a rigid
+/// line. Its gap is linear in the displacements: `gap + coefficients . u`.
+#[derive(Debug, Clone)]
+struct ContactPair {
+    /// The node that may touch the target
+    node: usize,
+    /// What the node may touch, e.g. `body 1` or a rigid line's name
+    target: String,
+    /// The undeformed gap; negative where the node overlaps the target
+    gap: f64,
+    /// The change in gap with each degree of freedom
+    coefficients: Vec<(usize, f64)>,
+    /// The contact normal, pointing from the target towards the node
+    normal: Vertex,
+    /// The Lagrange multiplier estimate, for the augmented Lagrangian method
+    multiplier: f64,
+}
+
+/// The contact force on a node at the end of a contact solve
+#[derive(Debug, Clone)]
+pub struct ContactForce {
+    pub node: usize,
+    pub target: String,
+    /// The gap after the solve; negative where the node overlaps the target
+    pub gap: f64,
+    /// The force pressing the node away from the target
+    pub force: f64,
+    /// The direction of the force
+    pub normal: Vertex,
+}
+
+/// A straight segment on the boundary of a body
+struct Segment {
+    start: usize,
+    end: usize,
+    body: usize,
+}
+
+/// Labels each node with the body it belongs to; nodes are in the same body
+/// if elements connect them
+///
+/// # Arguments
+/// * `nodes` - The mesh nodes
+/// * `elements` - The mesh elements
+///
+/// # Returns
+/// The body of each node, numbered from zero, and the number of bodies
+pub fn find_bodies(nodes: &[Node], elements: &[Element]) -> (Vec<usize>, usize) {
+    let mut parent: Vec<usize> = (0..nodes.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for element in elements {
+        let [a, b, c] = element.nodes;
+        for (x, y) in [(a, b), (b, c)] {
+            let (rx, ry) = (root(&mut parent, x), root(&mut parent, y));
+            parent[rx] = ry;
+        }
+    }
+
+    let mut numbers: HashMap<usize, usize> = HashMap::new();
+    let bodies = (0..nodes.len())
+        .map(|i| {
+            let r = root(&mut parent, i);
+            let next = numbers.len();
+            *numbers.entry(r).or_insert(next)
+        })
+        .collect();
+    (bodies, numbers.len())
+}
+
+/// Finds the boundary segments of a mesh, ordered so that the body is on
+/// their left
+fn boundary_segments(elements: &[Element], bodies: &[usize]) -> Vec<Segment> {
+    let mut edges: HashMap<(usize, usize), usize> = HashMap::new();
+    for element in elements {
+        let [a, b, c] = element.nodes;
+        for (start, end) in [(a, b), (b, c), (c, a)] {
+            *edges.entry((start.min(end), start.max(end))).or_insert(0) += 1;
+        }
+    }
+
+    // Elements are counter-clockwise, so their edges have the body on the
+    // left
+    let mut segments = Vec::new();
+    for element in elements {
+        let [a, b, c] = element.nodes;
+        for (start, end) in [(a, b), (b, c), (c, a)] {
+            if edges[&(start.min(end), start.max(end))] == 1 {
+                segments.push(Segment {
+                    start,
+                    end,
+                    body: bodies[start],
+                });
+            }
+        }
+    }
+    segments
+}
+
+/// Projects a point onto a segment
+///
+/// # Returns
+/// The position along the segment, clamped to between 0 and 1, the
+/// outwards unit normal on the right of the segment, and the signed distance
+/// along the normal
+fn project(point: &Vertex, start: &Vertex, end: &Vertex) -> Option<(f64, Vertex, f64, f64)> {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return None;
+    }
+    let along = ((point.x - start.x) * dx + (point.y - start.y) * dy) / (length * length);
+    let along = along.clamp(0.0, 1.0);
+    let closest = Vertex {
+        x: start.x + along * dx,
+        y: start.y + along * dy,
+    };
+    let normal = Vertex {
+        x: dy / length,
+        y: -dx / length,
+    };
+    let gap = (point.x - closest.x) * normal.x + (point.y - closest.y) * normal.y;
+    let distance = (point.x - closest.x).hypot(point.y - closest.y);
+    Some((along, normal, gap, distance))
+}
+
+/// Finds the nodes that may come into contact, in the undeformed geometry
+///
+/// # Arguments
+/// * `nodes` - The mesh nodes
+/// * `elements` - The mesh elements
+/// * `options` - The contact settings
+///
+/// # Returns
+/// The possible ContactPairs
+fn find_pairs(nodes: &[Node], elements: &[Element], options: &ContactOptions) -> Vec<ContactPair> {
+    let (bodies, body_count) = find_bodies(nodes, elements);
+    let segments = boundary_segments(elements, &bodies);
+    let mut on_boundary = vec![false; nodes.len()];
+    for segment in &segments {
+        on_boundary[segment.start] = true;
+    }
+
+    let mut pairs = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if !on_boundary[i] {
+            continue;
+        }
+
+        // The nearest boundary segment of another body
+        if body_count > 1 {
+            let nearest = segments
+                .iter()
+                .filter(|s| s.body != bodies[i])
+                .filter_map(|s| {
+                    let (along, normal, gap, distance) =
+                        project(&node.vertex, &nodes[s.start].vertex, &nodes[s.end].vertex)?;
+                    Some((s, along, normal, gap, distance))
+                })
+                .min_by(|a, b| a.4.total_cmp(&b.4));
+            if let Some((segment, along, normal, gap, distance)) = nearest {
+                if distance <= options.search_distance {
+                    let mut coefficients = Vec::new();
+                    for (n, weight) in [
+                        (i, 1.0),
+                        (segment.start, along - 1.0),
+                        (segment.end, -along),
+                    ] {
+                        coefficients.push((DOF * n, weight * normal.x));
+                        coefficients.push((DOF * n + 1, weight * normal.y));
+                    }
+                    pairs.push(ContactPair {
+                        node: i,
+                        target: format!("body {}", segment.body),
+                        gap,
+                        coefficients,
+                        normal,
+                        multiplier: 0.0,
+                    });
+                }
+            }
+        }
+
+        // Rigid lines keep parts on their left, so the normal is flipped
+        for obstacle in &options.obstacles {
+            let RigidLine { name, start, end } = obstacle;
+            let Some((_, normal, gap, distance)) = project(&node.vertex, end, start) else {
+                continue;
+            };
+            if distance <= options.search_distance {
+                pairs.push(ContactPair {
+                    node: i,
+                    target: name.clone(),
+                    gap,
+                    coefficients: vec![(DOF * i, normal.x), (DOF * i + 1, normal.y)],
+                    normal,
+                    multiplier: 0.0,
+                });
+            }
+        }
+    }
+    pairs
+}
+
+/// Solves a linear-elastic model with frictionless contact between its
+/// bodies and against rigid lines. Contacts are found in the undeformed
+/// geometry, so displacements must be small. An active set iteration wraps
+/// the linear solve: contacts that pull apart are released and contacts
+/// that overlap are engaged, until the set stops changing.
+///
+/// # Arguments
+/// * `nodes` - A mutable reference to the vector of nodes
+/// * `elements` - A mutable reference to the vector of elements
+/// * `materials` - The material of each element
+/// * `model_metadata` - The model metadata
+/// * `options` - The contact settings
+///
+/// # Returns
+/// The contact force on each node in contact
+pub fn run(
+    nodes: &mut [Node],
+    elements: &mut [Element],
+    materials: &[Material],
+    model_metadata: &ModelMetadata,
+    options: &ContactOptions,
+) -> Result<Vec<ContactForce>, MagnetiteError> {
+    let mut pairs = find_pairs(nodes, elements, options);
+    // Scaled to the stiffest material, so no body sinks into another
+    let youngs_modulus = materials
+        .iter()
+        .map(|m| m.youngs_modulus)
+        .fold(model_metadata.material.youngs_modulus, f64::max);
+    let penalty = options.penalty_factor * youngs_modulus * model_metadata.part_thickness;
+    info!(
+        "found {} possible contacts within {} of each other",
+        pairs.len(),
+        options.search_distance
+    );
+
+    let stiffness = solver::assemble_stiffness(nodes, elements, materials, model_metadata);
+    let dof_count = DOF * nodes.len();
+
+    // Contacts start engaged where they touch or overlap
+    let mut active: Vec<bool> = pairs.iter().map(|p| p.gap <= 0.0).collect();
+    let mut displacements = vec![0.0; dof_count];
+    let mut solved: Vec<Node>;
+
+    let mut iteration = 0;
+    loop {
+        iteration += 1;
+
+        // Engaged contacts add a penalty spring along their normal
+        let mut contact_stiffness: DMatrix<f64> = stiffness.clone();
+        solved = nodes.to_vec();
+        for (pair, _) in std::iter::zip(&pairs, &active).filter(|(_, active)| **active) {
+            for (row, a) in &pair.coefficients {
+                for (col, b) in &pair.coefficients {
+                    contact_stiffness[(*row, *col)] += penalty * a * b;
+                }
+                let preload = (pair.multiplier - penalty * pair.gap) * a;
+                let node = &mut solved[row / DOF];
+                let force = if row % DOF == 0 {
+                    &mut node.fx
+                } else {
+                    &mut node.fy
+                };
+                if let Some(force) = force {
+                    *force += preload;
+                }
+            }
+        }
+
+        FactoredStiffness::new(&contact_stiffness, &solved)?.solve(&mut solved)?;
+        for (i, node) in solved.iter().enumerate() {
+            let (Some(ux), Some(uy)) = (node.ux, node.uy) else {
+                return Err(MagnetiteError::Solver(format!(
+                    "Node {i} has no displacement after the contact solve"
+                )));
+            };
+            displacements[DOF * i] = ux;
+            displacements[DOF * i + 1] = uy;
+        }
+
+        // A contact is engaged while it would carry a compressive force
+        let gaps: Vec<f64> = pairs
+            .iter()
+            .map(|p| {
+                p.gap
+                    + p.coefficients
+                        .iter()
+                        .map(|(dof, c)| c * displacements[*dof])
+                        .sum::<f64>()
+            })
+            .collect();
+        let next_active: Vec<bool> = std::iter::zip(&pairs, &gaps)
+            .map(|(p, gap)| p.multiplier - penalty * gap > 0.0)
+            .collect();
+        let overlap = std::iter::zip(&gaps, &next_active)
+            .filter(|(_, active)| **active)
+            .map(|(gap, _)| -gap)
+            .fold(0.0, f64::max);
+        let settled = next_active == active;
+        info!(
+            "contact iteration {iteration}: {} of {} contacts engaged, largest overlap {overlap:.3e}",
+            next_active.iter().filter(|a| **a).count(),
+            pairs.len()
+        );
+
+        let converged = settled
+            && match options.method {
+                ContactMethod::Penalty => true,
+                ContactMethod::AugmentedLagrangian => overlap <= options.tolerance,
+            };
+        if converged {
+            for (pair, gap) in std::iter::zip(&mut pairs, &gaps) {
+                pair.gap = *gap;
+            }
+            break;
+        }
+        if iteration == options.max_iterations {
+            return Err(MagnetiteError::Solver(format!(
+                "Contact did not settle in {} iterations; try a larger penalty factor or check for unconstrained bodies",
+                options.max_iterations
+            )));
+        }
+
+        if settled {
+            // Augmented Lagrangian update; carry the penalty force over
+            for (pair, gap) in std::iter::zip(&mut pairs, &gaps) {
+                pair.multiplier = (pair.multiplier - penalty * gap).max(0.0);
+            }
+        }
+        active = next_active;
+    }
+
+    // The solve saw contact forces as applied loads; report the applied
+    // loads, with reactions that exclude the contact forces
+    let contact_forces: Vec<ContactForce> = std::iter::zip(&pairs, &active)
+        .filter(|(_, active)| **active)
+        .map(|(pair, _)| ContactForce {
+            node: pair.node,
+            target: pair.target.clone(),
+            gap: pair.gap,
+            force: pair.multiplier - penalty * pair.gap,
+            normal: pair.normal.clone(),
+        })
+        .collect();
+    let mut contact_load = vec![0.0; dof_count];
+    for (pair, _) in std::iter::zip(&pairs, &active).filter(|(_, active)| **active) {
+        let force = pair.multiplier - penalty * pair.gap;
+        for (dof, c) in &pair.coefficients {
+            contact_load[*dof] += force * c;
+        }
+    }
+    for (i, (node, original)) in std::iter::zip(solved.iter_mut(), nodes.iter()).enumerate() {
+        for (d, (force, applied, prescribed)) in [
+            (&mut node.fx, original.fx, original.ux),
+            (&mut node.fy, original.fy, original.uy),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let dof = DOF * i + d;
+            *force = match prescribed {
+                Some(_) => Some(
+                    (0..dof_count)
+                        .map(|col| stiffness[(dof, col)] * displacements[col])
+                        .sum::<f64>()
+                        - contact_load[dof],
+                ),
+                None => applied,
+            };
+        }
+    }
+    nodes.clone_from_slice(&solved);
+
+    solver::compute_stress(elements, nodes, materials);
+
+    let total: f64 = contact_forces.iter().map(|c| c.force).sum();
+    info!(
+        "contact settled after {iteration} iterations with {} nodes in contact, carrying {total:.4e} in total",
+        contact_forces.len()
+    );
+
+    Ok(contact_forces)
+}
+
+/// Writes the contact force on each node in contact to a csv file
+///
+/// # Arguments
+/// * `contact_forces` - The contact forces
+/// * `nodes` - The mesh nodes
+/// * `units` - The units to write results in, if declared
+/// * `output` - The filepath of the csv file
+pub fn write_contact_forces(
+    contact_forces: &[ContactForce],
+    nodes: &[Node],
+    units: Option<&Units>,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let force = units.map_or(1.0, |u| u.force.factor());
+    let length_unit = units.map(|u| u.length.name());
+    let force_unit = units.map(|u| u.force.name());
+
+    let mut table = format!(
+        "node,target,{},{},{},{},{},{}\n",
+        label("x", length_unit),
+        label("y", length_unit),
+        label("gap", length_unit),
+        label("force", force_unit),
+        label("fx", force_unit),
+        label("fy", force_unit)
+    );
+    for contact in contact_forces {
+        let vertex = &nodes[contact.node].vertex;
+        table += &format!(
+            "{},{},{},{},{},{},{},{}\n",
+            contact.node,
+            contact.target,
+            vertex.x / length,
+            vertex.y / length,
+            contact.gap / length,
+            contact.force / force,
+            contact.force * contact.normal.x / force,
+            contact.force * contact.normal.y / force
+        );
+    }
+
+    if let Err(err) = std::fs::write(output, table) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    info!("wrote contact forces to {output}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{Material, MeshOptions};
+
+    /// Adds a unit-square grid of `width` by `height` cells, with its
+    /// bottom-left corner at `(x, y)`
+    fn add_block(
+        nodes: &mut Vec<Node>,
+        elements: &mut Vec<Element>,
+        x: f64,
+        y: f64,
+        width: usize,
+        height: usize,
+    ) {
+        let first = nodes.len();
+        for j in 0..=height {
+            for i in 0..=width {
+                nodes.push(Node {
+                    vertex: Vertex {
+                        x: x + i as f64,
+                        y: y + j as f64,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                });
+            }
+        }
+        let index = |i: usize, j: usize| first + j * (width + 1) + i;
+        for j in 0..height {
+            for i in 0..width {
+                for nodes in [
+                    [index(i, j), index(i + 1, j), index(i + 1, j + 1)],
+                    [index(i, j), index(i + 1, j + 1), index(i, j + 1)],
+                ] {
+                    elements.push(Element {
+                        nodes,
+                        stress: None,
+                        stress_tensor: None,
+                        plastic_strain: None,
+                        physical_tags: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Loads the top edge of the nodes at `top` downwards with `load` in
+    /// total, and stops the top-left node moving sideways
+    fn press(nodes: &mut [Node], range: std::ops::Range<usize>, top: f64, load: f64) {
+        let on_top: Vec<usize> = range.filter(|i| nodes[*i].vertex.y == top).collect();
+        let spacing = load / (on_top.len() - 1) as f64;
+        for (k, i) in on_top.iter().enumerate() {
+            let end = k == 0 || k == on_top.len() - 1;
+            nodes[*i].fy = Some(if end { -spacing / 2.0 } else { -spacing });
+        }
+        nodes[on_top[0]].ux = Some(0.0);
+        nodes[on_top[0]].fx = None;
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.3,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        }
+    }
+
+    fn options(method: ContactMethod) -> ContactOptions {
+        ContactOptions {
+            method,
+            penalty_factor: 100.0,
+            search_distance: 0.5,
+            tolerance: 1e-9,
+            max_iterations: 50,
+            obstacles: vec![RigidLine {
+                name: "floor".to_string(),
+                start: Vertex { x: -10.0, y: 0.0 },
+                end: Vertex { x: 10.0, y: 0.0 },
+            }],
+        }
+    }
+
+    #[test]
+    fn block_rests_on_a_rigid_floor() {
+        let (mut nodes, mut elements) = (Vec::new(), Vec::new());
+        add_block(&mut nodes, &mut elements, 0.0, 0.0, 3, 2);
+        press(&mut nodes, 0..12, 2.0, 6.0);
+
+        let materials = vec![metadata().material; elements.len()];
+        let contacts = run(
+            &mut nodes,
+            &mut elements,
+            &materials,
+            &metadata(),
+            &options(ContactMethod::AugmentedLagrangian),
+        )
+        .unwrap();
+
+        // The floor carries the whole load, in uniform compression
+        assert_eq!(contacts.len(), 4);
+        assert!(contacts
+            .iter()
+            .all(|c| c.target == "floor" && c.force > 0.0));
+        let total: f64 = contacts.iter().map(|c| c.force).sum();
+        assert!((total - 6.0).abs() < 1e-9, "{total}");
+        assert!(contacts.iter().all(|c| c.gap.abs() <= 1e-9));
+
+        // The top shortens by stress / E times the height
+        let expected = -(6.0 / 3.0) / 1000.0 * 2.0;
+        let uy = nodes[11].uy.unwrap();
+        assert!((uy - expected).abs() < 1e-8, "{uy}");
+    }
+
+    #[test]
+    fn stacked_bodies_pass_the_load_through_contact() {
+        let (mut nodes, mut elements) = (Vec::new(), Vec::new());
+        add_block(&mut nodes, &mut elements, 0.0, 0.0, 4, 1);
+        add_block(&mut nodes, &mut elements, 1.0, 1.0, 2, 1);
+        nodes[0].ux = Some(0.0);
+        nodes[0].fx = None;
+        press(&mut nodes, 10..16, 2.0, 4.0);
+
+        let (bodies, count) = find_bodies(&nodes, &elements);
+        assert_eq!(count, 2);
+        assert_eq!(bodies[9], 0);
+        assert_eq!(bodies[10], 1);
+
+        let materials = vec![metadata().material; elements.len()];
+        let contacts = run(
+            &mut nodes,
+            &mut elements,
+            &materials,
+            &metadata(),
+            &options(ContactMethod::Penalty),
+        )
+        .unwrap();
+
+        // Each interface carries the load, whichever body's nodes it acts on
+        let between = |target: &str| -> f64 {
+            contacts
+                .iter()
+                .filter(|c| c.target == target)
+                .map(|c| c.force)
+                .sum()
+        };
+        assert!((between("floor") - 4.0).abs() < 1e-9);
+        assert!((between("body 0") + between("body 1") - 4.0).abs() < 1e-9);
+
+        // The upper body sinks by more than the lower one
+        assert!(nodes[14].uy.unwrap() < nodes[7].uy.unwrap());
+        assert!(nodes[7].uy.unwrap() < 0.0);
+    }
+}
@@ -1,10 +1,14 @@
-#[derive(Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::{expression::Expression, units::Units};
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Vertex {
     pub x: f64,
     pub y: f64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     pub vertex: Vertex,
     pub ux: Option<f64>,
@@ -13,40 +17,398 @@ pub struct Node {
     pub fy: Option<f64>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Element {
     pub nodes: [usize; 3],
     pub stress: Option<f64>,
+    pub stress_tensor: Option<[f64; 3]>,
+    /// The equivalent plastic strain, when the solve models plasticity
+    pub plastic_strain: Option<f64>,
+    /// Tags of the surface physical groups the element belongs to, as
+    /// given by the mesh file
+    pub physical_tags: Vec<usize>,
 }
 
-#[derive(Debug)]
-pub struct ModelMetadata {
+#[derive(Debug, Clone)]
+pub struct Material {
     pub youngs_modulus: f64,
     pub poisson_ratio: f64,
-    pub part_thickness: f64,
+    pub density: Option<f64>,
+    pub thermal_expansion: Option<f64>,
+    pub yield_strength: Option<f64>,
+    pub ultimate_strength: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MeshOptions {
     pub characteristic_length_min: f32,
     pub characteristic_length_max: f32,
+    /// The Gmsh executable; `gmsh` on the PATH if unset
+    pub gmsh_path: Option<String>,
+    /// A file to save Gmsh's full output to
+    pub gmsh_log: Option<String>,
+    /// Keep the intermediate .geo and .msh files, even if meshing succeeds
+    pub keep_intermediates: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FailureCriterion {
+    #[default]
+    VonMises,
+    Tresca,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SafetyOptions {
+    pub criterion: FailureCriterion,
+    pub minimum_factor: Option<f64>,
+}
+
+/// The material of a named body, which replaces the model's material in
+/// the body's elements
+#[derive(Debug, Clone)]
+pub struct BodyMaterial {
+    /// The name of the body's surface physical group
+    pub body: String,
+    pub material: Material,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModelMetadata {
+    pub material: Material,
+    /// Materials of bodies made of something other than `material`
+    pub body_materials: Vec<BodyMaterial>,
+    pub part_thickness: f64,
+    pub mesh_options: MeshOptions,
+    pub units: Option<Units>,
+    pub safety: SafetyOptions,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BoundaryRegion {
     pub x_min: f64,
     pub x_max: f64,
     pub y_min: f64,
     pub y_max: f64,
+    pub physical_group: Option<PhysicalGroupRef>,
+}
+
+/// A reference to a physical group of the mesh, by name or by tag
+#[derive(Debug, Clone, PartialEq)]
+pub enum PhysicalGroupRef {
+    Name(String),
+    Tag(usize),
+}
+
+impl PhysicalGroupRef {
+    /// Checks whether a physical group is the referenced one
+    pub fn matches(&self, group: &PhysicalGroup) -> bool {
+        match self {
+            PhysicalGroupRef::Name(name) => &group.name == name,
+            PhysicalGroupRef::Tag(tag) => group.tag == *tag,
+        }
+    }
+}
+
+impl std::fmt::Display for PhysicalGroupRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PhysicalGroupRef::Name(name) => write!(f, "'{name}'"),
+            PhysicalGroupRef::Tag(tag) => write!(f, "tag {tag}"),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BoundaryTarget {
     pub ux: Option<f64>,
     pub uy: Option<f64>,
     pub fx: Option<f64>,
     pub fy: Option<f64>,
+    /// Scales the targets over time in a transient solve; static solves
+    /// ignore it
+    pub time_function: Option<TimeFunction>,
 }
 
-#[derive(Debug)]
+/// A factor that varies with time, in seconds
+#[derive(Debug, Clone)]
+pub enum TimeFunction {
+    /// Points of (time, factor), in increasing time. The factor is
+    /// interpolated linearly between points and held beyond the ends.
+    Table(Vec<(f64, f64)>),
+    Expression(Expression),
+}
+
+impl TimeFunction {
+    /// Evaluates the factor at a time
+    pub fn value(&self, time: f64) -> f64 {
+        match self {
+            TimeFunction::Table(points) => {
+                let index = points.partition_point(|&(t, _)| t <= time);
+                match (index.checked_sub(1).map(|i| points[i]), points.get(index)) {
+                    (Some((t0, v0)), Some(&(t1, v1))) => v0 + (v1 - v0) * (time - t0) / (t1 - t0),
+                    (Some((_, v)), None) | (None, Some(&(_, v))) => v,
+                    (None, None) => 0.0,
+                }
+            }
+            TimeFunction::Expression(expression) => expression.evaluate(time),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct BoundaryRule {
     pub name: String,
     pub region: BoundaryRegion,
     pub target: BoundaryTarget,
 }
+
+/// A region of the design domain with a fixed density
+#[derive(Debug, Clone)]
+pub struct NonDesignRegion {
+    pub name: String,
+    pub region: BoundaryRegion,
+    /// Elements in the region are solid if true, or void if false
+    pub solid: bool,
+}
+
+/// Settings for SIMP topology optimisation
+#[derive(Debug, Clone)]
+pub struct TopologyOptions {
+    /// The fraction of the design domain's area to keep
+    pub volume_fraction: f64,
+    /// The SIMP penalty exponent on element densities
+    pub penalty: f64,
+    /// The radius of the sensitivity filter
+    pub filter_radius: f64,
+    pub minimum_density: f64,
+    /// The largest change in an element's density in one iteration
+    pub move_limit: f64,
+    pub max_iterations: usize,
+    /// Optimisation stops once no density changes by more than this
+    pub tolerance: f64,
+    pub non_design: Vec<NonDesignRegion>,
+}
+
+/// How the yield surface of an elastic-plastic material moves as it yields
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Hardening {
+    /// The yield surface grows with the equivalent plastic strain
+    #[default]
+    Isotropic,
+    /// The yield surface translates with the plastic strain
+    Kinematic,
+}
+
+/// Settings for a von Mises elastic-plastic material, yielding at the
+/// material's yield strength
+#[derive(Debug, Clone)]
+pub struct PlasticityOptions {
+    pub hardening: Hardening,
+    /// The slope of the stress against plastic strain curve in uniaxial
+    /// tension; zero for perfect plasticity
+    pub hardening_modulus: f64,
+}
+
+/// Settings for a nonlinear solve
+#[derive(Debug, Clone)]
+pub struct NonlinearOptions {
+    /// The number of equal load increments
+    pub increments: usize,
+    /// The most Newton-Raphson iterations per increment
+    pub max_iterations: usize,
+    /// Increments converge once the out-of-balance force is this fraction
+    /// of the applied and reaction forces
+    pub tolerance: f64,
+    /// Whether to account for large displacements and rotations
+    pub geometric: bool,
+    /// The elastic-plastic material model, if the material can yield
+    pub plasticity: Option<PlasticityOptions>,
+}
+
+/// The time integration scheme of a transient solve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Integrator {
+    /// The Newmark-beta method; `beta = 0.25, gamma = 0.5` is the
+    /// unconditionally stable average acceleration method
+    Newmark { beta: f64, gamma: f64 },
+    /// The Hilber-Hughes-Taylor method, which damps high frequencies for
+    /// `alpha` between -1/3 and 0
+    Hht { alpha: f64 },
+}
+
+/// Settings for a transient dynamic solve
+#[derive(Debug, Clone)]
+pub struct TransientOptions {
+    /// The length of the simulation, in seconds
+    pub duration: f64,
+    pub time_step: f64,
+    /// The time between saved results
+    pub output_interval: f64,
+    pub integrator: Integrator,
+    /// The Rayleigh damping matrix is `mass_damping * M + stiffness_damping * K`
+    pub mass_damping: f64,
+    pub stiffness_damping: f64,
+}
+
+/// A named point whose response is tabulated by a harmonic solve
+#[derive(Debug, Clone)]
+pub struct Probe {
+    pub name: String,
+    pub vertex: Vertex,
+}
+
+/// Settings for a harmonic (frequency response) solve
+#[derive(Debug, Clone)]
+pub struct HarmonicOptions {
+    /// The load frequencies, in Hz
+    pub frequencies: Vec<f64>,
+    /// The Rayleigh damping matrix is `mass_damping * M + stiffness_damping * K`
+    pub mass_damping: f64,
+    pub stiffness_damping: f64,
+    /// Structural damping; the stiffness is `(1 + i * loss_factor) * K`
+    pub loss_factor: f64,
+    pub probes: Vec<Probe>,
+}
+
+/// A fixed straight line that parts can't cross. Parts stay on its left,
+/// looking from `start` to `end`.
+#[derive(Debug, Clone)]
+pub struct RigidLine {
+    pub name: String,
+    pub start: Vertex,
+    pub end: Vertex,
+}
+
+/// How contact constraints are enforced
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ContactMethod {
+    /// Stiff springs resist penetration, so contacts overlap slightly
+    #[default]
+    Penalty,
+    /// Penalty springs, with contact forces updated until the overlap is
+    /// within the tolerance
+    AugmentedLagrangian,
+}
+
+/// Settings for frictionless contact between bodies and against rigid lines
+#[derive(Debug, Clone)]
+pub struct ContactOptions {
+    pub method: ContactMethod,
+    /// The penalty stiffness, as a multiple of the Young's modulus times the
+    /// part thickness
+    pub penalty_factor: f64,
+    /// Nodes further than this from another body or a rigid line are never
+    /// in contact with it
+    pub search_distance: f64,
+    /// The largest overlap accepted by the augmented Lagrangian method
+    pub tolerance: f64,
+    /// The most active set iterations
+    pub max_iterations: usize,
+    pub obstacles: Vec<RigidLine>,
+}
+
+/// A named set of boundary rules, applied on top of a model's shared
+/// boundary rules
+#[derive(Debug, Clone)]
+pub struct LoadCase {
+    pub name: String,
+    pub boundary_rules: Vec<BoundaryRule>,
+}
+
+/// A named linear combination of load cases, as factors by load case name
+#[derive(Debug, Clone)]
+pub struct LoadCombination {
+    pub name: String,
+    pub factors: Vec<(String, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhysicalGroup {
+    pub name: String,
+    pub dimension: usize,
+    pub tag: usize,
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mesh {
+    pub nodes: Vec<Node>,
+    pub elements: Vec<Element>,
+    pub physical_groups: Vec<PhysicalGroup>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeResult {
+    pub vertex: Vertex,
+    pub ux: f64,
+    pub uy: f64,
+    pub fx: f64,
+    pub fy: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ElementResult {
+    pub nodes: [usize; 3],
+    pub stress: f64,
+    pub stress_tensor: [f64; 3],
+    pub von_mises: f64,
+    pub tresca: f64,
+    pub safety_factor: Option<f64>,
+    /// The equivalent plastic strain, when the solve models plasticity
+    pub plastic_strain: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Results {
+    pub nodes: Vec<NodeResult>,
+    pub elements: Vec<ElementResult>,
+}
+
+/// The nodes a boundary rule governs, once applied to a mesh
+#[derive(Debug, Clone)]
+pub struct BoundaryNodes {
+    /// The rule's name; load case rules are prefixed with their load case,
+    /// e.g. `dead: top`
+    pub name: String,
+    pub target: BoundaryTarget,
+    pub nodes: Vec<usize>,
+}
+
+/// Statistics of a static solve
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SolverStats {
+    /// `linear`, `nonlinear`, `contact` or `load_cases`
+    pub analysis: String,
+    pub degrees_of_freedom: usize,
+    /// Degrees of freedom with a prescribed displacement
+    pub constrained_degrees_of_freedom: usize,
+    /// Wall-clock time of the solve, in seconds
+    pub solve_time: f64,
+}
+
+/// The results of a load case or load combination
+#[derive(Debug, Clone)]
+pub struct CaseResults {
+    pub name: String,
+    pub results: Results,
+}
+
+/// A part meshed as one surface: an outer loop followed by the loops of its
+/// holes
+#[derive(Debug, Clone)]
+pub struct Body {
+    /// The name of the body's physical group in the mesh, which boundary
+    /// rules and body materials can target
+    pub name: String,
+    pub loops: Vec<Vec<Vertex>>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Geometry {
+    Loops(Vec<Vec<Vertex>>),
+    /// Separate bodies, each meshed as its own surface. Bodies share no
+    /// nodes, so they only interact through contact.
+    Bodies(Vec<Body>),
+    Mesh(Mesh),
+}
@@ -6,6 +6,7 @@ pub enum MagnetiteError {
     Mesher(String),
     Solver(String),
     PostProcessor(String),
+    DesignCheck(String),
 }
 
 impl Display for MagnetiteError {
@@ -15,8 +16,11 @@ impl Display for MagnetiteError {
             MagnetiteError::Mesher(v) => ("Mesher", v),
             MagnetiteError::Solver(v) => ("Solver", v),
             MagnetiteError::PostProcessor(v) => ("Post Processor", v),
+            MagnetiteError::DesignCheck(v) => ("Design check", v),
         };
 
         write!(f, "{} error: {}", err_name, value)
     }
 }
+
+impl std::error::Error for MagnetiteError {}
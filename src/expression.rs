@@ -0,0 +1,316 @@
+/// A node of a parsed expression
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Number(f64),
+    Time,
+    Negate(Box<Term>),
+    Binary(char, Box<Term>, Box<Term>),
+    Call(Function, Vec<Term>),
+}
+
+/// The functions an expression can call
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Ln,
+    Sqrt,
+    Abs,
+    Min,
+    Max,
+    /// The unit step; 0 for negative arguments and 1 otherwise
+    Step,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "tan" => Some(Function::Tan),
+            "exp" => Some(Function::Exp),
+            "ln" => Some(Function::Ln),
+            "sqrt" => Some(Function::Sqrt),
+            "abs" => Some(Function::Abs),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "step" => Some(Function::Step),
+            _ => None,
+        }
+    }
+
+    fn arguments(&self) -> usize {
+        match self {
+            Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Abs => args[0].abs(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+            Function::Step => {
+                if args[0] < 0.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
+}
+
+/// A mathematical expression of time `t`, such as `sin(2 * pi * 50 * t)`.
+///
+/// Expressions support `+`, `-`, `*`, `/` and `^`, parentheses, the
+/// constants `pi` and `e`, and the functions `sin`, `cos`, `tan`, `exp`,
+/// `ln`, `sqrt`, `abs`, `min`, `max` and `step`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Term,
+}
+
+/// A recursive descent parser over the characters of an expression
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    source: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|(_, c)| *c)
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.source.len(), |(i, _)| *i)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            _ => Err(format!(
+                "expected '{expected}' at position {}",
+                self.position() + 1
+            )),
+        }
+    }
+
+    /// sum = product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Term, String> {
+        let mut term = self.product()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            term = Term::Binary(op, Box::new(term), Box::new(self.product()?));
+        }
+        Ok(term)
+    }
+
+    /// product = unary (('*' | '/') unary)*
+    fn product(&mut self) -> Result<Term, String> {
+        let mut term = self.unary()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            term = Term::Binary(op, Box::new(term), Box::new(self.unary()?));
+        }
+        Ok(term)
+    }
+
+    /// unary = '-' unary | power
+    fn unary(&mut self) -> Result<Term, String> {
+        if self.peek() == Some('-') {
+            self.chars.next();
+            return Ok(Term::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// power = primary ('^' unary)?
+    fn power(&mut self) -> Result<Term, String> {
+        let base = self.primary()?;
+        if self.peek() == Some('^') {
+            self.chars.next();
+            return Ok(Term::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    /// primary = number | name | name '(' arguments ')' | '(' sum ')'
+    fn primary(&mut self) -> Result<Term, String> {
+        let start = self.position();
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let term = self.sum()?;
+                self.expect(')')?;
+                Ok(term)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let mut end = start;
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|(_, c)| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E'))
+                {
+                    end = i + c.len_utf8();
+                    // Exponents may be signed
+                    if matches!(c, 'e' | 'E') {
+                        if let Some((i, c)) = self.chars.next_if(|(_, c)| matches!(c, '+' | '-')) {
+                            end = i + c.len_utf8();
+                        }
+                    }
+                }
+                let text = &self.source[start..end];
+                match text.parse::<f64>() {
+                    Ok(value) => Ok(Term::Number(value)),
+                    Err(_) => Err(format!("invalid number '{text}'")),
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut end = start;
+                while let Some((i, c)) = self
+                    .chars
+                    .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+                {
+                    end = i + c.len_utf8();
+                }
+                let name = &self.source[start..end];
+                match name {
+                    "t" => return Ok(Term::Time),
+                    "pi" => return Ok(Term::Number(std::f64::consts::PI)),
+                    "e" => return Ok(Term::Number(std::f64::consts::E)),
+                    _ => (),
+                }
+
+                let Some(function) = Function::from_name(name) else {
+                    return Err(format!("unknown name '{name}'"));
+                };
+                self.expect('(')?;
+                let mut args = vec![self.sum()?];
+                while self.peek() == Some(',') {
+                    self.chars.next();
+                    args.push(self.sum()?);
+                }
+                self.expect(')')?;
+                if args.len() != function.arguments() {
+                    return Err(format!(
+                        "{name} takes {} arguments, not {}",
+                        function.arguments(),
+                        args.len()
+                    ));
+                }
+                Ok(Term::Call(function, args))
+            }
+            Some(c) => Err(format!("unexpected '{c}' at position {}", start + 1)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Term {
+    fn evaluate(&self, t: f64) -> f64 {
+        match self {
+            Term::Number(value) => *value,
+            Term::Time => t,
+            Term::Negate(term) => -term.evaluate(t),
+            Term::Binary(op, left, right) => {
+                let (left, right) = (left.evaluate(t), right.evaluate(t));
+                match op {
+                    '+' => left + right,
+                    '-' => left - right,
+                    '*' => left * right,
+                    '/' => left / right,
+                    _ => left.powf(right),
+                }
+            }
+            Term::Call(function, args) => {
+                let args: Vec<f64> = args.iter().map(|a| a.evaluate(t)).collect();
+                function.apply(&args)
+            }
+        }
+    }
+}
+
+impl Expression {
+    /// Parses an expression of time
+    ///
+    /// # Arguments
+    /// * `source` - The expression, e.g. `1 - exp(-t / 0.01)`
+    ///
+    /// # Returns
+    /// The parsed Expression, or a description of the syntax error
+    pub fn parse(source: &str) -> Result<Expression, String> {
+        let mut parser = Parser {
+            chars: source.char_indices().peekable(),
+            source,
+        };
+        let root = parser.sum()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!(
+                "unexpected '{c}' at position {}",
+                parser.position() + 1
+            ));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Evaluates the expression at a time
+    pub fn evaluate(&self, t: f64) -> f64 {
+        self.root.evaluate(t)
+    }
+
+    /// Returns the text the expression was parsed from
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_expressions() {
+        let cases = [
+            ("1 + 2 * 3", 0.0, 7.0),
+            ("(1 + 2) * 3", 0.0, 9.0),
+            ("-2^2", 0.0, -4.0),
+            ("2^3^2", 0.0, 512.0),
+            ("10 * t - 1.5e-1", 0.5, 4.85),
+            ("sin(2 * pi * t)", 0.25, 1.0),
+            ("max(0, 1 - t / 2) * step(t - 1)", 1.0, 0.5),
+            ("max(0, 1 - t / 2) * step(t - 1)", 0.5, 0.0),
+        ];
+        for (source, t, expected) in cases {
+            let value = Expression::parse(source).unwrap().evaluate(t);
+            assert!((value - expected).abs() < 1e-12, "{source}: {value}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for source in ["", "1 +", "sin t", "x * 2", "max(1)", "(1 + 2", "2 3"] {
+            assert!(Expression::parse(source).is_err(), "{source}");
+        }
+    }
+}
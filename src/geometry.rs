@@ -0,0 +1,363 @@
+use crate::{
+    datatypes::{Body, Vertex},
+    error::MagnetiteError,
+};
+use tracing::{info, warn};
+
+/// Formats a vertex for error messages
+fn at(vertex: &Vertex) -> String {
+    format!("({}, {})", vertex.x, vertex.y)
+}
+
+/// The cross product of `b - a` and `c - a`; positive if `a`, `b`, `c` turn
+/// counter-clockwise
+fn cross(a: &Vertex, b: &Vertex, c: &Vertex) -> f64 {
+    (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)
+}
+
+/// The length of the diagonal of a loop's bounding box, which sets the
+/// tolerance of the geometric tests
+fn extent(vertices: &[Vertex]) -> f64 {
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for v in vertices {
+        (x_min, x_max) = (x_min.min(v.x), x_max.max(v.x));
+        (y_min, y_max) = (y_min.min(v.y), y_max.max(v.y));
+    }
+    (x_max - x_min).hypot(y_max - y_min)
+}
+
+/// Computes the signed area of a loop; positive if it runs counter-clockwise
+///
+/// # Arguments
+/// * `vertices` - The vertices of the loop, without a closing vertex
+pub fn signed_area(vertices: &[Vertex]) -> f64 {
+    let mut area = 0.0;
+    for (i, a) in vertices.iter().enumerate() {
+        let b = &vertices[(i + 1) % vertices.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+/// Tests whether a point is inside a polygon, by counting how many edges a
+/// ray from the point crosses
+///
+/// # Arguments
+/// * `point` - The point to test
+/// * `polygon` - The vertices of the polygon, without a closing vertex
+pub fn point_in_polygon(point: &Vertex, polygon: &[Vertex]) -> bool {
+    let mut inside = false;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Finds where two segments touch, if they do
+///
+/// # Arguments
+/// * `a`, `b` - The ends of the first segment
+/// * `c`, `d` - The ends of the second segment
+/// * `tolerance` - Cross products smaller than this count as collinear
+///
+/// # Returns
+/// A point the segments share
+fn intersection(a: &Vertex, b: &Vertex, c: &Vertex, d: &Vertex, tolerance: f64) -> Option<Vertex> {
+    let sign = |value: f64| {
+        if value.abs() <= tolerance {
+            0
+        } else if value > 0.0 {
+            1
+        } else {
+            -1
+        }
+    };
+    let (d1, d2) = (sign(cross(a, b, c)), sign(cross(a, b, d)));
+    let (d3, d4) = (sign(cross(c, d, a)), sign(cross(c, d, b)));
+
+    // A point collinear with a segment touches it if it lies between its ends
+    let within = |p: &Vertex, start: &Vertex, end: &Vertex| {
+        p.x >= start.x.min(end.x) - tolerance
+            && p.x <= start.x.max(end.x) + tolerance
+            && p.y >= start.y.min(end.y) - tolerance
+            && p.y <= start.y.max(end.y) + tolerance
+    };
+
+    if d1 * d2 < 0 && d3 * d4 < 0 {
+        let t = cross(c, d, a) / (cross(c, d, a) - cross(c, d, b));
+        return Some(Vertex {
+            x: a.x + t * (b.x - a.x),
+            y: a.y + t * (b.y - a.y),
+        });
+    }
+    for (point, collinear, start, end) in
+        [(c, d1, a, b), (d, d2, a, b), (a, d3, c, d), (b, d4, c, d)]
+    {
+        if collinear == 0 && within(point, start, end) {
+            return Some(point.clone());
+        }
+    }
+    None
+}
+
+/// Finds the vertices of a loop that lie partway along a straight edge,
+/// between collinear segments running the same way
+///
+/// # Arguments
+/// * `vertices` - The vertices of the loop
+/// * `tolerance` - Cross products smaller than this count as collinear
+///
+/// # Returns
+/// The indexes of the vertices
+fn straight_points(vertices: &[Vertex], tolerance: f64) -> Vec<usize> {
+    let n = vertices.len();
+    (0..n)
+        .filter(|i| {
+            let (previous, vertex, next) = (
+                &vertices[(i + n - 1) % n],
+                &vertices[*i],
+                &vertices[(i + 1) % n],
+            );
+            let forward = (vertex.x - previous.x) * (next.x - vertex.x)
+                + (vertex.y - previous.y) * (next.y - vertex.y);
+            cross(previous, vertex, next).abs() <= tolerance && forward > 0.0
+        })
+        .collect()
+}
+
+/// Checks a single loop, and removes a closing vertex that repeats the
+/// first one
+///
+/// # Arguments
+/// * `name` - Describes the loop in error messages, e.g. `OUTER in part.svg`
+/// * `vertices` - The vertices of the loop
+///
+/// # Returns
+/// An Input error if the loop has too few vertices, zero-length segments,
+/// segments that fold back on each other, no area, or crosses itself.
+/// Points partway along a straight edge are kept, with a warning.
+pub fn validate_loop(name: &str, vertices: &mut Vec<Vertex>) -> Result<(), MagnetiteError> {
+    if vertices.len() > 1 && vertices.first() == vertices.last() {
+        vertices.pop();
+        info!("removed the closing point of {name}, which repeats its first point");
+    }
+    if vertices.len() < 3 {
+        return Err(MagnetiteError::Input(format!(
+            "{name} has {} distinct points; a loop needs at least 3",
+            vertices.len()
+        )));
+    }
+
+    let size = extent(vertices);
+    let tolerance = 1e-9 * size * size;
+    let n = vertices.len();
+
+    for i in 0..n {
+        let (a, b) = (&vertices[i], &vertices[(i + 1) % n]);
+        if (b.x - a.x).hypot(b.y - a.y) <= 1e-9 * size {
+            return Err(MagnetiteError::Input(format!(
+                "{name} has a zero-length segment at {}",
+                at(a)
+            )));
+        }
+    }
+
+    // Collinear segments that reverse direction overlap each other
+    for i in 0..n {
+        let (previous, vertex, next) = (
+            &vertices[(i + n - 1) % n],
+            &vertices[i],
+            &vertices[(i + 1) % n],
+        );
+        let forward = (vertex.x - previous.x) * (next.x - vertex.x)
+            + (vertex.y - previous.y) * (next.y - vertex.y);
+        if cross(previous, vertex, next).abs() <= tolerance && forward < 0.0 {
+            return Err(MagnetiteError::Input(format!(
+                "{name} doubles back on itself at {}; the segments either side of it are collinear",
+                at(vertex)
+            )));
+        }
+    }
+
+    // Collinear segments that carry on in the same direction only add a
+    // mesh node, which may be wanted, e.g. to place a point load
+    let straight = straight_points(vertices, tolerance);
+    if let Some(first) = straight.first() {
+        warn!(
+            "{name} has {} points partway along straight edges, the first at {}; each is kept as a mesh node",
+            straight.len(),
+            at(&vertices[*first])
+        );
+    }
+
+    for i in 0..n {
+        // Neighbouring segments share a vertex, so only check the rest
+        for j in i + 2..n {
+            if i == 0 && j == n - 1 {
+                continue;
+            }
+            let (a, b) = (&vertices[i], &vertices[(i + 1) % n]);
+            let (c, d) = (&vertices[j], &vertices[(j + 1) % n]);
+            if let Some(point) = intersection(a, b, c, d, tolerance) {
+                return Err(MagnetiteError::Input(format!(
+                    "{name} crosses itself at {}, between its segments from {} to {} and from {} to {}",
+                    at(&point),
+                    at(a),
+                    at(b),
+                    at(c),
+                    at(d)
+                )));
+            }
+        }
+    }
+
+    if signed_area(vertices).abs() <= tolerance {
+        return Err(MagnetiteError::Input(format!(
+            "{name} encloses no area; its first point is at {}",
+            at(&vertices[0])
+        )));
+    }
+
+    Ok(())
+}
+
+/// Checks that no two loops cross or touch each other
+///
+/// # Arguments
+/// * `loops` - Each loop's description for error messages, and its vertices
+///
+/// # Returns
+/// An Input error naming the first pair of loops that touch, and where
+pub fn check_crossings(loops: &[(String, &[Vertex])]) -> Result<(), MagnetiteError> {
+    for (i, (first_name, first)) in loops.iter().enumerate() {
+        for (second_name, second) in &loops[i + 1..] {
+            let size = extent(first).max(extent(second));
+            let tolerance = 1e-9 * size * size;
+            for (k, a) in first.iter().enumerate() {
+                let b = &first[(k + 1) % first.len()];
+                for (l, c) in second.iter().enumerate() {
+                    let d = &second[(l + 1) % second.len()];
+                    if let Some(point) = intersection(a, b, c, d, tolerance) {
+                        return Err(MagnetiteError::Input(format!(
+                            "{first_name} and {second_name} overlap; they touch at {}",
+                            at(&point)
+                        )));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Orients a body's loops so that its outer loop runs counter-clockwise and
+/// its holes run clockwise
+///
+/// # Arguments
+/// * `body` - The body to orient
+///
+/// # Returns
+/// The number of loops that were reversed
+pub fn orient(body: &mut Body) -> usize {
+    let mut reversed = 0;
+    for (i, vertices) in body.loops.iter_mut().enumerate() {
+        let counter_clockwise = signed_area(vertices) > 0.0;
+        if counter_clockwise != (i == 0) {
+            vertices.reverse();
+            reversed += 1;
+        }
+    }
+    reversed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertices(points: &[(f64, f64)]) -> Vec<Vertex> {
+        points
+            .iter()
+            .map(|(x, y)| Vertex { x: *x, y: *y })
+            .collect()
+    }
+
+    fn error(points: &[(f64, f64)]) -> String {
+        match validate_loop("OUTER in part.svg", &mut vertices(points)) {
+            Err(MagnetiteError::Input(message)) => message,
+            other => panic!("expected an input error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_loops() {
+        let message = error(&[(0.0, 0.0), (2.0, 2.0), (2.0, 0.0), (0.0, 2.0)]);
+        assert!(
+            message.starts_with("OUTER in part.svg crosses itself at (1, 1)"),
+            "{message}"
+        );
+
+        let message = error(&[(0.0, 0.0), (1.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+        assert_eq!(
+            message,
+            "OUTER in part.svg has a zero-length segment at (1, 0)"
+        );
+
+        let message = error(&[(0.0, 0.0), (2.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+        assert!(
+            message.starts_with("OUTER in part.svg doubles back on itself at (2, 0)"),
+            "{message}"
+        );
+
+        let message = error(&[(0.0, 0.0), (1.0, 0.0), (0.0, 0.0)]);
+        assert!(message.contains("has 2 distinct points"), "{message}");
+    }
+
+    #[test]
+    fn repairs_closing_points_and_orientation() {
+        let mut outer = vertices(&[(0.0, 0.0), (0.0, 4.0), (4.0, 4.0), (4.0, 0.0), (0.0, 0.0)]);
+        validate_loop("outer", &mut outer).unwrap();
+        assert_eq!(outer.len(), 4);
+
+        // Collinear points along a straight edge are kept, with a warning
+        let mut hole = vertices(&[(1.0, 1.0), (2.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0)]);
+        validate_loop("hole", &mut hole).unwrap();
+        assert_eq!(hole.len(), 5);
+        assert_eq!(straight_points(&hole, 1e-9), [1]);
+        assert!(straight_points(&outer, 1e-9).is_empty());
+
+        let mut body = Body {
+            name: "plate".to_string(),
+            loops: vec![outer, hole],
+        };
+        assert_eq!(orient(&mut body), 2);
+        assert!(signed_area(&body.loops[0]) > 0.0);
+        assert!(signed_area(&body.loops[1]) < 0.0);
+        assert_eq!(orient(&mut body), 0);
+    }
+
+    #[test]
+    fn finds_overlapping_loops() {
+        let first = vertices(&[(0.0, 0.0), (2.0, 0.0), (2.0, 2.0), (0.0, 2.0)]);
+        let second = vertices(&[(1.0, 1.0), (3.0, 1.0), (3.0, 3.0), (1.0, 3.0)]);
+        let apart = vertices(&[(5.0, 0.0), (6.0, 0.0), (6.0, 1.0)]);
+        let loops = [
+            ("INNER_a".to_string(), first.as_slice()),
+            ("INNER_b".to_string(), second.as_slice()),
+        ];
+        let Err(MagnetiteError::Input(message)) = check_crossings(&loops) else {
+            panic!("expected an input error");
+        };
+        assert_eq!(message, "INNER_a and INNER_b overlap; they touch at (2, 1)");
+
+        let loops = [
+            ("first".to_string(), first.as_slice()),
+            ("apart".to_string(), apart.as_slice()),
+        ];
+        assert!(check_crossings(&loops).is_ok());
+    }
+}
@@ -0,0 +1,702 @@
+use nalgebra::{Complex, DMatrix, DVector, SymmetricEigen};
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix, CsrMatrix};
+
+use crate::{
+    datatypes::{HarmonicOptions, Mesh, ModelMetadata, Node},
+    error::MagnetiteError,
+    solver::{compute_element_mass_matrix, compute_element_stiffness_matrix, element_dofs, DOF},
+    units::{label, Units},
+};
+use tracing::info;
+
+/// The most natural frequencies listed when reporting those in range
+const REPORTED_MODES: usize = 10;
+
+/// The fewest natural modes in the modal basis
+const MIN_MODES: usize = 20;
+
+/// The modal basis spans the natural frequencies up to this multiple of the
+/// highest load frequency
+const MODE_CUTOFF: f64 = 2.0;
+
+/// The relative change in the natural frequencies at which subspace
+/// iteration stops
+const MODE_TOLERANCE: f64 = 1e-10;
+
+/// The most subspace iterations before giving up on the natural modes
+const MAX_ITERATIONS: usize = 500;
+
+/// The steady-state response at one load frequency
+#[derive(Debug, Clone)]
+pub struct HarmonicResponse {
+    /// The load frequency, in Hz
+    pub frequency: f64,
+    /// The complex amplitudes `[ux, uy]` of each node; the displacement is
+    /// the real part of `u * exp(i * omega * t)`
+    pub displacements: Vec<[Complex<f64>; 2]>,
+}
+
+/// The results of a harmonic solve
+#[derive(Debug, Clone)]
+pub struct HarmonicResult {
+    /// The lowest undamped natural frequencies of the constrained part, in
+    /// Hz; those of the modal basis
+    pub natural_frequencies: Vec<f64>,
+    pub responses: Vec<HarmonicResponse>,
+    /// The name of each probe and the node nearest to it
+    pub probes: Vec<(String, usize)>,
+}
+
+/// Finds the lowest natural modes of `K phi = lambda M phi` by subspace
+/// iteration, which only needs the sparse factor of the stiffness matrix
+///
+/// # Arguments
+/// * `stiffness` - The free partition of the stiffness matrix
+/// * `mass` - The free partition of the mass matrix
+/// * `factor` - The Cholesky factor of `stiffness`
+/// * `count` - The number of modes to find
+///
+/// # Returns
+/// The eigenvalues, in ascending order, and the mass-normalised mode shapes
+/// as columns
+fn lowest_modes(
+    stiffness: &CsrMatrix<f64>,
+    mass: &CsrMatrix<f64>,
+    factor: &CscCholesky<f64>,
+    count: usize,
+) -> Result<(Vec<f64>, DMatrix<f64>), MagnetiteError> {
+    let size = stiffness.nrows();
+    // Extra vectors speed up convergence of the highest wanted modes
+    let width = size.min((2 * count).max(count + 8));
+
+    // Deterministic pseudo-random starting vectors
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut vectors = DMatrix::from_fn(size, width, |_, _| {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        (seed >> 11) as f64 / (1u64 << 53) as f64 - 0.5
+    });
+
+    let rank_error = || {
+        MagnetiteError::Solver(
+            "Subspace iteration lost rank while finding the natural modes".to_owned(),
+        )
+    };
+    let mut previous: Option<Vec<f64>> = None;
+    for _ in 0..MAX_ITERATIONS {
+        let iterated = factor.solve(&(mass * &vectors));
+
+        // Rayleigh-Ritz projection onto the iterated vectors
+        let projected_stiffness = iterated.tr_mul(&(stiffness * &iterated));
+        let projected_mass = iterated.tr_mul(&(mass * &iterated));
+        let Some(cholesky) = (0.5 * (&projected_mass + projected_mass.transpose())).cholesky()
+        else {
+            return Err(rank_error());
+        };
+        let lower = cholesky.l();
+        let half = lower
+            .solve_lower_triangular(&projected_stiffness)
+            .ok_or_else(rank_error)?;
+        let reduced = lower
+            .solve_lower_triangular(&half.transpose())
+            .ok_or_else(rank_error)?;
+        let eigen = SymmetricEigen::new(0.5 * (&reduced + reduced.transpose()));
+        let ritz = lower
+            .transpose()
+            .solve_upper_triangular(&eigen.eigenvectors)
+            .ok_or_else(rank_error)?;
+
+        let mut order: Vec<usize> = (0..width).collect();
+        order.sort_by(|a, b| eigen.eigenvalues[*a].total_cmp(&eigen.eigenvalues[*b]));
+        let eigenvalues: Vec<f64> = order.iter().map(|i| eigen.eigenvalues[*i]).collect();
+        vectors = iterated * ritz.select_columns(&order);
+
+        let converged = width == size
+            || previous.as_ref().is_some_and(|previous| {
+                std::iter::zip(previous, &eigenvalues)
+                    .take(count)
+                    .all(|(old, new)| (new - old).abs() <= MODE_TOLERANCE * new.abs())
+            });
+        if converged {
+            return Ok((
+                eigenvalues[..count].to_vec(),
+                vectors.columns(0, count).into_owned(),
+            ));
+        }
+        previous = Some(eigenvalues);
+    }
+
+    Err(MagnetiteError::Solver(format!(
+        "The natural modes did not converge in {MAX_ITERATIONS} subspace iterations"
+    )))
+}
+
+/// Finds the steady-state response to sinusoidal loading at each load
+/// frequency. Forces and prescribed displacements are the amplitudes of the
+/// loading, all in phase. The damped equations of motion are decoupled with
+/// the lowest undamped mode shapes, up to twice the highest load frequency;
+/// the higher modes respond statically, by the mode acceleration method, so
+/// the response at zero frequency matches a static solve.
+///
+/// # Arguments
+/// * `mesh` - The mesh
+/// * `nodes` - The mesh nodes, with boundary conditions applied
+/// * `model_metadata` - The model metadata; the material needs a density
+/// * `options` - The harmonic settings
+///
+/// # Returns
+/// The HarmonicResult
+pub fn run(
+    mesh: &Mesh,
+    nodes: &[Node],
+    model_metadata: &ModelMetadata,
+    options: &HarmonicOptions,
+) -> Result<HarmonicResult, MagnetiteError> {
+    let Some(density) = model_metadata.material.density else {
+        return Err(MagnetiteError::Input(
+            "Harmonic analysis needs a material density; set metadata.density or use a library material"
+                .to_owned(),
+        ));
+    };
+    if options.frequencies.is_empty() {
+        return Err(MagnetiteError::Input(
+            "Harmonic analysis needs at least one load frequency".to_owned(),
+        ));
+    }
+    let dof_count = DOF * nodes.len();
+
+    let mut applied_forces = DVector::<f64>::zeros(dof_count);
+    let mut prescribed = DVector::<f64>::zeros(dof_count);
+    let mut free_index: Vec<Option<usize>> = vec![None; dof_count];
+    let mut free_count = 0;
+    for (i, node) in nodes.iter().enumerate() {
+        for (d, (u, f)) in [(node.ux, node.fx), (node.uy, node.fy)]
+            .into_iter()
+            .enumerate()
+        {
+            match u {
+                Some(u) => prescribed[DOF * i + d] = u,
+                None => {
+                    let Some(f) = f else {
+                        return Err(MagnetiteError::Solver(format!(
+                            "Node {i} has neither a displacement nor a force in {}",
+                            ["x", "y"][d]
+                        )));
+                    };
+                    applied_forces[DOF * i + d] = f;
+                    free_index[DOF * i + d] = Some(free_count);
+                    free_count += 1;
+                }
+            }
+        }
+    }
+
+    // The free partitions of the stiffness and mass matrices give the modes;
+    // the full matrices couple in the prescribed displacements
+    let mut stiffness_coo = CooMatrix::new(dof_count, dof_count);
+    let mut mass_coo = CooMatrix::new(dof_count, dof_count);
+    let mut free_stiffness_coo = CooMatrix::new(free_count, free_count);
+    let mut free_mass_coo = CooMatrix::new(free_count, free_count);
+    for element in &mesh.elements {
+        let stiffness_mat = compute_element_stiffness_matrix(
+            element,
+            nodes,
+            model_metadata.material.poisson_ratio,
+            model_metadata.material.youngs_modulus,
+            model_metadata.part_thickness,
+        );
+        let mass_mat =
+            compute_element_mass_matrix(element, nodes, density, model_metadata.part_thickness);
+
+        let dofs = element_dofs(element);
+        for (a, row) in dofs.iter().enumerate() {
+            for (b, col) in dofs.iter().enumerate() {
+                stiffness_coo.push(*row, *col, stiffness_mat[(a, b)]);
+                mass_coo.push(*row, *col, mass_mat[(a, b)]);
+                if let (Some(r), Some(c)) = (free_index[*row], free_index[*col]) {
+                    free_stiffness_coo.push(r, c, stiffness_mat[(a, b)]);
+                    free_mass_coo.push(r, c, mass_mat[(a, b)]);
+                }
+            }
+        }
+    }
+    let stiffness = CsrMatrix::from(&stiffness_coo);
+    let mass = CsrMatrix::from(&mass_coo);
+    let free_stiffness = CsrMatrix::from(&free_stiffness_coo);
+    let free_mass = CsrMatrix::from(&free_mass_coo);
+    let factor = match CscCholesky::factor(&CscMatrix::from(&free_stiffness_coo)) {
+        Ok(factor) => factor,
+        Err(_err) => {
+            return Err(MagnetiteError::Solver(
+                "Stiffness matrix is singular; check that the model is fully constrained"
+                    .to_owned(),
+            ))
+        }
+    };
+
+    let restrict = |full: &DVector<f64>| -> DVector<f64> {
+        let mut free = DVector::zeros(free_count);
+        for (dof, index) in free_index.iter().enumerate() {
+            if let Some(r) = index {
+                free[*r] = full[dof];
+            }
+        }
+        free
+    };
+    let static_solve =
+        |load: &DVector<f64>| -> DVector<f64> { factor.solve(load).column(0).into() };
+
+    let lowest = options.frequencies.iter().copied().fold(f64::MAX, f64::min);
+    let highest = options.frequencies.iter().copied().fold(0.0, f64::max);
+
+    // Grow the modal basis until it spans the cutoff frequency
+    info!("finding the natural modes of {free_count} free degrees of freedom...");
+    let cutoff = (2.0 * std::f64::consts::PI * MODE_CUTOFF * highest).powi(2);
+    let mut count = MIN_MODES.min(free_count);
+    let (eigenvalues, modes) = loop {
+        let (eigenvalues, modes) = lowest_modes(&free_stiffness, &free_mass, &factor, count)?;
+        if count == free_count || eigenvalues.last().is_some_and(|l| *l >= cutoff) {
+            break (eigenvalues, modes);
+        }
+        count = (2 * count).min(free_count);
+    };
+    info!("kept {count} natural modes in the modal basis");
+
+    let natural_frequencies: Vec<f64> = eigenvalues
+        .iter()
+        .map(|lambda| lambda.max(0.0).sqrt() / (2.0 * std::f64::consts::PI))
+        .collect();
+
+    let in_range: Vec<String> = natural_frequencies
+        .iter()
+        .filter(|f| **f >= lowest && **f <= highest)
+        .map(|f| format!("{f:.4e}"))
+        .collect();
+    match in_range.len() {
+        0 => info!("no natural frequencies between {lowest} and {highest} Hz"),
+        n if n > REPORTED_MODES => info!(
+            "{n} natural frequencies between {lowest} and {highest} Hz, starting {} Hz",
+            in_range[..REPORTED_MODES].join(", ")
+        ),
+        n => info!(
+            "{n} natural frequencies between {lowest} and {highest} Hz: {} Hz",
+            in_range.join(", ")
+        ),
+    }
+
+    // Loads from the applied forces and the prescribed displacements, which
+    // load the free degrees of freedom through the coupling stiffness and
+    // mass, with their static responses and modal components
+    let free_forces = restrict(&applied_forces);
+    let coupled_stiffness = restrict(&(&stiffness * &prescribed));
+    let coupled_mass = restrict(&(&mass * &prescribed));
+    let static_forces = static_solve(&free_forces);
+    let static_coupled_stiffness = static_solve(&coupled_stiffness);
+    let static_coupled_mass = static_solve(&coupled_mass);
+    let modal_forces = modes.tr_mul(&free_forces);
+    let modal_coupled_stiffness = modes.tr_mul(&coupled_stiffness);
+    let modal_coupled_mass = modes.tr_mul(&coupled_mass);
+
+    let mut responses = Vec::new();
+    for frequency in &options.frequencies {
+        let omega = 2.0 * std::f64::consts::PI * frequency;
+
+        // The dynamic stiffness is (1 + i (eta + omega b)) K + (i omega a - omega^2) M
+        let stiffness_factor =
+            Complex::new(1.0, options.loss_factor + omega * options.stiffness_damping);
+        let mass_factor = Complex::new(-omega * omega, omega * options.mass_damping);
+
+        // The static response to the load, scaled by the stiffness factor,
+        // so every mode starts with its static response
+        let inverse = stiffness_factor.inv();
+        let static_real = &static_forces
+            - stiffness_factor.re * &static_coupled_stiffness
+            - mass_factor.re * &static_coupled_mass;
+        let static_imaginary = -stiffness_factor.im * &static_coupled_stiffness
+            - mass_factor.im * &static_coupled_mass;
+        let mut free_real = inverse.re * &static_real - inverse.im * &static_imaginary;
+        let mut free_imaginary = inverse.re * &static_imaginary + inverse.im * &static_real;
+
+        // Each mode of the basis then adds its dynamic amplification
+        let mut modal_real = DVector::<f64>::zeros(count);
+        let mut modal_imaginary = DVector::<f64>::zeros(count);
+        for (j, lambda) in eigenvalues.iter().enumerate() {
+            let load = modal_forces[j]
+                - stiffness_factor * modal_coupled_stiffness[j]
+                - mass_factor * modal_coupled_mass[j];
+            let dynamic_stiffness = stiffness_factor * *lambda + mass_factor;
+            if dynamic_stiffness.norm() == 0.0 {
+                return Err(MagnetiteError::Solver(format!(
+                    "The response at {frequency} Hz is unbounded; it is a natural frequency and the part is undamped"
+                )));
+            }
+            let amplitude = load / dynamic_stiffness - load / (stiffness_factor * *lambda);
+            modal_real[j] = amplitude.re;
+            modal_imaginary[j] = amplitude.im;
+        }
+        free_real += &modes * modal_real;
+        free_imaginary += &modes * modal_imaginary;
+
+        let displacement = |dof: usize| match free_index[dof] {
+            Some(r) => Complex::new(free_real[r], free_imaginary[r]),
+            None => Complex::new(prescribed[dof], 0.0),
+        };
+        responses.push(HarmonicResponse {
+            frequency: *frequency,
+            displacements: (0..nodes.len())
+                .map(|i| [displacement(DOF * i), displacement(DOF * i + 1)])
+                .collect(),
+        });
+    }
+
+    let probes = options
+        .probes
+        .iter()
+        .map(|probe| {
+            let distance = |node: &Node| {
+                (node.vertex.x - probe.vertex.x).hypot(node.vertex.y - probe.vertex.y)
+            };
+            match (0..nodes.len())
+                .min_by(|a, b| distance(&nodes[*a]).total_cmp(&distance(&nodes[*b])))
+            {
+                Some(nearest) => Ok((probe.name.clone(), nearest)),
+                None => Err(MagnetiteError::Input(format!(
+                    "Probe '{}' has no node to follow; the mesh is empty",
+                    probe.name
+                ))),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(HarmonicResult {
+        natural_frequencies,
+        responses,
+        probes,
+    })
+}
+
+/// Writes the complex displacement of each node at each frequency to a csv
+/// file
+///
+/// # Arguments
+/// * `result` - The harmonic results
+/// * `units` - The units to write displacements in, if declared
+/// * `output` - The filepath of the csv file
+pub fn write_fields(
+    result: &HarmonicResult,
+    units: Option<&Units>,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let length_unit = units.map(|u| u.length.name());
+
+    let mut table = format!(
+        "frequency [Hz],node,{},{},{},{}\n",
+        label("ux_real", length_unit),
+        label("ux_imaginary", length_unit),
+        label("uy_real", length_unit),
+        label("uy_imaginary", length_unit)
+    );
+    for response in &result.responses {
+        for (i, [ux, uy]) in response.displacements.iter().enumerate() {
+            table += &format!(
+                "{},{i},{},{},{},{}\n",
+                response.frequency,
+                ux.re / length,
+                ux.im / length,
+                uy.re / length,
+                uy.im / length
+            );
+        }
+    }
+
+    if let Err(err) = std::fs::write(output, table) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    info!(
+        "wrote displacements at {} frequencies to {output}",
+        result.responses.len()
+    );
+
+    Ok(())
+}
+
+/// Writes the amplitude and phase of each probe's displacement against
+/// frequency to a csv file, and reports the frequency of each probe's peak
+/// response
+///
+/// # Arguments
+/// * `result` - The harmonic results
+/// * `units` - The units to write displacements in, if declared
+/// * `output` - The filepath of the csv file
+pub fn write_probes(
+    result: &HarmonicResult,
+    units: Option<&Units>,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let length_unit = units.map(|u| u.length.name());
+
+    let mut table = format!(
+        "frequency [Hz],probe,node,{},ux_phase [deg],{},uy_phase [deg]\n",
+        label("ux_amplitude", length_unit),
+        label("uy_amplitude", length_unit)
+    );
+    for response in &result.responses {
+        for (name, node) in &result.probes {
+            let [ux, uy] = response.displacements[*node];
+            table += &format!(
+                "{},{name},{node},{},{},{},{}\n",
+                response.frequency,
+                ux.norm() / length,
+                ux.arg().to_degrees(),
+                uy.norm() / length,
+                uy.arg().to_degrees()
+            );
+        }
+    }
+
+    if let Err(err) = std::fs::write(output, table) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    for (name, node) in &result.probes {
+        let magnitude = |response: &HarmonicResponse| {
+            let [ux, uy] = response.displacements[*node];
+            ux.norm().hypot(uy.norm())
+        };
+        if let Some(peak) = result
+            .responses
+            .iter()
+            .max_by(|a, b| magnitude(a).total_cmp(&magnitude(b)))
+        {
+            info!(
+                "probe '{name}' (node {node}) peaks at {} Hz with amplitude {:.4e}{}",
+                peak.frequency,
+                magnitude(peak) / length,
+                length_unit.map_or(String::new(), |u| format!(" {u}"))
+            );
+        }
+    }
+
+    info!("wrote probe responses to {output}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datatypes::{Element, Material, MeshOptions, Probe, Vertex},
+        solver,
+    };
+
+    /// A `length` by 1 strip of elements, fixed at x = 0 with a downwards
+    /// force on the two nodes at the free end
+    fn cantilever(length: usize) -> (Mesh, Vec<Node>) {
+        let mut nodes = Vec::new();
+        for j in 0..=1 {
+            for i in 0..=length {
+                nodes.push(Node {
+                    vertex: Vertex {
+                        x: i as f64,
+                        y: j as f64,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                });
+            }
+        }
+        let row = length + 1;
+        let elements = (0..length)
+            .flat_map(|i| [[i, i + 1, i + row + 1], [i, i + row + 1, i + row]])
+            .map(|nodes| Element {
+                nodes,
+                stress: None,
+                stress_tensor: None,
+                plastic_strain: None,
+                physical_tags: Vec::new(),
+            })
+            .collect();
+        let mesh = Mesh {
+            nodes: nodes.clone(),
+            elements,
+            physical_groups: Vec::new(),
+        };
+
+        for node in nodes.iter_mut() {
+            if node.vertex.x == 0.0 {
+                (node.ux, node.uy, node.fx, node.fy) = (Some(0.0), Some(0.0), None, None);
+            }
+            if node.vertex.x == length as f64 {
+                node.fy = Some(-0.1);
+            }
+        }
+        (mesh, nodes)
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.3,
+                density: Some(1.0),
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        }
+    }
+
+    fn options(frequencies: Vec<f64>, mass_damping: f64) -> HarmonicOptions {
+        HarmonicOptions {
+            frequencies,
+            mass_damping,
+            stiffness_damping: 0.0,
+            loss_factor: 0.0,
+            probes: vec![Probe {
+                name: "tip".to_string(),
+                vertex: Vertex { x: 4.1, y: -0.1 },
+            }],
+        }
+    }
+
+    #[test]
+    fn zero_frequency_matches_static_solve() {
+        let (mesh, nodes) = cantilever(4);
+        let result = run(&mesh, &nodes, &metadata(), &options(vec![0.0], 0.0)).unwrap();
+
+        let mut static_nodes = nodes.clone();
+        let stiffness = solver::assemble_stiffness(
+            &static_nodes,
+            &mesh.elements,
+            &vec![metadata().material; mesh.elements.len()],
+            &metadata(),
+        );
+        solver::FactoredStiffness::new(&stiffness, &static_nodes)
+            .unwrap()
+            .solve(&mut static_nodes)
+            .unwrap();
+
+        assert_eq!(result.probes, vec![("tip".to_string(), 4)]);
+        for (node, [ux, uy]) in std::iter::zip(&static_nodes, &result.responses[0].displacements) {
+            assert!((ux.re - node.ux.unwrap()).abs() < 1e-9 && ux.im == 0.0);
+            assert!((uy.re - node.uy.unwrap()).abs() < 1e-9 && uy.im == 0.0);
+        }
+    }
+
+    #[test]
+    fn response_lags_a_quarter_turn_at_resonance() {
+        let (mesh, nodes) = cantilever(4);
+        let first = run(&mesh, &nodes, &metadata(), &options(vec![0.0], 0.0))
+            .unwrap()
+            .natural_frequencies[0];
+        let frequencies = vec![0.0, 0.5 * first, first, 2.0 * first];
+        let result = run(&mesh, &nodes, &metadata(), &options(frequencies, 0.05)).unwrap();
+
+        let tip: Vec<Complex<f64>> = result
+            .responses
+            .iter()
+            .map(|r| r.displacements[4][1])
+            .collect();
+        // Phase relative to the static deflection
+        let phase = |u: &Complex<f64>| (u / tip[0]).arg().to_degrees();
+
+        assert!(phase(&tip[1]).abs() < 5.0);
+        assert!((phase(&tip[2]) + 90.0).abs() < 5.0, "{}", phase(&tip[2]));
+        assert!(tip[2].norm() > 5.0 * tip[0].norm());
+        assert!(phase(&tip[3]) < -170.0, "{}", phase(&tip[3]));
+    }
+
+    #[test]
+    fn rejects_missing_frequencies_and_forces() {
+        let (mesh, mut nodes) = cantilever(4);
+        let message = run(&mesh, &nodes, &metadata(), &options(Vec::new(), 0.0))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("at least one load frequency"), "{message}");
+
+        nodes[3].fy = None;
+        let message = run(&mesh, &nodes, &metadata(), &options(vec![1.0], 0.0))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("Node 3 has neither a displacement nor a force in y"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn truncated_modes_match_a_direct_solve() {
+        let (mesh, nodes) = cantilever(40);
+        let metadata = metadata();
+        let first = run(&mesh, &nodes, &metadata, &options(vec![0.0], 0.0))
+            .unwrap()
+            .natural_frequencies[0];
+        let frequencies = vec![0.3 * first, 1.1 * first, 4.0 * first];
+        let mut options = options(frequencies.clone(), 0.02);
+        options.loss_factor = 0.01;
+        let result = run(&mesh, &nodes, &metadata, &options).unwrap();
+        assert!(result.natural_frequencies.len() < DOF * (nodes.len() - 2));
+
+        // Solve the dense complex equations of motion over every node; the
+        // fixed nodes are made stiff enough not to move
+        let dof_count = DOF * nodes.len();
+        let mut stiffness = DMatrix::<f64>::zeros(dof_count, dof_count);
+        let mut mass = DMatrix::<f64>::zeros(dof_count, dof_count);
+        for element in &mesh.elements {
+            let k = compute_element_stiffness_matrix(element, &nodes, 0.3, 1000.0, 1.0);
+            let m = compute_element_mass_matrix(element, &nodes, 1.0, 1.0);
+            let dofs = element_dofs(element);
+            for (a, row) in dofs.iter().enumerate() {
+                for (b, col) in dofs.iter().enumerate() {
+                    stiffness[(*row, *col)] += k[(a, b)];
+                    mass[(*row, *col)] += m[(a, b)];
+                }
+            }
+        }
+        for (i, node) in nodes.iter().enumerate() {
+            if node.ux.is_some() {
+                stiffness[(DOF * i, DOF * i)] += 1e20;
+                stiffness[(DOF * i + 1, DOF * i + 1)] += 1e20;
+            }
+        }
+        let forces = DVector::from_iterator(
+            dof_count,
+            nodes
+                .iter()
+                .flat_map(|n| [n.fx.unwrap_or(0.0), n.fy.unwrap_or(0.0)])
+                .map(|f| Complex::new(f, 0.0)),
+        );
+
+        for (frequency, response) in std::iter::zip(&frequencies, &result.responses) {
+            let omega = 2.0 * std::f64::consts::PI * frequency;
+            let stiffness_factor = Complex::new(1.0, options.loss_factor);
+            let mass_factor = Complex::new(-omega * omega, omega * options.mass_damping);
+            let dynamic = stiffness.map(|k| stiffness_factor * k) + mass.map(|m| mass_factor * m);
+            let expected = dynamic.lu().solve(&forces).unwrap();
+
+            let tip = response.displacements[40][1];
+            let error = (tip - expected[DOF * 40 + 1]).norm() / expected[DOF * 40 + 1].norm();
+            assert!(error < 1e-6, "{frequency} Hz: {error}");
+        }
+    }
+}
@@ -0,0 +1,2192 @@
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    datatypes::{
+        BodyMaterial, BoundaryRegion, BoundaryRule, BoundaryTarget, ContactMethod, ContactOptions,
+        FailureCriterion, Hardening, HarmonicOptions, Integrator, LoadCase, LoadCombination,
+        Material, MeshOptions, ModelMetadata, NonDesignRegion, NonlinearOptions, PhysicalGroupRef,
+        PlasticityOptions, Probe, RigidLine, SafetyOptions, TimeFunction, TopologyOptions,
+        TransientOptions, Vertex,
+    },
+    error::MagnetiteError,
+    expression::Expression,
+    materials::MaterialLibrary,
+    units::Units,
+};
+use tracing::{info, warn};
+
+/// A Magnetite input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct InputFile {
+    /// The units values are given in. Without this block, values are used
+    /// as-is and must already be consistent.
+    #[serde(default)]
+    pub units: Option<Units>,
+    /// Properties of the part, its material and its mesh
+    pub metadata: MetadataInput,
+    /// Named boundary rules. Rules are applied in order, so later rules
+    /// override earlier ones where they overlap.
+    pub boundary_conditions: IndexMap<String, BoundaryConditionInput>,
+    /// Named load cases, each solved on top of the shared
+    /// `boundary_conditions`. All load cases must constrain the same degrees
+    /// of freedom.
+    #[serde(default)]
+    pub load_cases: IndexMap<String, LoadCaseInput>,
+    /// Named linear combinations of load cases, as factors by load case
+    /// name, e.g. `{ "dead": 1.2, "live": 1.6 }`
+    #[serde(default)]
+    pub combinations: IndexMap<String, IndexMap<String, f64>>,
+    /// Factor of safety settings
+    #[serde(default)]
+    pub safety: SafetyInput,
+    /// Parameters to vary with `magnetite sweep`
+    pub sweep: Option<SweepInput>,
+    /// Topology optimisation settings for `magnetite topology`
+    pub topology: Option<TopologyInput>,
+    /// Solve with large displacements, applying the loads in increments.
+    /// Without this or a `plasticity` section, the solve is linear.
+    pub nonlinear: Option<NonlinearInput>,
+    /// Let the material yield, with von Mises plasticity. Loads are applied
+    /// in the increments of the `nonlinear` section, if there is one.
+    pub plasticity: Option<PlasticityInput>,
+    /// Transient dynamic settings for `magnetite transient`
+    pub transient: Option<TransientInput>,
+    /// Frequency response settings for `magnetite harmonic`
+    pub harmonic: Option<HarmonicInput>,
+    /// Frictionless contact between bodies and against rigid lines
+    pub contact: Option<ContactInput>,
+}
+
+/// The `contact` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ContactInput {
+    #[serde(default)]
+    pub method: ContactMethodInput,
+    /// The penalty stiffness, as a multiple of the Young's modulus times the
+    /// part thickness
+    #[serde(default = "default_contact_penalty")]
+    pub penalty: f64,
+    /// Nodes further than this from another body or an obstacle never touch
+    /// it. Defaults to the maximum characteristic length.
+    pub search_distance: Option<f64>,
+    /// The largest overlap accepted by the augmented Lagrangian method.
+    /// Defaults to 1e-4 times the maximum characteristic length.
+    pub tolerance: Option<f64>,
+    /// The most active set iterations
+    #[serde(default = "default_contact_iterations")]
+    pub max_iterations: usize,
+    /// Named rigid lines. Parts stay on the left of each line, looking from
+    /// `start` to `end`.
+    #[serde(default)]
+    pub obstacles: IndexMap<String, ObstacleInput>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ContactMethodInput {
+    #[default]
+    Penalty,
+    AugmentedLagrangian,
+}
+
+fn default_contact_penalty() -> f64 {
+    100.0
+}
+
+fn default_contact_iterations() -> usize {
+    50
+}
+
+/// A single entry of the contact `obstacles` section
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ObstacleInput {
+    pub start: ProbeInput,
+    pub end: ProbeInput,
+}
+
+/// The `harmonic` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HarmonicInput {
+    /// The load frequencies in Hz; a list, or a range
+    pub frequencies: FrequenciesInput,
+    /// Rayleigh damping coefficients
+    #[serde(default)]
+    pub rayleigh: RayleighInput,
+    /// The structural damping loss factor, which makes the stiffness
+    /// `(1 + i * loss_factor) * K`
+    #[serde(default)]
+    pub loss_factor: f64,
+    /// Named points whose response is tabulated against frequency. Each
+    /// probe reports the node nearest to it.
+    #[serde(default)]
+    pub probes: IndexMap<String, ProbeInput>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FrequenciesInput {
+    List(Vec<f64>),
+    Range(FrequencyRangeInput),
+}
+
+/// Evenly spaced frequencies from `start` to `stop`, inclusive
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct FrequencyRangeInput {
+    pub start: f64,
+    pub stop: f64,
+    /// The number of frequencies
+    #[serde(default = "default_frequency_steps")]
+    pub steps: usize,
+    #[serde(default)]
+    pub spacing: SpacingInput,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SpacingInput {
+    #[default]
+    Linear,
+    /// Evenly spaced on a log scale; `start` must be positive
+    Logarithmic,
+}
+
+fn default_frequency_steps() -> usize {
+    50
+}
+
+/// A point
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProbeInput {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The `transient` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TransientInput {
+    /// The length of the simulation, in seconds
+    pub duration: f64,
+    /// The time step, in seconds
+    pub time_step: f64,
+    /// The time between saved results, in seconds. Defaults to every time
+    /// step.
+    pub output_interval: Option<f64>,
+    /// The time integration scheme
+    #[serde(default)]
+    pub method: IntegratorInput,
+    /// The Newmark beta parameter
+    #[serde(default = "default_newmark_beta")]
+    pub beta: f64,
+    /// The Newmark gamma parameter
+    #[serde(default = "default_newmark_gamma")]
+    pub gamma: f64,
+    /// The HHT alpha parameter, between -1/3 and 0. More negative values
+    /// damp high frequencies more.
+    #[serde(default = "default_hht_alpha")]
+    pub alpha: f64,
+    /// Rayleigh damping coefficients
+    #[serde(default)]
+    pub rayleigh: RayleighInput,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorInput {
+    /// Newmark-beta, with `beta` and `gamma`
+    #[default]
+    Newmark,
+    /// Hilber-Hughes-Taylor, with `alpha`
+    Hht,
+}
+
+/// Rayleigh damping, `C = mass * M + stiffness * K`
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RayleighInput {
+    /// The mass proportional coefficient, in 1/s
+    #[serde(default)]
+    pub mass: f64,
+    /// The stiffness proportional coefficient, in s
+    #[serde(default)]
+    pub stiffness: f64,
+}
+
+fn default_newmark_beta() -> f64 {
+    0.25
+}
+
+fn default_newmark_gamma() -> f64 {
+    0.5
+}
+
+fn default_hht_alpha() -> f64 {
+    -0.05
+}
+
+/// The `plasticity` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PlasticityInput {
+    /// How the yield surface changes as the material yields
+    #[serde(default)]
+    pub hardening: HardeningInput,
+    /// The slope of the stress against plastic strain curve in uniaxial
+    /// tension, in stress units. Zero, the default, is perfectly plastic.
+    #[serde(default)]
+    pub hardening_modulus: f64,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HardeningInput {
+    #[default]
+    Isotropic,
+    Kinematic,
+}
+
+/// The `nonlinear` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NonlinearInput {
+    /// The number of equal steps the loads and prescribed displacements are
+    /// applied in
+    #[serde(default = "default_increments")]
+    pub increments: usize,
+    /// The most Newton-Raphson iterations in each increment
+    #[serde(default = "default_newton_iterations")]
+    pub max_iterations: usize,
+    /// Increments converge once the out-of-balance force is this fraction
+    /// of the applied and reaction forces
+    #[serde(default = "default_residual_tolerance")]
+    pub tolerance: f64,
+}
+
+fn default_increments() -> usize {
+    10
+}
+
+fn default_newton_iterations() -> usize {
+    25
+}
+
+fn default_residual_tolerance() -> f64 {
+    1e-6
+}
+
+/// The `topology` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TopologyInput {
+    /// The fraction of the design domain's area to keep, between 0 and 1
+    pub volume_fraction: f64,
+    /// The SIMP penalty exponent on element densities
+    #[serde(default = "default_penalty")]
+    pub penalty: f64,
+    /// The radius of the sensitivity filter. A few element sizes is
+    /// typical; larger radii give thicker, simpler members.
+    pub filter_radius: f64,
+    /// The lowest density an element can take
+    #[serde(default = "default_minimum_density")]
+    pub minimum_density: f64,
+    /// The largest change in an element's density in one iteration
+    #[serde(default = "default_move_limit")]
+    pub move_limit: f64,
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+    /// Optimisation stops once no density changes by more than this
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f64,
+    /// Named regions that are kept solid or void. An element is in a region
+    /// if its centroid is.
+    #[serde(default)]
+    pub non_design: IndexMap<String, NonDesignInput>,
+}
+
+fn default_penalty() -> f64 {
+    3.0
+}
+
+fn default_minimum_density() -> f64 {
+    1e-3
+}
+
+fn default_move_limit() -> f64 {
+    0.2
+}
+
+fn default_max_iterations() -> usize {
+    100
+}
+
+fn default_tolerance() -> f64 {
+    0.01
+}
+
+/// A single entry of the topology `non_design` section
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct NonDesignInput {
+    pub region: RegionInput,
+    #[serde(default)]
+    pub density: NonDesignDensity,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NonDesignDensity {
+    #[default]
+    Solid,
+    Void,
+}
+
+/// The `sweep` section of an input file
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SweepInput {
+    /// How parameter values are combined into cases
+    #[serde(default)]
+    pub mode: SweepMode,
+    /// Values of each swept parameter, by dotted path into the input file,
+    /// e.g. `metadata.part_thickness` or
+    /// `boundary_conditions.load.targets.fy`. `geometry.<name>` parameters
+    /// replace `${name}` in the geometry files.
+    pub parameters: IndexMap<String, Vec<serde_json::Value>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SweepMode {
+    /// Run every combination of the parameter values
+    #[default]
+    Grid,
+    /// Run the first value of every parameter together, then the second,
+    /// and so on
+    List,
+}
+
+/// A single entry of the `load_cases` section
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LoadCaseInput {
+    /// Named boundary rules, applied after the shared boundary conditions
+    pub boundary_conditions: IndexMap<String, BoundaryConditionInput>,
+}
+
+/// The `safety` section of an input file
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SafetyInput {
+    /// The failure criterion used to compute factors of safety against yield
+    #[serde(default)]
+    pub criterion: CriterionInput,
+    /// The lowest acceptable factor of safety. If any element falls below
+    /// it, the run fails.
+    pub minimum_factor: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CriterionInput {
+    #[default]
+    VonMises,
+    Tresca,
+}
+
+/// The `metadata` section of an input file
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MetadataInput {
+    /// The thickness of the part
+    pub part_thickness: f64,
+    /// The name of a material in the material library, e.g. "Al6061-T6"
+    pub material: Option<String>,
+    /// A json, toml or yaml file of materials, in SI units, that add to or
+    /// override the built-in library. Relative to the input file.
+    pub material_library: Option<String>,
+    /// The Young's modulus of the material. Overrides the library value.
+    pub material_elasticity: Option<f64>,
+    /// The Poisson's ratio of the material. Overrides the library value.
+    pub poisson_ratio: Option<f64>,
+    /// The yield strength of the material. Overrides the library value.
+    pub yield_strength: Option<f64>,
+    /// The ultimate strength of the material. Overrides the library value.
+    pub ultimate_strength: Option<f64>,
+    /// The mass density of the material in kg/m³, whatever the declared
+    /// units. Overrides the library value.
+    pub density: Option<f64>,
+    /// The library material of each named body, e.g. {"pin": "Steel-A36"}.
+    /// Other bodies are made of the part's material.
+    #[serde(default)]
+    pub body_materials: IndexMap<String, String>,
+    /// The minimum mesh element size
+    #[serde(default)]
+    pub characteristic_length_min: f32,
+    /// The maximum mesh element size
+    pub characteristic_length_max: f32,
+}
+
+/// A single entry of the `boundary_conditions` section
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BoundaryConditionInput {
+    /// The nodes this rule applies to
+    pub region: RegionInput,
+    /// The displacements and forces applied to each node in the region
+    pub targets: TargetsInput,
+}
+
+/// The region of a boundary rule. Omitted limits are unbounded.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RegionInput {
+    pub x_target_min: Option<f64>,
+    pub x_target_max: Option<f64>,
+    pub y_target_min: Option<f64>,
+    pub y_target_max: Option<f64>,
+    /// Restricts the region to a physical group, by name or by tag. Groups
+    /// come from a .msh geometry, or are the bodies of svg and csv geometry.
+    pub physical_group: Option<PhysicalGroupInput>,
+}
+
+/// A reference to a physical group, by name or by tag
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum PhysicalGroupInput {
+    Name(String),
+    Tag(usize),
+}
+
+/// The targets of a boundary rule. Exactly one of `ux` and `fx`, and one of
+/// `uy` and `fy`, must be set.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct TargetsInput {
+    /// Prescribed displacement in x
+    pub ux: Option<f64>,
+    /// Prescribed displacement in y
+    pub uy: Option<f64>,
+    /// Applied force in x
+    pub fx: Option<f64>,
+    /// Applied force in y
+    pub fy: Option<f64>,
+    /// Scales the targets over time in `magnetite transient`; either an
+    /// expression of `t` in seconds, e.g. `"sin(2 * pi * 50 * t)"`, or a
+    /// table of `[t, factor]` points
+    pub time_function: Option<TimeFunctionInput>,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum TimeFunctionInput {
+    Expression(String),
+    Table(Vec<[f64; 2]>),
+}
+
+/// File formats accepted for input files
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl InputFormat {
+    /// Picks the input format from a file's extension
+    pub fn from_path(input_file: &str) -> Result<InputFormat, MagnetiteError> {
+        let extension = std::path::Path::new(input_file)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("json") => Ok(InputFormat::Json),
+            Some("toml") => Ok(InputFormat::Toml),
+            Some("yaml") | Some("yml") => Ok(InputFormat::Yaml),
+            _ => Err(MagnetiteError::Input(format!(
+                "Unrecognized input filetype {input_file}. Use .json, .toml or .yaml"
+            ))),
+        }
+    }
+}
+
+/// Builds an input error pointing at a location in the input file
+fn input_error(
+    source: &str,
+    position: Option<(usize, usize)>,
+    path: &serde_path_to_error::Path,
+    message: &str,
+) -> MagnetiteError {
+    let location = match position {
+        Some((line, column)) => format!("{source} line {line}, column {column}"),
+        None => source.to_string(),
+    };
+    MagnetiteError::Input(format!("{location}: error at '{path}': {message}"))
+}
+
+/// Converts a byte offset into one-based line and column numbers
+fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = 1 + before.matches('\n').count();
+    let column = 1 + before.len() - before.rfind('\n').map_or(0, |i| i + 1);
+    (line, column)
+}
+
+/// Removes a trailing " at line X column Y" from an error message
+fn strip_position(message: &str) -> &str {
+    match message.rfind(" at line ") {
+        Some(index) => &message[..index],
+        None => message,
+    }
+}
+
+/// Parses the contents of an input file, or of any other file that shares
+/// its formats, such as a material library
+///
+/// # Arguments
+/// * `contents` - The contents of the file
+/// * `source` - The name of the file, used in error messages
+/// * `format` - The format of the file
+///
+/// # Returns
+/// The parsed document, usually an InputFile
+pub fn parse_input<T: DeserializeOwned>(
+    contents: &str,
+    source: &str,
+    format: InputFormat,
+) -> Result<T, MagnetiteError> {
+    match format {
+        InputFormat::Json => {
+            let deserializer = &mut serde_json::Deserializer::from_str(contents);
+            serde_path_to_error::deserialize(deserializer).map_err(|err| {
+                let inner = err.inner();
+                input_error(
+                    source,
+                    Some((inner.line(), inner.column())),
+                    err.path(),
+                    strip_position(&inner.to_string()),
+                )
+            })
+        }
+        InputFormat::Toml => {
+            let deserializer = toml::Deserializer::new(contents);
+            serde_path_to_error::deserialize(deserializer).map_err(|err| {
+                let inner = err.inner();
+                input_error(
+                    source,
+                    inner.span().map(|span| line_column(contents, span.start)),
+                    err.path(),
+                    inner.message(),
+                )
+            })
+        }
+        InputFormat::Yaml => {
+            let deserializer = serde_yaml::Deserializer::from_str(contents);
+            serde_path_to_error::deserialize(deserializer).map_err(|err| {
+                let inner = err.inner();
+                let path = err.path().to_string();
+                // serde_yaml prefixes messages with the path of the parent
+                let message = inner.to_string();
+                let message = strip_position(&message);
+                let message = match message.split_once(": ") {
+                    Some((prefix, rest)) if path.starts_with(prefix) => rest,
+                    _ => message,
+                };
+                input_error(
+                    source,
+                    inner.location().map(|l| (l.line(), l.column())),
+                    err.path(),
+                    message,
+                )
+            })
+        }
+    }
+}
+
+/// Converts the metadata section into ModelMetadata, in SI units
+///
+/// # Arguments
+/// * `metadata` - The parsed metadata section
+/// * `units` - The units the input file is written in, if declared
+/// * `library` - The material library to look up named materials in
+///
+/// # Returns
+/// A ModelMetadata instance
+fn convert_metadata(
+    metadata: &MetadataInput,
+    units: Option<&Units>,
+    library: &MaterialLibrary,
+) -> Result<ModelMetadata, MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress = units.map_or(1.0, |u| u.stress.factor());
+
+    // Start from the library material, if any, then apply explicit values
+    let base = match &metadata.material {
+        Some(name) => Some(library.material(name)?),
+        None => None,
+    };
+    let youngs_modulus = match (metadata.material_elasticity, &base) {
+        (Some(e), _) => e * stress,
+        (None, Some(base)) => base.youngs_modulus,
+        (None, None) => {
+            return Err(MagnetiteError::Input(
+                "metadata needs either a material or material_elasticity".to_owned(),
+            ))
+        }
+    };
+    let poisson_ratio = match (metadata.poisson_ratio, &base) {
+        (Some(nu), _) => nu,
+        (None, Some(base)) => base.poisson_ratio,
+        (None, None) => {
+            return Err(MagnetiteError::Input(
+                "metadata needs either a material or poisson_ratio".to_owned(),
+            ))
+        }
+    };
+
+    if metadata.part_thickness <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "metadata.part_thickness must be positive".to_owned(),
+        ));
+    }
+    if youngs_modulus <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "metadata.material_elasticity must be positive".to_owned(),
+        ));
+    }
+    if poisson_ratio <= -1.0 || poisson_ratio >= 0.5 {
+        return Err(MagnetiteError::Input(
+            "metadata.poisson_ratio must be between -1 and 0.5".to_owned(),
+        ));
+    }
+    if metadata.density.is_some_and(|density| density <= 0.0) {
+        return Err(MagnetiteError::Input(
+            "metadata.density must be positive".to_owned(),
+        ));
+    }
+    if metadata.characteristic_length_min > metadata.characteristic_length_max {
+        return Err(MagnetiteError::Input(
+            "metadata.characteristic_length_min is greater than characteristic_length_max"
+                .to_owned(),
+        ));
+    }
+
+    Ok(ModelMetadata {
+        material: Material {
+            youngs_modulus,
+            poisson_ratio,
+            density: metadata.density.or(base.as_ref().and_then(|m| m.density)),
+            thermal_expansion: base.as_ref().and_then(|m| m.thermal_expansion),
+            yield_strength: metadata
+                .yield_strength
+                .map(|s| s * stress)
+                .or(base.as_ref().and_then(|m| m.yield_strength)),
+            ultimate_strength: metadata
+                .ultimate_strength
+                .map(|s| s * stress)
+                .or(base.as_ref().and_then(|m| m.ultimate_strength)),
+        },
+        body_materials: metadata
+            .body_materials
+            .iter()
+            .map(|(body, name)| {
+                Ok(BodyMaterial {
+                    body: body.clone(),
+                    material: library.material(name)?,
+                })
+            })
+            .collect::<Result<_, MagnetiteError>>()?,
+        part_thickness: metadata.part_thickness * length,
+        mesh_options: MeshOptions {
+            characteristic_length_min: metadata.characteristic_length_min * length as f32,
+            characteristic_length_max: metadata.characteristic_length_max * length as f32,
+            gmsh_path: None,
+            gmsh_log: None,
+            keep_intermediates: false,
+        },
+        units: units.copied(),
+        safety: SafetyOptions::default(),
+    })
+}
+
+/// Converts the safety section into SafetyOptions
+///
+/// # Arguments
+/// * `safety` - The parsed safety section
+///
+/// # Returns
+/// A SafetyOptions instance
+fn convert_safety(safety: &SafetyInput) -> Result<SafetyOptions, MagnetiteError> {
+    if let Some(minimum) = safety.minimum_factor {
+        if minimum <= 0.0 {
+            return Err(MagnetiteError::Input(
+                "safety.minimum_factor must be positive".to_owned(),
+            ));
+        }
+    }
+
+    Ok(SafetyOptions {
+        criterion: match safety.criterion {
+            CriterionInput::VonMises => FailureCriterion::VonMises,
+            CriterionInput::Tresca => FailureCriterion::Tresca,
+        },
+        minimum_factor: safety.minimum_factor,
+    })
+}
+
+/// Converts a region into a BoundaryRegion, in SI units
+///
+/// # Arguments
+/// * `name` - The name of the boundary condition or region
+/// * `region` - The parsed region
+/// * `units` - The units the input file is written in, if declared
+///
+/// # Returns
+/// A validated BoundaryRegion
+fn convert_region(
+    name: &str,
+    region: &RegionInput,
+    units: Option<&Units>,
+) -> Result<BoundaryRegion, MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+
+    let boundary_region = BoundaryRegion {
+        x_min: region.x_target_min.map_or(f64::MIN, |x| x * length),
+        x_max: region.x_target_max.map_or(f64::MAX, |x| x * length),
+        y_min: region.y_target_min.map_or(f64::MIN, |y| y * length),
+        y_max: region.y_target_max.map_or(f64::MAX, |y| y * length),
+        physical_group: region.physical_group.as_ref().map(|group| match group {
+            PhysicalGroupInput::Name(name) => PhysicalGroupRef::Name(name.clone()),
+            PhysicalGroupInput::Tag(tag) => PhysicalGroupRef::Tag(*tag),
+        }),
+    };
+
+    if boundary_region.x_min > boundary_region.x_max {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' has x_target_min greater than x_target_max"
+        )));
+    }
+    if boundary_region.y_min > boundary_region.y_max {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' has y_target_min greater than y_target_max"
+        )));
+    }
+
+    Ok(boundary_region)
+}
+
+/// Converts the topology section into TopologyOptions, in SI units
+///
+/// # Arguments
+/// * `topology` - The parsed topology section
+/// * `units` - The units the input file is written in, if declared
+///
+/// # Returns
+/// Validated TopologyOptions
+fn convert_topology(
+    topology: &TopologyInput,
+    units: Option<&Units>,
+) -> Result<TopologyOptions, MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+
+    if topology.volume_fraction <= 0.0 || topology.volume_fraction > 1.0 {
+        return Err(MagnetiteError::Input(
+            "topology.volume_fraction must be between 0 and 1".to_owned(),
+        ));
+    }
+    if topology.penalty < 1.0 {
+        return Err(MagnetiteError::Input(
+            "topology.penalty must be at least 1".to_owned(),
+        ));
+    }
+    if topology.filter_radius <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "topology.filter_radius must be positive".to_owned(),
+        ));
+    }
+    if topology.minimum_density <= 0.0 || topology.minimum_density >= topology.volume_fraction {
+        return Err(MagnetiteError::Input(
+            "topology.minimum_density must be positive and less than volume_fraction".to_owned(),
+        ));
+    }
+    if topology.move_limit <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "topology.move_limit must be positive".to_owned(),
+        ));
+    }
+
+    let non_design = topology
+        .non_design
+        .iter()
+        .map(|(name, region)| {
+            Ok(NonDesignRegion {
+                name: name.clone(),
+                region: convert_region(name, &region.region, units)?,
+                solid: matches!(region.density, NonDesignDensity::Solid),
+            })
+        })
+        .collect::<Result<Vec<NonDesignRegion>, MagnetiteError>>()?;
+
+    Ok(TopologyOptions {
+        volume_fraction: topology.volume_fraction,
+        penalty: topology.penalty,
+        filter_radius: topology.filter_radius * length,
+        minimum_density: topology.minimum_density,
+        move_limit: topology.move_limit,
+        max_iterations: topology.max_iterations,
+        tolerance: topology.tolerance,
+        non_design,
+    })
+}
+
+/// Converts the nonlinear and plasticity sections into NonlinearOptions.
+/// The solve is geometrically nonlinear if there is a nonlinear section;
+/// plasticity alone uses the default increments with small displacements.
+///
+/// # Arguments
+/// * `input` - The parsed input file
+/// * `material` - The converted material, in SI units
+///
+/// # Returns
+/// The converted NonlinearOptions, or None for a linear solve
+fn convert_nonlinear(
+    input: &InputFile,
+    material: &Material,
+) -> Result<Option<NonlinearOptions>, MagnetiteError> {
+    let defaults = NonlinearInput {
+        increments: default_increments(),
+        max_iterations: default_newton_iterations(),
+        tolerance: default_residual_tolerance(),
+    };
+    let nonlinear = match (&input.nonlinear, &input.plasticity) {
+        (Some(nonlinear), _) => nonlinear,
+        (None, Some(_)) => &defaults,
+        (None, None) => return Ok(None),
+    };
+
+    if nonlinear.increments == 0 {
+        return Err(MagnetiteError::Input(
+            "nonlinear.increments must be at least 1".to_owned(),
+        ));
+    }
+    if nonlinear.max_iterations == 0 {
+        return Err(MagnetiteError::Input(
+            "nonlinear.max_iterations must be at least 1".to_owned(),
+        ));
+    }
+    if nonlinear.tolerance <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "nonlinear.tolerance must be positive".to_owned(),
+        ));
+    }
+
+    let plasticity = match &input.plasticity {
+        Some(plasticity) => {
+            if material.yield_strength.is_none() {
+                return Err(MagnetiteError::Input(
+                    "plasticity needs a yield strength; set metadata.yield_strength or use a library material".to_owned(),
+                ));
+            }
+            if plasticity.hardening_modulus < 0.0 {
+                return Err(MagnetiteError::Input(
+                    "plasticity.hardening_modulus must not be negative".to_owned(),
+                ));
+            }
+            let stress = input.units.as_ref().map_or(1.0, |u| u.stress.factor());
+            Some(PlasticityOptions {
+                hardening: match plasticity.hardening {
+                    HardeningInput::Isotropic => Hardening::Isotropic,
+                    HardeningInput::Kinematic => Hardening::Kinematic,
+                },
+                hardening_modulus: plasticity.hardening_modulus * stress,
+            })
+        }
+        None => None,
+    };
+
+    Ok(Some(NonlinearOptions {
+        increments: nonlinear.increments,
+        max_iterations: nonlinear.max_iterations,
+        tolerance: nonlinear.tolerance,
+        geometric: input.nonlinear.is_some(),
+        plasticity,
+    }))
+}
+
+/// Converts a boundary condition into a BoundaryRule, in SI units
+///
+/// # Arguments
+/// * `name` - The name of the boundary condition
+/// * `condition` - The parsed boundary condition
+/// * `units` - The units the input file is written in, if declared
+///
+/// # Returns
+/// A validated BoundaryRule
+fn convert_boundary_rule(
+    name: &str,
+    condition: &BoundaryConditionInput,
+    units: Option<&Units>,
+) -> Result<BoundaryRule, MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let force = units.map_or(1.0, |u| u.force.factor());
+
+    let boundary_region = convert_region(name, &condition.region, units)?;
+
+    let targets = &condition.targets;
+    let time_function = match &targets.time_function {
+        Some(time_function) => Some(convert_time_function(name, time_function)?),
+        None => None,
+    };
+    let boundary_target = BoundaryTarget {
+        ux: targets.ux.map(|u| u * length),
+        uy: targets.uy.map(|u| u * length),
+        fx: targets.fx.map(|f| f * force),
+        fy: targets.fy.map(|f| f * force),
+        time_function,
+    };
+
+    // Validate input
+    if boundary_target.fx.is_none() && boundary_target.ux.is_none() {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' is under-constrained in x-axis"
+        )));
+    }
+    if boundary_target.fy.is_none() && boundary_target.uy.is_none() {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' is under-constrained in y-axis"
+        )));
+    }
+    if boundary_target.fx.is_some() && boundary_target.ux.is_some() {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' is over-constrained in x-axis"
+        )));
+    }
+    if boundary_target.fy.is_some() && boundary_target.uy.is_some() {
+        return Err(MagnetiteError::Input(format!(
+            "Boundary '{name}' is over-constrained in y-axis"
+        )));
+    }
+
+    Ok(BoundaryRule {
+        name: name.to_string(),
+        region: boundary_region,
+        target: boundary_target,
+    })
+}
+
+/// Converts a boundary rule's time function
+///
+/// # Arguments
+/// * `name` - The name of the boundary rule
+/// * `time_function` - The parsed time function
+///
+/// # Returns
+/// The converted TimeFunction
+fn convert_time_function(
+    name: &str,
+    time_function: &TimeFunctionInput,
+) -> Result<TimeFunction, MagnetiteError> {
+    match time_function {
+        TimeFunctionInput::Expression(source) => match Expression::parse(source) {
+            Ok(expression) => Ok(TimeFunction::Expression(expression)),
+            Err(err) => Err(MagnetiteError::Input(format!(
+                "Boundary '{name}' has an invalid time function: {err}"
+            ))),
+        },
+        TimeFunctionInput::Table(points) => {
+            if points.is_empty() {
+                return Err(MagnetiteError::Input(format!(
+                    "Boundary '{name}' has an empty time function table"
+                )));
+            }
+            if points.windows(2).any(|pair| pair[1][0] <= pair[0][0]) {
+                return Err(MagnetiteError::Input(format!(
+                    "Boundary '{name}' has a time function table whose times don't increase"
+                )));
+            }
+            Ok(TimeFunction::Table(
+                points.iter().map(|[t, factor]| (*t, *factor)).collect(),
+            ))
+        }
+    }
+}
+
+/// Converts the transient section into TransientOptions
+///
+/// # Arguments
+/// * `transient` - The parsed transient section
+/// * `material` - The converted material, which needs a density
+///
+/// # Returns
+/// The converted TransientOptions
+fn convert_transient(
+    transient: &TransientInput,
+    material: &Material,
+) -> Result<TransientOptions, MagnetiteError> {
+    if material.density.is_none() {
+        return Err(MagnetiteError::Input(
+            "Transient analysis needs a material density; set metadata.density or use a library material".to_owned(),
+        ));
+    }
+    if transient.duration <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "transient.duration must be positive".to_owned(),
+        ));
+    }
+    if transient.time_step <= 0.0 || transient.time_step > transient.duration {
+        return Err(MagnetiteError::Input(
+            "transient.time_step must be positive and no longer than the duration".to_owned(),
+        ));
+    }
+    let output_interval = transient.output_interval.unwrap_or(transient.time_step);
+    if output_interval < transient.time_step {
+        return Err(MagnetiteError::Input(
+            "transient.output_interval must be at least the time step".to_owned(),
+        ));
+    }
+    if transient.rayleigh.mass < 0.0 || transient.rayleigh.stiffness < 0.0 {
+        return Err(MagnetiteError::Input(
+            "transient.rayleigh coefficients must not be negative".to_owned(),
+        ));
+    }
+
+    let integrator = match transient.method {
+        IntegratorInput::Newmark => {
+            let (beta, gamma) = (transient.beta, transient.gamma);
+            // Implicit Newmark is unconditionally stable in this range
+            if !(gamma >= 0.5 && 2.0 * beta >= gamma) {
+                return Err(MagnetiteError::Input(
+                    "transient.beta and gamma must satisfy 2 beta >= gamma >= 0.5".to_owned(),
+                ));
+            }
+            Integrator::Newmark { beta, gamma }
+        }
+        IntegratorInput::Hht => {
+            if !(-1.0 / 3.0..=0.0).contains(&transient.alpha) {
+                return Err(MagnetiteError::Input(
+                    "transient.alpha must be between -1/3 and 0".to_owned(),
+                ));
+            }
+            Integrator::Hht {
+                alpha: transient.alpha,
+            }
+        }
+    };
+
+    Ok(TransientOptions {
+        duration: transient.duration,
+        time_step: transient.time_step,
+        output_interval,
+        integrator,
+        mass_damping: transient.rayleigh.mass,
+        stiffness_damping: transient.rayleigh.stiffness,
+    })
+}
+
+/// Converts the harmonic section into HarmonicOptions
+///
+/// # Arguments
+/// * `harmonic` - The parsed harmonic section
+/// * `material` - The converted material, which needs a density
+/// * `units` - The units of the input file, if declared
+///
+/// # Returns
+/// The converted HarmonicOptions
+fn convert_harmonic(
+    harmonic: &HarmonicInput,
+    material: &Material,
+    units: Option<&Units>,
+) -> Result<HarmonicOptions, MagnetiteError> {
+    if material.density.is_none() {
+        return Err(MagnetiteError::Input(
+            "Harmonic analysis needs a material density; set metadata.density or use a library material".to_owned(),
+        ));
+    }
+
+    let frequencies = match &harmonic.frequencies {
+        FrequenciesInput::List(frequencies) => frequencies.clone(),
+        FrequenciesInput::Range(range) => {
+            if range.steps == 0 {
+                return Err(MagnetiteError::Input(
+                    "harmonic.frequencies.steps must be at least 1".to_owned(),
+                ));
+            }
+            if range.stop < range.start {
+                return Err(MagnetiteError::Input(
+                    "harmonic.frequencies.stop must not be below start".to_owned(),
+                ));
+            }
+            if matches!(range.spacing, SpacingInput::Logarithmic) && range.start <= 0.0 {
+                return Err(MagnetiteError::Input(
+                    "harmonic.frequencies.start must be positive for logarithmic spacing"
+                        .to_owned(),
+                ));
+            }
+            let fraction = |i: usize| match range.steps {
+                1 => 0.0,
+                steps => i as f64 / (steps - 1) as f64,
+            };
+            (0..range.steps)
+                .map(|i| match range.spacing {
+                    SpacingInput::Linear => range.start + (range.stop - range.start) * fraction(i),
+                    SpacingInput::Logarithmic => {
+                        range.start * (range.stop / range.start).powf(fraction(i))
+                    }
+                })
+                .collect()
+        }
+    };
+    if frequencies.is_empty() {
+        return Err(MagnetiteError::Input(
+            "harmonic.frequencies must not be empty".to_owned(),
+        ));
+    }
+    if frequencies.iter().any(|f| !f.is_finite() || *f < 0.0) {
+        return Err(MagnetiteError::Input(
+            "harmonic.frequencies must not be negative".to_owned(),
+        ));
+    }
+    if harmonic.rayleigh.mass < 0.0 || harmonic.rayleigh.stiffness < 0.0 {
+        return Err(MagnetiteError::Input(
+            "harmonic.rayleigh coefficients must not be negative".to_owned(),
+        ));
+    }
+    if harmonic.loss_factor < 0.0 {
+        return Err(MagnetiteError::Input(
+            "harmonic.loss_factor must not be negative".to_owned(),
+        ));
+    }
+
+    let length = units.map_or(1.0, |u| u.length.factor());
+    Ok(HarmonicOptions {
+        frequencies,
+        mass_damping: harmonic.rayleigh.mass,
+        stiffness_damping: harmonic.rayleigh.stiffness,
+        loss_factor: harmonic.loss_factor,
+        probes: harmonic
+            .probes
+            .iter()
+            .map(|(name, probe)| Probe {
+                name: name.clone(),
+                vertex: Vertex {
+                    x: probe.x * length,
+                    y: probe.y * length,
+                },
+            })
+            .collect(),
+    })
+}
+
+/// Checks that a load case or combination name can be used in output
+/// filenames
+fn check_case_name(kind: &str, name: &str) -> Result<(), MagnetiteError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(MagnetiteError::Input(format!(
+            "{kind} name '{name}' must only contain letters, numbers, '-' and '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// Converts the load case and combination sections
+///
+/// # Arguments
+/// * `input` - The parsed input file
+///
+/// # Returns
+/// A tuple with the LoadCases and LoadCombinations, in that order
+fn convert_load_cases(
+    input: &InputFile,
+) -> Result<(Vec<LoadCase>, Vec<LoadCombination>), MagnetiteError> {
+    let units = input.units.as_ref();
+
+    let mut load_cases = Vec::new();
+    for (case_name, case) in &input.load_cases {
+        check_case_name("Load case", case_name)?;
+        let boundary_rules = case
+            .boundary_conditions
+            .iter()
+            .map(|(name, condition)| {
+                convert_boundary_rule(&format!("{case_name}.{name}"), condition, units)
+            })
+            .collect::<Result<Vec<BoundaryRule>, MagnetiteError>>()?;
+
+        load_cases.push(LoadCase {
+            name: case_name.clone(),
+            boundary_rules,
+        });
+    }
+
+    let mut combinations = Vec::new();
+    for (name, factors) in &input.combinations {
+        check_case_name("Combination", name)?;
+        if input.load_cases.contains_key(name) {
+            return Err(MagnetiteError::Input(format!(
+                "Combination '{name}' has the same name as a load case"
+            )));
+        }
+        if factors.is_empty() {
+            return Err(MagnetiteError::Input(format!(
+                "Combination '{name}' has no load cases"
+            )));
+        }
+        if let Some(case_name) = factors.keys().find(|c| !input.load_cases.contains_key(*c)) {
+            return Err(MagnetiteError::Input(format!(
+                "Combination '{name}' references unknown load case '{case_name}'"
+            )));
+        }
+
+        combinations.push(LoadCombination {
+            name: name.clone(),
+            factors: factors.iter().map(|(c, f)| (c.clone(), *f)).collect(),
+        });
+    }
+
+    if !load_cases.is_empty() {
+        info!(
+            "loaded {} load cases and {} combinations from input file",
+            load_cases.len(),
+            combinations.len()
+        );
+    }
+
+    Ok((load_cases, combinations))
+}
+
+/// Converts the contact section into ContactOptions
+///
+/// # Arguments
+/// * `contact` - The contact section
+/// * `mesh_options` - The mesh options, in SI units, which set the default
+///   search distance and tolerance
+/// * `units` - The units of the input file, if declared
+///
+/// # Returns
+/// The converted ContactOptions
+fn convert_contact(
+    contact: &ContactInput,
+    mesh_options: &MeshOptions,
+    units: Option<&Units>,
+) -> Result<ContactOptions, MagnetiteError> {
+    if contact.penalty <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "contact.penalty must be positive".to_owned(),
+        ));
+    }
+    if contact.max_iterations == 0 {
+        return Err(MagnetiteError::Input(
+            "contact.max_iterations must be at least 1".to_owned(),
+        ));
+    }
+
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let element_size = mesh_options.characteristic_length_max as f64;
+    let search_distance = contact.search_distance.map_or(element_size, |d| d * length);
+    let tolerance = contact
+        .tolerance
+        .map_or(1e-4 * element_size, |t| t * length);
+    if search_distance <= 0.0 || tolerance <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "contact.search_distance and contact.tolerance must be positive".to_owned(),
+        ));
+    }
+
+    let point = |p: &ProbeInput| Vertex {
+        x: p.x * length,
+        y: p.y * length,
+    };
+    let mut obstacles = Vec::new();
+    for (name, obstacle) in &contact.obstacles {
+        let (start, end) = (point(&obstacle.start), point(&obstacle.end));
+        if start.x == end.x && start.y == end.y {
+            return Err(MagnetiteError::Input(format!(
+                "contact.obstacles.{name} starts and ends at the same point"
+            )));
+        }
+        obstacles.push(RigidLine {
+            name: name.clone(),
+            start,
+            end,
+        });
+    }
+
+    Ok(ContactOptions {
+        method: match contact.method {
+            ContactMethodInput::Penalty => ContactMethod::Penalty,
+            ContactMethodInput::AugmentedLagrangian => ContactMethod::AugmentedLagrangian,
+        },
+        penalty_factor: contact.penalty,
+        search_distance,
+        tolerance,
+        max_iterations: contact.max_iterations,
+        obstacles,
+    })
+}
+
+/// A converted input file, in SI units
+#[derive(Debug, Clone)]
+pub struct ModelInput {
+    pub metadata: ModelMetadata,
+    /// The shared boundary rules
+    pub boundary_rules: Vec<BoundaryRule>,
+    pub load_cases: Vec<LoadCase>,
+    pub combinations: Vec<LoadCombination>,
+    pub topology: Option<TopologyOptions>,
+    pub nonlinear: Option<NonlinearOptions>,
+    pub transient: Option<TransientOptions>,
+    pub harmonic: Option<HarmonicOptions>,
+    pub contact: Option<ContactOptions>,
+}
+
+/// Converts a parsed input file into model metadata, boundary rules and load
+/// cases. If the file declares its units, values are converted to SI.
+///
+/// # Arguments
+/// * `input` - The parsed input file
+/// * `library` - The material library to look up named materials in
+///
+/// # Returns
+/// The converted ModelInput
+pub fn convert(input: &InputFile, library: &MaterialLibrary) -> Result<ModelInput, MagnetiteError> {
+    let units = input.units.as_ref();
+    if let Some(units) = units {
+        info!(
+            "converting inputs from {}, {} and {} to SI",
+            units.length.name(),
+            units.force.name(),
+            units.stress.name()
+        );
+        if let Some(mismatch) = units.inconsistency() {
+            warn!("{mismatch}. Check that values are in the declared units");
+        }
+    }
+
+    let mut model_metadata = convert_metadata(&input.metadata, units, library)?;
+    model_metadata.safety = convert_safety(&input.safety)?;
+    if model_metadata.safety.minimum_factor.is_some()
+        && model_metadata.material.yield_strength.is_none()
+    {
+        return Err(MagnetiteError::Input(
+            "safety.minimum_factor needs a yield strength; set metadata.yield_strength or use a library material".to_owned(),
+        ));
+    }
+
+    let boundary_rules = input
+        .boundary_conditions
+        .iter()
+        .map(|(name, condition)| convert_boundary_rule(name, condition, units))
+        .collect::<Result<Vec<BoundaryRule>, MagnetiteError>>()?;
+
+    info!(
+        "loaded {} boundary rules from input file",
+        boundary_rules.len()
+    );
+
+    let (load_cases, combinations) = convert_load_cases(input)?;
+    let topology = match &input.topology {
+        Some(topology) => Some(convert_topology(topology, units)?),
+        None => None,
+    };
+    let nonlinear = convert_nonlinear(input, &model_metadata.material)?;
+    if nonlinear.is_some() && !load_cases.is_empty() {
+        return Err(MagnetiteError::Input(
+            "Load cases rely on superposition, so they can't be used with a nonlinear solve"
+                .to_owned(),
+        ));
+    }
+    let transient = match &input.transient {
+        Some(transient) => Some(convert_transient(transient, &model_metadata.material)?),
+        None => None,
+    };
+    let harmonic = match &input.harmonic {
+        Some(harmonic) => Some(convert_harmonic(harmonic, &model_metadata.material, units)?),
+        None => None,
+    };
+    let contact = match &input.contact {
+        Some(contact) => Some(convert_contact(
+            contact,
+            &model_metadata.mesh_options,
+            units,
+        )?),
+        None => None,
+    };
+    if contact.is_some() && (nonlinear.is_some() || !load_cases.is_empty()) {
+        return Err(MagnetiteError::Input(
+            "Contact can't be combined with a nonlinear solve or load cases".to_owned(),
+        ));
+    }
+
+    Ok(ModelInput {
+        metadata: model_metadata,
+        boundary_rules,
+        load_cases,
+        combinations,
+        topology,
+        nonlinear,
+        transient,
+        harmonic,
+        contact,
+    })
+}
+
+/// Loads model metadata, boundary rules and load cases from an input file
+///
+/// # Arguments
+/// * `input_file` - The path to the input json, toml or yaml file
+///
+/// # Returns
+/// The converted ModelInput
+pub fn load(input_file: &str) -> Result<ModelInput, MagnetiteError> {
+    let input: InputFile = read(input_file)?;
+    let library = load_library(input_file, &input)?;
+    convert(&input, &library)
+}
+
+/// Reads and parses an input file
+///
+/// # Arguments
+/// * `input_file` - The path to the input json, toml or yaml file
+///
+/// # Returns
+/// The parsed document, usually an InputFile
+pub fn read<T: DeserializeOwned>(input_file: &str) -> Result<T, MagnetiteError> {
+    let format = InputFormat::from_path(input_file)?;
+    let contents = match std::fs::read_to_string(input_file) {
+        Ok(f) => f,
+        Err(_err) => {
+            return Err(MagnetiteError::Input(format!(
+                "Unable to open input file {}",
+                input_file
+            )))
+        }
+    };
+
+    parse_input(&contents, input_file, format)
+}
+
+/// Builds the material library for an input file; the built-in library,
+/// plus its `material_library` overrides. Library paths are relative to the
+/// input file.
+fn load_library(input_file: &str, input: &InputFile) -> Result<MaterialLibrary, MagnetiteError> {
+    let mut library = MaterialLibrary::builtin();
+    if let Some(library_file) = &input.metadata.material_library {
+        let library_path = std::path::Path::new(input_file)
+            .parent()
+            .unwrap_or(std::path::Path::new(""))
+            .join(library_file);
+        library.load_overrides(&library_path.to_string_lossy())?;
+    }
+    Ok(library)
+}
+
+/// Sets a value in a parsed input document by dotted path, e.g.
+/// `metadata.part_thickness`. Missing objects along the path are created.
+///
+/// # Arguments
+/// * `document` - The parsed input document
+/// * `path` - The dotted path of the value
+/// * `value` - The new value
+pub fn set_parameter(
+    document: &mut serde_json::Value,
+    path: &str,
+    value: serde_json::Value,
+) -> Result<(), MagnetiteError> {
+    let mut target = document;
+    for key in path.split('.') {
+        let serde_json::Value::Object(object) = target else {
+            return Err(MagnetiteError::Input(format!(
+                "Cannot set parameter '{path}'; '{key}' is not inside a section"
+            )));
+        };
+        target = object
+            .entry(key)
+            .or_insert(serde_json::Value::Object(Default::default()));
+    }
+    *target = value;
+    Ok(())
+}
+
+/// Loads an input file like [`load`], with some of its values replaced
+///
+/// # Arguments
+/// * `input_file` - The path to the input json, toml or yaml file
+/// * `parameters` - Dotted paths into the input file and their new values
+///
+/// # Returns
+/// The converted ModelInput
+pub fn load_with_parameters(
+    input_file: &str,
+    parameters: &[(String, serde_json::Value)],
+) -> Result<ModelInput, MagnetiteError> {
+    let mut document: serde_json::Value = read(input_file)?;
+    for (path, value) in parameters {
+        set_parameter(&mut document, path, value.clone())?;
+    }
+
+    let input: InputFile = serde_path_to_error::deserialize(document).map_err(|err| {
+        let message = err.inner().to_string();
+        input_error(input_file, None, err.path(), &message)
+    })?;
+    let library = load_library(input_file, &input)?;
+    convert(&input, &library)
+}
+
+/// Generates a JSON Schema describing input files, for editor validation
+/// and autocompletion
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(InputFile);
+    serde_json::to_string_pretty(&schema).expect("Failed to serialize input schema")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{ForceUnit, LengthUnit, StressUnit};
+
+    const VALID: &str = r#"{
+  "metadata": {
+    "part_thickness": 0.5,
+    "material_elasticity": 69e9,
+    "poisson_ratio": 0.33,
+    "characteristic_length_max": 0.9
+  },
+  "boundary_conditions": {
+    "restraint": {
+      "region": { "x_target_max": -10 },
+      "targets": { "ux": 0, "uy": 0 }
+    },
+    "load": {
+      "region": { "x_target_min": 10, "physical_group": 3 },
+      "targets": { "fx": -6e8, "fy": 0 }
+    }
+  }
+}"#;
+
+    fn input_error(contents: &str) -> String {
+        let result = parse_input(contents, "input.json", InputFormat::Json)
+            .and_then(|input| convert(&input, &MaterialLibrary::builtin()));
+        match result {
+            Err(MagnetiteError::Input(message)) => message,
+            other => panic!("expected an input error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parses_valid_input_with_defaults() {
+        let input = parse_input(VALID, "input.json", InputFormat::Json).unwrap();
+        let ModelInput {
+            metadata,
+            boundary_rules: rules,
+            ..
+        } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+
+        assert_eq!(metadata.material.youngs_modulus, 69e9);
+        assert_eq!(metadata.mesh_options.characteristic_length_min, 0.0);
+
+        // Rules keep the order they were written in
+        let names: Vec<&str> = rules.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["restraint", "load"]);
+        assert_eq!(rules[0].region.x_min, f64::MIN);
+        assert_eq!(rules[0].target.fx, None);
+        assert_eq!(
+            rules[1].region.physical_group,
+            Some(PhysicalGroupRef::Tag(3))
+        );
+    }
+
+    #[test]
+    fn reports_path_and_position_of_bad_values() {
+        let message = input_error(&VALID.replace("0.5", "\"thick\""));
+        assert!(
+            message.starts_with("input.json line 3, column"),
+            "{message}"
+        );
+        assert!(message.contains("'metadata.part_thickness'"), "{message}");
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let message = input_error(&VALID.replace("x_target_max", "x_max"));
+        assert!(
+            message.contains("'boundary_conditions.restraint.region.x_max'"),
+            "{message}"
+        );
+        assert!(message.contains("unknown field `x_max`"), "{message}");
+    }
+
+    #[test]
+    fn reports_missing_fields() {
+        let message = input_error(&VALID.replace("\"part_thickness\": 0.5,", ""));
+        assert!(
+            message.contains("missing field `part_thickness`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_over_constrained_rules() {
+        let message = input_error(&VALID.replace("\"fy\": 0", "\"fy\": 0, \"uy\": 0"));
+        assert_eq!(message, "Boundary 'load' is over-constrained in y-axis");
+    }
+
+    const VALID_TOML: &str = r#"
+# Same model as VALID
+[metadata]
+part_thickness = 0.5
+material_elasticity = 69e9
+poisson_ratio = 0.33
+characteristic_length_max = 0.9
+
+[boundary_conditions.restraint]
+region = { x_target_max = -10 }
+targets = { ux = 0, uy = 0 }
+
+[boundary_conditions.load]
+region = { x_target_min = 10, physical_group = 3 }
+targets = { fx = -6e8, fy = 0 }
+"#;
+
+    const VALID_YAML: &str = r#"
+# Same model as VALID
+metadata:
+  part_thickness: 0.5
+  material_elasticity: 69.0e9
+  poisson_ratio: 0.33
+  characteristic_length_max: 0.9
+boundary_conditions:
+  restraint:
+    region: { x_target_max: -10 }
+    targets: { ux: 0, uy: 0 }
+  load:
+    region: { x_target_min: 10, physical_group: 3 }
+    targets: { fx: -6.0e8, fy: 0 }
+"#;
+
+    #[test]
+    fn picks_format_from_extension() {
+        assert_eq!(
+            InputFormat::from_path("a/b.json").unwrap(),
+            InputFormat::Json
+        );
+        assert_eq!(InputFormat::from_path("b.TOML").unwrap(), InputFormat::Toml);
+        assert_eq!(InputFormat::from_path("b.yml").unwrap(), InputFormat::Yaml);
+        assert!(InputFormat::from_path("b.txt").is_err());
+    }
+
+    #[test]
+    fn toml_and_yaml_match_json() {
+        let summarize = |contents: &str, format: InputFormat| {
+            let ModelInput {
+                metadata,
+                boundary_rules: rules,
+                ..
+            } = convert(
+                &parse_input(contents, "input", format).unwrap(),
+                &MaterialLibrary::builtin(),
+            )
+            .unwrap();
+            format!("{metadata:?} {rules:?}")
+        };
+
+        let json = summarize(VALID, InputFormat::Json);
+        assert_eq!(summarize(VALID_TOML, InputFormat::Toml), json);
+        assert_eq!(summarize(VALID_YAML, InputFormat::Yaml), json);
+    }
+
+    #[test]
+    fn reports_toml_and_yaml_error_positions() {
+        let contents = VALID_TOML.replace("poisson_ratio = 0.33", "poisson_ratio = \"x\"");
+        let Err(MagnetiteError::Input(message)) =
+            parse_input::<InputFile>(&contents, "input.toml", InputFormat::Toml)
+        else {
+            panic!("expected an input error");
+        };
+        assert!(
+            message.starts_with("input.toml line 6, column 17: error at 'metadata.poisson_ratio'"),
+            "{message}"
+        );
+
+        let contents = VALID_YAML.replace("fy: 0", "fz: 0");
+        let Err(MagnetiteError::Input(message)) =
+            parse_input::<InputFile>(&contents, "input.yaml", InputFormat::Yaml)
+        else {
+            panic!("expected an input error");
+        };
+        assert!(
+            message.starts_with("input.yaml line 14, column"),
+            "{message}"
+        );
+        assert!(message.ends_with("error at 'boundary_conditions.load.targets.fz': unknown field `fz`, expected one of `ux`, `uy`, `fx`, `fy`, `time_function`"), "{message}");
+    }
+
+    #[test]
+    fn converts_declared_units_to_si() {
+        let contents = VALID.replace(
+            "\"metadata\"",
+            "\"units\": { \"length\": \"mm\", \"force\": \"kN\", \"stress\": \"GPa\" },\n  \"metadata\"",
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let ModelInput {
+            metadata,
+            boundary_rules: rules,
+            ..
+        } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+
+        assert_eq!(metadata.part_thickness, 0.5e-3);
+        assert_eq!(metadata.material.youngs_modulus, 69e18);
+        assert!((metadata.mesh_options.characteristic_length_max - 0.9e-3).abs() < 1e-9);
+        assert_eq!(rules[0].region.x_max, -10e-3);
+        assert_eq!(rules[1].target.fx, Some(-6e11));
+    }
+
+    #[test]
+    fn detects_inconsistent_units() {
+        let units = |length, stress| Units {
+            length,
+            force: ForceUnit::Newton,
+            stress,
+        };
+
+        assert!(units(LengthUnit::Millimeter, StressUnit::Megapascal)
+            .inconsistency()
+            .is_none());
+        let imperial = Units {
+            length: LengthUnit::Inch,
+            force: ForceUnit::PoundForce,
+            stress: StressUnit::Psi,
+        };
+        assert!(imperial.inconsistency().is_none());
+        assert!(units(LengthUnit::Meter, StressUnit::Pascal)
+            .inconsistency()
+            .is_none());
+
+        let mismatch = units(LengthUnit::Millimeter, StressUnit::Pascal).inconsistency();
+        assert_eq!(
+            mismatch.as_deref(),
+            Some("units mix mm and N with stresses in Pa; N over mm^2 is MPa")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_units() {
+        let contents = VALID.replace(
+            "\"metadata\"",
+            "\"units\": { \"length\": \"furlong\" },\n  \"metadata\"",
+        );
+        let message = input_error(&contents);
+        assert!(
+            message.contains("error at 'units.length': unknown variant `furlong`"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn resolves_library_materials() {
+        let contents = VALID.replace(
+            "\"material_elasticity\": 69e9,\n    \"poisson_ratio\": 0.33,",
+            "\"material\": \"Ti-6Al-4V\",",
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let ModelInput { metadata, .. } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(metadata.material.youngs_modulus, 113.8e9);
+        assert_eq!(metadata.material.poisson_ratio, 0.342);
+        assert_eq!(metadata.material.yield_strength, Some(880e6));
+
+        // Explicit values override the library, in the declared units
+        let contents = VALID.replace(
+            "\"material_elasticity\": 69e9,",
+            "\"material\": \"Ti-6Al-4V\", \"material_elasticity\": 110,",
+        );
+        let contents = contents.replace(
+            "\"metadata\"",
+            "\"units\": { \"stress\": \"GPa\" },\n  \"metadata\"",
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let ModelInput { metadata, .. } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(metadata.material.youngs_modulus, 110e9);
+        assert_eq!(metadata.material.poisson_ratio, 0.33);
+        assert_eq!(metadata.material.density, Some(4430.0));
+    }
+
+    #[test]
+    fn resolves_body_materials() {
+        let contents = VALID.replace(
+            "\"poisson_ratio\": 0.33,",
+            "\"poisson_ratio\": 0.33,\n    \"body_materials\": { \"pin\": \"Ti-6Al-4V\" },",
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let ModelInput { metadata, .. } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(metadata.body_materials.len(), 1);
+        assert_eq!(metadata.body_materials[0].body, "pin");
+        assert_eq!(metadata.body_materials[0].material.youngs_modulus, 113.8e9);
+        assert_eq!(metadata.material.youngs_modulus, 69e9);
+
+        let message = input_error(&VALID.replace(
+            "\"poisson_ratio\": 0.33,",
+            "\"poisson_ratio\": 0.33, \"body_materials\": { \"pin\": \"Unobtainium\" },",
+        ));
+        assert!(
+            message.starts_with("Unknown material 'Unobtainium'"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn requires_material_or_elasticity() {
+        let message = input_error(&VALID.replace("\"material_elasticity\": 69e9,", ""));
+        assert_eq!(
+            message,
+            "metadata needs either a material or material_elasticity"
+        );
+
+        let message = input_error(&VALID.replace(
+            "\"material_elasticity\": 69e9,",
+            "\"material\": \"Unobtainium\",",
+        ));
+        assert!(
+            message.starts_with("Unknown material 'Unobtainium'"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn parses_safety_section() {
+        let contents = VALID
+            .replace(
+                "\"poisson_ratio\": 0.33,",
+                "\"poisson_ratio\": 0.33, \"yield_strength\": 250,",
+            )
+            .replace(
+                "\"metadata\"",
+                "\"units\": { \"stress\": \"MPa\" },\n  \"safety\": { \"criterion\": \"tresca\", \"minimum_factor\": 1.5 },\n  \"metadata\"",
+            );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let ModelInput { metadata, .. } = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(metadata.material.yield_strength, Some(250e6));
+        assert_eq!(metadata.safety.criterion, FailureCriterion::Tresca);
+        assert_eq!(metadata.safety.minimum_factor, Some(1.5));
+
+        let message = input_error(&VALID.replace(
+            "\"metadata\"",
+            "\"safety\": { \"minimum_factor\": 1.5 },\n  \"metadata\"",
+        ));
+        assert!(
+            message.starts_with("safety.minimum_factor needs a yield strength"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn parses_load_cases_and_combinations() {
+        let cases = r#""load_cases": {
+    "dead": { "boundary_conditions": { "weight": { "region": {}, "targets": { "fx": 0, "fy": -1 } } } },
+    "live": { "boundary_conditions": {} }
+  },
+  "combinations": { "uls": { "dead": 1.2, "live": 1.6 } },
+  "metadata""#;
+        let contents = VALID.replace("\"metadata\"", cases);
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let model_input = convert(&input, &MaterialLibrary::builtin()).unwrap();
+
+        assert_eq!(model_input.boundary_rules.len(), 2);
+        assert_eq!(model_input.load_cases.len(), 2);
+        assert_eq!(
+            model_input.load_cases[0].boundary_rules[0].name,
+            "dead.weight"
+        );
+        assert_eq!(
+            model_input.combinations[0].factors,
+            [("dead".to_string(), 1.2), ("live".to_string(), 1.6)]
+        );
+
+        let message = input_error(&contents.replace("\"live\": 1.6", "\"wind\": 1.6"));
+        assert_eq!(
+            message,
+            "Combination 'uls' references unknown load case 'wind'"
+        );
+
+        let message = input_error(&contents.replace("\"uls\"", "\"dead\""));
+        assert_eq!(
+            message,
+            "Combination 'dead' has the same name as a load case"
+        );
+
+        let message = input_error(&contents.replace("\"live\": {", "\"live load\": {"));
+        assert_eq!(
+            message,
+            "Load case name 'live load' must only contain letters, numbers, '-' and '_'"
+        );
+    }
+
+    #[test]
+    fn parses_topology_section() {
+        let topology = r#""units": { "length": "mm" },
+  "topology": {
+    "volume_fraction": 0.3,
+    "filter_radius": 2,
+    "non_design": {
+      "mount": { "region": { "x_target_max": 5 } },
+      "keepout": { "region": { "x_target_min": 10 }, "density": "void" }
+    }
+  },
+  "metadata""#;
+        let contents = VALID.replace("\"metadata\"", topology);
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .topology
+            .unwrap();
+
+        assert_eq!(options.volume_fraction, 0.3);
+        assert_eq!(options.penalty, 3.0);
+        assert!((options.filter_radius - 2e-3).abs() < 1e-12);
+        assert_eq!(options.non_design.len(), 2);
+        assert!(options.non_design[0].solid);
+        assert!((options.non_design[0].region.x_max - 5e-3).abs() < 1e-12);
+        assert!(!options.non_design[1].solid);
+
+        let message = input_error(&VALID.replace(
+            "\"metadata\"",
+            "\"topology\": { \"volume_fraction\": 1.5, \"filter_radius\": 1 },\n  \"metadata\"",
+        ));
+        assert!(
+            message.starts_with("topology.volume_fraction must be between 0 and 1"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn parses_nonlinear_section() {
+        let contents = VALID.replace(
+            "\"metadata\"",
+            "\"nonlinear\": { \"increments\": 20 },\n  \"metadata\"",
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .nonlinear
+            .unwrap();
+        assert_eq!(options.increments, 20);
+        assert_eq!(options.max_iterations, 25);
+        assert_eq!(options.tolerance, 1e-6);
+
+        let message = input_error(&VALID.replace(
+            "\"metadata\"",
+            "\"nonlinear\": { \"increments\": 0 },\n  \"metadata\"",
+        ));
+        assert!(
+            message.starts_with("nonlinear.increments must be at least 1"),
+            "{message}"
+        );
+
+        // Plasticity alone solves with small displacements
+        let contents = VALID
+            .replace(
+                "\"poisson_ratio\": 0.33,",
+                "\"poisson_ratio\": 0.33, \"yield_strength\": 250,",
+            )
+            .replace(
+                "\"metadata\"",
+                "\"plasticity\": { \"hardening\": \"kinematic\", \"hardening_modulus\": 1000 },\n  \"metadata\"",
+            );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .nonlinear
+            .unwrap();
+        assert!(!options.geometric);
+        assert_eq!(options.increments, 10);
+        let plasticity = options.plasticity.unwrap();
+        assert_eq!(plasticity.hardening, Hardening::Kinematic);
+        assert_eq!(plasticity.hardening_modulus, 1000.0);
+
+        let message =
+            input_error(&VALID.replace("\"metadata\"", "\"plasticity\": {},\n  \"metadata\""));
+        assert!(
+            message.starts_with("plasticity needs a yield strength"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn parses_transient_section() {
+        let contents = VALID
+            .replace(
+                "\"poisson_ratio\": 0.33,",
+                "\"poisson_ratio\": 0.33, \"density\": 2700,",
+            )
+            .replace(
+                "\"fx\": -6e8, \"fy\": 0",
+                "\"fx\": -6e8, \"fy\": 0, \"time_function\": [[0, 0], [0.01, 1]]",
+            )
+            .replace(
+                "\"metadata\"",
+                "\"transient\": { \"duration\": 0.1, \"time_step\": 1e-4, \"method\": \"hht\", \"rayleigh\": { \"mass\": 2 } },\n  \"metadata\"",
+            );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let model_input = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(model_input.metadata.material.density, Some(2700.0));
+        let options = model_input.transient.unwrap();
+        assert_eq!(options.integrator, Integrator::Hht { alpha: -0.05 });
+        assert_eq!(options.output_interval, 1e-4);
+        assert_eq!(options.mass_damping, 2.0);
+        let time_function = model_input.boundary_rules[1]
+            .target
+            .time_function
+            .as_ref()
+            .unwrap();
+        assert_eq!(time_function.value(0.005), 0.5);
+        assert_eq!(time_function.value(1.0), 1.0);
+
+        let message = input_error(&VALID.replace(
+            "\"metadata\"",
+            "\"transient\": { \"duration\": 0.1, \"time_step\": 1e-4 },\n  \"metadata\"",
+        ));
+        assert!(
+            message.starts_with("Transient analysis needs a material density"),
+            "{message}"
+        );
+
+        let message = input_error(&VALID.replace(
+            "\"fx\": -6e8, \"fy\": 0",
+            "\"fx\": -6e8, \"fy\": 0, \"time_function\": \"sin(2 * pi * f * t)\"",
+        ));
+        assert_eq!(
+            message,
+            "Boundary 'load' has an invalid time function: unknown name 'f'"
+        );
+    }
+
+    #[test]
+    fn parses_harmonic_section() {
+        let with_harmonic = |harmonic: &str| {
+            VALID
+                .replace(
+                    "\"poisson_ratio\": 0.33,",
+                    "\"poisson_ratio\": 0.33, \"density\": 2700,",
+                )
+                .replace(
+                    "\"metadata\"",
+                    &format!("\"harmonic\": {harmonic},\n  \"metadata\""),
+                )
+        };
+
+        let contents = with_harmonic(
+            r#"{ "frequencies": { "start": 10, "stop": 1000, "steps": 3, "spacing": "logarithmic" }, "loss_factor": 0.02, "probes": { "tip": { "x": 12, "y": 0 } } }"#,
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .harmonic
+            .unwrap();
+        assert_eq!(options.frequencies.len(), 3);
+        assert!((options.frequencies[1] - 100.0).abs() < 1e-9);
+        assert_eq!(options.frequencies[2], 1000.0);
+        assert_eq!(options.loss_factor, 0.02);
+        assert_eq!(options.probes[0].name, "tip");
+        assert_eq!(options.probes[0].vertex, Vertex { x: 12.0, y: 0.0 });
+
+        let contents = with_harmonic(r#"{ "frequencies": [50, 60] }"#);
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .harmonic
+            .unwrap();
+        assert_eq!(options.frequencies, vec![50.0, 60.0]);
+
+        let message = input_error(&with_harmonic(
+            r#"{ "frequencies": { "start": 0, "stop": 100, "spacing": "logarithmic" } }"#,
+        ));
+        assert!(
+            message.starts_with("harmonic.frequencies.start must be positive"),
+            "{message}"
+        );
+
+        let message = input_error(&with_harmonic(r#"{ "frequencies": [] }"#));
+        assert!(
+            message.starts_with("harmonic.frequencies must not be empty"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn parses_contact_section() {
+        let with_contact = |contact: &str| {
+            VALID.replace(
+                "\"metadata\"",
+                &format!("\"contact\": {contact},\n  \"metadata\""),
+            )
+        };
+
+        let contents = with_contact(
+            r#"{ "method": "augmented_lagrangian", "obstacles": { "floor": { "start": { "x": -20, "y": -1 }, "end": { "x": 20, "y": -1 } } } }"#,
+        );
+        let input = parse_input(&contents, "input.json", InputFormat::Json).unwrap();
+        let options = convert(&input, &MaterialLibrary::builtin())
+            .unwrap()
+            .contact
+            .unwrap();
+        assert_eq!(options.method, ContactMethod::AugmentedLagrangian);
+        assert_eq!(options.penalty_factor, 100.0);
+        assert!((options.search_distance - 0.9).abs() < 1e-6);
+        assert_eq!(options.obstacles[0].name, "floor");
+        assert_eq!(options.obstacles[0].end, Vertex { x: 20.0, y: -1.0 });
+
+        let message = input_error(&with_contact(
+            r#"{ "obstacles": { "point": { "start": { "x": 1, "y": 1 }, "end": { "x": 1, "y": 1 } } } }"#,
+        ));
+        assert!(
+            message.starts_with("contact.obstacles.point starts and ends at the same point"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn sets_parameters_by_path() {
+        let mut document: serde_json::Value =
+            parse_input(VALID, "input.json", InputFormat::Json).unwrap();
+        set_parameter(
+            &mut document,
+            "metadata.part_thickness",
+            serde_json::json!(2.0),
+        )
+        .unwrap();
+        set_parameter(
+            &mut document,
+            "boundary_conditions.load.targets.fy",
+            serde_json::json!(-5),
+        )
+        .unwrap();
+        set_parameter(
+            &mut document,
+            "metadata.yield_strength",
+            serde_json::json!(250e6),
+        )
+        .unwrap();
+
+        let input: InputFile = serde_json::from_value(document.clone()).unwrap();
+        let model_input = convert(&input, &MaterialLibrary::builtin()).unwrap();
+        assert_eq!(model_input.metadata.part_thickness, 2.0);
+        assert_eq!(model_input.metadata.material.yield_strength, Some(250e6));
+        assert_eq!(model_input.boundary_rules[1].target.fy, Some(-5.0));
+
+        let result = set_parameter(
+            &mut document,
+            "metadata.part_thickness.value",
+            serde_json::json!(1),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,57 @@
+/*
+
+--- Magnetite ---
+
+Magnetite is a 2D finite-element solver for mechanical problems.
+
+Kyle Tennison
+March 29, 2024
+
+*/
+
+//! Magnetite is a 2D finite-element solver for mechanical problems. It
+//! solves linear-elastic models, and models with large displacements,
+//! plasticity or contact, along with load cases, topology optimisation, and
+//! transient and harmonic analyses.
+//!
+//! Build a [`Model`] from geometry, a material and boundary rules, then call
+//! [`Model::mesh`] and [`Model::solve`]. The [`input`] and [`mesher`]
+//! modules load models from the same files the `magnetite` CLI accepts,
+//! and [`post_processor`] and [`renderer`] write results to disk.
+//! [`commands`] runs the whole workflow of each CLI command.
+//! Progress and warnings are reported through [`tracing`]; see [`logging`].
+
+pub mod commands;
+pub mod contact;
+pub mod datatypes;
+pub mod error;
+pub mod expression;
+pub mod geometry;
+pub mod harmonic;
+pub mod input;
+pub mod logging;
+pub mod materials;
+pub mod mesher;
+mod model;
+pub mod msh;
+pub mod nonlinear;
+pub mod plasticity;
+pub mod post_processor;
+pub mod renderer;
+pub mod results_file;
+pub mod solver;
+pub mod sweep;
+pub mod topology;
+pub mod transient;
+pub mod units;
+
+pub use datatypes::{
+    Body, BoundaryNodes, BoundaryRegion, BoundaryRule, BoundaryTarget, CaseResults, ContactMethod,
+    ContactOptions, ElementResult, FailureCriterion, Geometry, Hardening, HarmonicOptions,
+    Integrator, LoadCase, LoadCombination, Material, Mesh, MeshOptions, ModelMetadata, NodeResult,
+    NonlinearOptions, PhysicalGroupRef, PlasticityOptions, Probe, Results, RigidLine,
+    SafetyOptions, SolverStats, TimeFunction, TransientOptions, Vertex,
+};
+pub use error::MagnetiteError;
+pub use model::Model;
+pub use units::Units;
@@ -0,0 +1,244 @@
+use std::{
+    fs::File,
+    io::IsTerminal,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+use indicatif::ProgressBar;
+use tracing::{
+    field::{Field, Visit},
+    span::EnteredSpan,
+    Event, Level, Span, Subscriber,
+};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    fmt::{format::Writer, writer::MakeWriterExt, FmtContext, FormatEvent, FormatFields},
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+    Layer,
+};
+
+use crate::error::MagnetiteError;
+
+/// Whether progress bars may be drawn
+static PROGRESS: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables progress bars. They are enabled by default, but are
+/// still only drawn when stdout is a terminal
+pub fn set_progress(enabled: bool) {
+    PROGRESS.store(enabled, Ordering::Relaxed);
+}
+
+/// Creates a progress bar, which is hidden if progress bars are disabled or
+/// stdout isn't a terminal, e.g. when output is piped to a file
+///
+/// # Arguments
+/// * `len` - The number of steps the bar counts to
+pub fn progress_bar(len: u64) -> ProgressBar {
+    if PROGRESS.load(Ordering::Relaxed) && std::io::stdout().is_terminal() {
+        ProgressBar::new(len)
+    } else {
+        ProgressBar::hidden()
+    }
+}
+
+/// Times a step of a solve, such as assembly or stress recovery. The step's
+/// span is entered while the timer lives, and its duration is logged at
+/// debug level when the timer is dropped.
+pub struct Timer {
+    name: &'static str,
+    start: Instant,
+    _span: EnteredSpan,
+}
+
+impl Timer {
+    /// Enters a span and starts timing it
+    ///
+    /// # Arguments
+    /// * `span` - The span of the step, e.g. `info_span!("assembly")`
+    pub fn start(span: Span) -> Timer {
+        Timer {
+            name: span.metadata().map_or("step", |m| m.name()),
+            start: Instant::now(),
+            _span: span.entered(),
+        }
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let seconds = self.start.elapsed().as_secs_f64();
+        tracing::debug!(
+            step = self.name,
+            seconds,
+            "{} took {seconds:.3} seconds",
+            self.name
+        );
+    }
+}
+
+/// Picks the most verbose level to print from the number of `-v` and `-q`
+/// flags. Info is printed by default; each `-v` adds debug then trace, and
+/// each `-q` drops info then warnings. Errors are always printed.
+///
+/// # Arguments
+/// * `verbose` - The number of `-v` flags
+/// * `quiet` - The number of `-q` flags
+pub fn level(verbose: u8, quiet: u8) -> LevelFilter {
+    match i16::from(verbose) - i16::from(quiet) {
+        i16::MIN..=-2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        2.. => LevelFilter::TRACE,
+    }
+}
+
+/// Prints diagnostics as `info: ...` and `warning: ...` lines on stdout, with
+/// errors on stderr, and optionally writes them to a JSON-lines file too.
+/// Applications embedding Magnetite can install their own `tracing`
+/// subscriber instead.
+///
+/// # Arguments
+/// * `level` - The most verbose level to print
+/// * `json_log` - A file to write diagnostics to as one JSON object per line.
+///   It gets debug messages, including step timings, whatever `level` is.
+pub fn init(level: LevelFilter, json_log: Option<&str>) -> Result<(), MagnetiteError> {
+    let json = match json_log {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| {
+                MagnetiteError::Input(format!("Unable to create log file {path}: {err}"))
+            })?;
+            let layer = tracing_subscriber::fmt::layer()
+                .json()
+                .with_current_span(false)
+                .with_writer(Mutex::new(file))
+                .with_filter(LevelFilter::DEBUG);
+            Some(layer)
+        }
+        None => None,
+    };
+    let terminal = tracing_subscriber::fmt::layer()
+        .event_format(Terminal)
+        .with_writer(
+            std::io::stderr
+                .with_max_level(Level::ERROR)
+                .or_else(std::io::stdout),
+        )
+        .with_filter(level);
+
+    tracing_subscriber::registry()
+        .with(terminal)
+        .with(json)
+        .try_init()
+        .map_err(|err| MagnetiteError::Input(format!("Unable to set up logging: {err}")))
+}
+
+/// Formats events as `level: message`, leaving out their other fields
+struct Terminal;
+
+impl<S, N> FormatEvent<S, N> for Terminal
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let level = match *event.metadata().level() {
+            Level::ERROR => "error",
+            Level::WARN => "warning",
+            Level::INFO => "info",
+            Level::DEBUG => "debug",
+            Level::TRACE => "trace",
+        };
+        let mut message = Message::default();
+        event.record(&mut message);
+        writeln!(writer, "{level}: {}", message.0)
+    }
+}
+
+/// Collects the message of an event
+#[derive(Default)]
+struct Message(String);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_owned();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{value:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn picks_levels_from_flags() {
+        assert_eq!(level(0, 0), LevelFilter::INFO);
+        assert_eq!(level(1, 0), LevelFilter::DEBUG);
+        assert_eq!(level(3, 0), LevelFilter::TRACE);
+        assert_eq!(level(0, 1), LevelFilter::WARN);
+        assert_eq!(level(0, 4), LevelFilter::ERROR);
+        assert_eq!(level(1, 1), LevelFilter::INFO);
+    }
+
+    #[test]
+    fn prints_messages_with_their_level() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let writer = {
+            let output = output.clone();
+            move || Buffer(output.clone())
+        };
+        let subscriber = tracing_subscriber::registry().with(
+            tracing_subscriber::fmt::layer()
+                .event_format(Terminal)
+                .with_writer(writer)
+                .with_filter(LevelFilter::DEBUG),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(nodes = 4, "loaded {} nodes", 4);
+            tracing::warn!("no yield strength given");
+            tracing::trace!("not printed");
+            drop(Timer::start(tracing::info_span!("assembly")));
+        });
+
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "info: loaded 4 nodes");
+        assert_eq!(lines[1], "warning: no yield strength given");
+        assert!(lines[2].starts_with("debug: assembly took "));
+        assert_eq!(lines.len(), 3);
+    }
+
+    /// A writer that appends to a shared buffer
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+}
@@ -10,67 +10,585 @@ March 29, 2024
 
 */
 
-use clap::{ArgAction, Parser};
-use error::MagnetiteError;
-mod datatypes;
-mod error;
-mod mesher;
-mod post_processor;
-mod solver;
+use clap::{Parser, Subcommand};
+use magnetite::{
+    commands::{self, OutputPaths, PlotSettings, SolveOutputs},
+    input, logging,
+    materials::MaterialLibrary,
+    renderer::{Colormap, DeformationScale, PlotField},
+    MagnetiteError, MeshOptions,
+};
+use tracing::{error, info};
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Mesh the geometry, save the mesh and report its quality
+    Mesh {
+        #[arg(value_name = "FILE", help = "Input file with the mesh options")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG or CSVs")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+
+        #[arg(
+            short,
+            long,
+            value_name = "FILE",
+            default_value = "mesh.msh",
+            help = "Mesh output file, which can be used as the geometry of a solve"
+        )]
+        output: String,
+    },
+    /// Check the input file, geometry and boundary rules without solving
+    Check {
+        #[arg(value_name = "FILE", help = "Input file")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+    },
+    /// Solve the model; the same as running without a subcommand
+    Solve(SolveArgs),
+    /// Plot or export results again from a results file, or from the nodes
+    /// and elements csv files
+    Post {
+        #[arg(
+            required = true,
+            value_name = "FILE",
+            num_args = 1..=2,
+            help = "Results file written by a solve, or its nodes and elements csv files"
+        )]
+        files: Vec<String>,
+
+        #[command(flatten)]
+        plot: PlotArgs,
+
+        #[arg(long, help = "Write the nodes and elements csv files again")]
+        csv: bool,
+    },
+    /// Print a JSON Schema for input files, for editor autocompletion
+    Schema {
+        #[arg(short, long, value_name = "FILE", help = "Write the schema to a file")]
+        output: Option<String>,
+    },
+    /// List the materials in the material library
+    Materials {
+        #[arg(short, long, value_name = "FILE", help = "Extra material library file")]
+        library: Option<String>,
+    },
+    /// Run every case of the input file's sweep section and summarize them
+    Sweep {
+        #[arg(value_name = "FILE", help = "Input file with a sweep section")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "sweep.csv",
+            help = "Summary table output file"
+        )]
+        summary: String,
+
+        #[arg(
+            short,
+            long,
+            help = "Number of cases to run at once; defaults to the number of CPUs"
+        )]
+        jobs: Option<usize>,
+    },
+    /// Optimise the material layout with the input file's topology section
+    Topology {
+        #[arg(value_name = "FILE", help = "Input file with a topology section")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "density.csv",
+            help = "Element density output file"
+        )]
+        density: String,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "topology.svg",
+            help = "Optimised boundary output file, for meshing again"
+        )]
+        boundary: String,
+
+        #[arg(
+            long,
+            default_value_t = 0.5,
+            help = "Density at the optimised boundary"
+        )]
+        threshold: f64,
+    },
+    /// Integrate the model through time with the input file's transient section
+    Transient {
+        #[arg(value_name = "FILE", help = "Input file with a transient section")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "displacement_history.csv",
+            help = "Nodal displacement history output file"
+        )]
+        displacements: String,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "stress_history.csv",
+            help = "Element stress history output file"
+        )]
+        stresses: String,
+    },
+    /// Find the frequency response with the input file's harmonic section
+    Harmonic {
+        #[arg(value_name = "FILE", help = "Input file with a harmonic section")]
+        input_file: String,
+
+        #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+        geometry_files: Vec<String>,
+
+        #[command(flatten)]
+        gmsh: GmshArgs,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "harmonic_displacements.csv",
+            help = "Complex nodal displacement output file"
+        )]
+        displacements: String,
+
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = "harmonic_probes.csv",
+            help = "Probe amplitude and phase output file"
+        )]
+        probes: String,
+    },
+}
+
+/// Options for running Gmsh, for the commands that mesh geometry
+#[derive(clap::Args, Debug)]
+#[command(about = None, long_about = None)]
+struct GmshArgs {
     #[arg(
-        short,
         long,
-        index = 1,
         value_name = "FILE",
-        help = "Input Json with boundary conditions"
+        help = "Gmsh executable; defaults to gmsh on the PATH"
     )]
-    input_file: String,
+    gmsh_path: Option<String>,
 
-    #[arg(short, long, index=2, required=true, value_name="FILE", num_args=0.., help="Geometry SVG or CSVs")]
-    geometry_files: Vec<String>,
+    #[arg(long, value_name = "FILE", help = "Save Gmsh's full output to a file")]
+    gmsh_log: Option<String>,
+
+    #[arg(
+        long,
+        help = "Keep intermediate files, which are otherwise only kept when a step fails"
+    )]
+    keep_intermediates: bool,
+}
 
-    #[arg(short, long, default_value = "coolwarm", help = "cmap for python plot")]
+/// Options for plotting and animating results
+#[derive(clap::Args, Debug)]
+#[command(about = None, long_about = None)]
+struct PlotArgs {
+    #[arg(
+        short,
+        long,
+        default_value = "coolwarm",
+        help = "Colormap for stress plot"
+    )]
     cmap: String,
 
-    #[arg(short, long, help = "skip python plot")]
+    #[arg(
+        long,
+        default_value = "stress",
+        help = "Plotted field; stress, von_mises, tresca, safety_factor or plastic_strain"
+    )]
+    field: String,
+
+    #[arg(short, long, help = "Skip plotting and animating results")]
     skip: bool,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        num_args = 1..,
+        default_value = "results.png",
+        help = "Plot output files; .svg or .png"
+    )]
+    plot: Vec<String>,
+
+    #[arg(
+        long,
+        default_value = "auto",
+        help = "Scale factor applied to displacements when plotting, or 'auto'"
+    )]
+    scale: String,
+
+    #[arg(long, default_value_t = 1600, help = "Plot width in pixels")]
+    plot_width: u32,
+
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Animate the deformation; .gif, or .png/.svg for numbered frames"
+    )]
+    animate: Option<String>,
+
+    #[arg(long, default_value_t = 30, help = "Number of animation frames")]
+    frames: usize,
+}
+
+/// The input, geometry and outputs of a solve
+#[derive(clap::Args, Debug)]
+#[command(about = None, long_about = None)]
+struct SolveArgs {
+    // Optional so the top-level arguments parse when a subcommand is given;
+    // clap still requires it otherwise
+    #[arg(
+        required = true,
+        value_name = "FILE",
+        help = "Input file (.json, .toml or .yaml) with boundary conditions"
+    )]
+    input_file: Option<String>,
+
+    #[arg(required=true, value_name="FILE", num_args=1.., help="Geometry SVG, CSVs or Gmsh MSH")]
+    geometry_files: Vec<String>,
+
+    #[command(flatten)]
+    gmsh: GmshArgs,
+
+    #[command(flatten)]
+    plot: PlotArgs,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "contact.csv",
+        help = "Contact force output file, when the input file has a contact section"
+    )]
+    contact: String,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = "results.json",
+        help = "Results file output, which post can read"
+    )]
+    results: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Directory to write outputs to; created if it doesn't exist"
+    )]
+    output_dir: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        default_value = "",
+        help = "Prefix added to the name of every output file"
+    )]
+    prefix: String,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        conflicts_with = "quiet",
+        help = "Print more detail, such as step timings; repeat for even more"
+    )]
+    verbose: u8,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        action = clap::ArgAction::Count,
+        help = "Print only warnings and errors, without progress bars; repeat for errors only"
+    )]
+    quiet: u8,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        help = "Also write diagnostics and step timings to a file as JSON lines"
+    )]
+    log_json: Option<String>,
+
+    #[command(flatten)]
+    solve: SolveArgs,
 }
 
 fn main() {
     match entry() {
         Ok(_) => (),
         Err(err) => {
-            eprintln!("Received error: {err}");
-            std::process::exit(1);
+            // Errors from before logging was set up still need printing
+            if tracing::dispatcher::has_been_set() {
+                error!("{err}");
+            } else {
+                eprintln!("error: {err}");
+            }
+            // Failed design checks get their own status so CI can tell them
+            // apart from errors running the model
+            match err {
+                MagnetiteError::DesignCheck(_) => std::process::exit(2),
+                _ => std::process::exit(1),
+            }
         }
     }
 }
 
-/// Entry point to simulator
-fn entry() -> Result<(), MagnetiteError> {
-    let args = Args::parse();
+impl GmshArgs {
+    /// The Gmsh settings, as mesh options that override the input file's
+    fn settings(&self) -> MeshOptions {
+        MeshOptions {
+            gmsh_path: self.gmsh_path.clone(),
+            gmsh_log: self.gmsh_log.clone(),
+            keep_intermediates: self.keep_intermediates,
+            ..Default::default()
+        }
+    }
+}
 
-    // Parse input files
-    let (mut nodes, mut elements, model_metadata) = mesher::run(
-        args.geometry_files.iter().map(|f| f.as_str()).collect(),
-        &args.input_file,
-    )?;
+impl PlotArgs {
+    /// The plot settings; no plots or animation when skipped
+    fn settings(&self) -> Result<PlotSettings, MagnetiteError> {
+        Ok(PlotSettings {
+            colormap: Colormap::from_name(&self.cmap)?,
+            field: PlotField::from_name(&self.field)?,
+            scale: DeformationScale::from_arg(&self.scale)?,
+            width: self.plot_width,
+            plots: match self.skip {
+                true => Vec::new(),
+                false => self.plot.clone(),
+            },
+            animation: self.animate.clone().filter(|_| !self.skip),
+            frames: self.frames,
+        })
+    }
+}
 
-    // Run simulation
-    solver::run(&mut nodes, &mut elements, &model_metadata)?;
+/// Borrows command line file arguments as strs
+fn file_refs(files: &[String]) -> Vec<&str> {
+    files.iter().map(|f| f.as_str()).collect()
+}
 
-    // Output
-    let nodes_output = "nodes.csv";
-    let elements_output = "elements.csv";
-    post_processor::csv_output(&elements, &nodes, nodes_output, elements_output)?;
+/// Prints a table of the materials in a library, with strengths in MPa
+fn print_materials(library: &MaterialLibrary) {
+    let optional = |value: Option<f64>, scale: f64| match value {
+        Some(v) => format!("{}", v / scale),
+        None => "-".to_string(),
+    };
 
-    if !args.skip {
-        post_processor::pyplot(nodes_output, elements_output, &args.cmap)?;
+    println!(
+        "{:<12} {:>8} {:>6} {:>8} {:>8} {:>8}  description",
+        "name", "E [GPa]", "nu", "rho", "Sy [MPa]", "Su [MPa]"
+    );
+    for (name, entry) in library.iter() {
+        println!(
+            "{:<12} {:>8} {:>6} {:>8} {:>8} {:>8}  {}",
+            name,
+            entry.youngs_modulus / 1e9,
+            entry.poisson_ratio,
+            optional(entry.density, 1.0),
+            optional(entry.yield_strength, 1e6),
+            optional(entry.ultimate_strength, 1e6),
+            entry.description
+        );
     }
+}
 
+/// Writes the input file JSON Schema to a file, or prints it
+fn write_schema(output: Option<&str>) -> Result<(), MagnetiteError> {
+    let schema = input::schema();
+    match output {
+        Some(output) => {
+            if let Err(err) = std::fs::write(output, schema + "\n") {
+                return Err(MagnetiteError::Input(format!(
+                    "Failed to write {output}: {err}"
+                )));
+            }
+            info!("wrote input schema to {output}");
+        }
+        None => println!("{schema}"),
+    }
     Ok(())
 }
+
+/// Solves a model, and writes its outputs
+fn solve(args: &SolveArgs, outputs: &OutputPaths) -> Result<(), MagnetiteError> {
+    let Some(input_file) = &args.input_file else {
+        return Err(MagnetiteError::Input("Missing input file".to_owned()));
+    };
+    commands::solve(
+        input_file,
+        &file_refs(&args.geometry_files),
+        &args.gmsh.settings(),
+        &args.plot.settings()?,
+        outputs,
+        &SolveOutputs {
+            contact: args.contact.clone(),
+            results: args.results.clone(),
+        },
+    )
+}
+
+/// Entry point to simulator
+fn entry() -> Result<(), MagnetiteError> {
+    let args = Args::parse();
+    logging::init(
+        logging::level(args.verbose, args.quiet),
+        args.log_json.as_deref(),
+    )?;
+    logging::set_progress(args.quiet == 0);
+
+    let outputs = OutputPaths {
+        dir: args.output_dir,
+        prefix: args.prefix,
+    };
+    if !matches!(
+        args.command,
+        Some(Command::Schema { .. } | Command::Materials { .. })
+    ) {
+        outputs.create_dir()?;
+    }
+
+    match args.command {
+        Some(Command::Schema { output }) => write_schema(output.as_deref()),
+        Some(Command::Materials { library }) => {
+            let mut materials = MaterialLibrary::builtin();
+            if let Some(library) = library {
+                materials.load_overrides(&library)?;
+            }
+            print_materials(&materials);
+            Ok(())
+        }
+        Some(Command::Mesh {
+            input_file,
+            geometry_files,
+            gmsh,
+            output,
+        }) => commands::mesh(
+            &input_file,
+            &file_refs(&geometry_files),
+            &gmsh.settings(),
+            &outputs,
+            &output,
+        ),
+        Some(Command::Check {
+            input_file,
+            geometry_files,
+            gmsh,
+        }) => commands::check(&input_file, &file_refs(&geometry_files), &gmsh.settings()),
+        Some(Command::Solve(solve_args)) => solve(&solve_args, &outputs),
+        Some(Command::Post { files, plot, csv }) => {
+            commands::post(&file_refs(&files), &plot.settings()?, &outputs, csv)
+        }
+        Some(Command::Sweep {
+            input_file,
+            geometry_files,
+            gmsh,
+            summary,
+            jobs,
+        }) => commands::sweep(
+            &input_file,
+            &file_refs(&geometry_files),
+            &gmsh.settings(),
+            jobs.unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+            &outputs,
+            &summary,
+        ),
+        Some(Command::Topology {
+            input_file,
+            geometry_files,
+            gmsh,
+            density,
+            boundary,
+            threshold,
+        }) => commands::topology(
+            &input_file,
+            &file_refs(&geometry_files),
+            &gmsh.settings(),
+            &outputs,
+            &density,
+            &boundary,
+            threshold,
+        ),
+        Some(Command::Transient {
+            input_file,
+            geometry_files,
+            gmsh,
+            displacements,
+            stresses,
+        }) => commands::transient(
+            &input_file,
+            &file_refs(&geometry_files),
+            &gmsh.settings(),
+            &outputs,
+            &displacements,
+            &stresses,
+        ),
+        Some(Command::Harmonic {
+            input_file,
+            geometry_files,
+            gmsh,
+            displacements,
+            probes,
+        }) => commands::harmonic(
+            &input_file,
+            &file_refs(&geometry_files),
+            &gmsh.settings(),
+            &outputs,
+            &displacements,
+            &probes,
+        ),
+        None => solve(&args.solve, &outputs),
+    }
+}
@@ -0,0 +1,174 @@
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use crate::{
+    datatypes::Material,
+    error::MagnetiteError,
+    input::{parse_input, InputFormat},
+};
+use tracing::info;
+
+/// The bundled material library, in SI units
+const BUILTIN_LIBRARY: &str = include_str!("materials.toml");
+
+/// A material library entry, in SI units
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaterialEntry {
+    #[serde(default)]
+    pub description: String,
+    pub youngs_modulus: f64,
+    pub poisson_ratio: f64,
+    pub density: Option<f64>,
+    pub thermal_expansion: Option<f64>,
+    pub yield_strength: Option<f64>,
+    pub ultimate_strength: Option<f64>,
+}
+
+impl MaterialEntry {
+    /// Converts the entry into a Material
+    pub fn material(&self) -> Material {
+        Material {
+            youngs_modulus: self.youngs_modulus,
+            poisson_ratio: self.poisson_ratio,
+            density: self.density,
+            thermal_expansion: self.thermal_expansion,
+            yield_strength: self.yield_strength,
+            ultimate_strength: self.ultimate_strength,
+        }
+    }
+}
+
+/// A named collection of materials that input files can reference
+#[derive(Debug, Clone)]
+pub struct MaterialLibrary {
+    entries: IndexMap<String, MaterialEntry>,
+}
+
+impl MaterialLibrary {
+    /// Returns the bundled material library
+    pub fn builtin() -> MaterialLibrary {
+        let entries = parse_input(BUILTIN_LIBRARY, "materials.toml", InputFormat::Toml)
+            .expect("Built-in material library is invalid");
+        MaterialLibrary { entries }
+    }
+
+    /// Adds the materials from a library file, replacing any existing
+    /// entries with the same name. The file may be json, toml or yaml, and
+    /// its values must be in SI units.
+    ///
+    /// # Arguments
+    /// * `library_file` - The path to the library file
+    pub fn load_overrides(&mut self, library_file: &str) -> Result<(), MagnetiteError> {
+        let format = InputFormat::from_path(library_file)?;
+        let contents = match std::fs::read_to_string(library_file) {
+            Ok(f) => f,
+            Err(_err) => {
+                return Err(MagnetiteError::Input(format!(
+                    "Unable to open material library {}",
+                    library_file
+                )))
+            }
+        };
+
+        let entries: IndexMap<String, MaterialEntry> =
+            parse_input(&contents, library_file, format)?;
+        let overridden = entries
+            .keys()
+            .filter(|name| self.entries.contains_key(*name))
+            .count();
+
+        info!(
+            "loaded {} materials from {library_file}, overriding {overridden} built-in materials",
+            entries.len()
+        );
+
+        self.entries.extend(entries);
+
+        Ok(())
+    }
+
+    /// Looks up a material by name. Names are matched exactly first, then
+    /// case-insensitively.
+    pub fn get(&self, name: &str) -> Option<&MaterialEntry> {
+        self.entries.get(name).or_else(|| {
+            self.entries
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, entry)| entry)
+        })
+    }
+
+    /// Looks up a material by name, erroring if it is not in the library
+    pub fn material(&self, name: &str) -> Result<Material, MagnetiteError> {
+        match self.get(name) {
+            Some(entry) => Ok(entry.material()),
+            None => Err(MagnetiteError::Input(format!(
+                "Unknown material '{name}'. Run `magnetite materials` to list available materials"
+            ))),
+        }
+    }
+
+    /// Iterates over the materials in the library, in order
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &MaterialEntry)> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!(
+            "{}/tests/fixtures/materials/{}",
+            env!("CARGO_MANIFEST_DIR"),
+            name
+        )
+    }
+
+    #[test]
+    fn builtin_library_has_complete_entries() {
+        let library = MaterialLibrary::builtin();
+        assert!(library.iter().count() >= 15);
+
+        for (name, entry) in library.iter() {
+            assert!(entry.youngs_modulus > 1e9, "{name}");
+            assert!(
+                entry.poisson_ratio > 0.0 && entry.poisson_ratio < 0.5,
+                "{name}"
+            );
+            assert!(entry.density.is_some(), "{name}");
+            assert!(entry.thermal_expansion.is_some(), "{name}");
+            assert!(entry.yield_strength <= entry.ultimate_strength, "{name}");
+        }
+    }
+
+    #[test]
+    fn looks_up_materials_by_name() {
+        let library = MaterialLibrary::builtin();
+
+        let material = library.material("Al6061-T6").unwrap();
+        assert_eq!(material.youngs_modulus, 68.9e9);
+        assert_eq!(material.yield_strength, Some(276e6));
+
+        assert!(library.get("al6061-t6").is_some());
+        assert!(library.material("Unobtainium").is_err());
+    }
+
+    #[test]
+    fn library_files_override_entries() {
+        let mut library = MaterialLibrary::builtin();
+        let builtin_count = library.iter().count();
+        library.load_overrides(&fixture("overrides.toml")).unwrap();
+
+        assert_eq!(library.iter().count(), builtin_count + 1);
+
+        let material = library.material("Al6061-T6").unwrap();
+        assert_eq!(material.youngs_modulus, 69.0e9);
+        assert_eq!(material.yield_strength, Some(290e6));
+        assert_eq!(material.density, None);
+
+        assert_eq!(library.material("Acme-X1").unwrap().youngs_modulus, 150e9);
+    }
+}
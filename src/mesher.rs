@@ -1,29 +1,60 @@
-use json::JsonValue;
-use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::{
     datatypes::{
-        BoundaryRegion, BoundaryRule, BoundaryTarget, Element, ModelMetadata, Node, Vertex,
+        Body, BoundaryRule, Element, Geometry, Mesh, MeshOptions, ModelMetadata, Node,
+        PhysicalGroup, PhysicalGroupRef, Vertex,
     },
     error::MagnetiteError,
+    geometry, msh,
     solver::compute_element_area,
 };
+use tracing::{info, warn};
 
-enum MeshParseState {
-    Nodes,
-    Elements,
-    Entities,
-    Limbo,
+/// Whether a loop was declared as the outside of a part or as a hole
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LoopKind {
+    Outer,
+    Inner,
 }
 
-/// Parses a .svg file into a list of Vertexes
+/// A closed loop read from a geometry file, before it is nested into bodies
+#[derive(Debug, Clone)]
+struct RawLoop {
+    /// The svg id or csv file stem the loop came from
+    label: String,
+    /// Describes the loop in messages, e.g. `INNER_1 in part.svg`
+    description: String,
+    /// The kind given by an svg id prefix, if any
+    declared: Option<LoopKind>,
+    vertices: Vec<Vertex>,
+}
+
+/// Parses a number from an svg attribute
+///
+/// # Arguments
+/// * `value` - The text of the number
+/// * `svg_file` - The svg file, for the error message
+/// * `node` - The svg element the number belongs to
+///
+/// # Returns
+/// The number, or a Mesher error naming the element
+fn svg_number(value: &str, svg_file: &str, node: roxmltree::NodeId) -> Result<f64, MagnetiteError> {
+    value.parse().map_err(|_| {
+        MagnetiteError::Mesher(format!(
+            "Non-float value '{value}' in svg points at node {node:?} in {svg_file}"
+        ))
+    })
+}
+
+/// Parses a .svg file into a list of loops
 ///
 /// # Arguments
 /// * `svg_file` - The path to the input svg file
 ///
 /// # Returns
-/// An ordered vector of Vertex instances
-fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>, MagnetiteError> {
+/// The OUTER and INNER loops of the file, in the order they appear
+fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<RawLoop>, MagnetiteError> {
     let contents = match std::fs::read_to_string(svg_file) {
         Ok(file) => file,
         Err(_err) => {
@@ -37,15 +68,31 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
     let mut skipped_vertices: usize = 0; // count number of skips
 
     // Parse polylines and polygons from svg xml
-    let doc = roxmltree::Document::parse(&contents).unwrap();
+    let doc = roxmltree::Document::parse(&contents).map_err(|err| {
+        MagnetiteError::Mesher(format!("Unable to parse svg file {svg_file}: {err}"))
+    })?;
     let polylines: Vec<roxmltree::Node> = doc
         .descendants()
-        .into_iter()
         .filter(|n| n.tag_name().name() == "polyline" || n.tag_name().name() == "polygon")
         .collect();
 
-    let mut vertex_containers: Vec<Vec<Vertex>> = Vec::new();
-    vertex_containers.push(Vec::new()); // placeholder for outer
+    let mut loops: Vec<RawLoop> = Vec::new();
+    let mut push_loop = |id: &str, vertices: Vec<Vertex>| {
+        let declared = if id.trim().starts_with("INNER") {
+            LoopKind::Inner
+        } else if id.trim().starts_with("OUTER") {
+            LoopKind::Outer
+        } else {
+            warn!("skipping geometry with id {id}. Only supports OUTER and INNER");
+            return;
+        };
+        loops.push(RawLoop {
+            label: id.trim().to_string(),
+            description: format!("{} in {svg_file}", id.trim()),
+            declared: Some(declared),
+            vertices,
+        });
+    };
 
     for polyline in polylines {
         // Read points from points attribute
@@ -64,14 +111,17 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
         let mut points: Vec<Vertex> = Vec::new();
         let mut points_nopair: Vec<f64> = Vec::new();
         for point_str in points_raw {
-            let point: f64 = point_str.parse().expect("Non-float value in svg points");
-            points_nopair.push(point);
+            points_nopair.push(svg_number(point_str, svg_file, polyline.id())?);
         }
-        let mut i: usize = 0;
-        while i < points_nopair.len() {
-            let x = points_nopair[i];
-            let y = -points_nopair[i + 1]; // invert y
-            i += 2;
+        if !points_nopair.len().is_multiple_of(2) {
+            return Err(MagnetiteError::Mesher(format!(
+                "Odd number of coordinates in svg points at node {:?} in {svg_file}",
+                polyline.id()
+            )));
+        }
+        for pair in points_nopair.chunks_exact(2) {
+            let x = pair[0];
+            let y = -pair[1]; // invert y
 
             let vertex = Vertex { x, y };
 
@@ -107,19 +157,7 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
         }
 
         if let Some(id) = item_id {
-            if id.trim().starts_with("INNER") {
-                vertex_containers.push(points)
-            } else if id.trim().starts_with("OUTER") {
-                if vertex_containers[0].is_empty() {
-                    vertex_containers[0] = points
-                } else {
-                    return Err(MagnetiteError::Input(
-                        "Multiple OUTER geometries in SVG".to_owned(),
-                    ));
-                }
-            } else {
-                println!("warning: skipping polyline geometry with id {id}. Only supports OUTER and INNER");
-            }
+            push_loop(id, points);
         } else {
             return Err(MagnetiteError::Input(
                 "Error in svg file. Missing id field on polyline".to_owned(),
@@ -128,21 +166,17 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
     }
 
     // Parse rectangles from svg xml
-    let doc = roxmltree::Document::parse(&contents).unwrap();
     let rectangles: Vec<roxmltree::Node> = doc
         .descendants()
-        .into_iter()
         .filter(|n| n.tag_name().name() == "rect")
         .collect();
 
     for rect in rectangles {
         let x: f64 = match rect.attribute("x") {
-            Some(x) => x
-                .parse()
-                .expect(format!("Non-float value in svg points at node {:?}", rect.id()).as_str()),
+            Some(x) => svg_number(x, svg_file, rect.id())?,
             None => {
-                println!(
-                    "warning [mesh]: Missing x definition in rectangle {:?}. Assuming zero.",
+                warn!(
+                    "Missing x definition in rectangle {:?}. Assuming zero.",
                     rect.id()
                 );
                 0.0
@@ -150,19 +184,17 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
         };
 
         let y: f64 = match rect.attribute("y") {
-            Some(y) => y
-                .parse()
-                .expect(format!("Non-float value in svg points at node {:?}", rect.id()).as_str()),
+            Some(y) => svg_number(y, svg_file, rect.id())?,
             None => {
-                println!(
-                    "warning [mesh]: Missing y definition in rectangle {:?}. Assuming zero.",
+                warn!(
+                    "Missing y definition in rectangle {:?}. Assuming zero.",
                     rect.id()
                 );
                 0.0
             }
         };
 
-        let width: f64 = match rect.attribute("width") {
+        let width = match rect.attribute("width") {
             Some(width) => width,
             None => {
                 return Err(MagnetiteError::Input(format!(
@@ -170,10 +202,9 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
                     rect.id()
                 )));
             }
-        }
-        .parse()
-        .expect("Non-float value in svg points");
-        let height: f64 = match rect.attribute("height") {
+        };
+        let width = svg_number(width, svg_file, rect.id())?;
+        let height = match rect.attribute("height") {
             Some(height) => height,
             None => {
                 return Err(MagnetiteError::Input(format!(
@@ -181,12 +212,11 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
                     rect.id()
                 )));
             }
-        }
-        .parse()
-        .expect("Non-float value in svg points");
+        };
+        let height = svg_number(height, svg_file, rect.id())?;
 
         let vertices = vec![
-            Vertex { x: x, y: -y },
+            Vertex { x, y: -y },
             Vertex {
                 x: x + width,
                 y: -y,
@@ -212,19 +242,7 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
         }
 
         if let Some(id) = item_id {
-            if id.trim().starts_with("INNER") {
-                vertex_containers.push(vertices)
-            } else if id.trim().starts_with("OUTER") {
-                if vertex_containers[0].is_empty() {
-                    vertex_containers[0] = vertices
-                } else {
-                    return Err(MagnetiteError::Input(
-                        "Multiple OUTER geometries in SVG".to_owned(),
-                    ));
-                }
-            } else {
-                println!("warning: skipping polyline geometer with id {id}. Only supports OUTER and INNER")
-            }
+            push_loop(id, vertices);
         } else {
             return Err(MagnetiteError::Input(
                 "Error in svg file. Missing id field on polyline".to_owned(),
@@ -233,14 +251,155 @@ fn parse_svg(svg_file: &str, min_element_length: f32) -> Result<Vec<Vec<Vertex>>
     }
 
     if skipped_vertices > 0 {
-        println!("info: skipped {} vertices during meshing", skipped_vertices);
+        info!("skipped {} vertices during meshing", skipped_vertices);
     }
 
-    if vertex_containers[0].is_empty() {
-        return Err(MagnetiteError::Input("No OUTER geometry".to_owned()));
+    if !loops.iter().any(|l| l.declared == Some(LoopKind::Outer)) {
+        return Err(MagnetiteError::Input(format!(
+            "No OUTER geometry in {svg_file}"
+        )));
     }
 
-    Ok(vertex_containers)
+    Ok(loops)
+}
+
+/// Checks that no loops of bodies from different geometry files cross or
+/// touch. Loops from the same file are checked as they are nested.
+///
+/// # Arguments
+/// * `bodies` - The bodies of every geometry file, file by file
+/// * `starts` - The index of the first body of each file
+///
+/// # Returns
+/// An Input error naming the first pair of loops that touch, and where
+fn check_crossings_between(bodies: &[Body], starts: &[usize]) -> Result<(), MagnetiteError> {
+    let file_of = |body: usize| starts.partition_point(|start| *start <= body);
+    let described: Vec<(usize, String, &[Vertex])> = bodies
+        .iter()
+        .enumerate()
+        .flat_map(|(i, body)| {
+            body.loops.iter().enumerate().map(move |(j, vertices)| {
+                let description = match j {
+                    0 => format!("the outside of {}", body.name),
+                    _ => format!("hole {j} of {}", body.name),
+                };
+                (file_of(i), description, vertices.as_slice())
+            })
+        })
+        .collect();
+
+    for (i, (first_file, first_name, first)) in described.iter().enumerate() {
+        for (second_file, second_name, second) in &described[i + 1..] {
+            if first_file != second_file {
+                geometry::check_crossings(&[
+                    (first_name.clone(), *first),
+                    (second_name.clone(), *second),
+                ])?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Nests loops into bodies by how many other loops surround them. A loop
+/// inside no other loop, or inside a hole, is the outside of a body; a loop
+/// directly inside a body's outer loop is one of its holes.
+///
+/// Loops are validated first, and oriented with outer loops
+/// counter-clockwise and holes clockwise.
+///
+/// # Arguments
+/// * `loops` - The loops to nest
+/// * `unnamed` - The number of bodies already found, to number bodies
+///   without a label
+///
+/// # Returns
+/// The bodies, each named after its outer loop, or an Input error if a loop
+/// is invalid, loops overlap, or a declared kind disagrees with the nesting
+fn nest_loops(mut loops: Vec<RawLoop>, unnamed: usize) -> Result<Vec<Body>, MagnetiteError> {
+    for raw in loops.iter_mut() {
+        geometry::validate_loop(&raw.description, &mut raw.vertices)?;
+    }
+    let described: Vec<(String, &[Vertex])> = loops
+        .iter()
+        .map(|l| (l.description.clone(), l.vertices.as_slice()))
+        .collect();
+    geometry::check_crossings(&described)?;
+
+    // Loops don't cross, so any one point of a loop tells which loops
+    // surround it
+    let containers: Vec<Vec<usize>> = loops
+        .iter()
+        .enumerate()
+        .map(|(i, inner)| {
+            (0..loops.len())
+                .filter(|j| {
+                    *j != i && geometry::point_in_polygon(&inner.vertices[0], &loops[*j].vertices)
+                })
+                .collect()
+        })
+        .collect();
+    let depth = |i: usize| containers[i].len();
+    let parent = |i: usize| {
+        containers[i]
+            .iter()
+            .copied()
+            .find(|j| depth(*j) + 1 == depth(i))
+    };
+
+    for (i, raw) in loops.iter().enumerate() {
+        let first = &raw.vertices[0];
+        match (raw.declared, depth(i) % 2 == 0) {
+            (Some(LoopKind::Inner), true) => {
+                return Err(MagnetiteError::Input(format!(
+                    "{} is not inside an OUTER loop; its first point is at ({}, {})",
+                    raw.description, first.x, first.y
+                )))
+            }
+            (Some(LoopKind::Outer), false) => {
+                let container = &loops[parent(i).expect("Odd nesting depth without a container")];
+                return Err(MagnetiteError::Input(format!(
+                    "{} is inside {} at ({}, {}); put it inside an INNER loop to make an island",
+                    raw.description, container.description, first.x, first.y
+                )));
+            }
+            _ => (),
+        }
+    }
+
+    let mut bodies: Vec<Body> = Vec::new();
+    let mut body_of: Vec<Option<usize>> = vec![None; loops.len()];
+    for (i, raw) in loops.iter().enumerate().filter(|(i, _)| depth(*i) % 2 == 0) {
+        let mut name = match raw.label.is_empty() {
+            true => format!("body_{}", unnamed + bodies.len()),
+            false => raw.label.clone(),
+        };
+        if bodies.iter().any(|b| b.name == name) {
+            name = format!("{name}_{}", unnamed + bodies.len());
+        }
+        body_of[i] = Some(bodies.len());
+        bodies.push(Body {
+            name,
+            loops: vec![raw.vertices.clone()],
+        });
+    }
+
+    for (i, raw) in loops.iter().enumerate() {
+        if depth(i) % 2 == 0 {
+            continue;
+        }
+        let body = parent(i)
+            .and_then(|j| body_of[j])
+            .expect("Odd nesting depth without a surrounding part");
+        bodies[body].loops.push(raw.vertices.clone());
+    }
+
+    let reversed: usize = bodies.iter_mut().map(geometry::orient).sum();
+    if reversed > 0 {
+        info!("reversed {reversed} loops so that outer loops run counter-clockwise and holes clockwise");
+    }
+
+    Ok(bodies)
 }
 
 /// Parses a CSV file into a list of vertices
@@ -266,12 +425,12 @@ fn parse_csv(csv_file: &str) -> Result<Vec<Vertex>, MagnetiteError> {
     let mut y_index: usize = 0;
     let mut vertices: Vec<Vertex> = Vec::new();
 
-    for line in contents.split("\n") {
+    for (number, line) in contents.split("\n").enumerate() {
         if line.is_empty() {
             continue;
         }
 
-        if headers.len() == 0 {
+        if headers.is_empty() {
             headers = line.split(",").map(|x| x.trim()).collect();
 
             if !headers.contains(&"x") || !headers.contains(&"y") {
@@ -283,13 +442,25 @@ fn parse_csv(csv_file: &str) -> Result<Vec<Vertex>, MagnetiteError> {
             x_index = headers.iter().position(|f| f == &"x").unwrap();
             y_index = headers.iter().position(|f| f == &"y").unwrap();
         } else {
-            let line_contents: Vec<f64> = line
-                .split(",")
-                .map(|x| x.trim().parse().expect("Non-float value in csv points"))
-                .collect();
-
-            let x = line_contents[x_index];
-            let y = line_contents[y_index];
+            let fields: Vec<&str> = line.split(",").map(|x| x.trim()).collect();
+            let field = |index: usize| -> Result<f64, MagnetiteError> {
+                let Some(value) = fields.get(index) else {
+                    return Err(MagnetiteError::Input(format!(
+                        "Missing {} value in csv points at line {} of {csv_file}",
+                        headers[index],
+                        number + 1
+                    )));
+                };
+                value.parse().map_err(|_| {
+                    MagnetiteError::Input(format!(
+                        "Non-float value '{value}' in csv points at line {} of {csv_file}",
+                        number + 1
+                    ))
+                })
+            };
+
+            let x = field(x_index)?;
+            let y = field(y_index)?;
 
             vertices.push(Vertex { x, y });
         }
@@ -298,677 +469,1184 @@ fn parse_csv(csv_file: &str) -> Result<Vec<Vertex>, MagnetiteError> {
     Ok(vertices)
 }
 
-/// Builds a .geo file with from a list of vertices
+/// Builds a .geo file from bodies, with a plane surface and a physical
+/// surface for each body
 ///
 /// # Arguments
-/// * `vertices` - The vector of vertices to parse into a geometry
+/// * `bodies` - The bodies, each an outer loop followed by its holes
 /// * `output_file` - The output .geo file
 fn build_geo(
-    vertices_containers: &Vec<Vec<Vertex>>,
+    bodies: &[Body],
     output_file: &str,
     characteristic_length_min: f32,
     characteristic_length_max: f32,
 ) -> Result<(), MagnetiteError> {
-    let mut geo_file = std::fs::File::create(output_file).expect("Failed to create .geo file");
-
-    // Define outer points
-    geo_file
-        .write("// Define outer points\n".as_bytes())
-        .unwrap();
-    for (i, vertex) in vertices_containers[0].iter().enumerate() {
-        geo_file
-            .write(format!("Point({}) = {{ {}, {}, 0, 1.0 }};\n", i, vertex.x, vertex.y).as_bytes())
-            .unwrap();
-    }
-
-    // Define inner points
-    geo_file
-        .write("\n// Define inner points\n".as_bytes())
-        .unwrap();
+    let mut geo = String::new();
+    let mut point_count: usize = 0;
+    let mut loop_count: usize = 0;
+    let mut surfaces: Vec<Vec<usize>> = Vec::with_capacity(bodies.len());
+
+    for (b, body) in bodies.iter().enumerate() {
+        let mut surface_loops = Vec::with_capacity(body.loops.len());
+        for (l, vertices) in body.loops.iter().enumerate() {
+            let kind = if l == 0 { "outer" } else { "inner" };
+            geo += &format!("\n// Body {b} {kind} loop {l}\n");
+
+            let first = point_count + 1;
+            for vertex in vertices {
+                point_count += 1;
+                geo += &format!(
+                    "Point({point_count}) = {{ {}, {}, 0, 1.0 }};\n",
+                    vertex.x, vertex.y
+                );
+            }
 
-    let mut offset_counter: usize = vertices_containers[0].len();
-    let mut inner_offsets: Vec<usize> =
-        Vec::with_capacity(std::mem::size_of::<usize>() * (vertices_containers.len() - 1));
+            // Lines share the tag of their first point
+            for i in 0..vertices.len() {
+                let start = first + i;
+                let end = first + (i + 1) % vertices.len();
+                geo += &format!("Line({start}) = {{ {start}, {end} }};\n");
+            }
 
-    inner_offsets.push(0);
+            loop_count += 1;
+            let lines: Vec<String> = (first..first + vertices.len())
+                .map(|i| i.to_string())
+                .collect();
+            geo += &format!("Line Loop({loop_count}) = {{ {} }};\n", lines.join(", "));
+            surface_loops.push(loop_count);
+        }
+        surfaces.push(surface_loops);
+    }
 
-    for vertices in vertices_containers[1..].iter() {
-        inner_offsets.push(offset_counter);
+    // The first loop of each surface is its outside
+    geo += "\n// Define surfaces\n";
+    for (b, (body, surface_loops)) in std::iter::zip(bodies, &surfaces).enumerate() {
+        let loops: Vec<String> = surface_loops.iter().map(|l| l.to_string()).collect();
+        geo += &format!(
+            "Plane Surface({tag}) = {{ {} }};\nPhysical Surface(\"{}\", {tag}) = {{ {tag} }};\n",
+            loops.join(", "),
+            body.name.replace('"', "'"),
+            tag = b + 1
+        );
+    }
 
-        for (i, vertex) in vertices.iter().enumerate() {
-            geo_file
-                .write(
-                    format!(
-                        "Point({}) = {{ {}, {}, 0, 1.0 }};\n",
-                        i + offset_counter,
-                        vertex.x,
-                        vertex.y
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-        }
+    // Define meshing settings
+    geo += &format!(
+        "\n// Define Mesh Settings\n\
+        Mesh.ElementOrder = 1;\n\
+        Mesh.Algorithm  = 1;\n\
+        Mesh.CharacteristicLengthMin = {cl_min};\n\
+        Mesh.CharacteristicLengthMax = {cl_max};\n\
+        Mesh 2;\n\
+        ",
+        cl_min = characteristic_length_min,
+        cl_max = characteristic_length_max,
+    );
 
-        offset_counter += vertices.len();
+    if let Err(err) = std::fs::write(output_file, geo) {
+        return Err(MagnetiteError::Mesher(format!(
+            "Failed to write {output_file}: {err}"
+        )));
     }
 
-    // Connect points
-    geo_file.write("\n// Connect points\n".as_bytes()).unwrap();
+    Ok(())
+}
 
-    for (i, vertices) in vertices_containers.iter().enumerate() {
-        geo_file
-            .write(format!("\n// Point connections for surface {i}\n").as_bytes())
-            .unwrap();
+/// Counts scratch directories, to keep their names unique within a run
+static SCRATCH_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// The most names tried for a scratch directory before giving up
+const SCRATCH_ATTEMPTS: u32 = 16;
+
+/// A unique directory in the system temp directory for intermediate files,
+/// so that concurrent runs don't overwrite each other's files. The directory
+/// is deleted when dropped if its step succeeded, and kept for inspection if
+/// it failed or intermediates are to be kept.
+#[derive(Debug)]
+pub struct ScratchDir {
+    path: std::path::PathBuf,
+    keep: bool,
+    succeeded: bool,
+}
 
-        let point_offset = inner_offsets[i];
-
-        for i in 1..vertices.len() {
-            geo_file
-                .write(
-                    format!(
-                        "Line({}) = {{ {}, {} }};\n",
-                        i + point_offset - 1,
-                        i + point_offset - 1,
-                        i + point_offset
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-        }
-        geo_file
-            .write(
-                format!(
-                    "Line({}) = {{ {}, {} }};\n",
-                    vertices.len() + point_offset - 1,
-                    vertices.len() + point_offset - 1,
-                    point_offset
-                )
-                .as_bytes(),
-            )
-            .unwrap();
+impl ScratchDir {
+    /// Creates an empty scratch directory
+    ///
+    /// # Arguments
+    /// * `keep` - Keep the directory even if its step succeeds
+    pub fn new(keep: bool) -> Result<ScratchDir, MagnetiteError> {
+        let index = SCRATCH_COUNT.fetch_add(1, Ordering::Relaxed);
+        let name = format!("magnetite-{}-{index}", std::process::id());
+
+        // A directory kept by an earlier process with the same PID must not
+        // be reused, so only a newly created directory will do
+        for attempt in 0..SCRATCH_ATTEMPTS {
+            let path = match attempt {
+                0 => std::env::temp_dir().join(&name),
+                _ => {
+                    let nanos = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |t| t.subsec_nanos());
+                    std::env::temp_dir().join(format!("{name}-{nanos:x}{attempt}"))
+                }
+            };
+            match std::fs::create_dir(&path) {
+                Ok(()) => {
+                    return Ok(ScratchDir {
+                        path,
+                        keep,
+                        succeeded: false,
+                    })
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => {
+                    return Err(MagnetiteError::Mesher(format!(
+                        "Failed to create scratch directory {}: {err}",
+                        path.display()
+                    )))
+                }
+            }
+        }
+        Err(MagnetiteError::Mesher(format!(
+            "Failed to create a scratch directory in {}; {SCRATCH_ATTEMPTS} names were already taken",
+            std::env::temp_dir().display()
+        )))
     }
 
-    // Define loops
-    geo_file.write("\n//Register loops\n".as_bytes()).unwrap();
+    /// Returns the path of a file in the directory
+    ///
+    /// # Arguments
+    /// * `name` - The file name, e.g. `geom.msh`
+    pub fn file(&self, name: &str) -> String {
+        self.path.join(name).to_string_lossy().into_owned()
+    }
 
-    for (i, vertices) in vertices_containers.iter().enumerate() {
-        let point_offset = inner_offsets[i];
+    /// Marks the step as succeeded, so the directory is deleted when dropped
+    pub fn succeed(&mut self) {
+        self.succeeded = true;
+    }
+}
 
-        geo_file
-            .write(format!("Line Loop({}) = {{", i + 1).as_bytes())
-            .unwrap();
-        for i in 0..vertices.len() {
-            geo_file
-                .write(
-                    format!(
-                        "{} {}",
-                        ({
-                            if i != 0 {
-                                ","
-                            } else {
-                                ""
-                            }
-                        }),
-                        i + point_offset
-                    )
-                    .as_bytes(),
-                )
-                .unwrap();
-        }
-        geo_file.write(" };\n".as_bytes()).unwrap();
-    }
-
-    geo_file.write("\n//Define surface\n".as_bytes()).unwrap();
-
-    geo_file.write("Plane Surface(1) = {".as_bytes()).unwrap();
-
-    let iter: Vec<usize> = {
-        if vertices_containers.len() > 2 {
-            (0..vertices_containers.len()).collect()
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if self.succeeded && !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        } else if self.succeeded {
+            info!("kept intermediate files in {}", self.path.display());
         } else {
-            (0..vertices_containers.len()).rev().collect()
+            info!(
+                "kept intermediate files of the failed step in {}",
+                self.path.display()
+            );
         }
-    };
-
-    for (i, loop_idx) in iter.iter().enumerate() {
-        geo_file
-            .write(
-                format!(
-                    "{} {}",
-                    ({
-                        if i != 0 {
-                            ","
-                        } else {
-                            ""
-                        }
-                    }),
-                    loop_idx + 1
-                )
-                .as_bytes(),
-            )
-            .unwrap();
     }
-    geo_file.write(" };\n".as_bytes()).unwrap();
+}
 
-    // Define meshing settings
-    geo_file
-        .write(
-            format!(
-                "\n// Define Mesh Settings\n\
-                Mesh.ElementOrder = 1;\n\
-                Mesh.Algorithm  = 1;\n\
-                Mesh.CharacteristicLengthMin = {cl_min};\n\
-                Mesh.CharacteristicLengthMax = {cl_max};\n\
-                Mesh 2;\n\
-                ",
-                cl_min = characteristic_length_min,
-                cl_max = characteristic_length_max,
-            )
-            .as_bytes(),
-        )
-        .unwrap();
+/// The warnings and errors Gmsh reported while meshing
+#[derive(Debug, Default, PartialEq)]
+struct GmshDiagnostics {
+    warnings: Vec<String>,
+    errors: Vec<String>,
+}
 
-    Ok(())
+/// The most Gmsh warnings printed individually; the rest are counted
+const PRINTED_GMSH_WARNINGS: usize = 10;
+
+/// The number of trailing log lines quoted when Gmsh fails without
+/// reporting an error
+const GMSH_LOG_EXCERPT: usize = 10;
+
+/// Picks the warnings and errors out of Gmsh's output, whose lines start
+/// with `Info    :`, `Warning :` or `Error   :`
+///
+/// # Arguments
+/// * `log` - Gmsh's stdout and stderr
+fn parse_gmsh_log(log: &str) -> GmshDiagnostics {
+    let mut diagnostics = GmshDiagnostics::default();
+    for line in log.lines() {
+        let Some((level, message)) = line.split_once(':') else {
+            continue;
+        };
+        match level.trim() {
+            "Warning" => diagnostics.warnings.push(message.trim().to_string()),
+            "Error" => diagnostics.errors.push(message.trim().to_string()),
+            _ => (),
+        }
+    }
+    diagnostics
 }
 
-/// Runs Gmsh to create a mesh from a list of vertices
+/// Runs Gmsh to create a mesh from bodies
 ///
 /// # Arguments
-/// * `vertices` - A vector of vertex objects
+/// * `bodies` - The bodies to mesh
+/// * `scratch` - The directory to write the .geo file in
 /// * `output` - The output filepath of the .msh file
-/// * `characteristic_length` - Characteristic length of the mesh
-/// * `characteristic_length_variance` - Characteristic length variance of the mesh
+/// * `mesh_options` - The characteristic lengths, and where to find Gmsh and
+///   save its log
 fn compute_mesh(
-    vertices: &Vec<Vec<Vertex>>,
+    bodies: &[Body],
+    scratch: &ScratchDir,
     output: &str,
-    characteristic_length_min: f32,
-    characteristic_length_max: f32,
+    mesh_options: &MeshOptions,
 ) -> Result<(), MagnetiteError> {
-    let geo_filepath = "geom.geo";
+    let geo_filepath = &scratch.file("geom.geo");
 
-    println!(
-        "info: building .geo for Gmsh with {:.3}< CL < {:.3}",
-        characteristic_length_min, characteristic_length_max
+    info!(
+        "building .geo for Gmsh with {:.3}< CL < {:.3}",
+        mesh_options.characteristic_length_min, mesh_options.characteristic_length_max
     );
     build_geo(
-        vertices,
+        bodies,
         geo_filepath,
-        characteristic_length_min,
-        characteristic_length_max,
+        mesh_options.characteristic_length_min,
+        mesh_options.characteristic_length_max,
     )?;
 
-    println!("info: running gmsh...");
-    let _output = match std::process::Command::new("gmsh")
+    // Gmsh may fail without writing a mesh, so one left from an earlier run
+    // must not be mistaken for its output
+    match std::fs::remove_file(output) {
+        Ok(()) => (),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => {
+            return Err(MagnetiteError::Mesher(format!(
+                "Failed to remove the old mesh {output}: {err}"
+            )))
+        }
+    }
+
+    let gmsh = mesh_options.gmsh_path.as_deref().unwrap_or("gmsh");
+    info!("running {gmsh}...");
+    let result = std::process::Command::new(gmsh)
         .arg(geo_filepath)
         .arg("-2")
         .arg("-o")
         .arg(output)
-        .output()
-    {
-        Ok(out) => out,
+        .output();
+
+    let process = match result {
+        Ok(process) => process,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(MagnetiteError::Mesher(match &mesh_options.gmsh_path {
+                Some(path) => format!("Gmsh was not found at {path}"),
+                None => "Gmsh was not found on the PATH. Install it from https://gmsh.info, or give its location with --gmsh-path".to_owned(),
+            }));
+        }
         Err(err) => {
-            return Err(MagnetiteError::Mesher(
-                format!("Gmsh failed: {err}").to_string(),
-            ));
+            return Err(MagnetiteError::Mesher(format!(
+                "Failed to run {gmsh}: {err}"
+            )));
         }
     };
 
-    std::fs::remove_file(geo_filepath).expect("Failed to delete .geo file");
+    let log = String::from_utf8_lossy(&process.stdout).into_owned()
+        + &String::from_utf8_lossy(&process.stderr);
+    if let Some(log_file) = &mesh_options.gmsh_log {
+        match std::fs::write(log_file, &log) {
+            Ok(()) => info!("wrote the Gmsh log to {log_file}"),
+            Err(err) => warn!("failed to write the Gmsh log to {log_file}: {err}"),
+        }
+    }
 
-    Ok(())
+    let diagnostics = parse_gmsh_log(&log);
+    for warning in diagnostics.warnings.iter().take(PRINTED_GMSH_WARNINGS) {
+        warn!("gmsh: {warning}");
+    }
+    if diagnostics.warnings.len() > PRINTED_GMSH_WARNINGS {
+        warn!(
+            "gmsh reported {} more warnings{}",
+            diagnostics.warnings.len() - PRINTED_GMSH_WARNINGS,
+            match mesh_options.gmsh_log {
+                Some(_) => "; see the Gmsh log",
+                None => "; save them all with --gmsh-log",
+            }
+        );
+    }
+
+    let produced = std::path::Path::new(output).exists();
+    if process.status.success() && produced {
+        return Ok(());
+    }
+
+    // Quote Gmsh's errors, or the end of its log if it reported none
+    let excerpt = match diagnostics.errors.is_empty() {
+        false => diagnostics.errors.join("\n  "),
+        true => {
+            let lines: Vec<&str> = log.lines().collect();
+            lines[lines.len().saturating_sub(GMSH_LOG_EXCERPT)..].join("\n  ")
+        }
+    };
+    let status = match process.status.code() {
+        Some(code) if !process.status.success() => format!("exited with status {code}"),
+        Some(_) => "produced no mesh".to_owned(),
+        None => "was stopped by a signal".to_owned(),
+    };
+    Err(MagnetiteError::Mesher(format!(
+        "Gmsh {status}:\n  {excerpt}"
+    )))
 }
 
-/// Ensures that the order of nodes in the element is counter-clockwise
-fn check_ccw(element: &mut Element, nodes: &Vec<Node>) {
-    if compute_element_area(&element, nodes) < 1.0 {
+/// Ensures that the order of nodes in the element is counter-clockwise.
+/// Any negative area is clockwise, however small the element; meshes in
+/// millimetres or metres have elements far smaller than one unit of area.
+fn check_ccw(element: &mut Element, nodes: &[Node]) {
+    if compute_element_area(element, nodes) < 0.0 {
         element.nodes.reverse();
     }
 }
 
-/// Parses a .msh file into Nodes and Elements
+/// Parses a .msh file into Nodes, Elements and Physical Groups
 ///
 /// # Arguments
 /// * `mesh_file` - The path to the mesh file
 ///
 /// # Returns
-/// A tuple with a vector of the parsed nodes and a vector of the parsed
-/// elements, in that order.
-fn parse_mesh(mesh_file: &str) -> Result<(Vec<Node>, Vec<Element>), MagnetiteError> {
-    let mut elements: Vec<Element> = Vec::new();
-
-    let mut mesh_fs = match std::fs::File::open(mesh_file) {
-        Ok(f) => f,
-        Err(err) => {
-            return Err(MagnetiteError::Mesher(format!(
-                "Unable to open auto-generated mesh file: {err}"
-            )))
-        }
-    };
+/// The parsed Mesh, with elements ordered counter-clockwise
+pub fn parse_mesh(mesh_file: &str) -> Result<Mesh, MagnetiteError> {
+    let (nodes, mut elements, physical_groups) = msh::read_msh(mesh_file)?;
 
-    let mut mesh_contents: String = String::new();
-    mesh_fs
-        .read_to_string(&mut mesh_contents)
-        .expect("Failed to read mesh contents into String");
-
-    let mut parser_state = MeshParseState::Limbo;
-    let mut parsed_section_metadata = false;
-    let mut lines = mesh_contents.split("\n");
-
-    let mut nodes_unordered: Vec<Node> = Vec::new();
-    let mut node_indexes: Vec<usize> = Vec::new();
-
-    while let Some(line) = lines.next() {
-        if line.is_empty() {
-            continue;
-        }
-
-        if line.starts_with("$End") {
-            parser_state = MeshParseState::Limbo;
-        }
+    // Enforce ccw ordering on elements
+    for element in &mut elements {
+        check_ccw(element, &nodes);
+    }
 
-        match parser_state {
-            MeshParseState::Limbo => {
-                parsed_section_metadata = false;
+    info!(
+        "loaded {} nodes, {} elements and {} physical groups",
+        nodes.len(),
+        elements.len(),
+        physical_groups.len()
+    );
 
-                if line.starts_with("$Entities") {
-                    parser_state = MeshParseState::Entities;
-                } else if line.starts_with("$Node") {
-                    parser_state = MeshParseState::Nodes;
-                } else if line.starts_with("$Elements") {
-                    parser_state = MeshParseState::Elements;
-                }
-                continue;
-            }
-            MeshParseState::Nodes => {
-                if !parsed_section_metadata {
-                    parsed_section_metadata = true;
-                    continue;
-                }
+    Ok(Mesh {
+        nodes,
+        elements,
+        physical_groups,
+    })
+}
 
-                let node_data: Vec<usize> = line
-                    .split(" ")
-                    .map(|i| i.parse().expect("Unexpected non-int in mesh data"))
-                    .collect();
+/// Meshes closed loops of vertices with Gmsh, as a single body
+///
+/// # Arguments
+/// * `loops` - The outer loop, followed by any inner loops
+/// * `mesh_options` - The mesh settings passed to Gmsh
+///
+/// # Returns
+/// The generated Mesh
+pub fn mesh_loops(
+    loops: &[Vec<Vertex>],
+    mesh_options: &MeshOptions,
+) -> Result<Mesh, MagnetiteError> {
+    let body = Body {
+        name: "body_0".to_string(),
+        loops: loops.to_vec(),
+    };
+    mesh_bodies(&[body], mesh_options)
+}
 
-                let num_nodes_local = node_data[3];
+/// Meshes bodies with Gmsh, each as its own surface tagged with a physical
+/// group of the body's name. The bodies share no nodes, so they only
+/// interact through contact.
+///
+/// # Arguments
+/// * `bodies` - The bodies, each an outer loop followed by its holes
+/// * `mesh_options` - The mesh settings passed to Gmsh
+///
+/// # Returns
+/// The generated Mesh
+pub fn mesh_bodies(bodies: &[Body], mesh_options: &MeshOptions) -> Result<Mesh, MagnetiteError> {
+    if bodies.is_empty()
+        || bodies
+            .iter()
+            .any(|b| b.loops.is_empty() || b.loops[0].is_empty())
+    {
+        return Err(MagnetiteError::Mesher("No geometry to mesh".to_owned()));
+    }
 
-                let mut node_tags: Vec<usize> =
-                    Vec::with_capacity(num_nodes_local * std::mem::size_of::<usize>());
+    let mut scratch = ScratchDir::new(mesh_options.keep_intermediates)?;
+    let mesh_filepath = &scratch.file("geom.msh");
+    compute_mesh(bodies, &scratch, mesh_filepath, mesh_options)?;
 
-                for _ in 0..num_nodes_local {
-                    let tag: usize = lines
-                        .next()
-                        .unwrap()
-                        .parse()
-                        .expect("found non-int node tag");
-                    node_tags.push(tag);
-                }
+    let mesh = parse_mesh(mesh_filepath)?;
+    scratch.succeed();
+    Ok(mesh)
+}
 
-                for i in 0..num_nodes_local {
-                    let node_coords: Vec<f64> = lines
-                        .next()
-                        .unwrap()
-                        .split(" ")
-                        .map(|c| c.parse().expect("Non-float coordinate in mesh"))
-                        .collect();
-
-                    let node = Node {
-                        vertex: Vertex {
-                            x: node_coords[0],
-                            y: node_coords[1],
-                        },
-                        ux: None,
-                        uy: None,
-                        fx: Some(0.0),
-                        fy: Some(0.0),
-                    };
-
-                    nodes_unordered.push(node);
-                    node_indexes.push(node_tags[i] - 1);
-                }
-            }
-            MeshParseState::Elements => {
-                if !parsed_section_metadata {
-                    parsed_section_metadata = true;
-                    continue;
-                }
+/// Loads geometry from files
+///
+/// # Arguments
+/// * `geometry_files` - The geometry input files--either csv, svg or a
+///   pre-built Gmsh msh. Loops nest into bodies by which loops surround
+///   them, within each svg file and across the csv files
+/// * `model_metadata` - The model metadata. Geometry is converted from the
+///   declared length unit to meters, and vertices closer together than the
+///   minimum characteristic length are skipped in svg files
+///
+/// # Returns
+/// The loaded Geometry
+pub fn load_geometry(
+    geometry_files: &[&str],
+    model_metadata: &ModelMetadata,
+) -> Result<Geometry, MagnetiteError> {
+    let length = model_metadata.units.map_or(1.0, |u| u.length.factor());
+    let scale = |vertex: &mut Vertex| {
+        vertex.x *= length;
+        vertex.y *= length;
+    };
 
-                let element_data: Vec<usize> = line
-                    .split(" ")
-                    .map(|i| {
-                        i.parse()
-                            .expect(format!("Unexpected non-int in mesh data {}", i).as_str())
-                    })
-                    .collect();
-
-                let entity_dim = element_data[0];
-                let num_elements = element_data[3];
-
-                for _ in 0..num_elements {
-                    let metadata: Vec<usize> = lines
-                        .next()
-                        .unwrap()
-                        .trim()
-                        .split(" ")
-                        .map(|i| {
-                            i.parse()
-                                .expect(format!("Unexpected non-int in mesh data {}", i).as_str())
-                        })
-                        .collect();
-
-                    if entity_dim != 2 {
-                        continue;
-                    }
-
-                    let n0 = metadata[1] - 1;
-                    let n1 = metadata[2] - 1;
-                    let n2 = metadata[3] - 1;
-
-                    let element = Element {
-                        nodes: [n0, n1, n2],
-                        stress: None,
-                    };
-                    elements.push(element)
-                }
-            }
-            MeshParseState::Entities => continue,
+    if let Some(mesh_file) = geometry_files.iter().find(|f| f.ends_with(".msh")) {
+        if geometry_files.len() > 1 {
+            return Err(MagnetiteError::Input(format!(
+                "The mesh {mesh_file} cannot be combined with other geometry files; give it as the only geometry file"
+            )));
         }
+        info!("using existing mesh {mesh_file}");
+        let mut mesh = parse_mesh(mesh_file)?;
+        mesh.nodes
+            .iter_mut()
+            .for_each(|node| scale(&mut node.vertex));
+        return Ok(Geometry::Mesh(mesh));
     }
 
-    // Order nodes
-    let mut nodes: Vec<Node> =
-        Vec::with_capacity(std::mem::size_of::<Node>() * nodes_unordered.len());
+    // Loops are nested into bodies within each svg file, and across all csv
+    // files together
+    let mut bodies: Vec<Body> = Vec::new();
+    let mut csv_loops: Vec<RawLoop> = Vec::new();
+    // The first body of each svg file, and of the csv files
+    let mut starts: Vec<usize> = Vec::new();
 
-    unsafe {
-        nodes.set_len(nodes_unordered.len());
+    for geom in geometry_files {
+        if geom.ends_with(".svg") {
+            let min_element_length =
+                model_metadata.mesh_options.characteristic_length_min / length as f32;
+            let loops = parse_svg(geom, min_element_length)?;
+            starts.push(bodies.len());
+            bodies.extend(nest_loops(loops, bodies.len())?);
+        } else if geom.ends_with(".csv") {
+            csv_loops.push(RawLoop {
+                label: std::path::Path::new(geom)
+                    .file_stem()
+                    .map_or(String::new(), |s| s.to_string_lossy().into_owned()),
+                description: geom.to_string(),
+                declared: None,
+                vertices: parse_csv(geom)?,
+            });
+        } else {
+            return Err(MagnetiteError::Input(format!(
+                "Unrecognized geometry filetype {geom}"
+            )));
+        }
     }
-
-    for (idx, node) in std::iter::zip(node_indexes, nodes_unordered) {
-        nodes[idx] = node;
+    if !csv_loops.is_empty() {
+        starts.push(bodies.len());
+        bodies.extend(nest_loops(csv_loops, bodies.len())?);
     }
-
-    // Enforce ccw ordering on elements
-    for element in &mut elements {
-        check_ccw(element, &nodes);
+    check_crossings_between(&bodies, &starts)?;
+
+    bodies
+        .iter_mut()
+        .flat_map(|b| b.loops.iter_mut())
+        .flatten()
+        .for_each(scale);
+
+    if bodies.len() > 1 {
+        let names: Vec<&str> = bodies.iter().map(|b| b.name.as_str()).collect();
+        info!(
+            "loaded {} separate bodies: {}",
+            bodies.len(),
+            names.join(", ")
+        );
     }
-
-    println!(
-        "info: loaded {} nodes and {} elements",
-        nodes.len(),
-        elements.len()
-    );
-
-    std::fs::remove_file(mesh_file).expect("Failed to delete .msh file");
-
-    Ok((nodes, elements))
+    // Even a single body keeps its name, so boundary rules and body
+    // materials can target it
+    Ok(Geometry::Bodies(bodies))
 }
 
-/// Parses the input json into a JsonValue object
+/// Finds the physical group a region references. A name picks the first
+/// group with that name; a tag must pick out one group, since groups of
+/// different dimensions can share a tag.
 ///
 /// # Arguments
-/// * `input_file` - The path to the input file
+/// * `reference` - The name or tag of the group
+/// * `physical_groups` - The physical groups of the mesh
+/// * `region` - The referencing region, for error messages, e.g.
+///   `Boundary 'fixed'`
 ///
 /// # Returns
-/// A JsonValue object
-fn load_input_file(input_file: &str) -> Result<JsonValue, MagnetiteError> {
-    let file_string = match std::fs::read_to_string(input_file) {
-        Ok(f) => f,
-        Err(_err) => {
-            return Err(MagnetiteError::Input(format!(
-                "Unable to open input file {}",
-                input_file
-            )))
-        }
+/// The referenced group
+pub fn find_physical_group<'a>(
+    reference: &PhysicalGroupRef,
+    physical_groups: &'a [PhysicalGroup],
+    region: &str,
+) -> Result<&'a PhysicalGroup, MagnetiteError> {
+    let mut matches = physical_groups.iter().filter(|g| reference.matches(g));
+    let Some(group) = matches.next() else {
+        return Err(MagnetiteError::Input(format!(
+            "{region} references unknown physical group {reference}"
+        )));
     };
+    let others: Vec<String> = matches.map(|g| format!("'{}'", g.name)).collect();
+    if matches!(reference, PhysicalGroupRef::Tag(_)) && !others.is_empty() {
+        return Err(MagnetiteError::Input(format!(
+            "{region} references physical group {reference}, which '{}' and {} share; reference the group by name",
+            group.name,
+            others.join(", ")
+        )));
+    }
+    Ok(group)
+}
 
-    let input_file_json = match json::parse(&file_string) {
-        Ok(f) => f,
-        Err(err) => {
-            return Err(MagnetiteError::Input(format!(
-                "Error in input file json: {err}"
-            )))
-        }
-    };
+/// Finds the boundary rule that governs each node; the last rule whose
+/// region contains a node wins. Warns about rules that match no nodes.
+///
+/// # Arguments
+/// * `rules` - The boundary rules, in order of precedence
+/// * `nodes` - The nodes
+/// * `physical_groups` - The physical groups of the mesh, if any
+///
+/// # Returns
+/// The index of the governing rule of each node, if any
+pub fn governing_rules(
+    rules: &[BoundaryRule],
+    nodes: &[Node],
+    physical_groups: &[PhysicalGroup],
+) -> Result<Vec<Option<usize>>, MagnetiteError> {
+    // Resolve physical group membership for each rule
+    let rule_groups: Vec<Option<&PhysicalGroup>> = rules
+        .iter()
+        .map(|rule| match &rule.region.physical_group {
+            Some(reference) => find_physical_group(
+                reference,
+                physical_groups,
+                &format!("Boundary '{}'", rule.name),
+            )
+            .map(Some),
+            None => Ok(None),
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut rule_matches: Vec<usize> = vec![0; rules.len()];
+    let mut governing = vec![None; nodes.len()];
+
+    for (i, node) in nodes.iter().enumerate() {
+        for (r, (rule, group)) in std::iter::zip(rules, &rule_groups).enumerate() {
+            let in_group = match group {
+                Some(group) => group.nodes.binary_search(&i).is_ok(),
+                None => true,
+            };
+            let candidate = in_group
+                && node.vertex.x > rule.region.x_min
+                && node.vertex.x < rule.region.x_max
+                && node.vertex.y > rule.region.y_min
+                && node.vertex.y < rule.region.y_max;
 
-    if !input_file_json.has_key("metadata") {
-        return Err(MagnetiteError::Input(
-            "Input json missing metadata field".to_string(),
-        ));
-    }
-    if !input_file_json.has_key("boundary_conditions") {
-        return Err(MagnetiteError::Input(
-            "Input json missing boundary_conditions field in metadata section".to_string(),
-        ));
-    }
-    if !input_file_json["metadata"].has_key("part_thickness") {
-        return Err(MagnetiteError::Input(
-            "Input json missing part_thickness field in metadata section".to_string(),
-        ));
-    }
-    if !input_file_json["metadata"].has_key("material_elasticity") {
-        return Err(MagnetiteError::Input(
-            "Input json missing material_elasticity field in metadata section".to_string(),
-        ));
+            if candidate {
+                rule_matches[r] += 1;
+                governing[i] = Some(r);
+            }
+        }
     }
-    if !input_file_json["metadata"].has_key("poisson_ratio") {
-        return Err(MagnetiteError::Input(
-            "Input json missing poisson_ratio field in metadata section".to_string(),
-        ));
+
+    for (rule, matches) in std::iter::zip(rules, rule_matches) {
+        if matches == 0 {
+            warn!("boundary rule '{}' does not apply to any nodes", rule.name);
+        }
     }
 
-    Ok(input_file_json)
+    Ok(governing)
 }
 
-/// Parses Model Metadata from the input_json
+/// Applies boundary rules to a vector of nodes
 ///
 /// # Arguments
-/// * `input_json`: The input file as a JsonValue object
+/// * `rules` - The boundary rules to apply, in order of precedence
+/// * `nodes` - A mutable reference to the vector of nodes
+/// * `physical_groups` - The physical groups of the mesh, if any
 ///
 /// # Returns
-/// A ModelMetadata instance
-fn parse_input_metadata(input_json: &JsonValue) -> Result<ModelMetadata, MagnetiteError> {
-    let youngs_modulus = input_json["metadata"]["material_elasticity"].as_f64();
+/// The index of the governing rule of each node, if any
+pub fn apply_boundary_conditions(
+    rules: &[BoundaryRule],
+    nodes: &mut [Node],
+    physical_groups: &[PhysicalGroup],
+) -> Result<Vec<Option<usize>>, MagnetiteError> {
+    let governing = governing_rules(rules, nodes, physical_groups)?;
+
+    for (node, rule) in std::iter::zip(nodes, &governing) {
+        if let Some(rule) = rule {
+            let target = &rules[*rule].target;
+            node.ux = target.ux;
+            node.uy = target.uy;
+            node.fx = target.fx;
+            node.fy = target.fy;
+        }
+    }
 
-    let part_thickness = input_json["metadata"]["part_thickness"].as_f64();
+    Ok(governing)
+}
 
-    let poisson_ratio = input_json["metadata"]["poisson_ratio"].as_f64();
+/// Elements with a smallest angle below this, in degrees, count as poorly
+/// shaped
+pub const POOR_ELEMENT_ANGLE: f64 = 20.0;
+
+/// Shape statistics of a mesh's elements
+#[derive(Debug, Clone)]
+pub struct MeshQuality {
+    pub nodes: usize,
+    pub elements: usize,
+    /// The smallest angle of any element, in degrees
+    pub min_angle: f64,
+    /// The mean over the elements of their smallest angle, in degrees
+    pub mean_min_angle: f64,
+    /// The largest aspect ratio of any element; 1 for an equilateral
+    /// triangle
+    pub max_aspect_ratio: f64,
+    /// The element with the smallest angle
+    pub worst_element: usize,
+    /// The number of elements with an angle below 20 degrees
+    pub poor_elements: usize,
+    /// The number of elements with zero or negative area
+    pub inverted_elements: usize,
+}
 
-    let characteristic_length_min = input_json["metadata"]["characteristic_length_min"].as_f32();
+/// Measures the shape of a mesh's elements
+///
+/// # Arguments
+/// * `mesh` - The mesh to measure
+///
+/// # Returns
+/// The MeshQuality of the mesh
+pub fn mesh_quality(mesh: &Mesh) -> MeshQuality {
+    let mut quality = MeshQuality {
+        nodes: mesh.nodes.len(),
+        elements: mesh.elements.len(),
+        min_angle: 180.0,
+        mean_min_angle: 0.0,
+        max_aspect_ratio: 0.0,
+        worst_element: 0,
+        poor_elements: 0,
+        inverted_elements: 0,
+    };
 
-    let characteristic_length_max = input_json["metadata"]["characteristic_length_max"].as_f32();
+    for (index, element) in mesh.elements.iter().enumerate() {
+        let area = compute_element_area(element, &mesh.nodes);
+        if area <= 0.0 {
+            quality.inverted_elements += 1;
+        }
 
-    if youngs_modulus.is_none() {
-        return Err(MagnetiteError::Input(
-            "Input json missing material elasticity".to_owned(),
-        ));
-    }
-    if poisson_ratio.is_none() {
-        return Err(MagnetiteError::Input(
-            "Input json missing poisson ratio".to_owned(),
-        ));
+        let vertices = element.nodes.map(|n| &mesh.nodes[n].vertex);
+        let lengths = [0, 1, 2].map(|i| {
+            (vertices[(i + 1) % 3].x - vertices[i].x).hypot(vertices[(i + 1) % 3].y - vertices[i].y)
+        });
+        // Each angle is opposite an edge, by the law of cosines
+        let min_angle = (0..3)
+            .map(|i| {
+                let (a, b, c) = (lengths[i], lengths[(i + 1) % 3], lengths[(i + 2) % 3]);
+                ((b * b + c * c - a * a) / (2.0 * b * c))
+                    .clamp(-1.0, 1.0)
+                    .acos()
+                    .to_degrees()
+            })
+            .fold(180.0, f64::min);
+        // The longest edge over the shortest altitude, scaled so an
+        // equilateral triangle has a ratio of 1
+        let longest = lengths.iter().copied().fold(0.0, f64::max);
+        let aspect_ratio = longest * longest * 3f64.sqrt() / (4.0 * area.abs());
+
+        quality.mean_min_angle += min_angle / mesh.elements.len() as f64;
+        quality.max_aspect_ratio = quality.max_aspect_ratio.max(aspect_ratio);
+        if min_angle < POOR_ELEMENT_ANGLE {
+            quality.poor_elements += 1;
+        }
+        if min_angle < quality.min_angle {
+            quality.min_angle = min_angle;
+            quality.worst_element = index;
+        }
     }
-    if characteristic_length_min.is_none() {
-        return Err(MagnetiteError::Input(
-            "Input json missing minimum characteristic length".to_owned(),
-        ));
+
+    quality
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square loop with its bottom-left corner at `(x, y)`
+    fn square(x: f64, y: f64, size: f64) -> Vec<Vertex> {
+        vec![
+            Vertex { x, y },
+            Vertex { x: x + size, y },
+            Vertex {
+                x: x + size,
+                y: y + size,
+            },
+            Vertex { x, y: y + size },
+        ]
     }
-    if characteristic_length_max.is_none() {
-        return Err(MagnetiteError::Input(
-            "Input json missing maximum characteristic length".to_owned(),
-        ));
+
+    #[test]
+    fn nests_islands_and_separate_bodies() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg">
+  <rect id="OUTER_lug" x="0" y="0" width="10" height="10"/>
+  <g id="INNER">
+    <rect x="2" y="2" width="6" height="6"/>
+  </g>
+  <polygon id="OUTER_pin" points="4 4 6 4 6 6 4 6"/>
+  <rect id="OUTER" x="20" y="0" width="10" height="10"/>
+</svg>"#;
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let svg_file = scratch.file("nested.svg");
+        std::fs::write(&svg_file, svg).unwrap();
+        let loops = parse_svg(&svg_file, 0.0);
+        scratch.succeed();
+
+        let bodies = nest_loops(loops.unwrap(), 0).unwrap();
+        let names: Vec<&str> = bodies.iter().map(|b| b.name.as_str()).collect();
+        // Polygons are read before rectangles
+        assert_eq!(names, ["OUTER_pin", "OUTER_lug", "OUTER"]);
+        let loop_counts: Vec<usize> = bodies.iter().map(|b| b.loops.len()).collect();
+        assert_eq!(loop_counts, [1, 2, 1]);
+        assert_eq!(bodies[1].loops[1][0], Vertex { x: 2.0, y: -2.0 });
     }
 
-    Ok(ModelMetadata {
-        youngs_modulus: youngs_modulus.unwrap(),
-        poisson_ratio: poisson_ratio.unwrap(),
-        part_thickness: part_thickness.unwrap(),
-        characteristic_length_min: characteristic_length_min.unwrap(),
-        characteristic_length_max: characteristic_length_max.unwrap(),
-    })
-}
+    #[test]
+    fn reports_malformed_svg_files() {
+        let cases = [
+            ("<svg><polygon id=\"OUTER\"", "Unable to parse svg file"),
+            (
+                r#"<svg><polygon id="OUTER" points="0 0 1 zero 1 1"/></svg>"#,
+                "Non-float value 'zero'",
+            ),
+            (
+                r#"<svg><polygon id="OUTER" points="0 0 1 0 1"/></svg>"#,
+                "Odd number of coordinates",
+            ),
+            (
+                r#"<svg><rect id="OUTER" x="0" y="0" width="wide" height="1"/></svg>"#,
+                "Non-float value 'wide'",
+            ),
+        ];
 
-/// Applies boundary conditions to a vector of nodes from the input json
-///
-/// # Arguments
-/// * `input_json` - The input file as a JsonValue object
-/// * `nodes` - A mutable reference to the vector of nodes
-fn apply_boundary_conditions(
-    input_json: &JsonValue,
-    nodes: &mut Vec<Node>,
-) -> Result<(), MagnetiteError> {
-    let mut rules: Vec<BoundaryRule> = Vec::new();
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let svg_file = scratch.file("bad.svg");
+        for (svg, expected) in cases {
+            std::fs::write(&svg_file, svg).unwrap();
+            match parse_svg(&svg_file, 0.0) {
+                Err(MagnetiteError::Mesher(message)) => {
+                    assert!(message.contains(expected), "{message}")
+                }
+                Err(other) => panic!("expected a mesher error, got {other:?}"),
+                Ok(_) => panic!("expected {svg} to be rejected"),
+            }
+        }
+        scratch.succeed();
+    }
 
-    // Load rules from json
-    for (name, rule_json) in input_json["boundary_conditions"].entries() {
-        if !rule_json.has_key("region") {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary rule {name} is missing region field"
-            )));
+    #[test]
+    fn reports_malformed_csv_files() {
+        let cases = [
+            (
+                "x,y\n0,0\n1,zero\n",
+                "Non-float value 'zero' in csv points at line 3",
+            ),
+            ("y,x\n0,0\n1\n", "Missing x value in csv points at line 3"),
+        ];
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let csv_file = scratch.file("bad.csv");
+        for (csv, expected) in cases {
+            std::fs::write(&csv_file, csv).unwrap();
+            match parse_csv(&csv_file) {
+                Err(MagnetiteError::Input(message)) => {
+                    assert!(message.contains(expected), "{message}");
+                    assert!(message.contains(&csv_file), "{message}");
+                }
+                Err(other) => panic!("expected an input error, got {other:?}"),
+                Ok(_) => panic!("expected {csv} to be rejected"),
+            }
         }
-        if !rule_json.has_key("targets") {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary rule {name} is missing target field"
-            )));
+        scratch.succeed();
+    }
+
+    #[test]
+    fn rejects_meshes_mixed_with_other_geometry() {
+        for files in [["plate.svg", "mesh.msh"], ["mesh.msh", "hole.csv"]] {
+            let message = load_geometry(&files, &metadata()).unwrap_err().to_string();
+            assert!(
+                message.contains("The mesh mesh.msh cannot be combined"),
+                "{message}"
+            );
         }
+    }
 
-        // Register region
-        let mut boundary_region = BoundaryRegion {
-            x_min: f64::MIN,
-            x_max: f64::MAX,
-            y_min: f64::MIN,
-            y_max: f64::MAX,
+    #[test]
+    fn matches_physical_groups_by_name_or_tag() {
+        let nodes: Vec<Node> = (0..3)
+            .map(|i| Node {
+                vertex: Vertex {
+                    x: i as f64,
+                    y: 0.0,
+                },
+                ux: None,
+                uy: None,
+                fx: None,
+                fy: None,
+            })
+            .collect();
+        let group = |name: &str, dimension, tag, nodes| PhysicalGroup {
+            name: name.to_string(),
+            dimension,
+            tag,
+            nodes,
         };
-        if rule_json["region"].has_key("x_target_min") {
-            boundary_region.x_min = rule_json["region"]["x_target_min"]
-                .as_f64()
-                .expect(format!("Bad value for x_target_min in {name}").as_str())
-        }
-        if rule_json["region"].has_key("x_target_max") {
-            boundary_region.x_max = rule_json["region"]["x_target_max"]
-                .as_f64()
-                .expect(format!("Bad value for x_target_max in {name}").as_str())
-        }
-        if rule_json["region"].has_key("y_target_min") {
-            boundary_region.y_min = rule_json["region"]["y_target_min"]
-                .as_f64()
-                .expect(format!("Bad value for y_target_min in {name}").as_str())
-        }
-        if rule_json["region"].has_key("y_target_max") {
-            boundary_region.y_max = rule_json["region"]["y_target_max"]
-                .as_f64()
-                .expect(format!("Bad value for y_target_max in {name}").as_str())
-        }
-
-        // Register target
-        let boundary_target = BoundaryTarget {
-            ux: rule_json["targets"]["ux"].as_f64(),
-            uy: rule_json["targets"]["uy"].as_f64(),
-            fx: rule_json["targets"]["fx"].as_f64(),
-            fy: rule_json["targets"]["fy"].as_f64(),
+        let groups = vec![
+            group("left", 1, 5, vec![0]),
+            group("right", 1, 7, vec![2]),
+            group("plate", 2, 7, vec![0, 1, 2]),
+        ];
+        let rule = |reference| crate::datatypes::BoundaryRule {
+            name: "fixed".to_string(),
+            region: crate::datatypes::BoundaryRegion {
+                x_min: f64::MIN,
+                x_max: f64::MAX,
+                y_min: f64::MIN,
+                y_max: f64::MAX,
+                physical_group: Some(reference),
+            },
+            target: crate::datatypes::BoundaryTarget {
+                ux: Some(0.0),
+                uy: Some(0.0),
+                fx: None,
+                fy: None,
+                time_function: None,
+            },
         };
 
-        // Validate input
-        if boundary_region.x_min > boundary_region.x_max {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' has x_target_min greater than x_target_max"
-            )));
-        }
-        if boundary_region.y_min > boundary_region.y_max {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' has y_target_min greater than y_target_max"
-            )));
-        }
-        if boundary_target.fx.is_none() && boundary_target.ux.is_none() {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' is under-constrained in x-axis"
-            )));
+        for reference in [
+            PhysicalGroupRef::Tag(5),
+            PhysicalGroupRef::Name("left".to_string()),
+        ] {
+            let governing = governing_rules(&[rule(reference)], &nodes, &groups).unwrap();
+            assert_eq!(governing, [Some(0), None, None]);
         }
-        if boundary_target.fy.is_none() && boundary_target.uy.is_none() {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' is under-constrained in y-axis"
-            )));
+
+        let message = governing_rules(&[rule(PhysicalGroupRef::Tag(3))], &nodes, &groups)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("Boundary 'fixed' references unknown physical group tag 3"),
+            "{message}"
+        );
+        let message = governing_rules(&[rule(PhysicalGroupRef::Tag(7))], &nodes, &groups)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("which 'right' and 'plate' share"),
+            "{message}"
+        );
+    }
+
+    fn material(youngs_modulus: f64) -> crate::datatypes::Material {
+        crate::datatypes::Material {
+            youngs_modulus,
+            poisson_ratio: 0.3,
+            density: None,
+            thermal_expansion: None,
+            yield_strength: None,
+            ultimate_strength: None,
         }
-        if boundary_target.fx.is_some() && boundary_target.ux.is_some() {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' is over-constrained in x-axis"
-            )));
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: material(1.0),
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions::default(),
+            units: None,
+            safety: Default::default(),
         }
-        if boundary_target.fy.is_some() && boundary_target.uy.is_some() {
-            return Err(MagnetiteError::Input(format!(
-                "Boundary '{name}' is over-constrained in y-axis"
-            )));
+    }
+
+    #[test]
+    fn finds_loops_crossing_between_files() {
+        let metadata = metadata();
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let lug = scratch.file("lug.svg");
+        std::fs::write(
+            &lug,
+            r#"<svg><rect id="OUTER_lug" x="0" y="0" width="10" height="10"/></svg>"#,
+        )
+        .unwrap();
+        let pin = scratch.file("pin.svg");
+        let apart = scratch.file("apart.svg");
+        std::fs::write(
+            &pin,
+            r#"<svg><rect id="OUTER_pin" x="8" y="2" width="4" height="4"/></svg>"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &apart,
+            r#"<svg><rect id="OUTER_pin" x="20" y="2" width="4" height="4"/></svg>"#,
+        )
+        .unwrap();
+
+        let crossing = load_geometry(&[&lug, &pin], &metadata);
+        let separate = load_geometry(&[&lug, &apart], &metadata);
+        scratch.succeed();
+
+        match crossing {
+            Err(MagnetiteError::Input(message)) => assert_eq!(
+                message,
+                "the outside of OUTER_lug and the outside of OUTER_pin overlap; they touch at (10, -6)"
+            ),
+            other => panic!("expected an input error, got {other:?}"),
         }
+        assert!(matches!(separate, Ok(Geometry::Bodies(bodies)) if bodies.len() == 2));
+    }
 
-        rules.push(BoundaryRule {
-            name: name.to_string(),
-            region: boundary_region,
-            target: boundary_target,
-        })
+    #[test]
+    fn nests_loops_by_position() {
+        let raw = |label: &str, declared, vertices| RawLoop {
+            label: label.to_string(),
+            description: label.to_string(),
+            declared,
+            vertices,
+        };
+        let loops = vec![
+            raw("", None, square(1.0, 1.0, 1.0)),
+            raw("", None, square(0.0, 0.0, 3.0)),
+            raw("", None, square(5.0, 0.0, 1.0)),
+        ];
+
+        let bodies = nest_loops(loops, 2).unwrap();
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0].name, "body_2");
+        // Holes are turned clockwise
+        let mut hole = square(1.0, 1.0, 1.0);
+        hole.reverse();
+        assert_eq!(bodies[0].loops, vec![square(0.0, 0.0, 3.0), hole]);
+        assert_eq!(bodies[1].name, "body_3");
+
+        let message = |loops| match nest_loops(loops, 0) {
+            Err(MagnetiteError::Input(message)) => message,
+            other => panic!("expected an input error, got {other:?}"),
+        };
+        assert_eq!(
+            message(vec![
+                raw("OUTER_a", Some(LoopKind::Outer), square(0.0, 0.0, 3.0)),
+                raw("OUTER_b", Some(LoopKind::Outer), square(1.0, 1.0, 1.0)),
+            ]),
+            "OUTER_b is inside OUTER_a at (1, 1); put it inside an INNER loop to make an island"
+        );
+        assert_eq!(
+            message(vec![
+                raw("OUTER", Some(LoopKind::Outer), square(0.0, 0.0, 3.0)),
+                raw("INNER", Some(LoopKind::Inner), square(4.0, 0.0, 1.0)),
+            ]),
+            "INNER is not inside an OUTER loop; its first point is at (4, 0)"
+        );
     }
-    println!(
-        "info: loaded {} boundary rules from input file",
-        &rules.len()
-    );
 
-    for node in nodes {
-        for rule in &rules {
-            let candidate = node.vertex.x > rule.region.x_min
-                && node.vertex.x < rule.region.x_max
-                && node.vertex.y > rule.region.y_min
-                && node.vertex.y < rule.region.y_max;
+    #[test]
+    fn reverses_small_clockwise_elements() {
+        let node = |x: f64, y: f64| Node {
+            vertex: Vertex { x, y },
+            ux: None,
+            uy: None,
+            fx: None,
+            fy: None,
+        };
+        let nodes = vec![node(0.0, 0.0), node(0.01, 0.0), node(0.0, 0.01)];
+        let element = |nodes: [usize; 3]| Element {
+            nodes,
+            stress: None,
+            stress_tensor: None,
+            plastic_strain: None,
+            physical_tags: Vec::new(),
+        };
 
-            if candidate {
-                node.ux = rule.target.ux;
-                node.uy = rule.target.uy;
-                node.fx = rule.target.fx;
-                node.fy = rule.target.fy;
-            }
-        }
+        // Clockwise, with an area well below one
+        let mut clockwise = element([0, 2, 1]);
+        check_ccw(&mut clockwise, &nodes);
+        assert_eq!(clockwise.nodes, [1, 2, 0]);
+        assert!(compute_element_area(&clockwise, &nodes) > 0.0);
+
+        let mut counter_clockwise = element([0, 1, 2]);
+        check_ccw(&mut counter_clockwise, &nodes);
+        assert_eq!(counter_clockwise.nodes, [0, 1, 2]);
     }
 
-    Ok(())
-}
+    #[test]
+    fn measures_element_shapes() {
+        let node = |x: f64, y: f64| Node {
+            vertex: Vertex { x, y },
+            ux: None,
+            uy: None,
+            fx: None,
+            fy: None,
+        };
+        let element = |nodes: [usize; 3]| Element {
+            nodes,
+            stress: None,
+            stress_tensor: None,
+            plastic_strain: None,
+            physical_tags: Vec::new(),
+        };
+        let mesh = Mesh {
+            nodes: vec![
+                node(0.0, 0.0),
+                node(1.0, 0.0),
+                node(0.5, 3f64.sqrt() / 2.0),
+                node(10.0, 0.0),
+            ],
+            elements: vec![element([0, 1, 2]), element([1, 3, 2])],
+            physical_groups: Vec::new(),
+        };
 
-/// Runs the mesher
-///
-/// # Arguments
-/// * `geometry_file` - The geometry input file--either csv or svg
-/// * `input_file` - The input file that contains boundary conditions
-/// * `characteristic_length` - Characteristic length of the mesh
-/// * `characteristic_length_variance` - Characteristic length variance of the mesh
-pub fn run(
-    geometry_files: Vec<&str>,
-    input_file: &str,
-) -> Result<(Vec<Node>, Vec<Element>, ModelMetadata), MagnetiteError> {
-    let input_file_json = load_input_file(input_file)?;
-    let model_metadata = parse_input_metadata(&input_file_json)?;
-
-    let mut vertices: Vec<Vec<Vertex>> = Vec::new();
+        let quality = mesh_quality(&mesh);
+        assert_eq!(quality.elements, 2);
+        assert_eq!(quality.worst_element, 1);
+        assert_eq!(quality.poor_elements, 1);
+        assert_eq!(quality.inverted_elements, 0);
+        assert!(quality.min_angle < 10.0 && quality.mean_min_angle > 30.0);
+        assert!(quality.max_aspect_ratio > 10.0);
+
+        // Only the equilateral triangle is left
+        let mesh = Mesh {
+            elements: vec![element([0, 1, 2])],
+            ..mesh
+        };
+        let quality = mesh_quality(&mesh);
+        assert!((quality.min_angle - 60.0).abs() < 1e-9);
+        assert!((quality.max_aspect_ratio - 1.0).abs() < 1e-9);
+    }
 
-    for geom in geometry_files {
-        if geom.ends_with(".svg") {
-            vertices = parse_svg(geom, model_metadata.characteristic_length_min)?;
-            break;
-        } else if geom.ends_with(".csv") {
-            vertices.push(parse_csv(geom)?);
-        } else {
-            return Err(MagnetiteError::Input(
-                format!("Unrecognized geometry filetype {geom}").to_string(),
-            ));
-        }
+    #[test]
+    fn picks_warnings_and_errors_from_gmsh_log() {
+        let log = "Info    : Meshing 1D...\n\
+                   Warning : Curve 3 has a zero length\n\
+                   Info    : Done meshing 1D (Wall 0.001s)\n\
+                   Error   : Self-intersecting surface 1: Plane Surface\n";
+        assert_eq!(
+            parse_gmsh_log(log),
+            GmshDiagnostics {
+                warnings: vec!["Curve 3 has a zero length".to_string()],
+                errors: vec!["Self-intersecting surface 1: Plane Surface".to_string()],
+            }
+        );
     }
 
-    let mesh_filepath = "geom.msh";
-    compute_mesh(
-        &vertices,
-        mesh_filepath,
-        model_metadata.characteristic_length_min,
-        model_metadata.characteristic_length_max,
-    )?;
+    #[test]
+    fn builds_a_surface_for_each_body() {
+        let bodies = [
+            Body {
+                name: "plate".to_string(),
+                loops: vec![square(0.0, 0.0, 3.0), square(1.0, 1.0, 1.0)],
+            },
+            Body {
+                name: "pin".to_string(),
+                loops: vec![square(5.0, 0.0, 1.0)],
+            },
+        ];
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let geo_file = scratch.file("bodies.geo");
+        build_geo(&bodies, &geo_file, 0.1, 0.5).unwrap();
+        let geo = std::fs::read_to_string(&geo_file).unwrap();
+        scratch.succeed();
+
+        assert!(geo.contains("Line(8) = { 8, 5 };"), "{geo}");
+        assert!(geo.contains("Line Loop(3) = { 9, 10, 11, 12 };"), "{geo}");
+        assert!(geo.contains("Plane Surface(1) = { 1, 2 };"), "{geo}");
+        assert!(
+            geo.contains("Physical Surface(\"plate\", 1) = { 1 };"),
+            "{geo}"
+        );
+        assert!(geo.contains("Plane Surface(2) = { 3 };"), "{geo}");
+        assert!(
+            geo.contains("Physical Surface(\"pin\", 2) = { 2 };"),
+            "{geo}"
+        );
+    }
 
-    let (mut nodes, elements) = parse_mesh(mesh_filepath)?;
+    #[test]
+    fn never_reuses_a_scratch_directory() {
+        // Take the name the next scratch directory would get, as a kept
+        // directory from an earlier process with the same PID would
+        let next = SCRATCH_COUNT.load(Ordering::Relaxed);
+        let stale = std::env::temp_dir().join(format!("magnetite-{}-{next}", std::process::id()));
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::write(stale.join("geom.msh"), "stale").unwrap();
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        assert_ne!(scratch.path, stale);
+        assert!(!std::path::Path::new(&scratch.file("geom.msh")).exists());
+        scratch.succeed();
+        std::fs::remove_dir_all(&stale).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn does_not_mistake_an_old_mesh_for_gmsh_output() {
+        let bodies = [Body {
+            name: "plate".to_string(),
+            loops: vec![square(0.0, 0.0, 1.0)],
+        }];
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let output = scratch.file("geom.msh");
+        std::fs::write(&output, "stale").unwrap();
+
+        // A Gmsh that succeeds without writing a mesh
+        let options = MeshOptions {
+            gmsh_path: Some("true".to_string()),
+            ..Default::default()
+        };
+        let err = compute_mesh(&bodies, &scratch, &output, &options).unwrap_err();
+        assert!(err.to_string().contains("produced no mesh"), "{err}");
+        scratch.succeed();
+    }
 
-    apply_boundary_conditions(&input_file_json, &mut nodes)?;
+    #[cfg(unix)]
+    #[test]
+    fn applies_a_material_to_a_single_named_body() {
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let svg = scratch.file("plate.svg");
+        std::fs::write(
+            &svg,
+            r#"<svg><rect id="OUTER_plate" x="0" y="0" width="1" height="1"/></svg>"#,
+        )
+        .unwrap();
 
-    Ok((nodes, elements, model_metadata))
+        // A Gmsh that meshes any geometry as two triangles, in a surface
+        // named after the first physical surface of the .geo file
+        let gmsh = scratch.file("gmsh.sh");
+        std::fs::write(
+            &gmsh,
+            r#"#!/bin/sh
+name=$(sed -n 's/^Physical Surface("\([^"]*\)".*/\1/p' "$1" | head -n 1)
+printf '$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$PhysicalNames\n1\n2 1 "%s"\n$EndPhysicalNames\n$Nodes\n4\n1 0 0 0\n2 1 0 0\n3 1 1 0\n4 0 1 0\n$EndNodes\n$Elements\n2\n1 2 2 1 1 1 2 3\n2 2 2 1 1 1 3 4\n$EndElements\n' "$name" > "$4"
+"#,
+        )
+        .unwrap();
+        std::process::Command::new("chmod")
+            .args(["+x", &gmsh])
+            .status()
+            .unwrap();
+
+        let mut metadata = metadata();
+        metadata.mesh_options.gmsh_path = Some(gmsh);
+        metadata.body_materials = vec![crate::datatypes::BodyMaterial {
+            body: "OUTER_plate".to_string(),
+            material: material(5.0),
+        }];
+        let geometry = load_geometry(&[&svg], &metadata).unwrap();
+        assert!(matches!(&geometry, Geometry::Bodies(bodies) if bodies[0].name == "OUTER_plate"));
+
+        let mut model = crate::Model::new(geometry, metadata, Vec::new());
+        let mesh = model.mesh().unwrap().clone();
+        let materials = crate::solver::element_materials(&mesh, &model.metadata).unwrap();
+        scratch.succeed();
+
+        assert_eq!(materials.len(), 2);
+        assert!(materials.iter().all(|m| m.youngs_modulus == 5.0));
+    }
 }
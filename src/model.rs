@@ -0,0 +1,790 @@
+use crate::{
+    contact::{self, ContactForce},
+    datatypes::{
+        BoundaryNodes, BoundaryRule, CaseResults, ContactOptions, Element, ElementResult,
+        FailureCriterion, Geometry, HarmonicOptions, LoadCase, LoadCombination, Material, Mesh,
+        ModelMetadata, Node, NodeResult, NonlinearOptions, Results, SolverStats, TimeFunction,
+        TopologyOptions, TransientOptions,
+    },
+    error::MagnetiteError,
+    harmonic::{self, HarmonicResult},
+    input::ModelInput,
+    mesher, nonlinear, solver,
+    topology::{self, TopologyResult},
+    transient::{self, TransientFrame},
+};
+use tracing::info;
+
+/// A 2D plane stress model: its geometry, material, boundary rules and
+/// mesh options, along with its mesh and results once computed.
+///
+/// [`Model::solve`] runs a linear-elastic solve by default, or a large
+/// displacement and plastic one, or one with contact, when the model's
+/// `nonlinear` or `contact` options are set. Load cases, topology
+/// optimisation, transient and harmonic analyses have their own methods.
+///
+/// Values may be in any consistent unit system. Models loaded with
+/// [`crate::input::load`] are in SI units; `metadata.units` records the units
+/// of the input file so outputs can be converted back and labelled.
+///
+/// ```no_run
+/// use magnetite::{
+///     materials::MaterialLibrary, BoundaryRegion, BoundaryRule, BoundaryTarget, Geometry,
+///     MeshOptions, Model, ModelMetadata, Vertex,
+/// };
+///
+/// let square = vec![
+///     Vertex { x: 0.0, y: 0.0 },
+///     Vertex { x: 1.0, y: 0.0 },
+///     Vertex { x: 1.0, y: 1.0 },
+///     Vertex { x: 0.0, y: 1.0 },
+/// ];
+/// let metadata = ModelMetadata {
+///     material: MaterialLibrary::builtin().material("Steel-A36")?,
+///     body_materials: Vec::new(),
+///     part_thickness: 0.01,
+///     mesh_options: MeshOptions { characteristic_length_min: 0.05, characteristic_length_max: 0.1, ..Default::default() },
+///     units: None,
+///     safety: Default::default(),
+/// };
+/// let fixed = BoundaryRule {
+///     name: "fixed".to_string(),
+///     region: BoundaryRegion { x_min: f64::MIN, x_max: 0.01, y_min: f64::MIN, y_max: f64::MAX, physical_group: None },
+///     target: BoundaryTarget { ux: Some(0.0), uy: Some(0.0), fx: None, fy: None, time_function: None },
+/// };
+///
+/// let mut model = Model::new(Geometry::Loops(vec![square]), metadata, vec![fixed]);
+/// let results = model.solve()?;
+/// println!("{} nodes solved", results.nodes.len());
+/// # Ok::<(), magnetite::MagnetiteError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Model {
+    pub geometry: Geometry,
+    pub metadata: ModelMetadata,
+    pub boundary_rules: Vec<BoundaryRule>,
+    /// Load cases solved by [`Model::solve_load_cases`], on top of the shared
+    /// boundary rules
+    pub load_cases: Vec<LoadCase>,
+    /// Combinations of the load cases, found by superposition
+    pub combinations: Vec<LoadCombination>,
+    /// Solves incrementally, with large displacements or plasticity, when
+    /// set; otherwise the solve is linear
+    pub nonlinear: Option<NonlinearOptions>,
+    /// Solves with frictionless contact between bodies and against rigid
+    /// lines when set
+    pub contact: Option<ContactOptions>,
+    mesh: Option<Mesh>,
+    results: Option<Results>,
+    case_results: Vec<CaseResults>,
+    contact_forces: Vec<ContactForce>,
+    boundary_nodes: Vec<BoundaryNodes>,
+    solver_stats: Option<SolverStats>,
+}
+
+impl Model {
+    /// Creates an unmeshed, unsolved model
+    ///
+    /// # Arguments
+    /// * `geometry` - Closed loops of vertices to mesh, or an existing mesh
+    /// * `metadata` - The material, part thickness and mesh options
+    /// * `boundary_rules` - The boundary rules, in the order they are applied
+    pub fn new(
+        geometry: Geometry,
+        metadata: ModelMetadata,
+        boundary_rules: Vec<BoundaryRule>,
+    ) -> Model {
+        Model {
+            geometry,
+            metadata,
+            boundary_rules,
+            load_cases: Vec::new(),
+            combinations: Vec::new(),
+            nonlinear: None,
+            contact: None,
+            mesh: None,
+            results: None,
+            case_results: Vec::new(),
+            contact_forces: Vec::new(),
+            boundary_nodes: Vec::new(),
+            solver_stats: None,
+        }
+    }
+
+    /// Creates an unmeshed, unsolved model from a loaded input file, with
+    /// its load cases, combinations, and nonlinear and contact settings.
+    /// The topology, transient and harmonic sections are passed to their
+    /// own methods.
+    ///
+    /// # Arguments
+    /// * `model_input` - The loaded input file
+    /// * `geometry` - The geometry, loaded with the input file's metadata
+    pub fn from_input(model_input: ModelInput, geometry: Geometry) -> Model {
+        let mut model = Model::new(geometry, model_input.metadata, model_input.boundary_rules);
+        model.load_cases = model_input.load_cases;
+        model.combinations = model_input.combinations;
+        model.nonlinear = model_input.nonlinear;
+        model.contact = model_input.contact;
+        model
+    }
+
+    /// Meshes the geometry. Loops are meshed with Gmsh, each body
+    /// separately; an existing mesh is used as-is. Any previous mesh and
+    /// results are discarded, so call this again after changing the geometry
+    /// or mesh options.
+    ///
+    /// # Returns
+    /// A reference to the mesh
+    pub fn mesh(&mut self) -> Result<&Mesh, MagnetiteError> {
+        let mesh = match &self.geometry {
+            Geometry::Loops(loops) => mesher::mesh_loops(loops, &self.metadata.mesh_options)?,
+            Geometry::Bodies(bodies) => mesher::mesh_bodies(bodies, &self.metadata.mesh_options)?,
+            Geometry::Mesh(mesh) => mesh.clone(),
+        };
+
+        self.results = None;
+        self.case_results.clear();
+        self.contact_forces.clear();
+        self.boundary_nodes.clear();
+        self.solver_stats = None;
+        Ok(self.mesh.insert(mesh))
+    }
+
+    /// Checks that the boundary rules apply to the mesh without solving,
+    /// meshing the model first if needed. Unless contact holds the parts in
+    /// place, the shared rules and each load case must fix at least one node
+    /// in x and one in y.
+    ///
+    /// # Returns
+    /// The name of each boundary rule and the number of nodes it governs.
+    /// Load case rules are named after their load case, e.g. `dead: top`.
+    pub fn check(&mut self) -> Result<Vec<(String, usize)>, MagnetiteError> {
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        let (shared_nodes, case_nodes, boundary_nodes) =
+            self.apply_rules(mesh, &self.load_cases)?;
+
+        let mut scenarios: Vec<(String, Vec<Node>)> = std::iter::zip(&self.load_cases, case_nodes)
+            .map(|(load_case, nodes)| (format!("Load case '{}'", load_case.name), nodes))
+            .collect();
+        if scenarios.is_empty() {
+            scenarios.push(("The model".to_string(), shared_nodes));
+        }
+
+        if self.contact.is_none() {
+            for (name, nodes) in &scenarios {
+                for (direction, fixed) in [
+                    ("x", nodes.iter().any(|n| n.ux.is_some())),
+                    ("y", nodes.iter().any(|n| n.uy.is_some())),
+                ] {
+                    if !fixed {
+                        return Err(MagnetiteError::Input(format!(
+                            "{name} is free to move in {direction}; no boundary rule fixes u{direction} at any node"
+                        )));
+                    }
+                }
+            }
+        }
+
+        let counts = boundary_nodes
+            .iter()
+            .map(|b| (b.name.clone(), b.nodes.len()))
+            .collect();
+        self.boundary_nodes = boundary_nodes;
+        Ok(counts)
+    }
+
+    /// Applies the shared boundary rules to the mesh, and then each load
+    /// case's rules on top of them
+    ///
+    /// # Arguments
+    /// * `mesh` - The mesh of the model
+    /// * `load_cases` - The load cases to apply
+    ///
+    /// # Returns
+    /// The nodes with the shared rules applied, the nodes of each load case,
+    /// and the nodes each rule governs
+    #[allow(clippy::type_complexity)]
+    fn apply_rules(
+        &self,
+        mesh: &Mesh,
+        load_cases: &[LoadCase],
+    ) -> Result<(Vec<Node>, Vec<Vec<Node>>, Vec<BoundaryNodes>), MagnetiteError> {
+        let mut boundary_nodes = Vec::new();
+        let mut collect = |prefix: &str, rules: &[BoundaryRule], governing: &[Option<usize>]| {
+            for (r, rule) in rules.iter().enumerate() {
+                boundary_nodes.push(BoundaryNodes {
+                    name: format!("{prefix}{}", rule.name),
+                    target: rule.target.clone(),
+                    nodes: (0..governing.len())
+                        .filter(|n| governing[*n] == Some(r))
+                        .collect(),
+                });
+            }
+        };
+
+        let mut shared_nodes = mesh.nodes.clone();
+        let governing = mesher::apply_boundary_conditions(
+            &self.boundary_rules,
+            &mut shared_nodes,
+            &mesh.physical_groups,
+        )?;
+        collect("", &self.boundary_rules, &governing);
+
+        let mut case_nodes = Vec::new();
+        for load_case in load_cases {
+            let mut nodes = shared_nodes.clone();
+            let governing = mesher::apply_boundary_conditions(
+                &load_case.boundary_rules,
+                &mut nodes,
+                &mesh.physical_groups,
+            )?;
+            collect(
+                &format!("{}: ", load_case.name),
+                &load_case.boundary_rules,
+                &governing,
+            );
+            case_nodes.push(nodes);
+        }
+
+        Ok((shared_nodes, case_nodes, boundary_nodes))
+    }
+
+    /// Applies the boundary rules and solves the model, meshing it first if
+    /// needed. The solve is nonlinear if `nonlinear` is set, and models
+    /// contact if `contact` is set.
+    ///
+    /// # Returns
+    /// A reference to the results
+    pub fn solve(&mut self) -> Result<&Results, MagnetiteError> {
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        let (mut nodes, _, boundary_nodes) = self.apply_rules(mesh, &[])?;
+        let mut elements = mesh.elements.clone();
+        let materials = solver::element_materials(mesh, &self.metadata)?;
+
+        let analysis = match (&self.nonlinear, &self.contact) {
+            (Some(_), _) => "nonlinear",
+            (None, Some(_)) => "contact",
+            (None, None) => "linear",
+        };
+        let mut stats = solver_stats(analysis, &nodes);
+        let start = std::time::Instant::now();
+
+        self.contact_forces.clear();
+        match (&self.nonlinear, &self.contact) {
+            (Some(_), Some(_)) => {
+                return Err(MagnetiteError::Solver(
+                    "Contact can't be combined with a nonlinear solve".to_owned(),
+                ))
+            }
+            (Some(options), None) => {
+                self.single_material("nonlinear")?;
+                nonlinear::run(&mut nodes, &mut elements, &self.metadata, options)?
+            }
+            (None, Some(options)) => {
+                self.contact_forces = contact::run(
+                    &mut nodes,
+                    &mut elements,
+                    &materials,
+                    &self.metadata,
+                    options,
+                )?
+            }
+            (None, None) => solver::run(&mut nodes, &mut elements, &materials, &self.metadata)?,
+        }
+        stats.solve_time = start.elapsed().as_secs_f64();
+        self.boundary_nodes = boundary_nodes;
+        self.solver_stats = Some(stats);
+
+        let results = collect_results(nodes, elements, &materials, &self.metadata);
+        Ok(self.results.insert(results))
+    }
+
+    /// Solves each load case, then superposes the results of the load cases
+    /// for each combination. Every load case must constrain the same degrees
+    /// of freedom, so the stiffness matrix is assembled and factored once and
+    /// shared between them. Meshes the model first if needed.
+    ///
+    /// # Returns
+    /// The results of each load case, followed by each combination
+    pub fn solve_load_cases(&mut self) -> Result<&[CaseResults], MagnetiteError> {
+        if self.load_cases.is_empty() {
+            return Err(MagnetiteError::Input(
+                "Model has no load cases to solve".to_owned(),
+            ));
+        }
+        if self.nonlinear.is_some() || self.contact.is_some() {
+            return Err(MagnetiteError::Solver(
+                "Load cases rely on superposition, so they can't be used with a nonlinear solve or contact"
+                    .to_owned(),
+            ));
+        }
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        let (shared_nodes, mut case_nodes, boundary_nodes) =
+            self.apply_rules(mesh, &self.load_cases)?;
+        let mut stats = solver_stats("load_cases", &case_nodes[0]);
+        let start = std::time::Instant::now();
+
+        let materials = solver::element_materials(mesh, &self.metadata)?;
+        let total_stiffness_matrix =
+            solver::assemble_stiffness(&shared_nodes, &mesh.elements, &materials, &self.metadata);
+        let factored = solver::FactoredStiffness::new(&total_stiffness_matrix, &case_nodes[0])?;
+
+        let mut case_results = Vec::new();
+        for (load_case, nodes) in std::iter::zip(&self.load_cases, &mut case_nodes) {
+            if !factored.matches(nodes) {
+                return Err(MagnetiteError::Input(format!(
+                    "Load case '{}' constrains different degrees of freedom than load case '{}'; load cases may only change forces and prescribed displacement values",
+                    load_case.name, self.load_cases[0].name
+                )));
+            }
+
+            info!("solving load case '{}'", load_case.name);
+            factored.solve(nodes)?;
+
+            let mut elements = mesh.elements.clone();
+            solver::compute_stress(&mut elements, nodes, &materials);
+            case_results.push(CaseResults {
+                name: load_case.name.clone(),
+                results: collect_results(nodes.clone(), elements, &materials, &self.metadata),
+            });
+        }
+
+        for combination in &self.combinations {
+            info!("superposing load combination '{}'", combination.name);
+
+            let mut nodes = shared_nodes.clone();
+            for node in nodes.iter_mut() {
+                (node.ux, node.uy, node.fx, node.fy) = (Some(0.0), Some(0.0), Some(0.0), Some(0.0));
+            }
+
+            for (case_name, factor) in &combination.factors {
+                let Some(index) = self.load_cases.iter().position(|c| &c.name == case_name) else {
+                    return Err(MagnetiteError::Input(format!(
+                        "Combination '{}' references unknown load case '{case_name}'",
+                        combination.name
+                    )));
+                };
+
+                for (node, case_node) in std::iter::zip(&mut nodes, &case_nodes[index]) {
+                    let add = |total: Option<f64>, value: Option<f64>| {
+                        total.map(|t| t + factor * value.expect("Unknown value after solve"))
+                    };
+                    node.ux = add(node.ux, case_node.ux);
+                    node.uy = add(node.uy, case_node.uy);
+                    node.fx = add(node.fx, case_node.fx);
+                    node.fy = add(node.fy, case_node.fy);
+                }
+            }
+
+            // Stresses are linear in the displacements, so recomputing them
+            // from the superposed displacements superposes them too
+            let mut elements = mesh.elements.clone();
+            solver::compute_stress(&mut elements, &nodes, &materials);
+            case_results.push(CaseResults {
+                name: combination.name.clone(),
+                results: collect_results(nodes, elements, &materials, &self.metadata),
+            });
+        }
+
+        stats.solve_time = start.elapsed().as_secs_f64();
+        self.boundary_nodes = boundary_nodes;
+        self.solver_stats = Some(stats);
+        self.case_results = case_results;
+        Ok(&self.case_results)
+    }
+
+    /// Optimises the material layout with SIMP topology optimisation, under
+    /// the shared boundary rules. Meshes the model first if needed.
+    ///
+    /// # Arguments
+    /// * `options` - The topology optimisation settings
+    ///
+    /// # Returns
+    /// The density of each mesh element and the final compliance
+    pub fn optimize_topology(
+        &mut self,
+        options: &TopologyOptions,
+    ) -> Result<TopologyResult, MagnetiteError> {
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        self.single_material("topology optimisation")?;
+        let mut nodes = mesh.nodes.clone();
+        mesher::apply_boundary_conditions(&self.boundary_rules, &mut nodes, &mesh.physical_groups)?;
+
+        topology::optimize(mesh, &nodes, &self.metadata, options)
+    }
+
+    /// Integrates the model through time under the shared boundary rules,
+    /// scaling their targets by their time functions. Meshes the model first
+    /// if needed.
+    ///
+    /// # Arguments
+    /// * `options` - The transient settings
+    ///
+    /// # Returns
+    /// The results at each output time
+    pub fn solve_transient(
+        &mut self,
+        options: &TransientOptions,
+    ) -> Result<Vec<TransientFrame>, MagnetiteError> {
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        self.single_material("transient")?;
+        let mut nodes = mesh.nodes.clone();
+        let governing = mesher::apply_boundary_conditions(
+            &self.boundary_rules,
+            &mut nodes,
+            &mesh.physical_groups,
+        )?;
+        let time_functions: Vec<Option<&TimeFunction>> = governing
+            .into_iter()
+            .map(|rule| rule.and_then(|r| self.boundary_rules[r].target.time_function.as_ref()))
+            .collect();
+
+        transient::run(mesh, &nodes, &time_functions, &self.metadata, options)
+    }
+
+    /// Finds the steady-state response to sinusoidal loading over a range
+    /// of frequencies, with the shared boundary rules as load amplitudes.
+    /// Meshes the model first if needed.
+    ///
+    /// # Arguments
+    /// * `options` - The harmonic settings
+    ///
+    /// # Returns
+    /// The complex displacements at each frequency
+    pub fn solve_harmonic(
+        &mut self,
+        options: &HarmonicOptions,
+    ) -> Result<HarmonicResult, MagnetiteError> {
+        if self.mesh.is_none() {
+            self.mesh()?;
+        }
+        let mesh = self.mesh.as_ref().expect("Model has no mesh after meshing");
+
+        self.single_material("harmonic")?;
+        let mut nodes = mesh.nodes.clone();
+        mesher::apply_boundary_conditions(&self.boundary_rules, &mut nodes, &mesh.physical_groups)?;
+
+        harmonic::run(mesh, &nodes, &self.metadata, options)
+    }
+
+    /// Checks that the model is made of one material, for analyses that
+    /// don't support body materials
+    ///
+    /// # Arguments
+    /// * `analysis` - The name of the analysis, for the error message
+    fn single_material(&self, analysis: &str) -> Result<(), MagnetiteError> {
+        match self.metadata.body_materials.first() {
+            Some(body_material) => Err(MagnetiteError::Input(format!(
+                "Body materials are only supported by linear, load case and contact solves; remove the material of body '{}' for a {analysis} solve",
+                body_material.body
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the mesh, if the model has been meshed
+    pub fn current_mesh(&self) -> Option<&Mesh> {
+        self.mesh.as_ref()
+    }
+
+    /// Returns the results of the last solve, if any
+    pub fn results(&self) -> Option<&Results> {
+        self.results.as_ref()
+    }
+
+    /// Returns the results of the last load case solve, if any
+    pub fn case_results(&self) -> &[CaseResults] {
+        &self.case_results
+    }
+
+    /// Returns the contact forces from the last solve with contact, if any
+    pub fn contact_forces(&self) -> &[ContactForce] {
+        &self.contact_forces
+    }
+
+    /// Returns the nodes each boundary rule governed in the last check or
+    /// static solve
+    pub fn boundary_nodes(&self) -> &[BoundaryNodes] {
+        &self.boundary_nodes
+    }
+
+    /// Returns the statistics of the last static solve, if any
+    pub fn solver_stats(&self) -> Option<&SolverStats> {
+        self.solver_stats.as_ref()
+    }
+}
+
+/// Counts the degrees of freedom of a solve, before its solve time is known
+///
+/// # Arguments
+/// * `analysis` - The kind of solve
+/// * `nodes` - The nodes, with boundary rules applied
+fn solver_stats(analysis: &str, nodes: &[Node]) -> SolverStats {
+    SolverStats {
+        analysis: analysis.to_string(),
+        degrees_of_freedom: solver::DOF * nodes.len(),
+        constrained_degrees_of_freedom: nodes
+            .iter()
+            .map(|n| n.ux.is_some() as usize + n.uy.is_some() as usize)
+            .sum(),
+        solve_time: 0.0,
+    }
+}
+
+/// Collects solved nodes and elements into Results, computing equivalent
+/// stresses and factors of safety
+///
+/// # Arguments
+/// * `nodes` - The solved nodes
+/// * `elements` - The solved elements
+/// * `materials` - The material of each element
+/// * `model_metadata` - The model metadata
+pub(crate) fn collect_results(
+    nodes: Vec<Node>,
+    elements: Vec<Element>,
+    materials: &[Material],
+    model_metadata: &ModelMetadata,
+) -> Results {
+    let criterion = model_metadata.safety.criterion;
+
+    Results {
+        nodes: nodes
+            .into_iter()
+            .map(|node| NodeResult {
+                vertex: node.vertex,
+                ux: node.ux.expect("Unknown displacement after solve"),
+                uy: node.uy.expect("Unknown displacement after solve"),
+                fx: node.fx.expect("Unknown force after solve"),
+                fy: node.fy.expect("Unknown force after solve"),
+            })
+            .collect(),
+        elements: elements
+            .into_iter()
+            .zip(materials)
+            .map(|(element, material)| {
+                let stress_tensor = element.stress_tensor.expect("Unknown stress after solve");
+                let von_mises = solver::compute_von_mises_stress(&stress_tensor);
+                let tresca = solver::compute_tresca_stress(&stress_tensor);
+                let equivalent = match criterion {
+                    FailureCriterion::VonMises => von_mises,
+                    FailureCriterion::Tresca => tresca,
+                };
+
+                ElementResult {
+                    nodes: element.nodes,
+                    stress: element.stress.expect("Unknown stress after solve"),
+                    stress_tensor,
+                    von_mises,
+                    tresca,
+                    safety_factor: material.yield_strength.map(|s| s / equivalent),
+                    plastic_strain: element.plastic_strain,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{BoundaryRegion, BoundaryTarget, Material, MeshOptions, Vertex};
+
+    /// A 2x1 strip of four triangles, with its left edge at x = 0
+    fn strip() -> Mesh {
+        let vertices = [
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+            (0.0, 1.0),
+            (1.0, 1.0),
+            (2.0, 1.0),
+        ];
+        Mesh {
+            nodes: vertices
+                .iter()
+                .map(|(x, y)| Node {
+                    vertex: Vertex { x: *x, y: *y },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                })
+                .collect(),
+            elements: [[0, 1, 4], [0, 4, 3], [1, 2, 5], [1, 5, 4]]
+                .into_iter()
+                .map(|nodes| Element {
+                    nodes,
+                    stress: None,
+                    stress_tensor: None,
+                    plastic_strain: None,
+                    physical_tags: Vec::new(),
+                })
+                .collect(),
+            physical_groups: Vec::new(),
+        }
+    }
+
+    fn rule(name: &str, x_min: f64, x_max: f64, target: BoundaryTarget) -> BoundaryRule {
+        BoundaryRule {
+            name: name.to_string(),
+            region: BoundaryRegion {
+                x_min,
+                x_max,
+                y_min: f64::MIN,
+                y_max: f64::MAX,
+                physical_group: None,
+            },
+            target,
+        }
+    }
+
+    fn tip_load(fx: f64, fy: f64) -> LoadCase {
+        let target = BoundaryTarget {
+            ux: None,
+            uy: None,
+            fx: Some(fx),
+            fy: Some(fy),
+            time_function: None,
+        };
+        LoadCase {
+            name: format!("tip_{fx}_{fy}"),
+            boundary_rules: vec![rule("tip", 1.9, f64::MAX, target)],
+        }
+    }
+
+    fn strip_model() -> Model {
+        let metadata = ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.3,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        };
+        let fixed = BoundaryTarget {
+            ux: Some(0.0),
+            uy: Some(0.0),
+            fx: None,
+            fy: None,
+            time_function: None,
+        };
+        Model::new(
+            Geometry::Mesh(strip()),
+            metadata,
+            vec![rule("fixed", f64::MIN, 0.1, fixed)],
+        )
+    }
+
+    #[test]
+    fn superposes_load_combinations() {
+        let mut model = strip_model();
+        model.load_cases = vec![tip_load(10.0, 0.0), tip_load(0.0, -5.0)];
+        model.combinations = vec![LoadCombination {
+            name: "combined".to_string(),
+            factors: vec![("tip_10_0".to_string(), 1.2), ("tip_0_-5".to_string(), 1.6)],
+        }];
+
+        let case_results = model.solve_load_cases().unwrap();
+        let names: Vec<&str> = case_results.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["tip_10_0", "tip_0_-5", "combined"]);
+
+        let [pull, shear, combined] = [0, 1, 2].map(|i| &case_results[i].results);
+        assert!(pull.nodes[2].ux > 0.0);
+        assert!(shear.nodes[2].uy < 0.0);
+
+        for i in 0..combined.nodes.len() {
+            let expected = 1.2 * pull.nodes[i].uy + 1.6 * shear.nodes[i].uy;
+            assert!((combined.nodes[i].uy - expected).abs() < 1e-12);
+            let expected = 1.2 * pull.nodes[i].fx + 1.6 * shear.nodes[i].fx;
+            assert!((combined.nodes[i].fx - expected).abs() < 1e-9);
+        }
+        for i in 0..combined.elements.len() {
+            let expected =
+                1.2 * pull.elements[i].stress_tensor[2] + 1.6 * shear.elements[i].stress_tensor[2];
+            assert!((combined.elements[i].stress_tensor[2] - expected).abs() < 1e-9);
+        }
+
+        // Reactions at the fixed edge balance the applied load
+        let reaction: f64 = pull
+            .nodes
+            .iter()
+            .filter(|n| n.vertex.x == 0.0)
+            .map(|n| n.fx)
+            .sum();
+        assert!((reaction + 20.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn load_cases_must_share_constraints() {
+        let mut model = strip_model();
+        let mut pinned = tip_load(0.0, 0.0);
+        pinned.name = "pinned".to_string();
+        pinned.boundary_rules[0].target.fy = None;
+        pinned.boundary_rules[0].target.uy = Some(0.0);
+        model.load_cases = vec![tip_load(10.0, 0.0), pinned];
+
+        match model.solve_load_cases() {
+            Err(MagnetiteError::Input(message)) => {
+                assert!(
+                    message
+                        .starts_with("Load case 'pinned' constrains different degrees of freedom"),
+                    "{message}"
+                )
+            }
+            other => panic!("expected an input error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn checks_rules_without_solving() {
+        let mut model = strip_model();
+        model.load_cases = vec![tip_load(10.0, 0.0)];
+        let counts = model.check().unwrap();
+        assert_eq!(
+            counts,
+            [("fixed".to_string(), 2), ("tip_10_0: tip".to_string(), 2)]
+        );
+        assert!(model.results().is_none());
+
+        model.load_cases.clear();
+        model.boundary_rules[0].target.uy = None;
+        match model.check() {
+            Err(MagnetiteError::Input(message)) => assert_eq!(
+                message,
+                "The model is free to move in y; no boundary rule fixes uy at any node"
+            ),
+            other => panic!("expected an input error, got {other:?}"),
+        }
+    }
+}
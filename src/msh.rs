@@ -0,0 +1,1053 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    datatypes::{Element, Mesh, Node, PhysicalGroup, Vertex},
+    error::MagnetiteError,
+    units::Units,
+};
+use tracing::warn;
+
+/// The nodes, elements and physical groups read from a mesh file
+pub type MshContents = (Vec<Node>, Vec<Element>, Vec<PhysicalGroup>);
+
+/// The flavour of .msh file being parsed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MshVersion {
+    V2,
+    V4,
+}
+
+/// Cursor over the contents of a .msh file.
+///
+/// ASCII sections are read token-by-token, so the reader does not depend on
+/// how Gmsh happens to lay out lines. Binary sections are read as raw
+/// little-endian values.
+struct MshCursor<'a> {
+    data: &'a [u8],
+    source: &'a str,
+    pos: usize,
+    /// Start of the last item read; errors are reported against its line
+    mark: usize,
+    binary: bool,
+    size_t: usize,
+}
+
+impl<'a> MshCursor<'a> {
+    fn new(data: &'a [u8], source: &'a str) -> MshCursor<'a> {
+        MshCursor {
+            data,
+            source,
+            pos: 0,
+            mark: 0,
+            binary: false,
+            size_t: 8,
+        }
+    }
+
+    /// Builds a Mesher error pointing at the line of the last item read
+    fn error(&self, message: &str) -> MagnetiteError {
+        self.error_at(self.mark, message)
+    }
+
+    /// Builds a Mesher error pointing at the line containing a byte offset
+    fn error_at(&self, offset: usize, message: &str) -> MagnetiteError {
+        let line = 1 + self.data[..offset].iter().filter(|b| **b == b'\n').count();
+        MagnetiteError::Mesher(format!("{} line {}: {}", self.source, line, message))
+    }
+
+    /// Reads the rest of the current line, without its line terminator
+    fn next_line(&mut self) -> Option<&'a str> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let start = self.pos;
+        let end = match self.data[start..].iter().position(|b| *b == b'\n') {
+            Some(offset) => start + offset,
+            None => self.data.len(),
+        };
+        self.mark = start;
+        self.pos = end + 1;
+
+        let line = std::str::from_utf8(&self.data[start..end]).unwrap_or("");
+        Some(line.trim_end_matches('\r'))
+    }
+
+    /// Reads the next non-empty line
+    fn expect_line(&mut self, section: &str) -> Result<&'a str, MagnetiteError> {
+        while let Some(line) = self.next_line() {
+            if !line.trim().is_empty() {
+                return Ok(line.trim());
+            }
+        }
+        Err(self.error(&format!("unexpected end of file in ${section}")))
+    }
+
+    /// Reads the next whitespace-delimited ASCII token, crossing line
+    /// boundaries as needed
+    fn next_token(&mut self, section: &str) -> Result<&'a str, MagnetiteError> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+        self.mark = start;
+
+        if start == self.pos {
+            return Err(self.error(&format!("unexpected end of file in ${section}")));
+        }
+
+        let token = std::str::from_utf8(&self.data[start..self.pos]).unwrap_or("");
+        if token.starts_with('$') {
+            return Err(self.error(&format!("${section} ended early; found {token}")));
+        }
+        Ok(token)
+    }
+
+    /// Parses the next ASCII token into `T`
+    fn parse_token<T: std::str::FromStr>(
+        &mut self,
+        section: &str,
+        expected: &str,
+    ) -> Result<T, MagnetiteError> {
+        let token = self.next_token(section)?;
+        token.parse().map_err(|_| {
+            self.error(&format!(
+                "expected {expected} in ${section}, found '{token}'"
+            ))
+        })
+    }
+
+    /// Reads `n` raw bytes
+    fn take(&mut self, n: usize, section: &str) -> Result<&'a [u8], MagnetiteError> {
+        self.mark = self.pos;
+        if self.pos + n > self.data.len() {
+            return Err(self.error(&format!("unexpected end of binary data in ${section}")));
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    /// Reads an `int`, either as an ASCII token or as 4 binary bytes
+    fn int(&mut self, section: &str) -> Result<i64, MagnetiteError> {
+        if self.binary {
+            let bytes = self.take(4, section)?;
+            Ok(i32::from_le_bytes(bytes.try_into().unwrap()) as i64)
+        } else {
+            self.parse_token(section, "an integer")
+        }
+    }
+
+    /// Reads a non-negative `int`
+    fn uint(&mut self, section: &str) -> Result<usize, MagnetiteError> {
+        let value = self.int(section)?;
+        usize::try_from(value)
+            .map_err(|_| self.error(&format!("unexpected negative value {value} in ${section}")))
+    }
+
+    /// Reads a `size_t`, either as an ASCII token or as binary bytes of the
+    /// data-size declared in $MeshFormat
+    fn size(&mut self, section: &str) -> Result<usize, MagnetiteError> {
+        if !self.binary {
+            return self.parse_token(section, "an unsigned integer");
+        }
+        match self.size_t {
+            4 => Ok(u32::from_le_bytes(self.take(4, section)?.try_into().unwrap()) as usize),
+            _ => Ok(u64::from_le_bytes(self.take(8, section)?.try_into().unwrap()) as usize),
+        }
+    }
+
+    /// Reads a `double`, either as an ASCII token or as 8 binary bytes
+    fn double(&mut self, section: &str) -> Result<f64, MagnetiteError> {
+        if self.binary {
+            let bytes = self.take(8, section)?;
+            Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+        } else {
+            self.parse_token(section, "a number")
+        }
+    }
+
+    /// Skips ahead to the closing tag of a section, ignoring its contents
+    fn skip_to_end(&mut self, section: &str) -> Result<(), MagnetiteError> {
+        let end_tag = format!("$End{section}");
+        while let Some(line) = self.next_line() {
+            if line.trim() == end_tag {
+                return Ok(());
+            }
+        }
+        Err(self.error(&format!("missing {end_tag}")))
+    }
+
+    /// Consumes the closing tag of a fully-parsed section. In ASCII files,
+    /// any data left over before the closing tag is an error.
+    fn finish_section(&mut self, section: &str) -> Result<(), MagnetiteError> {
+        if self.binary {
+            return self.skip_to_end(section);
+        }
+
+        let end_tag = format!("$End{section}");
+        while let Some(line) = self.next_line() {
+            let line = line.trim();
+            if line == end_tag {
+                return Ok(());
+            }
+            if !line.is_empty() {
+                return Err(self.error(&format!("expected {end_tag}, found '{line}'")));
+            }
+        }
+        Err(self.error(&format!("missing {end_tag}")))
+    }
+}
+
+/// Describes a Gmsh element type
+struct ElementType {
+    /// Number of nodes in the element
+    num_nodes: usize,
+    /// Topological dimension of the element
+    dimension: usize,
+    /// Corner triangles of 2D elements, as local node indexes
+    triangles: &'static [[usize; 3]],
+}
+
+/// Looks up a Gmsh element type id
+fn element_type(id: usize) -> Option<ElementType> {
+    const NONE: &[[usize; 3]] = &[];
+    const TRIANGLE: &[[usize; 3]] = &[[0, 1, 2]];
+    const QUAD: &[[usize; 3]] = &[[0, 1, 2], [0, 2, 3]];
+
+    let (num_nodes, dimension, triangles) = match id {
+        15 => (1, 0, NONE),      // 1-node point
+        1 => (2, 1, NONE),       // 2-node line
+        8 => (3, 1, NONE),       // 3-node line
+        26 => (4, 1, NONE),      // 4-node line
+        2 => (3, 2, TRIANGLE),   // 3-node triangle
+        9 => (6, 2, TRIANGLE),   // 6-node triangle
+        20 => (9, 2, TRIANGLE),  // 9-node triangle
+        21 => (10, 2, TRIANGLE), // 10-node triangle
+        3 => (4, 2, QUAD),       // 4-node quadrangle
+        16 => (8, 2, QUAD),      // 8-node quadrangle
+        10 => (9, 2, QUAD),      // 9-node quadrangle
+        4 => (4, 3, NONE),       // 4-node tetrahedron
+        5 => (8, 3, NONE),       // 8-node hexahedron
+        6 => (6, 3, NONE),       // 6-node prism
+        7 => (5, 3, NONE),       // 5-node pyramid
+        11 => (10, 3, NONE),     // 10-node tetrahedron
+        _ => return None,
+    };
+
+    Some(ElementType {
+        num_nodes,
+        dimension,
+        triangles,
+    })
+}
+
+/// Looks up an element type, erroring on unknown ids
+fn lookup_element_type(cursor: &MshCursor, id: usize) -> Result<ElementType, MagnetiteError> {
+    element_type(id).ok_or_else(|| cursor.error(&format!("unsupported element type {id}")))
+}
+
+/// Collects the raw contents of a mesh file before nodes are re-indexed
+#[derive(Default)]
+struct RawMesh {
+    /// Node tag and coordinates, in file order
+    nodes: Vec<(usize, Vertex)>,
+    /// Node tags seen so far, used to catch duplicates
+    node_tags: HashSet<usize>,
+    /// Element tag, node tags, physical tags and file offset of each
+    /// triangle
+    triangles: Vec<(usize, [usize; 3], Vec<usize>, usize)>,
+    /// Number of higher-order 2D elements reduced to their corner nodes
+    reduced_elements: usize,
+    /// Node tags of elements belonging to each physical group, keyed by
+    /// (dimension, physical tag)
+    group_nodes: HashMap<(usize, usize), Vec<usize>>,
+    /// Names of physical groups, keyed by (dimension, physical tag)
+    group_names: HashMap<(usize, usize), String>,
+    /// Physical tags of each entity, keyed by (dimension, entity tag)
+    entity_physicals: HashMap<(usize, usize), Vec<usize>>,
+}
+
+impl RawMesh {
+    /// Registers a node read from the file
+    fn push_node(
+        &mut self,
+        cursor: &MshCursor,
+        tag: usize,
+        vertex: Vertex,
+    ) -> Result<(), MagnetiteError> {
+        if !self.node_tags.insert(tag) {
+            return Err(cursor.error(&format!("duplicate node tag {tag}")));
+        }
+        self.nodes.push((tag, vertex));
+        Ok(())
+    }
+
+    /// Registers an element read from the file
+    fn push_element(
+        &mut self,
+        cursor: &MshCursor,
+        element_tag: usize,
+        element_type: &ElementType,
+        physical_tags: &[usize],
+        node_tags: &[usize],
+    ) {
+        for physical in physical_tags {
+            self.group_nodes
+                .entry((element_type.dimension, *physical))
+                .or_default()
+                .extend_from_slice(node_tags);
+        }
+
+        if element_type.num_nodes > 4 && !element_type.triangles.is_empty() {
+            self.reduced_elements += 1;
+        }
+
+        for triangle in element_type.triangles {
+            self.triangles.push((
+                element_tag,
+                [
+                    node_tags[triangle[0]],
+                    node_tags[triangle[1]],
+                    node_tags[triangle[2]],
+                ],
+                physical_tags.to_vec(),
+                cursor.mark,
+            ));
+        }
+    }
+}
+
+/// Parses the $MeshFormat header
+fn parse_mesh_format(cursor: &mut MshCursor) -> Result<MshVersion, MagnetiteError> {
+    let version_str = cursor.next_token("MeshFormat")?;
+    let version = if version_str.starts_with("2.") {
+        MshVersion::V2
+    } else if version_str.starts_with("4.") {
+        MshVersion::V4
+    } else {
+        return Err(cursor.error(&format!(
+            "unsupported mesh file version {version_str}; supported versions are 2.2 and 4.1"
+        )));
+    };
+
+    let file_type: usize = cursor.parse_token("MeshFormat", "a file-type")?;
+    let data_size: usize = cursor.parse_token("MeshFormat", "a data-size")?;
+    if data_size != 4 && data_size != 8 {
+        return Err(cursor.error(&format!("unsupported data-size {data_size}")));
+    }
+    cursor.size_t = data_size;
+
+    if file_type == 1 {
+        // Binary files carry a one-int endianness marker on the next line
+        cursor.next_line();
+        cursor.binary = true;
+        if cursor.int("MeshFormat")? != 1 {
+            return Err(cursor.error("big-endian binary mesh files are not supported"));
+        }
+    }
+
+    cursor.finish_section("MeshFormat")?;
+
+    Ok(version)
+}
+
+/// Parses the $PhysicalNames section. This section is always ASCII.
+fn parse_physical_names(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    let count: usize = cursor.parse_token("PhysicalNames", "a count")?;
+    cursor.next_line();
+
+    for _ in 0..count {
+        let line = cursor.expect_line("PhysicalNames")?;
+        let mut parts = line.splitn(3, char::is_whitespace);
+        let (dim, tag, name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(d), Some(t), Some(n)) => (d.parse().ok(), t.parse().ok(), n.trim()),
+            _ => (None, None, ""),
+        };
+
+        match (dim, tag) {
+            (Some(dim), Some(tag)) if name.len() >= 2 && name.starts_with('"') => {
+                raw.group_names
+                    .insert((dim, tag), name.trim_matches('"').to_owned());
+            }
+            _ => {
+                return Err(cursor.error(&format!(
+                    "malformed physical name '{line}'; expected: dim tag \"name\""
+                )))
+            }
+        }
+    }
+
+    // $PhysicalNames stays ASCII in binary files
+    let binary = std::mem::replace(&mut cursor.binary, false);
+    let result = cursor.finish_section("PhysicalNames");
+    cursor.binary = binary;
+    result
+}
+
+/// Parses the MSH 4 $Entities section, recording the physical tags of
+/// each entity
+fn parse_entities_v4(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    let mut counts = [0; 4];
+    for count in counts.iter_mut() {
+        *count = cursor.size("Entities")?;
+    }
+
+    for (dim, count) in counts.iter().enumerate() {
+        for _ in 0..*count {
+            let tag = cursor.uint("Entities")?;
+
+            // points store a coordinate, higher dimensions a bounding box
+            let num_coords = if dim == 0 { 3 } else { 6 };
+            for _ in 0..num_coords {
+                cursor.double("Entities")?;
+            }
+
+            let num_physicals = cursor.size("Entities")?;
+            let mut physicals = Vec::new();
+            for _ in 0..num_physicals {
+                physicals.push(cursor.int("Entities")?.unsigned_abs() as usize);
+            }
+
+            if dim > 0 {
+                let num_bounding = cursor.size("Entities")?;
+                for _ in 0..num_bounding {
+                    cursor.int("Entities")?;
+                }
+            }
+
+            raw.entity_physicals.insert((dim, tag), physicals);
+        }
+    }
+
+    cursor.finish_section("Entities")
+}
+
+/// Parses the MSH 4 $Nodes section
+fn parse_nodes_v4(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    let num_blocks = cursor.size("Nodes")?;
+    let num_nodes = cursor.size("Nodes")?;
+    let _min_tag = cursor.size("Nodes")?;
+    let _max_tag = cursor.size("Nodes")?;
+
+    let mut read = 0;
+    for _ in 0..num_blocks {
+        let entity_dim = cursor.uint("Nodes")?;
+        let _entity_tag = cursor.int("Nodes")?;
+        let parametric = cursor.int("Nodes")? != 0;
+        let num_block_nodes = cursor.size("Nodes")?;
+
+        if entity_dim > 3 {
+            return Err(cursor.error(&format!("invalid entity dimension {entity_dim}")));
+        }
+
+        let mut tags: Vec<usize> = Vec::new();
+        for _ in 0..num_block_nodes {
+            tags.push(cursor.size("Nodes")?);
+        }
+
+        // parametric nodes carry one extra coordinate per entity dimension
+        let num_params = if parametric { entity_dim } else { 0 };
+
+        for tag in tags {
+            let x = cursor.double("Nodes")?;
+            let y = cursor.double("Nodes")?;
+            let _z = cursor.double("Nodes")?;
+            for _ in 0..num_params {
+                cursor.double("Nodes")?;
+            }
+            raw.push_node(cursor, tag, Vertex { x, y })?;
+        }
+
+        read += num_block_nodes;
+    }
+
+    if read != num_nodes {
+        return Err(cursor.error(&format!(
+            "$Nodes declares {num_nodes} nodes but contains {read}"
+        )));
+    }
+
+    cursor.finish_section("Nodes")
+}
+
+/// Parses the MSH 4 $Elements section
+fn parse_elements_v4(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    let num_blocks = cursor.size("Elements")?;
+    let num_elements = cursor.size("Elements")?;
+    let _min_tag = cursor.size("Elements")?;
+    let _max_tag = cursor.size("Elements")?;
+
+    let mut read = 0;
+    for _ in 0..num_blocks {
+        let entity_dim = cursor.uint("Elements")?;
+        let entity_tag = cursor.uint("Elements")?;
+        let type_id = cursor.uint("Elements")?;
+        let element_type = lookup_element_type(cursor, type_id)?;
+        let num_block_elements = cursor.size("Elements")?;
+
+        let physicals = raw
+            .entity_physicals
+            .get(&(entity_dim, entity_tag))
+            .cloned()
+            .unwrap_or_default();
+
+        let mut node_tags: Vec<usize> = vec![0; element_type.num_nodes];
+        for _ in 0..num_block_elements {
+            let element_tag = cursor.size("Elements")?;
+            for tag in node_tags.iter_mut() {
+                *tag = cursor.size("Elements")?;
+            }
+            raw.push_element(cursor, element_tag, &element_type, &physicals, &node_tags);
+        }
+
+        read += num_block_elements;
+    }
+
+    if read != num_elements {
+        return Err(cursor.error(&format!(
+            "$Elements declares {num_elements} elements but contains {read}"
+        )));
+    }
+
+    cursor.finish_section("Elements")
+}
+
+/// Parses the MSH 2 $Nodes section
+fn parse_nodes_v2(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    // the count is ASCII even in binary files
+    let num_nodes: usize = cursor.parse_token("Nodes", "a count")?;
+    if cursor.binary {
+        cursor.next_line();
+    }
+
+    for _ in 0..num_nodes {
+        let tag = cursor.uint("Nodes")?;
+        let x = cursor.double("Nodes")?;
+        let y = cursor.double("Nodes")?;
+        let _z = cursor.double("Nodes")?;
+        raw.push_node(cursor, tag, Vertex { x, y })?;
+    }
+
+    cursor.finish_section("Nodes")
+}
+
+/// Parses the MSH 2 $Elements section
+fn parse_elements_v2(cursor: &mut MshCursor, raw: &mut RawMesh) -> Result<(), MagnetiteError> {
+    // the count is ASCII even in binary files
+    let num_elements: usize = cursor.parse_token("Elements", "a count")?;
+    if cursor.binary {
+        cursor.next_line();
+    }
+
+    let mut read = 0;
+    while read < num_elements {
+        // Binary elements are grouped under (type, count, num_tags) headers;
+        // ASCII elements each carry their own type and tag count
+        let (type_id, count, binary_num_tags) = if cursor.binary {
+            let type_id = cursor.uint("Elements")?;
+            let count = cursor.uint("Elements")?;
+            (type_id, count, Some(cursor.uint("Elements")?))
+        } else {
+            (0, 1, None)
+        };
+
+        for _ in 0..count {
+            let element_tag = cursor.uint("Elements")?;
+            let (type_id, num_tags) = match binary_num_tags {
+                Some(num_tags) => (type_id, num_tags),
+                None => (cursor.uint("Elements")?, cursor.uint("Elements")?),
+            };
+            let element_type = lookup_element_type(cursor, type_id)?;
+
+            let mut tags: Vec<usize> = Vec::new();
+            for _ in 0..num_tags {
+                tags.push(cursor.int("Elements")?.unsigned_abs() as usize);
+            }
+            let mut node_tags: Vec<usize> = Vec::with_capacity(element_type.num_nodes);
+            for _ in 0..element_type.num_nodes {
+                node_tags.push(cursor.uint("Elements")?);
+            }
+
+            // The first tag is the physical group; zero means none
+            let physicals: Vec<usize> = match tags.first() {
+                Some(physical) if *physical != 0 => vec![*physical],
+                _ => Vec::new(),
+            };
+            raw.push_element(cursor, element_tag, &element_type, &physicals, &node_tags);
+        }
+
+        read += count;
+    }
+
+    cursor.finish_section("Elements")
+}
+
+/// Parses the contents of a Gmsh .msh file
+///
+/// # Arguments
+/// * `data` - The raw bytes of the mesh file
+/// * `source` - The name of the mesh file, used in error messages
+///
+/// # Returns
+/// A tuple with the parsed nodes, elements and physical groups, in that
+/// order.
+fn parse_msh(data: &[u8], source: &str) -> Result<MshContents, MagnetiteError> {
+    let mut cursor = MshCursor::new(data, source);
+    let mut raw = RawMesh::default();
+    let mut version: Option<MshVersion> = None;
+
+    while let Some(line) = cursor.next_line() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with('$') {
+            return Err(cursor.error(&format!("expected a section header, found '{line}'")));
+        }
+
+        let section = &line[1..];
+        match (section, version) {
+            ("MeshFormat", _) => version = Some(parse_mesh_format(&mut cursor)?),
+            (_, None) => return Err(cursor.error("missing $MeshFormat header")),
+            ("PhysicalNames", _) => parse_physical_names(&mut cursor, &mut raw)?,
+            ("Entities", Some(MshVersion::V4)) => parse_entities_v4(&mut cursor, &mut raw)?,
+            ("Nodes", Some(MshVersion::V4)) => parse_nodes_v4(&mut cursor, &mut raw)?,
+            ("Elements", Some(MshVersion::V4)) => parse_elements_v4(&mut cursor, &mut raw)?,
+            ("Nodes", Some(MshVersion::V2)) => parse_nodes_v2(&mut cursor, &mut raw)?,
+            ("Elements", Some(MshVersion::V2)) => parse_elements_v2(&mut cursor, &mut raw)?,
+            _ => cursor.skip_to_end(section)?,
+        }
+    }
+
+    if raw.reduced_elements > 0 {
+        warn!(
+            "reduced {} higher-order elements to linear triangles",
+            raw.reduced_elements
+        );
+    }
+
+    build_model(raw, &cursor)
+}
+
+/// Reads a Gmsh .msh file (versions 2.2 and 4.1, ASCII or binary) into
+/// Nodes, Elements and Physical Groups.
+///
+/// Triangles and quadrangles of any order are loaded into the model as
+/// linear triangles. Nodes that do not belong to any of these elements are
+/// dropped, and the remaining nodes are indexed in the order they appear in
+/// the file.
+///
+/// # Arguments
+/// * `mesh_file` - The path to the mesh file
+///
+/// # Returns
+/// A tuple with the parsed nodes, elements and physical groups, in that
+/// order.
+pub fn read_msh(mesh_file: &str) -> Result<MshContents, MagnetiteError> {
+    let data = match std::fs::read(mesh_file) {
+        Ok(d) => d,
+        Err(err) => {
+            return Err(MagnetiteError::Mesher(format!(
+                "Unable to open mesh file {mesh_file}: {err}"
+            )))
+        }
+    };
+
+    parse_msh(&data, mesh_file)
+}
+
+/// Re-indexes the raw mesh into contiguous Nodes and Elements
+fn build_model(raw: RawMesh, cursor: &MshCursor) -> Result<MshContents, MagnetiteError> {
+    if raw.triangles.is_empty() {
+        return Err(MagnetiteError::Mesher(format!(
+            "{} contains no 2D elements",
+            cursor.source
+        )));
+    }
+
+    let used_tags: HashSet<usize> = raw
+        .triangles
+        .iter()
+        .flat_map(|(_, nodes, _, _)| nodes.iter().copied())
+        .collect();
+
+    // Map node tags onto contiguous indexes
+    let mut tag_to_index: HashMap<usize, usize> = HashMap::new();
+    let mut nodes: Vec<Node> = Vec::with_capacity(used_tags.len());
+    for (tag, vertex) in raw.nodes {
+        if !used_tags.contains(&tag) {
+            continue;
+        }
+        tag_to_index.insert(tag, nodes.len());
+        nodes.push(Node {
+            vertex,
+            ux: None,
+            uy: None,
+            fx: Some(0.0),
+            fy: Some(0.0),
+        });
+    }
+
+    let mut elements: Vec<Element> = Vec::with_capacity(raw.triangles.len());
+    for (element_tag, triangle, physical_tags, offset) in raw.triangles {
+        let mut indexes = [0; 3];
+        for (i, tag) in triangle.iter().enumerate() {
+            indexes[i] = match tag_to_index.get(tag) {
+                Some(idx) => *idx,
+                None => {
+                    return Err(cursor.error_at(
+                        offset,
+                        &format!("element {element_tag} references undefined node {tag}"),
+                    ))
+                }
+            };
+        }
+        elements.push(Element {
+            nodes: indexes,
+            stress: None,
+            stress_tensor: None,
+            plastic_strain: None,
+            physical_tags,
+        });
+    }
+
+    // Resolve physical groups onto node indexes
+    let mut physical_groups: Vec<PhysicalGroup> = raw
+        .group_nodes
+        .into_iter()
+        .map(|((dimension, tag), node_tags)| {
+            let mut group_nodes: Vec<usize> = node_tags
+                .iter()
+                .filter_map(|t| tag_to_index.get(t).copied())
+                .collect();
+            group_nodes.sort_unstable();
+            group_nodes.dedup();
+
+            PhysicalGroup {
+                name: raw
+                    .group_names
+                    .get(&(dimension, tag))
+                    .cloned()
+                    .unwrap_or_else(|| tag.to_string()),
+                dimension,
+                tag,
+                nodes: group_nodes,
+            }
+        })
+        .collect();
+    physical_groups.sort_by_key(|g| (g.dimension, g.tag));
+
+    Ok((nodes, elements, physical_groups))
+}
+
+/// Writes a mesh to a Gmsh .msh file (version 2.2, ASCII), which can be
+/// read back as a geometry file.
+///
+/// Surface physical groups are written as the physical tag of their
+/// triangles; a triangle keeps only the first of its surface groups. Curve groups are written as the element edges between their nodes,
+/// and any other group as points on its nodes.
+///
+/// # Arguments
+/// * `mesh` - The mesh to write
+/// * `units` - The units of the input file, if declared; coordinates are
+///   converted back to them
+/// * `output` - The filepath of the .msh file
+pub fn write_msh(mesh: &Mesh, units: Option<&Units>, output: &str) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+
+    let mut edges: Vec<[usize; 2]> = mesh
+        .elements
+        .iter()
+        .flat_map(|e| [0, 1, 2].map(|i| [e.nodes[i], e.nodes[(i + 1) % 3]]))
+        .map(|[a, b]| [a.min(b), a.max(b)])
+        .collect();
+    edges.sort_unstable();
+    edges.dedup();
+
+    // Each element is written as (type, physical tag, node indexes). Points
+    // of different groups can't share a physical tag.
+    let mut names: Vec<(usize, usize, &str)> = Vec::new();
+    let mut lower: Vec<(usize, usize, Vec<usize>)> = Vec::new();
+    let mut point_tags: HashSet<usize> = HashSet::new();
+    let next_tag = mesh
+        .physical_groups
+        .iter()
+        .map(|g| g.tag)
+        .max()
+        .unwrap_or(0)
+        + 1;
+    for group in &mesh.physical_groups {
+        let member = |n: &usize| group.nodes.binary_search(n).is_ok();
+        if group.dimension == 2 {
+            names.push((2, group.tag, &group.name));
+            continue;
+        }
+        if group.dimension == 1 {
+            let lines: Vec<&[usize; 2]> = edges.iter().filter(|e| e.iter().all(member)).collect();
+            let on_lines = |n: &usize| lines.iter().any(|e| e.contains(n));
+            if group.nodes.iter().all(on_lines) {
+                names.push((1, group.tag, &group.name));
+                lower.extend(lines.iter().map(|e| (1, group.tag, e.to_vec())));
+                continue;
+            }
+        }
+        let tag = if point_tags.insert(group.tag) {
+            group.tag
+        } else {
+            let tag = next_tag + point_tags.len();
+            point_tags.insert(tag);
+            tag
+        };
+        names.push((0, tag, &group.name));
+        lower.extend(group.nodes.iter().map(|n| (15, tag, vec![*n])));
+    }
+
+    let triangles = mesh.elements.iter().map(|element| {
+        let tag = element.physical_tags.first().copied().unwrap_or(0);
+        (2, tag, element.nodes.to_vec())
+    });
+
+    let mut contents = String::from("$MeshFormat\n2.2 0 8\n$EndMeshFormat\n");
+    if !names.is_empty() {
+        contents += &format!("$PhysicalNames\n{}\n", names.len());
+        for (dimension, tag, name) in &names {
+            contents += &format!("{dimension} {tag} \"{name}\"\n");
+        }
+        contents += "$EndPhysicalNames\n";
+    }
+
+    contents += &format!("$Nodes\n{}\n", mesh.nodes.len());
+    for (i, node) in mesh.nodes.iter().enumerate() {
+        contents += &format!(
+            "{} {} {} 0\n",
+            i + 1,
+            node.vertex.x / length,
+            node.vertex.y / length
+        );
+    }
+    contents += "$EndNodes\n";
+
+    contents += &format!("$Elements\n{}\n", lower.len() + mesh.elements.len());
+    for (i, (type_id, tag, nodes)) in lower.into_iter().chain(triangles).enumerate() {
+        let nodes: Vec<String> = nodes.iter().map(|n| (n + 1).to_string()).collect();
+        contents += &format!("{} {type_id} 2 {tag} 1 {}\n", i + 1, nodes.join(" "));
+    }
+    contents += "$EndElements\n";
+
+    if let Err(err) = std::fs::write(output, contents) {
+        return Err(MagnetiteError::Mesher(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesher::ScratchDir;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/msh/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn group<'a>(groups: &'a [PhysicalGroup], name: &str) -> &'a PhysicalGroup {
+        groups
+            .iter()
+            .find(|g| g.name == name)
+            .unwrap_or_else(|| panic!("missing physical group {name}"))
+    }
+
+    fn error_message(result: Result<MshContents, MagnetiteError>) -> String {
+        match result {
+            Ok(_) => panic!("expected mesh parse to fail"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    /// The square fixtures all describe a unit square split into four
+    /// triangles around a centre node, with a "left" edge group
+    fn assert_unit_square(contents: MshContents) {
+        let (nodes, elements, groups) = contents;
+
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(elements.len(), 4);
+        assert_eq!(nodes[4].vertex, Vertex { x: 0.5, y: 0.5 });
+
+        let left = group(&groups, "left");
+        assert_eq!(left.dimension, 1);
+        let mut left_coords: Vec<(f64, f64)> = left
+            .nodes
+            .iter()
+            .map(|n| (nodes[*n].vertex.x, nodes[*n].vertex.y))
+            .collect();
+        left_coords.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(left_coords, vec![(0.0, 0.0), (0.0, 1.0)]);
+    }
+
+    #[test]
+    fn reads_v41_ascii() {
+        let contents = read_msh(&fixture("square_v41.msh")).unwrap();
+        let plate = group(&contents.2, "plate");
+        assert_eq!(plate.dimension, 2);
+        assert_eq!(plate.nodes.len(), 5);
+        assert!(contents.1.iter().all(|e| e.physical_tags == [plate.tag]));
+        assert_unit_square(contents);
+    }
+
+    #[test]
+    fn reads_v41_binary() {
+        assert_unit_square(read_msh(&fixture("square_v41_binary.msh")).unwrap());
+    }
+
+    #[test]
+    fn reads_v22_ascii() {
+        assert_unit_square(read_msh(&fixture("square_v22.msh")).unwrap());
+    }
+
+    #[test]
+    fn reads_v22_binary() {
+        assert_unit_square(read_msh(&fixture("square_v22_binary.msh")).unwrap());
+    }
+
+    #[test]
+    fn reads_crlf_line_endings() {
+        assert_unit_square(read_msh(&fixture("square_v41_crlf.msh")).unwrap());
+    }
+
+    #[test]
+    fn reads_sparse_tags_and_parametric_nodes() {
+        let (nodes, elements, groups) = read_msh(&fixture("sparse_parametric_v41.msh")).unwrap();
+
+        // the orphaned geometry point is dropped
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(elements.len(), 4);
+        assert_eq!(nodes[0].vertex, Vertex { x: 0.0, y: 0.0 });
+        assert_eq!(nodes[4].vertex, Vertex { x: 0.5, y: 0.5 });
+
+        // unnamed physical groups are referenced by tag
+        assert_eq!(group(&groups, "5").nodes.len(), 2);
+    }
+
+    #[test]
+    fn reads_mixed_element_types() {
+        let (nodes, elements, _) = read_msh(&fixture("mixed_elements_v41.msh")).unwrap();
+
+        // one quadrangle split in two, plus one 6-node triangle
+        assert_eq!(elements.len(), 3);
+        // mid-side nodes of the 6-node triangle are dropped
+        assert_eq!(nodes.len(), 5);
+    }
+
+    #[test]
+    fn writes_a_mesh_that_reads_back() {
+        let (nodes, elements, physical_groups) = read_msh(&fixture("square_v41.msh")).unwrap();
+        let mesh = Mesh {
+            nodes,
+            elements,
+            physical_groups,
+        };
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let output = scratch.file("square.msh");
+        write_msh(&mesh, None, &output).unwrap();
+        let contents = read_msh(&output).unwrap();
+        scratch.succeed();
+
+        assert_eq!(contents.1.len(), 4);
+        for (written, read) in std::iter::zip(&mesh.nodes, &contents.0) {
+            assert_eq!(written.vertex, read.vertex);
+        }
+        let plate = group(&contents.2, "plate");
+        assert_eq!(plate.nodes.len(), 5);
+        assert!(contents.1.iter().all(|e| e.physical_tags == [plate.tag]));
+        assert_unit_square(contents);
+    }
+
+    #[test]
+    fn reports_line_of_bad_token() {
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n$Nodes\n1 1 1 1\n2 1 0 1\n1\n0 zero 0\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "bad.msh"));
+        assert!(message.contains("bad.msh line 8:"), "{message}");
+        assert!(message.contains("'zero'"), "{message}");
+    }
+
+    #[test]
+    fn reports_truncated_section() {
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n$Nodes\n1 2 1 2\n2 1 0 2\n1\n2\n0 0 0\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "short.msh"));
+        assert!(message.contains("short.msh line 10:"), "{message}");
+        assert!(message.contains("$Nodes ended early"), "{message}");
+    }
+
+    #[test]
+    fn reports_leftover_section_data() {
+        let data = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Nodes\n1\n1 0 0 0\n2 1 0 0\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "extra.msh"));
+        assert!(
+            message.contains("extra.msh line 7: expected $EndNodes"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_duplicate_node_tags() {
+        let data = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Nodes\n2\n1 0 0 0\n1 1 0 0\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "dup.msh"));
+        assert!(
+            message.contains("dup.msh line 7: duplicate node tag 1"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_element_type() {
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n$Elements\n1 1 1 1\n2 1 99 1\n1 1 2 3\n$EndElements\n";
+        let message = error_message(parse_msh(data.as_bytes(), "type.msh"));
+        assert!(
+            message.contains("type.msh line 6: unsupported element type 99"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_mesh_format() {
+        let data = "$Nodes\n0\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "nofmt.msh"));
+        assert!(
+            message.contains("nofmt.msh line 1: missing $MeshFormat"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_undefined_node_reference() {
+        let data = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Nodes\n1\n1 0 0 0\n$EndNodes\n$Elements\n1\n1 2 0 1 2 3\n$EndElements\n";
+        let message = error_message(parse_msh(data.as_bytes(), "ref.msh"));
+        assert!(
+            message.contains("ref.msh line 10: element 1 references undefined node 2"),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_counts() {
+        // Counts are read from the file, so must not be trusted to size
+        // allocations before the data behind them has been read
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n$Entities\n0 0 1 0\n1 0 0 0 1 1 0 1000000000000000000 1\n$EndEntities\n";
+        let message = error_message(parse_msh(data.as_bytes(), "big.msh"));
+        assert!(message.contains("big.msh line 7:"), "{message}");
+
+        let data = "$MeshFormat\n4.1 0 8\n$EndMeshFormat\n$Nodes\n1 1 1 1\n2 1 0 1000000000000000000\n1\n$EndNodes\n";
+        let message = error_message(parse_msh(data.as_bytes(), "big.msh"));
+        assert!(message.contains("big.msh line 8:"), "{message}");
+
+        let data = "$MeshFormat\n2.2 0 8\n$EndMeshFormat\n$Elements\n1\n1 2 1000000000000000000 1 2 3\n$EndElements\n";
+        let message = error_message(parse_msh(data.as_bytes(), "big.msh"));
+        assert!(message.contains("big.msh line "), "{message}");
+    }
+}
@@ -0,0 +1,552 @@
+use nalgebra::{matrix, DVector, SMatrix, SVector};
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix};
+
+use crate::{
+    datatypes::{Element, ModelMetadata, Node, NonlinearOptions},
+    error::MagnetiteError,
+    logging::Timer,
+    plasticity::{J2Material, PlasticState},
+    solver::{
+        compute_element_area, compute_strain_displacement_matrix, compute_stress_strain_matrix,
+        element_dofs, set_element_stress, DOF,
+    },
+};
+use tracing::{info, info_span};
+
+/// The strain in a deformed element, and how it varies with the element's
+/// nodal displacements
+struct Kinematics {
+    /// Voigt strain, with engineering shear strain
+    strain: SVector<f64, 3>,
+    strain_mat: SMatrix<f64, 3, 6>,
+    deformation_gradient: SMatrix<f64, 2, 2>,
+    /// Shape function derivatives in x and y
+    derivatives: [[f64; 3]; 2],
+    volume: f64,
+}
+
+/// The internal forces, tangent stiffness and stress of a deformed element
+struct ElementResponse {
+    internal_force: SMatrix<f64, 6, 1>,
+    tangent: SMatrix<f64, 6, 6>,
+    /// The Cauchy (true) stress `[sigma_xx, sigma_yy, tau_xy]`
+    cauchy_stress: [f64; 3],
+    /// The material state reached, committed once the increment converges
+    state: PlasticState,
+}
+
+/// Computes the strain in an element. With `geometric` set, strains are
+/// Green-Lagrange strains measured from the undeformed element, as in the
+/// total Lagrangian formulation; otherwise they are small strains.
+///
+/// # Arguments
+/// * `element` - The element to target
+/// * `nodes` - The undeformed nodes
+/// * `displacements` - The element's nodal displacements
+/// * `geometric` - Whether to account for large displacements
+/// * `part_thickness` - The thickness of the part
+///
+/// # Returns
+/// The element's Kinematics
+fn compute_kinematics(
+    element: &Element,
+    nodes: &[Node],
+    displacements: &SMatrix<f64, 6, 1>,
+    geometric: bool,
+    part_thickness: f64,
+) -> Kinematics {
+    let element_area = compute_element_area(element, nodes);
+    let strain_displacement_mat = compute_strain_displacement_matrix(element, nodes, element_area);
+
+    // Shape function derivatives, taken from the linear strain-displacement
+    // matrix
+    let dx = [0, 2, 4].map(|c| strain_displacement_mat[(0, c)]);
+    let dy = [1, 3, 5].map(|c| strain_displacement_mat[(1, c)]);
+    let volume = element_area * part_thickness;
+
+    if !geometric {
+        return Kinematics {
+            strain: strain_displacement_mat * displacements,
+            strain_mat: strain_displacement_mat,
+            deformation_gradient: SMatrix::<f64, 2, 2>::identity(),
+            derivatives: [dx, dy],
+            volume,
+        };
+    }
+
+    // Deformation gradient F = I + du/dX
+    let mut f = SMatrix::<f64, 2, 2>::identity();
+    for a in 0..3 {
+        let (ux, uy) = (displacements[2 * a], displacements[2 * a + 1]);
+        f[(0, 0)] += dx[a] * ux;
+        f[(0, 1)] += dy[a] * ux;
+        f[(1, 0)] += dx[a] * uy;
+        f[(1, 1)] += dy[a] * uy;
+    }
+
+    // Green-Lagrange strain
+    let c = f.transpose() * f;
+    let strain = matrix![0.5 * (c[(0, 0)] - 1.0); 0.5 * (c[(1, 1)] - 1.0); c[(0, 1)]];
+
+    // Variation of the Green-Lagrange strain with the nodal displacements
+    let mut strain_mat = SMatrix::<f64, 3, 6>::zeros();
+    for a in 0..3 {
+        for i in 0..2 {
+            strain_mat[(0, 2 * a + i)] = f[(i, 0)] * dx[a];
+            strain_mat[(1, 2 * a + i)] = f[(i, 1)] * dy[a];
+            strain_mat[(2, 2 * a + i)] = f[(i, 0)] * dy[a] + f[(i, 1)] * dx[a];
+        }
+    }
+
+    Kinematics {
+        strain,
+        strain_mat,
+        deformation_gradient: f,
+        derivatives: [dx, dy],
+        volume,
+    }
+}
+
+/// Computes the response of an element from its kinematics and the stress
+/// and tangent stress-strain matrix of its material. With large
+/// displacements, stresses are second Piola-Kirchhoff stresses, and their
+/// geometric stiffness is included.
+///
+/// # Arguments
+/// * `kinematics` - The element's kinematics
+/// * `stress` - The stress at the element's strain
+/// * `tangent_mat` - The tangent stress-strain matrix
+/// * `geometric` - Whether to account for large displacements
+/// * `state` - The material state reached
+///
+/// # Returns
+/// The element's internal forces, tangent stiffness and Cauchy stress
+fn compute_element_response(
+    kinematics: &Kinematics,
+    stress: &SVector<f64, 3>,
+    tangent_mat: &SMatrix<f64, 3, 3>,
+    geometric: bool,
+    state: PlasticState,
+) -> ElementResponse {
+    let Kinematics {
+        strain_mat,
+        deformation_gradient: f,
+        derivatives: [dx, dy],
+        volume,
+        ..
+    } = kinematics;
+
+    let internal_force = strain_mat.transpose() * stress * *volume;
+
+    // Material stiffness, plus the geometric stiffness of the current stress
+    let mut tangent = strain_mat.transpose() * tangent_mat * strain_mat * *volume;
+    if geometric {
+        for a in 0..3 {
+            for b in 0..3 {
+                let geometric = (dx[a] * (stress[0] * dx[b] + stress[2] * dy[b])
+                    + dy[a] * (stress[2] * dx[b] + stress[1] * dy[b]))
+                    * volume;
+                tangent[(2 * a, 2 * b)] += geometric;
+                tangent[(2 * a + 1, 2 * b + 1)] += geometric;
+            }
+        }
+    }
+
+    // Push the second Piola-Kirchhoff stress forward to the Cauchy stress
+    let pk2 = matrix![stress[0], stress[2]; stress[2], stress[1]];
+    let cauchy = f * pk2 * f.transpose() / f.determinant();
+
+    ElementResponse {
+        internal_force,
+        tangent,
+        cauchy_stress: [cauchy[(0, 0)], cauchy[(1, 1)], cauchy[(0, 1)]],
+        state,
+    }
+}
+
+/// Runs a nonlinear solve. Loads and prescribed displacements are applied in
+/// equal increments, and each increment is equilibrated with Newton-Raphson
+/// iteration. With `options.geometric`, the equations are total Lagrangian,
+/// so displacements and rotations may be large; forces are dead loads that
+/// keep their direction as the part deforms. With `options.plasticity`, the
+/// material yields with von Mises plasticity. Updates values on nodes and
+/// elements vectors; element stresses are Cauchy stresses.
+///
+/// # Arguments
+/// * `nodes` - A mutable reference to the vector of nodes
+/// * `elements` - A mutable reference to the vector of elements
+/// * `model_metadata` - The model metadata
+/// * `options` - The increment, convergence and material settings
+pub fn run(
+    nodes: &mut [Node],
+    elements: &mut [Element],
+    model_metadata: &ModelMetadata,
+    options: &NonlinearOptions,
+) -> Result<(), MagnetiteError> {
+    let dof_count = DOF * nodes.len();
+    let mut applied_forces = DVector::<f64>::zeros(dof_count);
+    let mut prescribed: Vec<Option<f64>> = Vec::with_capacity(dof_count);
+    for (i, node) in nodes.iter().enumerate() {
+        for (d, (u, f)) in [(node.ux, node.fx), (node.uy, node.fy)]
+            .into_iter()
+            .enumerate()
+        {
+            prescribed.push(u);
+            if u.is_none() {
+                applied_forces[DOF * i + d] = f.expect("Free degree of freedom without a force");
+            }
+        }
+    }
+
+    // Index of each degree of freedom within the free partition
+    let partitioning = Timer::start(info_span!("partitioning"));
+    let mut free_index: Vec<Option<usize>> = vec![None; dof_count];
+    let mut free_count = 0;
+    for (dof, u) in prescribed.iter().enumerate() {
+        if u.is_none() {
+            free_index[dof] = Some(free_count);
+            free_count += 1;
+        }
+    }
+    drop(partitioning);
+
+    let stress_strain_mat = compute_stress_strain_matrix(
+        model_metadata.material.poisson_ratio,
+        model_metadata.material.youngs_modulus,
+    );
+    let plastic_material = options
+        .plasticity
+        .as_ref()
+        .map(|plasticity| J2Material::new(&model_metadata.material, plasticity))
+        .transpose()?;
+    let part_thickness = model_metadata.part_thickness;
+    let dofs: Vec<[usize; 6]> = elements.iter().map(element_dofs).collect();
+
+    info!(
+        "solving with {} in {} increments, {free_count} free degrees of freedom",
+        match (options.geometric, &options.plasticity) {
+            (true, Some(_)) => "large displacements and plasticity",
+            (true, None) => "large displacements",
+            (false, _) => "plasticity",
+        },
+        options.increments
+    );
+
+    let mut displacements = DVector::<f64>::zeros(dof_count);
+    let mut internal_forces = DVector::<f64>::zeros(dof_count);
+    let mut states: Vec<PlasticState> = vec![PlasticState::default(); elements.len()];
+    let mut responses: Vec<ElementResponse> = Vec::new();
+
+    for increment in 1..=options.increments {
+        let load_factor = increment as f64 / options.increments as f64;
+
+        let mut iteration = 0;
+        loop {
+            // Prescribed displacements move to this increment's values on
+            // the first iteration
+            let step: Vec<f64> = (0..dof_count)
+                .map(|dof| match prescribed[dof] {
+                    Some(u) => load_factor * u - displacements[dof],
+                    None => 0.0,
+                })
+                .collect();
+
+            let assembly = Timer::start(info_span!("assembly"));
+            responses = elements
+                .iter()
+                .zip(&dofs)
+                .zip(&states)
+                .map(|((element, dofs), state)| {
+                    let u = SMatrix::<f64, 6, 1>::from_iterator(dofs.map(|d| displacements[d]));
+                    let kinematics =
+                        compute_kinematics(element, nodes, &u, options.geometric, part_thickness);
+                    let (stress, tangent_mat, state) = match &plastic_material {
+                        Some(material) => material.update(&kinematics.strain, state),
+                        None => (
+                            stress_strain_mat * kinematics.strain,
+                            stress_strain_mat,
+                            state.clone(),
+                        ),
+                    };
+                    compute_element_response(
+                        &kinematics,
+                        &stress,
+                        &tangent_mat,
+                        options.geometric,
+                        state,
+                    )
+                })
+                .collect();
+
+            internal_forces.fill(0.0);
+            let mut tangent_coo: CooMatrix<f64> = CooMatrix::new(free_count, free_count);
+            let mut rhs = DVector::<f64>::zeros(free_count);
+            for (response, dofs) in std::iter::zip(&responses, &dofs) {
+                for (a, row) in dofs.iter().enumerate() {
+                    internal_forces[*row] += response.internal_force[a];
+                    let Some(r) = free_index[*row] else {
+                        continue;
+                    };
+                    for (b, col) in dofs.iter().enumerate() {
+                        let k = response.tangent[(a, b)];
+                        match free_index[*col] {
+                            Some(c) => tangent_coo.push(r, c, k),
+                            None => rhs[r] -= k * step[*col],
+                        }
+                    }
+                }
+            }
+            drop(assembly);
+
+            // Out-of-balance forces at the free degrees of freedom
+            let mut residual_norm = 0.0;
+            for (dof, index) in free_index.iter().enumerate() {
+                if let Some(r) = index {
+                    let residual = load_factor * applied_forces[dof] - internal_forces[dof];
+                    rhs[*r] += residual;
+                    residual_norm += residual * residual;
+                }
+            }
+            let residual_norm = residual_norm.sqrt();
+            let reference = internal_forces
+                .norm()
+                .max(load_factor * applied_forces.norm());
+
+            let stepped = step.iter().any(|s| *s != 0.0);
+            if !stepped && residual_norm <= options.tolerance * reference {
+                let yielded = match plastic_material {
+                    Some(_) => format!(
+                        ", {} elements yielded",
+                        responses
+                            .iter()
+                            .filter(|r| r.state.equivalent_plastic_strain > 0.0)
+                            .count()
+                    ),
+                    None => String::new(),
+                };
+                info!(
+                    "increment {increment}/{} (load factor {load_factor:.3}) converged in {iteration} iterations, residual {:.3e}{yielded}",
+                    options.increments,
+                    if reference > 0.0 { residual_norm / reference } else { 0.0 }
+                );
+                states = responses.iter().map(|r| r.state.clone()).collect();
+                break;
+            }
+            if !residual_norm.is_finite() || iteration == options.max_iterations {
+                // Without any load or internal force there's nothing to
+                // scale the residual by
+                let residual = match reference > 0.0 {
+                    true => format!("relative residual {:.3e}", residual_norm / reference),
+                    false => format!("absolute residual {residual_norm:.3e}"),
+                };
+                return Err(MagnetiteError::Solver(format!(
+                    "Increment {increment}/{} did not converge in {} iterations ({residual}); try more increments",
+                    options.increments,
+                    options.max_iterations,
+                )));
+            }
+            iteration += 1;
+
+            let _timer = Timer::start(info_span!("solve"));
+            let factor = match CscCholesky::factor(&CscMatrix::from(&tangent_coo)) {
+                Ok(f) => f,
+                Err(_err) => {
+                    return Err(MagnetiteError::Solver(format!(
+                        "Tangent stiffness matrix is not positive definite at increment {increment}; the part may have buckled or be under-constrained"
+                    )))
+                }
+            };
+            let correction = factor.solve(&rhs);
+
+            for dof in 0..dof_count {
+                match free_index[dof] {
+                    Some(r) => displacements[dof] += correction[(r, 0)],
+                    None => displacements[dof] += step[dof],
+                }
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter_mut().enumerate() {
+        // Reaction forces at constrained degrees of freedom
+        let force = |dof: usize| match prescribed[dof] {
+            Some(_) => internal_forces[dof],
+            None => applied_forces[dof],
+        };
+        node.ux = Some(displacements[DOF * i]);
+        node.uy = Some(displacements[DOF * i + 1]);
+        node.fx = Some(force(DOF * i));
+        node.fy = Some(force(DOF * i + 1));
+    }
+
+    let _timer = Timer::start(info_span!("stress_recovery"));
+    for ((element, response), state) in std::iter::zip(elements, &responses).zip(&states) {
+        set_element_stress(element, response.cauchy_stress);
+        if plastic_material.is_some() {
+            element.plastic_strain = Some(state.equivalent_plastic_strain);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datatypes::{Hardening, Material, MeshOptions, PlasticityOptions, Vertex},
+        solver,
+    };
+
+    /// A 4 by 1 strip of eight elements
+    fn strip() -> (Vec<Node>, Vec<Element>) {
+        let mut nodes = Vec::new();
+        for j in 0..=1 {
+            for i in 0..=4 {
+                nodes.push(Node {
+                    vertex: Vertex {
+                        x: i as f64,
+                        y: j as f64,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                });
+            }
+        }
+        let elements = (0..4)
+            .flat_map(|i| [[i, i + 1, i + 6], [i, i + 6, i + 5]])
+            .map(|nodes| Element {
+                nodes,
+                stress: None,
+                stress_tensor: None,
+                plastic_strain: None,
+                physical_tags: Vec::new(),
+            })
+            .collect();
+        (nodes, elements)
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.3,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        }
+    }
+
+    fn options(increments: usize) -> NonlinearOptions {
+        NonlinearOptions {
+            increments,
+            max_iterations: 25,
+            tolerance: 1e-10,
+            geometric: true,
+            plasticity: None,
+        }
+    }
+
+    #[test]
+    fn small_loads_match_linear_solve() {
+        let (mut nodes, mut elements) = strip();
+        for node in nodes.iter_mut() {
+            if node.vertex.x == 0.0 {
+                (node.ux, node.uy, node.fx, node.fy) = (Some(0.0), Some(0.0), None, None);
+            }
+            if node.vertex.x == 4.0 {
+                node.fy = Some(-1e-3);
+            }
+        }
+        let mut linear_nodes = nodes.clone();
+
+        run(&mut nodes, &mut elements, &metadata(), &options(2)).unwrap();
+        let stiffness = solver::assemble_stiffness(
+            &linear_nodes,
+            &elements,
+            &vec![metadata().material; elements.len()],
+            &metadata(),
+        );
+        solver::FactoredStiffness::new(&stiffness, &linear_nodes)
+            .unwrap()
+            .solve(&mut linear_nodes)
+            .unwrap();
+
+        let tip = nodes[4].uy.unwrap();
+        let linear_tip = linear_nodes[4].uy.unwrap();
+        assert!(tip < 0.0);
+        assert!(((tip - linear_tip) / linear_tip).abs() < 1e-4);
+
+        // Reactions balance the applied load
+        let reaction: f64 = [0, 5].iter().map(|i| nodes[*i].fy.unwrap()).sum();
+        assert!((reaction - 2e-3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rigid_rotation_is_stress_free() {
+        // Rotate the whole strip a quarter turn about the origin; a linear
+        // solve would see large strains
+        let (mut nodes, mut elements) = strip();
+        for node in nodes.iter_mut() {
+            let Vertex { x, y } = node.vertex;
+            (node.ux, node.uy, node.fx, node.fy) = (Some(-y - x), Some(x - y), None, None);
+        }
+
+        run(&mut nodes, &mut elements, &metadata(), &options(4)).unwrap();
+
+        for element in &elements {
+            for stress in element.stress_tensor.unwrap() {
+                assert!(stress.abs() < 1e-9, "{stress}");
+            }
+        }
+    }
+
+    #[test]
+    fn perfectly_plastic_bar_carries_its_yield_load() {
+        // Stretch the strip to twenty times its yield strain
+        let (mut nodes, mut elements) = strip();
+        for node in nodes.iter_mut() {
+            if node.vertex.x == 0.0 {
+                (node.ux, node.fx) = (Some(0.0), None);
+            }
+            if node.vertex.x == 4.0 {
+                (node.ux, node.fx) = (Some(0.08), None);
+            }
+        }
+        (nodes[0].uy, nodes[0].fy) = (Some(0.0), None);
+
+        let mut metadata = metadata();
+        metadata.material.yield_strength = Some(1.0);
+        let mut options = options(10);
+        options.geometric = false;
+        options.plasticity = Some(PlasticityOptions {
+            hardening: Hardening::Isotropic,
+            hardening_modulus: 0.0,
+        });
+
+        run(&mut nodes, &mut elements, &metadata, &options).unwrap();
+
+        let reaction: f64 = [4, 9].iter().map(|i| nodes[*i].fx.unwrap()).sum();
+        assert!((reaction - 1.0).abs() < 1e-6, "{reaction}");
+        for element in &elements {
+            let plastic_strain = element.plastic_strain.unwrap();
+            assert!(
+                (plastic_strain - (0.02 - 1e-3)).abs() < 1e-6,
+                "{plastic_strain}"
+            );
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use nalgebra::{matrix, SMatrix, SVector};
+
+use crate::{
+    datatypes::{Hardening, Material, PlasticityOptions},
+    error::MagnetiteError,
+    solver::compute_stress_strain_matrix,
+};
+
+/// Most steps when bracketing and bisecting the plastic multiplier
+const MAX_RETURN_STEPS: usize = 200;
+/// Relative size of the strain perturbations for the tangent matrix
+const TANGENT_PERTURBATION: f64 = 1e-6;
+
+/// The history of an elastic-plastic material point, in Voigt notation with
+/// engineering shear strains
+#[derive(Debug, Clone, Default)]
+pub struct PlasticState {
+    pub plastic_strain: SVector<f64, 3>,
+    /// The centre of the yield surface, for kinematic hardening
+    pub back_stress: SVector<f64, 3>,
+    pub equivalent_plastic_strain: f64,
+}
+
+/// A plane stress von Mises material with linear isotropic or kinematic
+/// hardening. Stresses are updated with the closest point return mapping.
+///
+/// The plastic multiplier is found by bisection and the tangent by finite
+/// differences, on purpose. Plane stress makes the multiplier the root of a
+/// nonlinear scalar equation even for linear hardening, and bisection finds
+/// it reliably for both hardening rules without a starting guess. The
+/// finite-difference tangent costs six more return mappings per yielding
+/// element, but follows the return mapping exactly as written, so it stays
+/// consistent when the hardening rules change; a closed form would have to
+/// be derived again for each.
+#[derive(Debug, Clone)]
+pub struct J2Material {
+    elastic: SMatrix<f64, 3, 3>,
+    yield_strength: f64,
+    isotropic_modulus: f64,
+    kinematic_modulus: f64,
+}
+
+/// The plane stress von Mises projection; `0.5 * s^T P s` is a third of the
+/// squared von Mises stress of `s`
+fn projection() -> SMatrix<f64, 3, 3> {
+    matrix![
+        2.0 / 3.0, -1.0 / 3.0, 0.0;
+        -1.0 / 3.0, 2.0 / 3.0, 0.0;
+        0.0, 0.0, 2.0;
+    ]
+}
+
+impl J2Material {
+    /// Creates the material model
+    ///
+    /// # Arguments
+    /// * `material` - The material, which must have a yield strength
+    /// * `options` - The hardening settings
+    ///
+    /// # Returns
+    /// The material model, or an Input error if the material has no yield
+    /// strength
+    pub fn new(
+        material: &Material,
+        options: &PlasticityOptions,
+    ) -> Result<J2Material, MagnetiteError> {
+        let Some(yield_strength) = material.yield_strength else {
+            return Err(MagnetiteError::Input(
+                "plasticity needs a yield strength; set metadata.yield_strength or use a library material".to_owned(),
+            ));
+        };
+        let (isotropic_modulus, kinematic_modulus) = match options.hardening {
+            Hardening::Isotropic => (options.hardening_modulus, 0.0),
+            Hardening::Kinematic => (0.0, options.hardening_modulus),
+        };
+        Ok(J2Material {
+            elastic: compute_stress_strain_matrix(material.poisson_ratio, material.youngs_modulus),
+            yield_strength,
+            isotropic_modulus,
+            kinematic_modulus,
+        })
+    }
+
+    /// Computes the stress at a strain, starting from a converged state
+    ///
+    /// # Arguments
+    /// * `strain` - The total strain
+    /// * `state` - The state at the end of the last converged increment
+    ///
+    /// # Returns
+    /// The stress and the updated state
+    pub fn stress(
+        &self,
+        strain: &SVector<f64, 3>,
+        state: &PlasticState,
+    ) -> (SVector<f64, 3>, PlasticState) {
+        let projection = projection();
+        let trial_stress = self.elastic * (strain - state.plastic_strain);
+        let trial_relative = trial_stress - state.back_stress;
+
+        // Ziegler's rule; the back stress moves towards the relative stress,
+        // so it stays a plane stress
+        let back_stress_rate = (2.0 / 3.0) * self.kinematic_modulus;
+        let flow = self.elastic * projection + back_stress_rate * SMatrix::<f64, 3, 3>::identity();
+
+        // The relative stress and yield function after a plastic step of
+        // `multiplier`
+        let relative = |multiplier: f64| -> SVector<f64, 3> {
+            (SMatrix::<f64, 3, 3>::identity() + multiplier * flow)
+                .try_inverse()
+                .expect("Return mapping matrix is singular")
+                * trial_relative
+        };
+        let equivalent_rate =
+            |s: &SVector<f64, 3>| (2.0 / 3.0 * (s.transpose() * projection * s)[(0, 0)]).sqrt();
+        let yield_function = |multiplier: f64| -> f64 {
+            let s = relative(multiplier);
+            let hardened = self.yield_strength
+                + self.isotropic_modulus
+                    * (state.equivalent_plastic_strain + multiplier * equivalent_rate(&s));
+            0.5 * (s.transpose() * projection * s)[(0, 0)] - hardened * hardened / 3.0
+        };
+
+        if yield_function(0.0) <= 0.0 {
+            return (trial_stress, state.clone());
+        }
+
+        // Bracket the plastic multiplier, then bisect to machine precision
+        let mut lower = 0.0;
+        let mut upper = 1.0 / self.elastic[(0, 0)];
+        for _ in 0..MAX_RETURN_STEPS {
+            if yield_function(upper) <= 0.0 {
+                break;
+            }
+            lower = upper;
+            upper *= 2.0;
+        }
+        for _ in 0..MAX_RETURN_STEPS {
+            let middle = 0.5 * (lower + upper);
+            if middle <= lower || middle >= upper {
+                break;
+            }
+            if yield_function(middle) > 0.0 {
+                lower = middle;
+            } else {
+                upper = middle;
+            }
+        }
+        let multiplier = 0.5 * (lower + upper);
+
+        let s = relative(multiplier);
+        let plastic_step = multiplier * projection * s;
+        let new_state = PlasticState {
+            plastic_strain: state.plastic_strain + plastic_step,
+            back_stress: state.back_stress + multiplier * back_stress_rate * s,
+            equivalent_plastic_strain: state.equivalent_plastic_strain
+                + multiplier * equivalent_rate(&s),
+        };
+        (trial_stress - self.elastic * plastic_step, new_state)
+    }
+
+    /// Computes the stress and tangent stress-strain matrix at a strain,
+    /// starting from a converged state. The tangent is found by perturbing
+    /// the strain, and is elastic while the material doesn't yield.
+    ///
+    /// # Arguments
+    /// * `strain` - The total strain
+    /// * `state` - The state at the end of the last converged increment
+    ///
+    /// # Returns
+    /// The stress, tangent and updated state
+    pub fn update(
+        &self,
+        strain: &SVector<f64, 3>,
+        state: &PlasticState,
+    ) -> (SVector<f64, 3>, SMatrix<f64, 3, 3>, PlasticState) {
+        let (stress, new_state) = self.stress(strain, state);
+        if new_state.equivalent_plastic_strain == state.equivalent_plastic_strain {
+            return (stress, self.elastic, new_state);
+        }
+
+        let size = TANGENT_PERTURBATION
+            * strain
+                .amax()
+                .max(self.yield_strength / self.elastic[(0, 0)]);
+        let mut tangent = SMatrix::<f64, 3, 3>::zeros();
+        for j in 0..3 {
+            let mut step = SVector::<f64, 3>::zeros();
+            step[j] = size;
+            let (above, _) = self.stress(&(strain + step), state);
+            let (below, _) = self.stress(&(strain - step), state);
+            tangent.set_column(j, &((above - below) / (2.0 * size)));
+        }
+
+        (stress, 0.5 * (tangent + tangent.transpose()), new_state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(hardening: Hardening, hardening_modulus: f64) -> J2Material {
+        J2Material::new(
+            &Material {
+                youngs_modulus: 200e9,
+                poisson_ratio: 0.3,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: Some(250e6),
+                ultimate_strength: None,
+            },
+            &PlasticityOptions {
+                hardening,
+                hardening_modulus,
+            },
+        )
+        .unwrap()
+    }
+
+    /// Finds the strain for a uniaxial stress state with a given axial
+    /// strain, by iterating on the transverse strain
+    fn uniaxial(
+        material: &J2Material,
+        axial: f64,
+        state: &PlasticState,
+    ) -> (SVector<f64, 3>, PlasticState) {
+        let mut strain = SVector::<f64, 3>::new(axial, -0.3 * axial, 0.0);
+        for _ in 0..50 {
+            let (stress, tangent, _) = material.update(&strain, state);
+            strain[1] -= stress[1] / tangent[(1, 1)];
+        }
+        material.stress(&strain, state)
+    }
+
+    #[test]
+    fn perfect_plasticity_caps_stress_at_yield() {
+        let material = material(Hardening::Isotropic, 0.0);
+        let (stress, state) = uniaxial(&material, 0.01, &PlasticState::default());
+
+        assert!((stress[0] - 250e6).abs() < 1e-3 * 250e6, "{stress}");
+        assert!(stress[1].abs() < 1.0);
+        assert!((state.equivalent_plastic_strain - (0.01 - 250e6 / 200e9)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn hardening_raises_the_reverse_yield_stress_differently() {
+        // Load past yield in tension, then into compression. Isotropic
+        // hardening grows the yield surface; kinematic hardening moves it,
+        // so the material yields earlier in compression
+        let hardening_modulus = 20e9;
+        let mut reverse_yield = Vec::new();
+        for hardening in [Hardening::Isotropic, Hardening::Kinematic] {
+            let material = material(hardening, hardening_modulus);
+            let (stress, state) = uniaxial(&material, 0.01, &PlasticState::default());
+            let plastic = state.equivalent_plastic_strain;
+            assert!((stress[0] - (250e6 + hardening_modulus * plastic)).abs() < 1e-3 * stress[0]);
+
+            let (compression, _) = uniaxial(&material, -0.01, &state);
+            reverse_yield.push(compression[0]);
+        }
+
+        assert!(reverse_yield[0] < reverse_yield[1]);
+    }
+
+    #[test]
+    fn requires_a_yield_strength() {
+        let material = Material {
+            youngs_modulus: 200e9,
+            poisson_ratio: 0.3,
+            density: None,
+            thermal_expansion: None,
+            yield_strength: None,
+            ultimate_strength: None,
+        };
+        let options = PlasticityOptions {
+            hardening: Hardening::Isotropic,
+            hardening_modulus: 0.0,
+        };
+        assert!(matches!(
+            J2Material::new(&material, &options),
+            Err(MagnetiteError::Input(_))
+        ));
+    }
+}
@@ -1,23 +1,23 @@
-use std::{
-    io::{BufWriter, Write},
-    process::ExitStatus,
-};
+use std::io::Write;
 
 use crate::{
-    datatypes::{Element, Node},
+    datatypes::{ElementResult, FailureCriterion, ModelMetadata, NodeResult, Results, Vertex},
     error::MagnetiteError,
+    units::{label, Units},
 };
+use tracing::info;
 
-/// Writes simulation results to two CSV files
+/// Writes simulation results to two CSV files. If units are declared,
+/// values are converted back to them and headers are labelled, e.g. `x [mm]`
 ///
 /// # Arguments
-/// * `elements` - A reference to the vector of post-solve elements
-/// * `nodes` - A reference to the vector of post-solve nodes
+/// * `results` - The results of a solved model
+/// * `units` - The units of the input file, if declared
 /// * `nodes_output` - The filename of the output nodes csv
 /// * `elements_output` - The filename of the output elements csv
 pub fn csv_output(
-    elements: &Vec<Element>,
-    nodes: &Vec<Node>,
+    results: &Results,
+    units: Option<&Units>,
     nodes_output: &str,
     elements_output: &str,
 ) -> Result<(), MagnetiteError> {
@@ -38,86 +38,490 @@ pub fn csv_output(
         }
     };
 
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress = units.map_or(1.0, |u| u.stress.factor());
+    let length_unit = units.map(|u| u.length.name());
+    let stress_unit = units.map(|u| u.stress.name());
+
     // Write nodes
-    nodes_file.write("x,y,ux,uy\n".as_bytes()).unwrap();
-    for node in nodes {
+    let headers = ["x", "y", "ux", "uy"].map(|h| label(h, length_unit));
+    nodes_file
+        .write_all(format!("{}\n", headers.join(",")).as_bytes())
+        .unwrap();
+    for node in &results.nodes {
         nodes_file
-            .write(
+            .write_all(
                 format!(
                     "{x},{y},{ux},{uy}\n",
-                    x = node.vertex.x,
-                    y = node.vertex.y,
-                    ux = node.ux.unwrap(),
-                    uy = node.uy.unwrap(),
+                    x = node.vertex.x / length,
+                    y = node.vertex.y / length,
+                    ux = node.ux / length,
+                    uy = node.uy / length,
                 )
                 .as_bytes(),
             )
             .unwrap();
     }
 
-    // Write vertices
+    // Write vertices. Factors of safety are only written if the yield
+    // strength is known.
+    let has_safety_factor = results.elements.iter().any(|e| e.safety_factor.is_some());
+    let mut headers = ["stress", "von_mises", "tresca"]
+        .map(|h| label(h, stress_unit))
+        .join(",");
+    if has_safety_factor {
+        headers += ",safety_factor";
+    }
+    // Plastic strains are only written if the solve modelled plasticity
+    let has_plastic_strain = results.elements.iter().any(|e| e.plastic_strain.is_some());
+    if has_plastic_strain {
+        headers += ",plastic_strain,yielded";
+    }
     elements_file
-        .write(format!("n0,n1,n2,stress\n").as_bytes())
+        .write_all(format!("n0,n1,n2,{headers}\n").as_bytes())
         .unwrap();
-    for element in elements {
+    for element in &results.elements {
+        let safety_factor = match (has_safety_factor, element.safety_factor) {
+            (true, Some(fos)) => format!(",{fos}"),
+            (true, None) => ",".to_string(),
+            (false, _) => String::new(),
+        };
+        let plastic_strain = match (has_plastic_strain, element.plastic_strain) {
+            (true, Some(strain)) => format!(",{strain},{}", strain > 0.0),
+            (true, None) => ",,".to_string(),
+            (false, _) => String::new(),
+        };
         elements_file
-            .write(
+            .write_all(
                 format!(
-                    "{n0},{n1},{n2},{stress}\n",
+                    "{n0},{n1},{n2},{stress},{von_mises},{tresca}{safety_factor}{plastic_strain}\n",
                     n0 = element.nodes[0],
                     n1 = element.nodes[1],
                     n2 = element.nodes[2],
-                    stress = element.stress.unwrap()
+                    stress = element.stress / stress,
+                    von_mises = element.von_mises / stress,
+                    tresca = element.tresca / stress,
                 )
                 .as_bytes(),
             )
             .unwrap();
     }
 
-    println!(
-        "info: wrote output to {} and {}",
-        nodes_output, elements_output
-    );
+    info!("wrote output to {} and {}", nodes_output, elements_output);
 
     Ok(())
 }
 
-/// Calls the python plotter to plot results
+/// A csv table read from a file, with unit labels split from its headers
+struct CsvTable {
+    source: String,
+    /// The name and unit of each column, e.g. `x` and `mm`
+    headers: Vec<(String, Option<String>)>,
+    /// The line number and cells of each row
+    rows: Vec<(usize, Vec<String>)>,
+}
+
+impl CsvTable {
+    fn read(source: &str) -> Result<CsvTable, MagnetiteError> {
+        let contents = match std::fs::read_to_string(source) {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Unable to open {source}: {err}"
+                )))
+            }
+        };
+        let mut lines = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let Some((_, header)) = lines.next() else {
+            return Err(MagnetiteError::PostProcessor(format!("{source} is empty")));
+        };
+        let headers = header
+            .split(',')
+            .map(|h| match h.trim().split_once(" [") {
+                Some((name, unit)) => (
+                    name.to_string(),
+                    Some(unit.trim_end_matches(']').to_string()),
+                ),
+                None => (h.trim().to_string(), None),
+            })
+            .collect();
+        let rows = lines
+            .map(|(i, l)| (i + 1, l.split(',').map(|c| c.trim().to_string()).collect()))
+            .collect();
+        Ok(CsvTable {
+            source: source.to_string(),
+            headers,
+            rows,
+        })
+    }
+
+    /// The index and unit of a column, if the table has it
+    fn column(&self, name: &str) -> Option<(usize, Option<&str>)> {
+        self.headers
+            .iter()
+            .position(|(n, _)| n == name)
+            .map(|i| (i, self.headers[i].1.as_deref()))
+    }
+
+    /// The index and unit of a column the table must have
+    fn required(&self, name: &str) -> Result<(usize, Option<&str>), MagnetiteError> {
+        self.column(name).ok_or_else(|| {
+            MagnetiteError::PostProcessor(format!("{} has no {name} column", self.source))
+        })
+    }
+
+    /// Parses a cell; empty cells are None
+    fn cell<T: std::str::FromStr>(
+        &self,
+        (line, cells): &(usize, Vec<String>),
+        column: usize,
+    ) -> Result<Option<T>, MagnetiteError> {
+        match cells.get(column).map(|c| c.as_str()) {
+            None | Some("") => Ok(None),
+            Some(cell) => cell.parse().map(Some).map_err(|_| {
+                MagnetiteError::PostProcessor(format!(
+                    "{} line {line}: '{cell}' is not a valid {}",
+                    self.source, self.headers[column].0
+                ))
+            }),
+        }
+    }
+
+    /// Parses a cell that must have a value
+    fn value<T: std::str::FromStr>(
+        &self,
+        row: &(usize, Vec<String>),
+        column: usize,
+    ) -> Result<T, MagnetiteError> {
+        self.cell(row, column)?.ok_or_else(|| {
+            MagnetiteError::PostProcessor(format!(
+                "{} line {}: missing {}",
+                self.source, row.0, self.headers[column].0
+            ))
+        })
+    }
+}
+
+/// Parses a unit label from a csv header, e.g. `mm`
+fn parse_unit<T: serde::de::DeserializeOwned + Default>(
+    unit: Option<&str>,
+    source: &str,
+) -> Result<T, MagnetiteError> {
+    let Some(unit) = unit else {
+        return Ok(T::default());
+    };
+    serde_json::from_value(serde_json::Value::String(unit.to_string()))
+        .map_err(|_| MagnetiteError::PostProcessor(format!("{source} has unknown unit '{unit}'")))
+}
+
+/// Reads results back from the csv files written by [`csv_output`], and
+/// converts them to SI units. Reactions aren't written to the csv files, so
+/// they are read as zero, as are the stress tensors of the elements.
 ///
 /// # Arguments
-/// * `nodes_csv` - The filepath to the nodes csv output
-/// * `elements_csv` - The filepath to the elements csv output
-pub fn pyplot(nodes_csv: &str, elements_csv: &str, cmap: &str) -> Result<(), MagnetiteError> {
-    // resolve plotter path
-    let current_dir = std::env::current_exe().unwrap();
-    let repo_dir = current_dir
-        .ancestors()
-        .into_iter()
-        .find(|p| p.ends_with("Magnetite"))
-        .expect("Unable to find root repo directory");
-    let plotter_path = repo_dir
-        .join("scripts/plot.py")
-        .canonicalize()
-        .expect("Unable to find plotter script")
-        .into_os_string()
-        .into_string()
-        .unwrap();
+/// * `nodes_file` - The path to the nodes csv
+/// * `elements_file` - The path to the elements csv
+///
+/// # Returns
+/// The results, and the units the files were written in if they are
+/// labelled
+pub fn read_csv_output(
+    nodes_file: &str,
+    elements_file: &str,
+) -> Result<(Results, Option<Units>), MagnetiteError> {
+    let nodes_table = CsvTable::read(nodes_file)?;
+    let elements_table = CsvTable::read(elements_file)?;
 
-    println!("info: plotting in python...");
-    let res = std::process::Command::new("python")
-        .arg(plotter_path)
-        .arg(nodes_csv)
-        .arg(elements_csv)
-        .arg(cmap)
-        .output()
-        .unwrap();
+    let [x, y, ux, uy] = ["x", "y", "ux", "uy"].map(|name| nodes_table.required(name));
+    let (x, length_unit) = x?;
+    let [y, ux, uy] = [y?.0, ux?.0, uy?.0];
+    let [n0, n1, n2] = ["n0", "n1", "n2"].map(|name| elements_table.required(name));
+    let [n0, n1, n2] = [n0?.0, n1?.0, n2?.0];
+    let (stress, stress_unit) = elements_table.required("stress")?;
+    let von_mises = elements_table.required("von_mises")?.0;
+    let tresca = elements_table.required("tresca")?.0;
+    let safety_factor = elements_table.column("safety_factor").map(|c| c.0);
+    let plastic_strain = elements_table.column("plastic_strain").map(|c| c.0);
 
-    if !res.status.success() {
-        return Err(MagnetiteError::PostProcessor(format!(
-            "error: python plotter raised error:\n\n{}",
-            String::from_utf8_lossy(res.stderr.iter().as_slice())
-        )));
+    let units = match (length_unit, stress_unit) {
+        (None, None) => None,
+        _ => Some(Units {
+            length: parse_unit(length_unit, nodes_file)?,
+            stress: parse_unit(stress_unit, elements_file)?,
+            ..Default::default()
+        }),
+    };
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress_factor = units.map_or(1.0, |u| u.stress.factor());
+
+    let mut nodes = Vec::with_capacity(nodes_table.rows.len());
+    for row in &nodes_table.rows {
+        let value = |column| nodes_table.value::<f64>(row, column).map(|v| v * length);
+        nodes.push(NodeResult {
+            vertex: Vertex {
+                x: value(x)?,
+                y: value(y)?,
+            },
+            ux: value(ux)?,
+            uy: value(uy)?,
+            fx: 0.0,
+            fy: 0.0,
+        });
+    }
+
+    let mut elements = Vec::with_capacity(elements_table.rows.len());
+    for row in &elements_table.rows {
+        let mut indexes = [0; 3];
+        for (index, column) in std::iter::zip(&mut indexes, [n0, n1, n2]) {
+            *index = elements_table.value(row, column)?;
+            if *index >= nodes.len() {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "{elements_file} line {}: node {index} is not in {nodes_file}",
+                    row.0
+                )));
+            }
+        }
+        let stress_value = |column| {
+            elements_table
+                .value::<f64>(row, column)
+                .map(|v| v * stress_factor)
+        };
+        let optional = |column: Option<usize>| match column {
+            Some(column) => elements_table.cell::<f64>(row, column),
+            None => Ok(None),
+        };
+        elements.push(ElementResult {
+            nodes: indexes,
+            stress: stress_value(stress)?,
+            stress_tensor: [0.0; 3],
+            von_mises: stress_value(von_mises)?,
+            tresca: stress_value(tresca)?,
+            safety_factor: optional(safety_factor)?,
+            plastic_strain: optional(plastic_strain)?,
+        });
+    }
+
+    info!(
+        "read {} nodes and {} elements from {nodes_file} and {elements_file}",
+        nodes.len(),
+        elements.len()
+    );
+
+    Ok((Results { nodes, elements }, units))
+}
+
+/// The most highly stressed element of a solved model
+#[derive(Debug, Clone)]
+pub struct SafetySummary {
+    pub element: usize,
+    pub centroid: Vertex,
+    pub equivalent_stress: f64,
+    pub yield_factor: Option<f64>,
+    /// The element with the smallest factor of safety against yield. With
+    /// body materials it need not be the most stressed element.
+    pub yield_element: usize,
+    /// Not found when bodies have their own materials
+    pub ultimate_factor: Option<f64>,
+}
+
+/// Finds the element with the highest equivalent stress, and its factors of
+/// safety against yield and ultimate strength
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+/// * `model_metadata` - The model metadata, with the material strengths and
+///   failure criterion
+///
+/// # Returns
+/// The SafetySummary, or None if the model has no elements
+pub fn safety_summary(results: &Results, model_metadata: &ModelMetadata) -> Option<SafetySummary> {
+    let criterion = model_metadata.safety.criterion;
+    let equivalent = |index: usize| match criterion {
+        FailureCriterion::VonMises => results.elements[index].von_mises,
+        FailureCriterion::Tresca => results.elements[index].tresca,
+    };
+
+    let element =
+        (0..results.elements.len()).max_by(|a, b| equivalent(*a).total_cmp(&equivalent(*b)))?;
+    let equivalent_stress = equivalent(element);
+
+    let nodes = results.elements[element]
+        .nodes
+        .map(|n| &results.nodes[n].vertex);
+    let centroid = Vertex {
+        x: nodes.iter().map(|v| v.x).sum::<f64>() / 3.0,
+        y: nodes.iter().map(|v| v.y).sum::<f64>() / 3.0,
+    };
+
+    // Each element's factor of safety is found with its own material
+    let (yield_element, yield_factor) = results
+        .elements
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.safety_factor.map(|f| (i, f)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or((element, None), |(i, f)| (i, Some(f)));
+
+    let material = &model_metadata.material;
+    Some(SafetySummary {
+        element,
+        centroid,
+        equivalent_stress,
+        yield_factor,
+        yield_element,
+        ultimate_factor: match model_metadata.body_materials.is_empty() {
+            true => material.ultimate_strength.map(|s| s / equivalent_stress),
+            false => None,
+        },
+    })
+}
+
+/// Reports which elements of a solved model yielded, and the peak
+/// equivalent plastic strain. Does nothing if the solve didn't model
+/// plasticity.
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+pub fn plasticity_report(results: &Results) {
+    let plastic_strains: Vec<(usize, f64)> = results
+        .elements
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| e.plastic_strain.map(|strain| (i, strain)))
+        .collect();
+    let Some((peak_element, peak)) = plastic_strains
+        .iter()
+        .copied()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+    else {
+        return;
+    };
+
+    let yielded = plastic_strains.iter().filter(|(_, s)| *s > 0.0).count();
+    if yielded == 0 {
+        info!("no elements yielded");
+        return;
+    }
+    info!(
+        "{yielded} of {} elements yielded; peak equivalent plastic strain is {peak:.4e} in element {peak_element}",
+        results.elements.len()
+    );
+}
+
+/// Reports the minimum factors of safety of a solved model, and checks them
+/// against the required minimum
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+/// * `model_metadata` - The model metadata
+///
+/// # Returns
+/// A DesignCheck error if the minimum factor of safety against yield is
+/// below `model_metadata.safety.minimum_factor`
+pub fn safety_report(
+    results: &Results,
+    model_metadata: &ModelMetadata,
+) -> Result<(), MagnetiteError> {
+    let Some(summary) = safety_summary(results, model_metadata) else {
+        return Ok(());
+    };
+
+    let units = model_metadata.units.as_ref();
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress = units.map_or(1.0, |u| u.stress.factor());
+    let length_unit = units.map_or(String::new(), |u| format!(" {}", u.length.name()));
+    let stress_unit = units.map_or(String::new(), |u| format!(" {}", u.stress.name()));
+
+    let criterion = match model_metadata.safety.criterion {
+        FailureCriterion::VonMises => "von Mises",
+        FailureCriterion::Tresca => "Tresca",
+    };
+
+    info!(
+        "peak {criterion} stress is {:.4}{stress_unit} in element {} at ({:.4}, {:.4}){length_unit}",
+        summary.equivalent_stress / stress,
+        summary.element,
+        summary.centroid.x / length,
+        summary.centroid.y / length,
+    );
+
+    match summary.yield_factor {
+        Some(factor) => info!("minimum factor of safety against yield is {factor:.3}"),
+        None => info!("no yield strength given; skipping factor of safety"),
+    }
+    if let Some(factor) = summary.ultimate_factor {
+        info!("minimum factor of safety against ultimate strength is {factor:.3}");
+    }
+
+    if let (Some(factor), Some(minimum)) =
+        (summary.yield_factor, model_metadata.safety.minimum_factor)
+    {
+        if factor < minimum {
+            return Err(MagnetiteError::DesignCheck(format!(
+                "minimum factor of safety {factor:.3} in element {} is below the required {minimum}",
+                summary.yield_element
+            )));
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mesher::ScratchDir,
+        units::{LengthUnit, StressUnit},
+    };
+
+    #[test]
+    fn reads_back_csv_output() {
+        let node = |x: f64, y: f64| NodeResult {
+            vertex: Vertex { x, y },
+            ux: 1e-6 * x,
+            uy: -2e-6 * y,
+            fx: 0.0,
+            fy: 0.0,
+        };
+        let results = Results {
+            nodes: vec![node(0.0, 0.0), node(0.01, 0.0), node(0.0, 0.01)],
+            elements: vec![ElementResult {
+                nodes: [0, 1, 2],
+                stress: 2.5e6,
+                stress_tensor: [0.0; 3],
+                von_mises: 2.5e6,
+                tresca: 2.75e6,
+                safety_factor: Some(100.0),
+                plastic_strain: None,
+            }],
+        };
+        let units = Units {
+            length: LengthUnit::Millimeter,
+            stress: StressUnit::Megapascal,
+            ..Default::default()
+        };
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let (nodes_file, elements_file) = (scratch.file("nodes.csv"), scratch.file("elements.csv"));
+        csv_output(&results, Some(&units), &nodes_file, &elements_file).unwrap();
+        let (read, read_units) = read_csv_output(&nodes_file, &elements_file).unwrap();
+        scratch.succeed();
+
+        assert_eq!(
+            read_units.map(|u| (u.length, u.stress)),
+            Some((units.length, units.stress))
+        );
+        assert_eq!(read.nodes.len(), 3);
+        assert!((read.nodes[1].vertex.x - 0.01).abs() < 1e-15);
+        assert!((read.nodes[2].uy + 2e-8).abs() < 1e-20);
+        let element = &read.elements[0];
+        assert_eq!(element.nodes, [0, 1, 2]);
+        assert!((element.tresca - 2.75e6).abs() < 1e-6);
+        assert_eq!(element.safety_factor, Some(100.0));
+        assert_eq!(element.plastic_strain, None);
+    }
+}
@@ -0,0 +1,1280 @@
+use std::io::Write;
+
+use crate::{
+    datatypes::{ElementResult, NodeResult, Results},
+    error::MagnetiteError,
+    units::{label, Units},
+};
+use tracing::info;
+
+const MARGIN: f64 = 30.0;
+const TITLE_HEIGHT: f64 = 40.0;
+const PANEL_TITLE_HEIGHT: f64 = 28.0;
+const COLORBAR_WIDTH: f64 = 160.0;
+const COLORBAR_STEPS: usize = 128;
+const COLORBAR_TICKS: usize = 6;
+const UNDEFORMED_FILL: Rgb = Rgb(0x4c, 0x4c, 0x4c);
+const EDGE_COLOR: Rgb = Rgb(0, 0, 0);
+const EDGE_OPACITY: f64 = 0.35;
+const TEXT_COLOR: Rgb = Rgb(0x22, 0x22, 0x22);
+const AUTO_DEFORMATION_FRACTION: f64 = 0.1;
+const FRAME_DELAY_MS: u16 = 60;
+const FINAL_FRAME_DELAY_MS: u16 = 1000;
+/// Factors of safety above this are plotted as this, so a few lightly loaded
+/// elements don't wash out the colour scale
+const SAFETY_FACTOR_CAP: f64 = 10.0;
+
+/// An opaque RGB colour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    fn lerp(&self, other: &Rgb, t: f64) -> Rgb {
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+        Rgb(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+}
+
+/// Colormaps available to the renderer. Names follow matplotlib; append
+/// `_r` to any name for the reversed map.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colormap {
+    stops: &'static [(f64, Rgb)],
+    reversed: bool,
+}
+
+const COOLWARM: &[(f64, Rgb)] = &[
+    (0.0, Rgb(59, 76, 192)),
+    (0.25, Rgb(141, 176, 254)),
+    (0.5, Rgb(221, 220, 220)),
+    (0.75, Rgb(244, 154, 123)),
+    (1.0, Rgb(180, 4, 38)),
+];
+const VIRIDIS: &[(f64, Rgb)] = &[
+    (0.0, Rgb(68, 1, 84)),
+    (0.25, Rgb(59, 82, 139)),
+    (0.5, Rgb(33, 145, 140)),
+    (0.75, Rgb(94, 201, 98)),
+    (1.0, Rgb(253, 231, 37)),
+];
+const PLASMA: &[(f64, Rgb)] = &[
+    (0.0, Rgb(13, 8, 135)),
+    (0.25, Rgb(126, 3, 168)),
+    (0.5, Rgb(204, 71, 120)),
+    (0.75, Rgb(248, 149, 64)),
+    (1.0, Rgb(240, 249, 33)),
+];
+const INFERNO: &[(f64, Rgb)] = &[
+    (0.0, Rgb(0, 0, 4)),
+    (0.25, Rgb(87, 16, 110)),
+    (0.5, Rgb(188, 55, 84)),
+    (0.75, Rgb(249, 142, 9)),
+    (1.0, Rgb(252, 255, 164)),
+];
+const MAGMA: &[(f64, Rgb)] = &[
+    (0.0, Rgb(0, 0, 4)),
+    (0.25, Rgb(81, 18, 124)),
+    (0.5, Rgb(183, 55, 121)),
+    (0.75, Rgb(252, 137, 97)),
+    (1.0, Rgb(252, 253, 191)),
+];
+const JET: &[(f64, Rgb)] = &[
+    (0.0, Rgb(0, 0, 128)),
+    (0.125, Rgb(0, 0, 255)),
+    (0.375, Rgb(0, 255, 255)),
+    (0.625, Rgb(255, 255, 0)),
+    (0.875, Rgb(255, 0, 0)),
+    (1.0, Rgb(128, 0, 0)),
+];
+const GIST_HEAT: &[(f64, Rgb)] = &[
+    (0.0, Rgb(0, 0, 0)),
+    (0.5, Rgb(191, 0, 0)),
+    (2.0 / 3.0, Rgb(255, 85, 0)),
+    (0.75, Rgb(255, 128, 0)),
+    (1.0, Rgb(255, 255, 255)),
+];
+const GRAY: &[(f64, Rgb)] = &[(0.0, Rgb(0, 0, 0)), (1.0, Rgb(255, 255, 255))];
+
+/// Names of the built-in colormaps
+pub const COLORMAP_NAMES: &[&str] = &[
+    "coolwarm",
+    "viridis",
+    "plasma",
+    "inferno",
+    "magma",
+    "jet",
+    "gist_heat",
+    "gray",
+];
+
+impl Colormap {
+    /// Looks up a colormap by name
+    pub fn from_name(name: &str) -> Result<Colormap, MagnetiteError> {
+        let (base, reversed) = match name.strip_suffix("_r") {
+            Some(base) => (base, true),
+            None => (name, false),
+        };
+
+        let stops = match base {
+            "coolwarm" => COOLWARM,
+            "viridis" => VIRIDIS,
+            "plasma" => PLASMA,
+            "inferno" => INFERNO,
+            "magma" => MAGMA,
+            "jet" => JET,
+            "gist_heat" => GIST_HEAT,
+            "gray" | "grey" => GRAY,
+            _ => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Unknown colormap '{name}'. Available colormaps: {}",
+                    COLORMAP_NAMES.join(", ")
+                )))
+            }
+        };
+
+        Ok(Colormap { stops, reversed })
+    }
+
+    /// Samples the colormap at `t` in [0, 1]
+    pub fn sample(&self, t: f64) -> Rgb {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let t = if self.reversed { 1.0 - t } else { t };
+
+        for pair in self.stops.windows(2) {
+            let (t0, c0) = pair[0];
+            let (t1, c1) = pair[1];
+            if t <= t1 {
+                return c0.lerp(&c1, (t - t0) / (t1 - t0));
+            }
+        }
+        self.stops[self.stops.len() - 1].1
+    }
+}
+
+/// How displacements are magnified when plotting the deformed mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DeformationScale {
+    /// Pick a factor so the largest displacement is a fixed fraction of the
+    /// model size
+    Auto,
+    /// Multiply displacements by a user-set factor
+    Factor(f64),
+}
+
+impl DeformationScale {
+    /// Parses a deformation scale from a command line argument; either
+    /// `auto` or a positive number.
+    pub fn from_arg(arg: &str) -> Result<DeformationScale, MagnetiteError> {
+        if arg.eq_ignore_ascii_case("auto") {
+            return Ok(DeformationScale::Auto);
+        }
+
+        match arg.parse::<f64>() {
+            Ok(factor) if factor.is_finite() && factor > 0.0 => {
+                Ok(DeformationScale::Factor(factor))
+            }
+            _ => Err(MagnetiteError::Input(format!(
+                "Invalid deformation scale '{arg}'. Use 'auto' or a positive number"
+            ))),
+        }
+    }
+
+    /// Resolves the scale into a displacement multiplier for a solved model
+    ///
+    /// # Arguments
+    /// * `results` - The results of a solved model
+    ///
+    /// # Returns
+    /// The factor to multiply displacements by
+    pub fn resolve(&self, results: &Results) -> f64 {
+        let factor = match self {
+            DeformationScale::Factor(factor) => return *factor,
+            DeformationScale::Auto => {
+                let mut bounds = Bounds::empty();
+                let mut max_displacement: f64 = 0.0;
+                for node in &results.nodes {
+                    bounds.include(node.vertex.x, node.vertex.y);
+                    max_displacement = max_displacement.max(node.ux.hypot(node.uy));
+                }
+
+                let extent = bounds.width().max(bounds.height());
+                if max_displacement <= 0.0 || results.nodes.is_empty() {
+                    1.0
+                } else {
+                    round_scale(AUTO_DEFORMATION_FRACTION * extent / max_displacement)
+                }
+            }
+        };
+
+        info!("auto deformation scale is x{}", format_value(factor));
+
+        factor
+    }
+}
+
+/// Rounds a scale factor down to 1, 2 or 5 times a power of ten so plot
+/// titles stay readable
+fn round_scale(factor: f64) -> f64 {
+    let magnitude = 10f64.powf(factor.log10().floor());
+    let leading = factor / magnitude;
+    let step = if leading >= 5.0 {
+        5.0
+    } else if leading >= 2.0 {
+        2.0
+    } else {
+        1.0
+    };
+    step * magnitude
+}
+
+/// The element value that plots are coloured by
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PlotField {
+    /// The signed stress magnitude from the solver
+    #[default]
+    Stress,
+    VonMises,
+    Tresca,
+    /// Factor of safety against yield, capped at 10
+    SafetyFactor,
+    /// Equivalent plastic strain
+    PlasticStrain,
+}
+
+impl PlotField {
+    /// Parses a plot field from a command line argument
+    pub fn from_name(name: &str) -> Result<PlotField, MagnetiteError> {
+        match name {
+            "stress" => Ok(PlotField::Stress),
+            "von_mises" => Ok(PlotField::VonMises),
+            "tresca" => Ok(PlotField::Tresca),
+            "safety_factor" => Ok(PlotField::SafetyFactor),
+            "plastic_strain" => Ok(PlotField::PlasticStrain),
+            _ => Err(MagnetiteError::Input(format!(
+                "Unknown plot field '{name}'. Use stress, von_mises, tresca, safety_factor or plastic_strain"
+            ))),
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            PlotField::Stress => "Stress",
+            PlotField::VonMises => "Von Mises",
+            PlotField::Tresca => "Tresca",
+            PlotField::SafetyFactor => "FoS",
+            PlotField::PlasticStrain => "PEEQ",
+        }
+    }
+
+    /// The value of an element under the full load
+    fn value(&self, element: &ElementResult) -> f64 {
+        match self {
+            PlotField::Stress => element.stress,
+            PlotField::VonMises => element.von_mises,
+            PlotField::Tresca => element.tresca,
+            PlotField::SafetyFactor => element
+                .safety_factor
+                .unwrap_or(SAFETY_FACTOR_CAP)
+                .min(SAFETY_FACTOR_CAP),
+            PlotField::PlasticStrain => element.plastic_strain.unwrap_or(0.0),
+        }
+    }
+
+    /// The value of an element with a fraction of the load applied, given
+    /// its value under the full load. Stresses scale linearly with the load,
+    /// so factors of safety scale inversely.
+    fn at_fraction(&self, value: f64, fraction: f64) -> f64 {
+        match self {
+            PlotField::SafetyFactor => (value / fraction).min(SAFETY_FACTOR_CAP),
+            _ => value * fraction,
+        }
+    }
+
+    /// The value of an unloaded element
+    fn unloaded(&self) -> f64 {
+        match self {
+            PlotField::SafetyFactor => SAFETY_FACTOR_CAP,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Options that control how results are plotted
+#[derive(Debug, Clone)]
+pub struct PlotOptions {
+    pub colormap: Colormap,
+    pub field: PlotField,
+    pub displacement_scale: f64,
+    pub width: u32,
+    pub units: Option<Units>,
+}
+
+/// Horizontal alignment of text
+#[derive(Debug, Clone, Copy)]
+enum Anchor {
+    Start,
+    Middle,
+}
+
+/// A drawing primitive in pixel coordinates, with y pointing down
+#[derive(Debug)]
+enum Shape {
+    Polygon {
+        points: Vec<(f64, f64)>,
+        fill: Rgb,
+        stroke: bool,
+    },
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        fill: Rgb,
+    },
+    Text {
+        x: f64,
+        y: f64,
+        size: f64,
+        text: String,
+        anchor: Anchor,
+    },
+}
+
+/// A backend-independent description of a plot
+#[derive(Debug)]
+struct Scene {
+    width: u32,
+    height: u32,
+    shapes: Vec<Shape>,
+}
+
+/// Axis-aligned bounds of the plotted geometry
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    x_min: f64,
+    x_max: f64,
+    y_min: f64,
+    y_max: f64,
+}
+
+impl Bounds {
+    fn empty() -> Bounds {
+        Bounds {
+            x_min: f64::MAX,
+            x_max: f64::MIN,
+            y_min: f64::MAX,
+            y_max: f64::MIN,
+        }
+    }
+
+    fn include(&mut self, x: f64, y: f64) {
+        self.x_min = self.x_min.min(x);
+        self.x_max = self.x_max.max(x);
+        self.y_min = self.y_min.min(y);
+        self.y_max = self.y_max.max(y);
+    }
+
+    fn width(&self) -> f64 {
+        (self.x_max - self.x_min).max(f64::EPSILON)
+    }
+
+    fn height(&self) -> f64 {
+        (self.y_max - self.y_min).max(f64::EPSILON)
+    }
+}
+
+/// Maps model coordinates into a panel on the canvas
+struct PanelTransform {
+    bounds: Bounds,
+    left: f64,
+    top: f64,
+    scale: f64,
+}
+
+impl PanelTransform {
+    fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.left + (x - self.bounds.x_min) * self.scale,
+            self.top + (self.bounds.y_max - y) * self.scale,
+        )
+    }
+}
+
+/// Formats a value for axis and colour bar labels
+fn format_value(value: f64) -> String {
+    if value == 0.0 {
+        "0".to_string()
+    } else if value.abs() >= 1e4 || value.abs() < 1e-2 {
+        format!("{:.3e}", value)
+    } else {
+        let fixed = format!("{:.3}", value);
+        fixed
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+/// Returns the deformed position of a node
+fn deformed_position(node: &NodeResult, scale: f64) -> (f64, f64) {
+    (
+        node.vertex.x + node.ux * scale,
+        node.vertex.y + node.uy * scale,
+    )
+}
+
+/// Lays out the undeformed and deformed meshes, a colour bar and titles
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+/// * `options` - The plot options
+/// * `frame` - If set, lay out a single animation frame with the load
+///   applied up to this fraction, instead of the side-by-side
+///   comparison
+///
+/// # Returns
+/// A Scene ready to be written by one of the backends
+fn build_scene(results: &Results, options: &PlotOptions, frame: Option<f64>) -> Scene {
+    let (nodes, elements) = (&results.nodes, &results.elements);
+    let scale = options.displacement_scale;
+
+    // Share bounds between panels so they are directly comparable
+    let mut bounds = Bounds::empty();
+    for node in nodes {
+        bounds.include(node.vertex.x, node.vertex.y);
+        let (x, y) = deformed_position(node, scale);
+        bounds.include(x, y);
+    }
+    let pad = 0.03 * bounds.width().max(bounds.height());
+    bounds.x_min -= pad;
+    bounds.x_max += pad;
+    bounds.y_min -= pad;
+    bounds.y_max += pad;
+
+    // Stack panels vertically if the part is wider than 16:9
+    let stacked = bounds.width() / 16.0 > bounds.height() / 9.0;
+    let (cols, rows) = if frame.is_some() {
+        (1.0, 1.0)
+    } else if stacked {
+        (1.0, 2.0)
+    } else {
+        (2.0, 1.0)
+    };
+
+    let width = options.width.max(400) as f64;
+    let panel_width = (width - COLORBAR_WIDTH - MARGIN * (cols + 1.0)) / cols;
+    let model_scale = panel_width / bounds.width();
+    let panel_height = (bounds.height() * model_scale).clamp(50.0, 3.0 * panel_width);
+    let model_scale = model_scale.min(panel_height / bounds.height());
+    let height = TITLE_HEIGHT + rows * (PANEL_TITLE_HEIGHT + panel_height) + MARGIN * (rows + 1.0);
+
+    let panel_origin = |index: f64| -> (f64, f64) {
+        if stacked {
+            (
+                MARGIN,
+                TITLE_HEIGHT + MARGIN + index * (PANEL_TITLE_HEIGHT + panel_height + MARGIN),
+            )
+        } else {
+            (
+                MARGIN + index * (panel_width + MARGIN),
+                TITLE_HEIGHT + MARGIN,
+            )
+        }
+    };
+
+    let mut shapes: Vec<Shape> = vec![Shape::Text {
+        x: width / 2.0,
+        y: TITLE_HEIGHT * 0.75,
+        size: 22.0,
+        text: "Simulation Results".to_string(),
+        anchor: Anchor::Middle,
+    }];
+
+    // Range of the plotted field for colouring
+    let field = options.field;
+    let stresses: Vec<f64> = elements.iter().map(|e| field.value(e)).collect();
+    let mut stress_min = stresses.iter().copied().fold(f64::MAX, f64::min);
+    let mut stress_max = stresses.iter().copied().fold(f64::MIN, f64::max);
+    if stresses.is_empty() {
+        (stress_min, stress_max) = (0.0, 0.0);
+    }
+    if frame.is_some() {
+        // Frames ramp the load up from zero, so keep the unloaded value on
+        // the colour bar
+        stress_min = stress_min.min(field.unloaded());
+        stress_max = stress_max.max(field.unloaded());
+    }
+    let stress_range = if stress_max > stress_min {
+        stress_max - stress_min
+    } else {
+        1.0
+    };
+
+    let solved_title = if scale == 1.0 {
+        "Solved Model".to_string()
+    } else {
+        format!("Solved Model (deformation x{})", format_value(scale))
+    };
+
+    // Each panel is drawn either undeformed or deformed up to a fraction of
+    // the full load
+    let panels: Vec<(String, Option<f64>)> = match frame {
+        Some(fraction) => vec![(
+            format!("{solved_title} - {:.0}% load", fraction * 100.0),
+            Some(fraction),
+        )],
+        None => vec![
+            ("Initial Model".to_string(), None),
+            (solved_title, Some(1.0)),
+        ],
+    };
+
+    for (index, (title, deformed)) in panels.iter().enumerate() {
+        let (left, top) = panel_origin(index as f64);
+
+        shapes.push(Shape::Text {
+            x: left + panel_width / 2.0,
+            y: top + PANEL_TITLE_HEIGHT * 0.7,
+            size: 16.0,
+            text: title.clone(),
+            anchor: Anchor::Middle,
+        });
+
+        // Centre the model within the panel
+        let used_width = bounds.width() * model_scale;
+        let used_height = bounds.height() * model_scale;
+        let transform = PanelTransform {
+            bounds,
+            left: left + (panel_width - used_width) / 2.0,
+            top: top + PANEL_TITLE_HEIGHT + (panel_height - used_height) / 2.0,
+            scale: model_scale,
+        };
+
+        for (element, stress) in std::iter::zip(elements, &stresses) {
+            let points: Vec<(f64, f64)> = element
+                .nodes
+                .iter()
+                .map(|n| {
+                    let (x, y) = if let Some(fraction) = deformed {
+                        deformed_position(&nodes[*n], scale * fraction)
+                    } else {
+                        (nodes[*n].vertex.x, nodes[*n].vertex.y)
+                    };
+                    transform.apply(x, y)
+                })
+                .collect();
+
+            let fill = if let Some(fraction) = deformed {
+                options
+                    .colormap
+                    .sample((field.at_fraction(*stress, *fraction) - stress_min) / stress_range)
+            } else {
+                UNDEFORMED_FILL
+            };
+
+            shapes.push(Shape::Polygon {
+                points,
+                fill,
+                stroke: true,
+            });
+        }
+    }
+
+    // Colour bar, to the right of the panels, labelled in the input's units
+    let (stress_unit, unit_name) = match field {
+        PlotField::SafetyFactor | PlotField::PlasticStrain => (1.0, None),
+        _ => (
+            options.units.map_or(1.0, |u| u.stress.factor()),
+            options.units.map(|u| u.stress.name()),
+        ),
+    };
+    let bar_left = width - COLORBAR_WIDTH + 10.0;
+    let bar_top = TITLE_HEIGHT + MARGIN + PANEL_TITLE_HEIGHT;
+    let bar_height = height - bar_top - MARGIN;
+    let bar_width = 22.0;
+
+    shapes.push(Shape::Text {
+        x: bar_left,
+        y: bar_top - 16.0,
+        size: 14.0,
+        text: label(field.title(), unit_name),
+        anchor: Anchor::Start,
+    });
+
+    let step_height = bar_height / COLORBAR_STEPS as f64;
+    for step in 0..COLORBAR_STEPS {
+        let t = 1.0 - (step as f64 + 0.5) / COLORBAR_STEPS as f64;
+        shapes.push(Shape::Rect {
+            x: bar_left,
+            y: bar_top + step as f64 * step_height,
+            width: bar_width,
+            // overlap steps slightly to avoid seams
+            height: step_height + 0.5,
+            fill: options.colormap.sample(t),
+        });
+    }
+
+    for tick in 0..COLORBAR_TICKS {
+        let t = tick as f64 / (COLORBAR_TICKS - 1) as f64;
+        let y = bar_top + (1.0 - t) * bar_height;
+        shapes.push(Shape::Rect {
+            x: bar_left + bar_width,
+            y: y - 0.5,
+            width: 5.0,
+            height: 1.0,
+            fill: TEXT_COLOR,
+        });
+        shapes.push(Shape::Text {
+            x: bar_left + bar_width + 8.0,
+            y: y + 4.0,
+            size: 12.0,
+            text: format_value((stress_min + t * (stress_max - stress_min)) / stress_unit),
+            anchor: Anchor::Start,
+        });
+    }
+
+    Scene {
+        width: width.round() as u32,
+        height: height.round() as u32,
+        shapes,
+    }
+}
+
+/// Escapes text for inclusion in SVG
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Serializes a scene into an SVG document
+fn scene_to_svg(scene: &Scene) -> String {
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n",
+        w = scene.width,
+        h = scene.height
+    );
+
+    for shape in &scene.shapes {
+        match shape {
+            Shape::Polygon {
+                points,
+                fill,
+                stroke,
+            } => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|(x, y)| format!("{:.2},{:.2}", x, y))
+                    .collect();
+                let stroke = if *stroke {
+                    format!(
+                        " stroke=\"{}\" stroke-opacity=\"{}\" stroke-width=\"0.5\" stroke-linejoin=\"round\"",
+                        EDGE_COLOR.hex(),
+                        EDGE_OPACITY
+                    )
+                } else {
+                    String::new()
+                };
+                svg.push_str(&format!(
+                    "<polygon points=\"{}\" fill=\"{}\"{}/>\n",
+                    points.join(" "),
+                    fill.hex(),
+                    stroke
+                ));
+            }
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                fill,
+            } => {
+                svg.push_str(&format!(
+                    "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"{}\"/>\n",
+                    x,
+                    y,
+                    width,
+                    height,
+                    fill.hex()
+                ));
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                text,
+                anchor,
+            } => {
+                let anchor = match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                };
+                svg.push_str(&format!(
+                    "<text x=\"{:.2}\" y=\"{:.2}\" font-family=\"sans-serif\" font-size=\"{}\" fill=\"{}\" text-anchor=\"{}\">{}</text>\n",
+                    x,
+                    y,
+                    size,
+                    TEXT_COLOR.hex(),
+                    anchor,
+                    escape_xml(text)
+                ));
+            }
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// 5x7 bitmap glyphs for the PNG backend. Each row is five bits, with the
+/// most significant bit on the left.
+fn glyph(c: char) -> [u8; 7] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '[' => [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E],
+        ']' => [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        '%' => [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+        _ => [0x00; 7],
+    }
+}
+
+/// A simple RGB raster used by the PNG backend
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width: width as usize,
+            height: height as usize,
+            pixels: vec![0xff; width as usize * height as usize * 3],
+        }
+    }
+
+    /// Blends a colour into a pixel with the given opacity
+    fn blend(&mut self, x: i64, y: i64, color: Rgb, alpha: f64) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let idx = (y as usize * self.width + x as usize) * 3;
+        for (offset, channel) in [color.0, color.1, color.2].iter().enumerate() {
+            let current = self.pixels[idx + offset] as f64;
+            self.pixels[idx + offset] =
+                (current + (*channel as f64 - current) * alpha).round() as u8;
+        }
+    }
+
+    /// Fills a polygon using even-odd scanline filling, sampling at pixel
+    /// centres
+    fn fill_polygon(&mut self, points: &[(f64, f64)], color: Rgb) {
+        if points.len() < 3 {
+            return;
+        }
+        let y_min = points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+        let y_max = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+        let row_start = (y_min - 0.5).ceil().max(0.0) as i64;
+        let row_end = (y_max - 0.5).floor().min(self.height as f64 - 1.0) as i64;
+
+        let mut crossings: Vec<f64> = Vec::with_capacity(points.len());
+        for row in row_start..=row_end {
+            let y = row as f64 + 0.5;
+            crossings.clear();
+            for i in 0..points.len() {
+                let (x0, y0) = points[i];
+                let (x1, y1) = points[(i + 1) % points.len()];
+                if (y0 <= y && y < y1) || (y1 <= y && y < y0) {
+                    crossings.push(x0 + (y - y0) / (y1 - y0) * (x1 - x0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            for span in crossings.chunks_exact(2) {
+                let col_start = (span[0] - 0.5).ceil().max(0.0) as i64;
+                let col_end = (span[1] - 0.5).floor().min(self.width as f64 - 1.0) as i64;
+                for col in col_start..=col_end {
+                    self.blend(col, row, color, 1.0);
+                }
+            }
+        }
+    }
+
+    /// Draws a one-pixel line with the given opacity
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), color: Rgb, alpha: f64) {
+        let steps = (to.0 - from.0)
+            .abs()
+            .max((to.1 - from.1).abs())
+            .ceil()
+            .max(1.0);
+        for step in 0..=steps as i64 {
+            let t = step as f64 / steps;
+            let x = from.0 + (to.0 - from.0) * t;
+            let y = from.1 + (to.1 - from.1) * t;
+            self.blend(x.floor() as i64, y.floor() as i64, color, alpha);
+        }
+    }
+
+    /// Draws text using the built-in bitmap font
+    fn text(&mut self, x: f64, y: f64, size: f64, text: &str, anchor: Anchor, color: Rgb) {
+        let pixel = (size / 7.0).round().max(1.0);
+        let advance = 6.0 * pixel;
+        let text_width = advance * text.chars().count() as f64 - pixel;
+        let left = match anchor {
+            Anchor::Start => x,
+            Anchor::Middle => x - text_width / 2.0,
+        };
+        // `y` is the baseline, as in SVG
+        let top = y - 7.0 * pixel;
+
+        for (i, c) in text.chars().enumerate() {
+            let rows = glyph(c);
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..5 {
+                    if bits & (0x10 >> col) == 0 {
+                        continue;
+                    }
+                    let px = left + i as f64 * advance + col as f64 * pixel;
+                    let py = top + row as f64 * pixel;
+                    for dy in 0..pixel as i64 {
+                        for dx in 0..pixel as i64 {
+                            self.blend(px as i64 + dx, py as i64 + dy, color, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Rasterizes a scene
+fn scene_to_canvas(scene: &Scene) -> Canvas {
+    let mut canvas = Canvas::new(scene.width, scene.height);
+
+    for shape in &scene.shapes {
+        match shape {
+            Shape::Polygon {
+                points,
+                fill,
+                stroke,
+            } => {
+                canvas.fill_polygon(points, *fill);
+                if *stroke {
+                    for i in 0..points.len() {
+                        canvas.line(
+                            points[i],
+                            points[(i + 1) % points.len()],
+                            EDGE_COLOR,
+                            EDGE_OPACITY,
+                        );
+                    }
+                }
+            }
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                fill,
+            } => {
+                let points = [
+                    (*x, *y),
+                    (x + width, *y),
+                    (x + width, y + height),
+                    (*x, y + height),
+                ];
+                canvas.fill_polygon(&points, *fill);
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                text,
+                anchor,
+            } => canvas.text(*x, *y, *size, text, *anchor, TEXT_COLOR),
+        }
+    }
+
+    canvas
+}
+
+/// Encodes a canvas as a PNG file
+fn write_png(canvas: &Canvas, output: &str) -> Result<(), MagnetiteError> {
+    let file = match std::fs::File::create(output) {
+        Ok(f) => f,
+        Err(err) => {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "Failed to create {output}: {err}"
+            )))
+        }
+    };
+
+    let mut encoder = png::Encoder::new(
+        std::io::BufWriter::new(file),
+        canvas.width as u32,
+        canvas.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&canvas.pixels))
+        .map_err(|err| MagnetiteError::PostProcessor(format!("Failed to write {output}: {err}")))
+}
+
+/// Renders the undeformed and deformed, stress-coloured mesh to an image.
+/// The image format is chosen by the extension of `output`: either `.svg`
+/// or `.png`.
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+/// * `output` - The filepath of the output image
+/// * `options` - The plot options
+pub fn render(
+    results: &Results,
+    output: &str,
+    options: &PlotOptions,
+) -> Result<(), MagnetiteError> {
+    check_field(results, options)?;
+    let scene = build_scene(results, options, None);
+
+    if output.ends_with(".svg") {
+        let mut file = match std::fs::File::create(output) {
+            Ok(f) => f,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Failed to create {output}: {err}"
+                )))
+            }
+        };
+        if let Err(err) = file.write_all(scene_to_svg(&scene).as_bytes()) {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "Failed to write {output}: {err}"
+            )));
+        }
+    } else if output.ends_with(".png") {
+        write_png(&scene_to_canvas(&scene), output)?;
+    } else {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Unrecognized plot filetype {output}. Use .svg or .png"
+        )));
+    }
+
+    info!("wrote plot to {output}");
+
+    Ok(())
+}
+
+/// Checks that the plotted field is available in the results
+fn check_field(results: &Results, options: &PlotOptions) -> Result<(), MagnetiteError> {
+    if options.field == PlotField::SafetyFactor
+        && results.elements.iter().all(|e| e.safety_factor.is_none())
+    {
+        return Err(MagnetiteError::PostProcessor(
+            "Cannot plot safety_factor without a material yield strength".to_string(),
+        ));
+    }
+    if options.field == PlotField::PlasticStrain
+        && results.elements.iter().all(|e| e.plastic_strain.is_none())
+    {
+        return Err(MagnetiteError::PostProcessor(
+            "Cannot plot plastic_strain without a plasticity section".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Appends a zero-padded frame number to a filepath, before its extension
+fn frame_path(output: &str, index: usize) -> String {
+    match output.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}_{index:03}.{extension}"),
+        None => format!("{output}_{index:03}"),
+    }
+}
+
+/// Encodes a sequence of canvases as a looping animated GIF. Each canvas is
+/// encoded as it is drawn, so only one frame is held in memory at a time.
+fn write_gif(canvases: impl Iterator<Item = Canvas>, output: &str) -> Result<(), MagnetiteError> {
+    let mut canvases = canvases.peekable();
+    let Some(first) = canvases.peek() else {
+        return Ok(());
+    };
+    if first.width > u16::MAX as usize || first.height > u16::MAX as usize {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Animation frames of {}x{} are too large for a GIF",
+            first.width, first.height
+        )));
+    }
+
+    let file = match std::fs::File::create(output) {
+        Ok(f) => f,
+        Err(err) => {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "Failed to create {output}: {err}"
+            )))
+        }
+    };
+
+    let write_err = |err: gif::EncodingError| {
+        MagnetiteError::PostProcessor(format!("Failed to write {output}: {err}"))
+    };
+
+    let mut encoder = gif::Encoder::new(
+        std::io::BufWriter::new(file),
+        first.width as u16,
+        first.height as u16,
+        &[],
+    )
+    .map_err(write_err)?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(write_err)?;
+
+    while let Some(canvas) = canvases.next() {
+        let mut frame = gif::Frame::from_rgb_speed(
+            canvas.width as u16,
+            canvas.height as u16,
+            &canvas.pixels,
+            10,
+        );
+        // GIF delays are in hundredths of a second
+        frame.delay = match canvases.peek() {
+            Some(_) => FRAME_DELAY_MS / 10,
+            None => FINAL_FRAME_DELAY_MS / 10,
+        };
+        encoder.write_frame(&frame).map_err(write_err)?;
+    }
+
+    Ok(())
+}
+
+/// Renders an animation of the model deforming from its initial shape to
+/// its solved shape. Writes an animated GIF if `output` ends in `.gif`;
+/// otherwise writes one numbered `.png` or `.svg` file per frame, e.g.
+/// `deform_000.png`, `deform_001.png`, ...
+///
+/// # Arguments
+/// * `results` - The results of a solved model
+/// * `output` - The filepath of the animation, or the frame filepath pattern
+/// * `options` - The plot options
+/// * `frames` - The number of frames, including the initial and final shape
+pub fn animate(
+    results: &Results,
+    output: &str,
+    options: &PlotOptions,
+    frames: usize,
+) -> Result<(), MagnetiteError> {
+    if frames < 2 {
+        return Err(MagnetiteError::Input(format!(
+            "Animations need at least 2 frames; got {frames}"
+        )));
+    }
+    check_field(results, options)?;
+
+    let scenes = (0..frames).map(|index| {
+        let fraction = index as f64 / (frames - 1) as f64;
+        build_scene(results, options, Some(fraction))
+    });
+
+    if output.ends_with(".gif") {
+        write_gif(scenes.map(|scene| scene_to_canvas(&scene)), output)?;
+        info!("wrote {frames} frame animation to {output}");
+    } else if output.ends_with(".png") || output.ends_with(".svg") {
+        for (index, scene) in scenes.enumerate() {
+            let path = frame_path(output, index);
+            if output.ends_with(".png") {
+                write_png(&scene_to_canvas(&scene), &path)?;
+            } else if let Err(err) = std::fs::write(&path, scene_to_svg(&scene)) {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Failed to write {path}: {err}"
+                )));
+            }
+        }
+        info!(
+            "wrote {frames} animation frames to {} ... {}",
+            frame_path(output, 0),
+            frame_path(output, frames - 1)
+        );
+    } else {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Unrecognized animation filetype {output}. Use .gif, .png or .svg"
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle 10 long and 5 high, with node 1 displaced by `(ux, uy)`
+    fn results(ux: f64, uy: f64) -> Results {
+        let node = |x, y, ux, uy| NodeResult {
+            vertex: crate::datatypes::Vertex { x, y },
+            ux,
+            uy,
+            fx: 0.0,
+            fy: 0.0,
+        };
+        Results {
+            nodes: vec![
+                node(0.0, 0.0, 0.0, 0.0),
+                node(10.0, 0.0, ux, uy),
+                node(0.0, 5.0, 0.0, 0.0),
+            ],
+            elements: vec![ElementResult {
+                nodes: [0, 1, 2],
+                stress: 1.0,
+                stress_tensor: [1.0, 0.0, 0.0],
+                von_mises: 1.0,
+                tresca: 1.0,
+                safety_factor: None,
+                plastic_strain: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn looks_up_colormaps_by_name() {
+        for name in COLORMAP_NAMES {
+            assert!(Colormap::from_name(name).is_ok(), "{name}");
+            assert!(
+                Colormap::from_name(&format!("{name}_r")).is_ok(),
+                "{name}_r"
+            );
+        }
+        assert_eq!(
+            Colormap::from_name("grey").unwrap(),
+            Colormap::from_name("gray").unwrap()
+        );
+
+        match Colormap::from_name("rainbow") {
+            Err(MagnetiteError::PostProcessor(message)) => {
+                assert!(
+                    message.starts_with("Unknown colormap 'rainbow'"),
+                    "{message}"
+                )
+            }
+            other => panic!("expected a post processor error, got {other:?}"),
+        }
+        assert!(Colormap::from_name("_r").is_err());
+    }
+
+    #[test]
+    fn samples_colormap_endpoints() {
+        let coolwarm = Colormap::from_name("coolwarm").unwrap();
+        assert_eq!(coolwarm.sample(0.0), Rgb(59, 76, 192));
+        assert_eq!(coolwarm.sample(1.0), Rgb(180, 4, 38));
+        assert_eq!(coolwarm.sample(0.5), Rgb(221, 220, 220));
+        // Out of range and NaN values are clamped
+        assert_eq!(coolwarm.sample(-1.0), Rgb(59, 76, 192));
+        assert_eq!(coolwarm.sample(2.0), Rgb(180, 4, 38));
+        assert_eq!(coolwarm.sample(f64::NAN), Rgb(59, 76, 192));
+
+        let reversed = Colormap::from_name("gray_r").unwrap();
+        assert_eq!(reversed.sample(0.0), Rgb(255, 255, 255));
+        assert_eq!(reversed.sample(1.0), Rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn picks_an_automatic_deformation_scale() {
+        // The largest displacement is 0.02, so 10% of the 10 long model
+        // needs a scale of 50
+        assert_eq!(DeformationScale::Auto.resolve(&results(0.012, 0.016)), 50.0);
+        // 33.3 rounds down to 20
+        assert_eq!(DeformationScale::Auto.resolve(&results(0.03, 0.0)), 20.0);
+        assert_eq!(DeformationScale::Auto.resolve(&results(0.0, 0.0)), 1.0);
+        assert_eq!(
+            DeformationScale::Factor(3.0).resolve(&results(0.03, 0.0)),
+            3.0
+        );
+
+        assert_eq!(
+            DeformationScale::from_arg("AUTO").unwrap(),
+            DeformationScale::Auto
+        );
+        assert!(DeformationScale::from_arg("-2").is_err());
+    }
+
+    #[test]
+    fn animates_the_requested_number_of_frames() {
+        let options = PlotOptions {
+            colormap: Colormap::from_name("coolwarm").unwrap(),
+            field: PlotField::default(),
+            displacement_scale: 10.0,
+            width: 200,
+            units: None,
+        };
+        let mut scratch = crate::mesher::ScratchDir::new(false).unwrap();
+        let output = scratch.file("deformation.gif");
+        animate(&results(0.1, 0.0), &output, &options, 4).unwrap();
+
+        let file = std::fs::File::open(&output).unwrap();
+        let mut decoder = gif::DecodeOptions::new().read_info(file).unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        scratch.succeed();
+
+        let (frame, last) = (FRAME_DELAY_MS / 10, FINAL_FRAME_DELAY_MS / 10);
+        assert_eq!(delays, [frame, frame, frame, last]);
+    }
+}
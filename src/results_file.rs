@@ -0,0 +1,750 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    datatypes::{
+        BodyMaterial, Element, ElementResult, Material, Mesh, MeshOptions, ModelMetadata, Node,
+        NodeResult, PhysicalGroup, Results, SolverStats, Vertex,
+    },
+    error::MagnetiteError,
+    model::Model,
+    solver,
+    units::Units,
+};
+use tracing::info;
+
+/// Identifies a Magnetite results file
+pub const FORMAT: &str = "magnetite-results";
+
+/// The newest version of the results file this build can read
+pub const VERSION: u32 = 1;
+
+/// The material of the part. Bodies given their own material are made of
+/// that instead; see [`BodyMaterialRecord`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MaterialRecord {
+    pub youngs_modulus: f64,
+    pub poisson_ratio: f64,
+    pub density: Option<f64>,
+    pub thermal_expansion: Option<f64>,
+    pub yield_strength: Option<f64>,
+    pub ultimate_strength: Option<f64>,
+    pub part_thickness: f64,
+}
+
+/// The material of a named body, which replaces the part's material in the
+/// elements tagged with the body's surface physical group
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BodyMaterialRecord {
+    pub body: String,
+    pub youngs_modulus: f64,
+    pub poisson_ratio: f64,
+    pub density: Option<f64>,
+    pub thermal_expansion: Option<f64>,
+    pub yield_strength: Option<f64>,
+    pub ultimate_strength: Option<f64>,
+}
+
+/// A node, its displacement and the force on it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeRecord {
+    pub x: f64,
+    pub y: f64,
+    pub ux: f64,
+    pub uy: f64,
+    /// The reaction where the node is fixed in x, otherwise the applied load
+    pub fx: f64,
+    /// The reaction where the node is fixed in y, otherwise the applied load
+    pub fy: f64,
+    /// Whether the displacement is prescribed in x and in y
+    pub fixed: [bool; 2],
+}
+
+/// An element and its stress and strain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElementRecord {
+    pub nodes: [usize; 3],
+    /// `[sxx, syy, txy]`
+    pub stress_tensor: [f64; 3],
+    /// `[exx, eyy, gxy]`, the small strain found from the displacements
+    pub strain: [f64; 3],
+    pub stress: f64,
+    pub von_mises: f64,
+    pub tresca: f64,
+    /// `"inf"` in an unstressed element; json has no infinity
+    #[serde(with = "safety_factor")]
+    pub safety_factor: Option<f64>,
+    pub plastic_strain: Option<f64>,
+    /// Tags of the surface physical groups the element belongs to
+    #[serde(default)]
+    pub physical_tags: Vec<usize>,
+}
+
+/// Writes an infinite factor of safety as `"inf"`, since serde_json would
+/// write it as `null` and it would read back as no factor of safety at all
+mod safety_factor {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(untagged)]
+    enum Factor {
+        Finite(f64),
+        Infinite(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &Option<f64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(f) if f.is_infinite() => Factor::Infinite("inf".to_owned()).serialize(serializer),
+            Some(f) => Factor::Finite(*f).serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<f64>, D::Error> {
+        match Option::<Factor>::deserialize(deserializer)? {
+            Some(Factor::Finite(f)) => Ok(Some(f)),
+            Some(Factor::Infinite(s)) if s == "inf" => Ok(Some(f64::INFINITY)),
+            Some(Factor::Infinite(s)) => Err(serde::de::Error::custom(format!(
+                "expected a number or \"inf\", found \"{s}\""
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A named group of nodes from the mesh
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupRecord {
+    pub name: String,
+    pub dimension: usize,
+    pub tag: usize,
+    pub nodes: Vec<usize>,
+}
+
+/// A boundary rule's targets, and the nodes it governs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleRecord {
+    pub name: String,
+    pub ux: Option<f64>,
+    pub uy: Option<f64>,
+    pub fx: Option<f64>,
+    pub fy: Option<f64>,
+    pub nodes: Vec<usize>,
+}
+
+/// The results of a static solve, with everything needed to post-process
+/// them again: the mesh, material, boundary rules, solver statistics and
+/// the input file. Values are in SI units; `units` records the units of the
+/// input file, for labelling outputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResultsFile {
+    /// Always `magnetite-results`
+    pub format: String,
+    pub version: u32,
+    /// The load case or combination the results are of, if any
+    pub case: Option<String>,
+    pub units: Option<Units>,
+    pub material: MaterialRecord,
+    #[serde(default)]
+    pub body_materials: Vec<BodyMaterialRecord>,
+    pub nodes: Vec<NodeRecord>,
+    pub elements: Vec<ElementRecord>,
+    pub physical_groups: Vec<GroupRecord>,
+    pub boundary_rules: Vec<RuleRecord>,
+    pub solver: SolverStats,
+    /// The input file, as it was read
+    pub input: serde_json::Value,
+}
+
+impl ResultsFile {
+    /// Collects the results of a solved model
+    ///
+    /// # Arguments
+    /// * `model` - The solved model
+    /// * `results` - The results to save; the model's results, or those of
+    ///   one of its load cases
+    /// * `case` - The load case or combination name of the results, if any
+    /// * `input` - The input file the model was loaded from
+    ///
+    /// # Returns
+    /// A PostProcessor error if the model has not been solved
+    pub fn new(
+        model: &Model,
+        results: &Results,
+        case: Option<&str>,
+        input: serde_json::Value,
+    ) -> Result<ResultsFile, MagnetiteError> {
+        let (Some(mesh), Some(stats)) = (model.current_mesh(), model.solver_stats()) else {
+            return Err(MagnetiteError::PostProcessor(
+                "Model has not been solved".to_owned(),
+            ));
+        };
+
+        let mut fixed = vec![[false; 2]; results.nodes.len()];
+        for rule in model.boundary_nodes() {
+            for node in &rule.nodes {
+                fixed[*node][0] |= rule.target.ux.is_some();
+                fixed[*node][1] |= rule.target.uy.is_some();
+            }
+        }
+
+        let material = &model.metadata.material;
+        Ok(ResultsFile {
+            format: FORMAT.to_string(),
+            version: VERSION,
+            case: case.map(str::to_string),
+            units: model.metadata.units,
+            material: MaterialRecord {
+                youngs_modulus: material.youngs_modulus,
+                poisson_ratio: material.poisson_ratio,
+                density: material.density,
+                thermal_expansion: material.thermal_expansion,
+                yield_strength: material.yield_strength,
+                ultimate_strength: material.ultimate_strength,
+                part_thickness: model.metadata.part_thickness,
+            },
+            body_materials: model
+                .metadata
+                .body_materials
+                .iter()
+                .map(|body_material| {
+                    let material = &body_material.material;
+                    BodyMaterialRecord {
+                        body: body_material.body.clone(),
+                        youngs_modulus: material.youngs_modulus,
+                        poisson_ratio: material.poisson_ratio,
+                        density: material.density,
+                        thermal_expansion: material.thermal_expansion,
+                        yield_strength: material.yield_strength,
+                        ultimate_strength: material.ultimate_strength,
+                    }
+                })
+                .collect(),
+            nodes: std::iter::zip(&results.nodes, fixed)
+                .map(|(node, fixed)| NodeRecord {
+                    x: node.vertex.x,
+                    y: node.vertex.y,
+                    ux: node.ux,
+                    uy: node.uy,
+                    fx: node.fx,
+                    fy: node.fy,
+                    fixed,
+                })
+                .collect(),
+            elements: std::iter::zip(&results.elements, &mesh.elements)
+                .map(|(element, mesh_element)| ElementRecord {
+                    nodes: element.nodes,
+                    stress_tensor: element.stress_tensor,
+                    strain: strain(element, &results.nodes),
+                    stress: element.stress,
+                    von_mises: element.von_mises,
+                    tresca: element.tresca,
+                    safety_factor: element.safety_factor,
+                    plastic_strain: element.plastic_strain,
+                    physical_tags: mesh_element.physical_tags.clone(),
+                })
+                .collect(),
+            physical_groups: mesh
+                .physical_groups
+                .iter()
+                .map(|group| GroupRecord {
+                    name: group.name.clone(),
+                    dimension: group.dimension,
+                    tag: group.tag,
+                    nodes: group.nodes.clone(),
+                })
+                .collect(),
+            boundary_rules: model
+                .boundary_nodes()
+                .iter()
+                .map(|rule| RuleRecord {
+                    name: rule.name.clone(),
+                    ux: rule.target.ux,
+                    uy: rule.target.uy,
+                    fx: rule.target.fx,
+                    fy: rule.target.fy,
+                    nodes: rule.nodes.clone(),
+                })
+                .collect(),
+            solver: stats.clone(),
+            input,
+        })
+    }
+
+    /// Writes the results file as JSON
+    ///
+    /// # Arguments
+    /// * `output` - The filepath of the results file
+    pub fn write(&self, output: &str) -> Result<(), MagnetiteError> {
+        let file = match std::fs::File::create(output) {
+            Ok(f) => f,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Failed to create {output}: {err}"
+                )))
+            }
+        };
+        let mut writer = std::io::BufWriter::new(file);
+        if let Err(err) = serde_json::to_writer(&mut writer, self)
+            .map_err(std::io::Error::from)
+            .and_then(|_| writer.flush())
+        {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "Failed to write {output}: {err}"
+            )));
+        }
+
+        info!("wrote results to {output}");
+        Ok(())
+    }
+
+    /// Reads a results file
+    ///
+    /// # Arguments
+    /// * `path` - The path to the results file
+    ///
+    /// # Returns
+    /// A PostProcessor error if the file is not a results file, is newer
+    /// than this build can read, or refers to nodes it doesn't have
+    pub fn read(path: &str) -> Result<ResultsFile, MagnetiteError> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "Unable to open results file {path}: {err}"
+                )))
+            }
+        };
+
+        // Check the header first, so other files get a clear error
+        let header: serde_json::Value = match serde_json::from_str(&contents) {
+            Ok(v) => v,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "{path} is not valid JSON: {err}"
+                )))
+            }
+        };
+        if header.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "{path} is not a Magnetite results file"
+            )));
+        }
+        match header.get("version").and_then(|v| v.as_u64()) {
+            Some(version) if version <= VERSION as u64 => (),
+            version => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "{path} has results file version {}; this build reads up to version {VERSION}",
+                    version.map_or("unknown".to_string(), |v| v.to_string())
+                )))
+            }
+        }
+
+        let results: ResultsFile = match serde_path_to_error::deserialize(header) {
+            Ok(r) => r,
+            Err(err) => {
+                return Err(MagnetiteError::PostProcessor(format!(
+                    "{path}: {} at {}",
+                    err.inner(),
+                    err.path()
+                )))
+            }
+        };
+
+        let referenced = results
+            .elements
+            .iter()
+            .flat_map(|e| e.nodes)
+            .chain(results.physical_groups.iter().flat_map(|g| g.nodes.clone()))
+            .chain(results.boundary_rules.iter().flat_map(|r| r.nodes.clone()));
+        if let Some(node) = referenced.filter(|n| *n >= results.nodes.len()).max() {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "{path} refers to node {node}, but has {} nodes",
+                results.nodes.len()
+            )));
+        }
+
+        Ok(results)
+    }
+
+    /// Returns the results, for post-processing
+    pub fn results(&self) -> Results {
+        Results {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| NodeResult {
+                    vertex: Vertex {
+                        x: node.x,
+                        y: node.y,
+                    },
+                    ux: node.ux,
+                    uy: node.uy,
+                    fx: node.fx,
+                    fy: node.fy,
+                })
+                .collect(),
+            elements: self
+                .elements
+                .iter()
+                .map(|element| ElementResult {
+                    nodes: element.nodes,
+                    stress: element.stress,
+                    stress_tensor: element.stress_tensor,
+                    von_mises: element.von_mises,
+                    tresca: element.tresca,
+                    safety_factor: element.safety_factor,
+                    plastic_strain: element.plastic_strain,
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the undeformed mesh, which can be solved again as a
+    /// [`crate::Geometry::Mesh`]
+    pub fn mesh(&self) -> Mesh {
+        Mesh {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|node| Node {
+                    vertex: Vertex {
+                        x: node.x,
+                        y: node.y,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                })
+                .collect(),
+            elements: self
+                .elements
+                .iter()
+                .map(|e| Element {
+                    physical_tags: e.physical_tags.clone(),
+                    ..element(e.nodes)
+                })
+                .collect(),
+            physical_groups: self
+                .physical_groups
+                .iter()
+                .map(|group| PhysicalGroup {
+                    name: group.name.clone(),
+                    dimension: group.dimension,
+                    tag: group.tag,
+                    nodes: group.nodes.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the material of the part
+    pub fn material(&self) -> Material {
+        Material {
+            youngs_modulus: self.material.youngs_modulus,
+            poisson_ratio: self.material.poisson_ratio,
+            density: self.material.density,
+            thermal_expansion: self.material.thermal_expansion,
+            yield_strength: self.material.yield_strength,
+            ultimate_strength: self.material.ultimate_strength,
+        }
+    }
+
+    /// Returns the material of each element: the part's material, or that
+    /// of the body the element belongs to
+    ///
+    /// # Returns
+    /// An Input error if a body material names a body the mesh does not have
+    pub fn element_materials(&self) -> Result<Vec<Material>, MagnetiteError> {
+        let metadata = ModelMetadata {
+            material: self.material(),
+            body_materials: self
+                .body_materials
+                .iter()
+                .map(|record| BodyMaterial {
+                    body: record.body.clone(),
+                    material: Material {
+                        youngs_modulus: record.youngs_modulus,
+                        poisson_ratio: record.poisson_ratio,
+                        density: record.density,
+                        thermal_expansion: record.thermal_expansion,
+                        yield_strength: record.yield_strength,
+                        ultimate_strength: record.ultimate_strength,
+                    },
+                })
+                .collect(),
+            part_thickness: self.material.part_thickness,
+            mesh_options: MeshOptions::default(),
+            units: self.units,
+            safety: Default::default(),
+        };
+        solver::element_materials(&self.mesh(), &metadata)
+    }
+}
+
+/// An unsolved element
+fn element(nodes: [usize; 3]) -> Element {
+    Element {
+        nodes,
+        stress: None,
+        stress_tensor: None,
+        plastic_strain: None,
+        physical_tags: Vec::new(),
+    }
+}
+
+/// Computes the small strain `[exx, eyy, gxy]` of an element from the
+/// displacements of its nodes
+fn strain(result: &ElementResult, nodes: &[NodeResult]) -> [f64; 3] {
+    let corners: Vec<Node> = result
+        .nodes
+        .iter()
+        .map(|n| Node {
+            vertex: nodes[*n].vertex.clone(),
+            ux: None,
+            uy: None,
+            fx: None,
+            fy: None,
+        })
+        .collect();
+    let local = element([0, 1, 2]);
+    let area = solver::compute_element_area(&local, &corners);
+    let b = solver::compute_strain_displacement_matrix(&local, &corners, area);
+    let displacements = nalgebra::SVector::<f64, 6>::from_iterator(
+        result
+            .nodes
+            .iter()
+            .flat_map(|n| [nodes[*n].ux, nodes[*n].uy]),
+    );
+    let strain = b * displacements;
+    [strain[0], strain[1], strain[2]]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datatypes::{BoundaryRegion, BoundaryRule, BoundaryTarget, Geometry},
+        mesher::ScratchDir,
+    };
+
+    /// A unit square of two triangles, fixed on its left edge and pulled on
+    /// its right. The lower triangle is the body `insert`.
+    fn pulled_square(body_materials: Vec<BodyMaterial>) -> Model {
+        let node = |x: f64, y: f64| Node {
+            vertex: Vertex { x, y },
+            ux: None,
+            uy: None,
+            fx: Some(0.0),
+            fy: Some(0.0),
+        };
+        let mesh = Mesh {
+            nodes: vec![
+                node(0.0, 0.0),
+                node(1.0, 0.0),
+                node(1.0, 1.0),
+                node(0.0, 1.0),
+            ],
+            elements: vec![
+                Element {
+                    physical_tags: vec![2],
+                    ..element([0, 1, 2])
+                },
+                element([0, 2, 3]),
+            ],
+            physical_groups: vec![
+                PhysicalGroup {
+                    name: "right".to_string(),
+                    dimension: 1,
+                    tag: 1,
+                    nodes: vec![1, 2],
+                },
+                PhysicalGroup {
+                    name: "insert".to_string(),
+                    dimension: 2,
+                    tag: 2,
+                    nodes: vec![0, 1, 2],
+                },
+            ],
+        };
+        let rule = |name: &str, x_min: f64, x_max: f64, target: BoundaryTarget| BoundaryRule {
+            name: name.to_string(),
+            region: BoundaryRegion {
+                x_min,
+                x_max,
+                y_min: f64::MIN,
+                y_max: f64::MAX,
+                physical_group: None,
+            },
+            target,
+        };
+        let fixed = BoundaryTarget {
+            ux: Some(0.0),
+            uy: Some(0.0),
+            fx: None,
+            fy: None,
+            time_function: None,
+        };
+        let pulled = BoundaryTarget {
+            ux: None,
+            uy: None,
+            fx: Some(5.0),
+            fy: Some(0.0),
+            time_function: None,
+        };
+        let metadata = ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.0,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: Some(50.0),
+                ultimate_strength: None,
+            },
+            body_materials,
+            part_thickness: 1.0,
+            mesh_options: MeshOptions::default(),
+            units: None,
+            safety: Default::default(),
+        };
+        Model::new(
+            Geometry::Mesh(mesh),
+            metadata,
+            vec![
+                rule("fixed", f64::MIN, 0.1, fixed),
+                rule("pulled", 0.9, f64::MAX, pulled),
+            ],
+        )
+    }
+
+    #[test]
+    fn writes_and_reads_back_results() {
+        let mut model = pulled_square(Vec::new());
+        let results = model.solve().unwrap().clone();
+        let input = serde_json::json!({ "metadata": { "part_thickness": 1.0 } });
+        let file = ResultsFile::new(&model, &results, None, input).unwrap();
+
+        assert_eq!(file.nodes[0].fixed, [true, true]);
+        assert_eq!(file.nodes[1].fixed, [false, false]);
+        assert_eq!(file.boundary_rules[1].name, "pulled");
+        assert_eq!(file.boundary_rules[1].nodes, [1, 2]);
+        assert_eq!(file.solver.analysis, "linear");
+        assert_eq!(file.solver.constrained_degrees_of_freedom, 4);
+        // A uniform pull of 10 N/m over a modulus of 1000 Pa
+        for element in &file.elements {
+            assert!((element.strain[0] - 0.01).abs() < 1e-9);
+            assert!(element.strain[1].abs() < 1e-9);
+        }
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let path = scratch.file("results.json");
+        file.write(&path).unwrap();
+        let read = ResultsFile::read(&path).unwrap();
+        scratch.succeed();
+
+        assert_eq!(read, file);
+        assert_eq!(read.mesh().physical_groups[0].nodes, [1, 2]);
+        assert_eq!(read.material().yield_strength, Some(50.0));
+        let reloaded = read.results();
+        assert_eq!(reloaded.nodes[2].ux, results.nodes[2].ux);
+        assert_eq!(
+            reloaded.elements[1].von_mises,
+            results.elements[1].von_mises
+        );
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let path = scratch.file("other.json");
+
+        std::fs::write(&path, r#"{"metadata": {}}"#).unwrap();
+        let Err(MagnetiteError::PostProcessor(message)) = ResultsFile::read(&path) else {
+            panic!("expected a post processor error");
+        };
+        assert!(
+            message.ends_with("is not a Magnetite results file"),
+            "{message}"
+        );
+
+        std::fs::write(&path, r#"{"format": "magnetite-results", "version": 99}"#).unwrap();
+        let Err(MagnetiteError::PostProcessor(message)) = ResultsFile::read(&path) else {
+            panic!("expected a post processor error");
+        };
+        assert!(
+            message.ends_with("this build reads up to version 1"),
+            "{message}"
+        );
+        scratch.succeed();
+    }
+
+    #[test]
+    fn keeps_infinite_safety_factors() {
+        let mut model = pulled_square(Vec::new());
+        let mut results = model.solve().unwrap().clone();
+        // An unstressed element is infinitely safe
+        results.elements[0].stress_tensor = [0.0; 3];
+        results.elements[0].safety_factor = Some(f64::INFINITY);
+        let file = ResultsFile::new(&model, &results, None, serde_json::Value::Null).unwrap();
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let path = scratch.file("results.json");
+        file.write(&path).unwrap();
+        let text = std::fs::read_to_string(&path).unwrap();
+        let read = ResultsFile::read(&path).unwrap();
+        scratch.succeed();
+
+        assert!(text.contains(r#""safety_factor":"inf""#), "{text}");
+        assert_eq!(read.elements[0].safety_factor, Some(f64::INFINITY));
+        assert_eq!(
+            read.elements[1].safety_factor,
+            file.elements[1].safety_factor
+        );
+        assert_eq!(
+            read.results().elements[0].safety_factor,
+            Some(f64::INFINITY)
+        );
+    }
+
+    #[test]
+    fn keeps_the_material_of_each_body() {
+        let insert = Material {
+            youngs_modulus: 1000.0,
+            poisson_ratio: 0.0,
+            density: None,
+            thermal_expansion: None,
+            yield_strength: Some(20.0),
+            ultimate_strength: None,
+        };
+        let mut model = pulled_square(vec![BodyMaterial {
+            body: "insert".to_string(),
+            material: insert,
+        }]);
+        let results = model.solve().unwrap().clone();
+        let file = ResultsFile::new(&model, &results, None, serde_json::Value::Null).unwrap();
+
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let path = scratch.file("results.json");
+        file.write(&path).unwrap();
+        let read = ResultsFile::read(&path).unwrap();
+        scratch.succeed();
+
+        assert_eq!(read.mesh().elements[0].physical_tags, [2]);
+        let yields: Vec<_> = read
+            .element_materials()
+            .unwrap()
+            .iter()
+            .map(|m| m.yield_strength)
+            .collect();
+        assert_eq!(yields, [Some(20.0), Some(50.0)]);
+        let reloaded = read.results();
+        for (element, material) in std::iter::zip(&reloaded.elements, yields) {
+            let safety_factor = material.unwrap() / element.von_mises;
+            assert!((element.safety_factor.unwrap() - safety_factor).abs() < 1e-9);
+        }
+    }
+}
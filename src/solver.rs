@@ -1,9 +1,11 @@
 use crate::{
-    datatypes::{Element, ModelMetadata, Node},
+    datatypes::{Element, Material, Mesh, ModelMetadata, Node},
     error::MagnetiteError,
+    logging::{self, Timer},
 };
 use indicatif::ProgressBar;
 use nalgebra::{matrix, DMatrix, DVector, SMatrix};
+use tracing::{info, info_span};
 
 use argmin::{
     core::{
@@ -12,11 +14,11 @@ use argmin::{
     },
     solver::conjugategradient::ConjugateGradient,
 };
-use nalgebra_sparse::{CooMatrix, CsrMatrix};
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix, CsrMatrix};
 
 pub const DOF: usize = 2;
 pub const MAX_CG_ITER: u64 = 1e7 as u64;
-pub const TARGET_CG_COST: f64 = 1e-4 as f64;
+pub const TARGET_CG_COST: f64 = 1e-4;
 const SOLVE_BAR_TOTAL: u64 = 1000;
 
 /// Runs multiplication for Conjugate Gradient Solver
@@ -46,7 +48,7 @@ struct ConjugateGradientObserverBar {
 impl ConjugateGradientObserverBar {
     fn new() -> ConjugateGradientObserverBar {
         ConjugateGradientObserverBar {
-            bar: ProgressBar::new(SOLVE_BAR_TOTAL),
+            bar: logging::progress_bar(SOLVE_BAR_TOTAL),
             final_mag: TARGET_CG_COST.log10().floor(),
             done_solve: false,
         }
@@ -55,10 +57,7 @@ impl ConjugateGradientObserverBar {
     fn argmin_float_to_f64<F: ArgminFloat>(&self, value: F) -> Option<f64> {
         // TODO: There absolutely should be a way to extract the value
         // from a ArgminFloat instance that doesn't need this
-        match format!("{:?}", value).parse() {
-            Ok(n) => Some(n),
-            Err(_) => None,
-        }
+        format!("{:?}", value).parse().ok()
     }
 }
 
@@ -98,8 +97,8 @@ where
         self.bar.finish();
         let iterations = state.get_iter();
 
-        println!(
-            "info: finished conjugate gradient approximation in {} iterations",
+        info!(
+            "finished conjugate gradient approximation in {} iterations",
             iterations
         );
         Ok(())
@@ -121,7 +120,7 @@ fn run_conjugate_gradient(
     b: &DVector<f64>,
 ) -> Result<DVector<f64>, MagnetiteError> {
     // Convert a to a sparse matrix
-    println!("info: converting into sparse matrix...");
+    info!("converting into sparse matrix...");
     let n = a.nrows();
     let mut a_sparse_coo: CooMatrix<f64> = CooMatrix::new(n, n);
 
@@ -137,8 +136,8 @@ fn run_conjugate_gradient(
     let a_sparse_csr: CsrMatrix<f64> = CsrMatrix::from(&a_sparse_coo);
 
     // Run Conjugate Gradient Solver
-    println!("info: running conjugate gradient solver:");
-    let b_flat: Vec<f64> = b.iter().map(|f| *f).collect();
+    info!("running conjugate gradient solver:");
+    let b_flat: Vec<f64> = b.iter().copied().collect();
     let solver: ConjugateGradient<_, f64> = ConjugateGradient::new(b_flat);
     let initial_guess: Vec<f64> = vec![0.0; b.nrows()];
 
@@ -184,7 +183,7 @@ fn run_conjugate_gradient(
 ///
 /// # Returns
 /// The area of the element
-pub fn compute_element_area(element: &Element, nodes: &Vec<Node>) -> f64 {
+pub fn compute_element_area(element: &Element, nodes: &[Node]) -> f64 {
     let v0 = &nodes[element.nodes[0]].vertex;
     let v1 = &nodes[element.nodes[1]].vertex;
     let v2 = &nodes[element.nodes[2]].vertex;
@@ -203,7 +202,7 @@ pub fn compute_element_area(element: &Element, nodes: &Vec<Node>) -> f64 {
 /// A 3x6 strain-displacement matrix
 pub fn compute_strain_displacement_matrix(
     element: &Element,
-    nodes: &Vec<Node>,
+    nodes: &[Node],
     element_area: f64,
 ) -> SMatrix<f64, 3, 6> {
     let v0 = &nodes[element.nodes[0]].vertex;
@@ -260,9 +259,9 @@ pub fn compute_stress_strain_matrix(poisson_ratio: f64, youngs_modulus: f64) ->
 ///
 /// # Returns
 /// A 6x6 stiffness matrix for the element
-fn compute_element_stiffness_matrix(
+pub fn compute_element_stiffness_matrix(
     element: &Element,
-    nodes: &Vec<Node>,
+    nodes: &[Node],
     poisson_ratio: f64,
     youngs_modulus: f64,
     part_thickness: f64,
@@ -277,29 +276,71 @@ fn compute_element_stiffness_matrix(
         * part_thickness
 }
 
+/// Returns the global degrees of freedom of an element
+pub fn element_dofs(element: &Element) -> [usize; 6] {
+    let n = element.nodes;
+    [
+        DOF * n[0],
+        DOF * n[0] + 1,
+        DOF * n[1],
+        DOF * n[1] + 1,
+        DOF * n[2],
+        DOF * n[2] + 1,
+    ]
+}
+
+/// Computes the consistent mass matrix of an element
+///
+/// # Arguments
+/// * `element` - The element to target
+/// * `nodes` - A reference to the vector of nodes
+/// * `density` - The mass density of the material
+/// * `part_thickness` - The thickness of the part
+///
+/// # Returns
+/// A 6x6 mass matrix for the element
+pub fn compute_element_mass_matrix(
+    element: &Element,
+    nodes: &[Node],
+    density: f64,
+    part_thickness: f64,
+) -> SMatrix<f64, 6, 6> {
+    let mass = density * compute_element_area(element, nodes) * part_thickness;
+
+    // Each node pair shares a twelfth of the mass, and each node carries
+    // another twelfth itself, in each direction
+    let mut mass_mat = SMatrix::<f64, 6, 6>::zeros();
+    for a in 0..3 {
+        for b in 0..3 {
+            let share = if a == b { mass / 6.0 } else { mass / 12.0 };
+            mass_mat[(DOF * a, DOF * b)] = share;
+            mass_mat[(DOF * a + 1, DOF * b + 1)] = share;
+        }
+    }
+    mass_mat
+}
+
 /// Compiles element stiffness matrices into a total stiffness matrix
 ///
 /// # Arguments
 /// * `nodes` - A reference to the vector of nodes
 /// * `elements` - A reference to the vector of elements
 /// * `element_stiffness_matrices` - A vector of element stiffness matrices
-///     that corresponds to the `elements` vector.
+///   that corresponds to the `elements` vector.
 ///
 /// # Returns
 /// A dynamically sized matrix
 fn build_total_stiffness_matrix(
-    nodes: &Vec<Node>,
-    elements: &Vec<Element>,
+    nodes: &[Node],
+    elements: &[Element],
     element_stiffness_matrices: Vec<SMatrix<f64, 6, 6>>,
 ) -> DMatrix<f64> {
     let mut total_stiffness_matrix: DMatrix<f64> =
         DMatrix::zeros(DOF * nodes.len(), DOF * nodes.len());
 
-    let bar = ProgressBar::new(elements.len() as u64);
-    for (i, (stiffness_mat, element)) in
-        std::iter::zip(element_stiffness_matrices, elements).enumerate()
-    {
-        bar.inc(i as u64);
+    let bar = logging::progress_bar(elements.len() as u64);
+    for (stiffness_mat, element) in std::iter::zip(element_stiffness_matrices, elements) {
+        bar.inc(1);
 
         for (local_row, node_row) in element.nodes.iter().enumerate() {
             for (local_col, node_col) in element.nodes.iter().enumerate() {
@@ -323,9 +364,7 @@ fn build_total_stiffness_matrix(
             }
         }
     }
-    bar.finish_with_message(format!(
-        "info: successfully assembled total stiffness matrix\n"
-    ));
+    bar.finish_and_clear();
 
     total_stiffness_matrix
 }
@@ -337,7 +376,7 @@ fn build_total_stiffness_matrix(
 ///
 /// # Returns
 /// The nodal forces and nodal displacements column vectors, in that order
-fn build_col_vecs(nodes: &Vec<Node>) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
+fn build_col_vecs(nodes: &[Node]) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
     let mut nodal_forces: Vec<Option<f64>> =
         Vec::with_capacity(std::mem::size_of::<Option<f64>>() * nodes.len() * DOF);
     let mut nodal_displacements: Vec<Option<f64>> =
@@ -363,8 +402,8 @@ fn build_col_vecs(nodes: &Vec<Node>) -> (Vec<Option<f64>>, Vec<Option<f64>>) {
 /// # Returns
 /// A tuple of the known matrix and the unknown matrix, in that order
 fn build_known_unknown_matrices(
-    nodal_forces: &Vec<Option<f64>>,
-    nodal_displacements: &Vec<Option<f64>>,
+    nodal_forces: &[Option<f64>],
+    nodal_displacements: &[Option<f64>],
     total_stiffness_matrix: &DMatrix<f64>,
 ) -> (DMatrix<f64>, DMatrix<f64>) {
     let num_known_displacements = nodal_displacements.iter().filter(|x| x.is_some()).count();
@@ -409,20 +448,19 @@ fn build_known_unknown_matrices(
 /// # Arguments
 /// * `nodes` - The vector of nodes
 /// * `total_stiffness_matrix` - The total stiffness matrix of the model
-fn solve(
-    nodes: &mut Vec<Node>,
-    total_stiffness_matrix: &DMatrix<f64>,
-) -> Result<(), MagnetiteError> {
-    println!("info: setting up system...");
+fn solve(nodes: &mut [Node], total_stiffness_matrix: &DMatrix<f64>) -> Result<(), MagnetiteError> {
+    info!("setting up system...");
 
     // Assemble column Matrixes
-    println!("info: collecting nodal forces and displacements");
+    info!("collecting nodal forces and displacements");
     let (mut nodal_forces, mut nodal_displacements) = build_col_vecs(nodes);
 
     // Setup equation for unknown displacements
-    println!("info: partitioning total stiffness matrix");
+    info!("partitioning total stiffness matrix");
+    let partitioning = Timer::start(info_span!("partitioning"));
     let (known_matrix, unknown_matrix) =
         build_known_unknown_matrices(&nodal_forces, &nodal_displacements, total_stiffness_matrix);
+    drop(partitioning);
 
     let mut known_matrix_summed: DVector<f64> = known_matrix.column_sum();
     let known_forces: Vec<&Option<f64>> = nodal_forces.iter().filter(|x| x.is_some()).collect();
@@ -434,11 +472,13 @@ fn solve(
     // Solve for nodal displacements
     let start = std::time::Instant::now();
 
-    println!("info: solving...");
+    info!("solving...");
+    let solving = Timer::start(info_span!("solve"));
     let displacement_solution = run_conjugate_gradient(&unknown_matrix, &known_matrix_summed)?;
+    drop(solving);
 
     let elapsed = (std::time::Instant::now() - start).as_secs_f32();
-    println!("info: solved system in {:.3} seconds", elapsed);
+    info!("solved system in {:.3} seconds", elapsed);
 
     // Load displacement solution into nodal_displacement vector
     let mut solution_cursor = 0;
@@ -481,7 +521,7 @@ fn solve(
         node.fy = Some(nodal_forces[2 * i + 1]);
     }
 
-    println!("info: solve complete");
+    info!("solve complete");
 
     Ok(())
 }
@@ -491,15 +531,10 @@ fn solve(
 /// # Arguments
 /// * `elements` - A mutable reference to the vector of elements
 /// * `nodes` - A mutable reference to the vector of nodes
-/// * `poisson_ratio` - The model's poisson ratio
-/// * `youngs_modulus` - The model's material elasticity
-fn compute_stress(
-    elements: &mut Vec<Element>,
-    nodes: &mut Vec<Node>,
-    poisson_ratio: f64,
-    youngs_modulus: f64,
-) {
-    for element in elements {
+/// * `materials` - The material of each element
+pub fn compute_stress(elements: &mut [Element], nodes: &[Node], materials: &[Material]) {
+    let _timer = Timer::start(info_span!("stress_recovery"));
+    for (element, material) in std::iter::zip(elements, materials) {
         let element_nodes = Vec::from(element.nodes.map(|i| &nodes[i]));
 
         let nodal_displacements: [f64; 6] = [
@@ -513,74 +548,412 @@ fn compute_stress(
 
         let displacement_mat: SMatrix<f64, { DOF * 3 }, 1> = SMatrix::from(nodal_displacements);
 
-        let stress = compute_stress_strain_matrix(poisson_ratio, youngs_modulus)
+        let stress = compute_stress_strain_matrix(material.poisson_ratio, material.youngs_modulus)
             * compute_strain_displacement_matrix(
                 element,
-                &nodes,
-                compute_element_area(element, &nodes),
+                nodes,
+                compute_element_area(element, nodes),
             )
             * displacement_mat;
 
-        let sign = {
-            if stress[0] + stress[1] < 1.0 {
-                -1
-            } else {
-                1
+        set_element_stress(element, [stress[0], stress[1], stress[2]]);
+    }
+}
+
+/// Stores an element's stress tensor, along with its signed stress
+/// magnitude
+///
+/// # Arguments
+/// * `element` - The element to update
+/// * `stress_tensor` - The stresses `[sigma_xx, sigma_yy, tau_xy]`
+pub fn set_element_stress(element: &mut Element, stress_tensor: [f64; 3]) {
+    let sign = {
+        if stress_tensor[0] + stress_tensor[1] < 1.0 {
+            -1
+        } else {
+            1
+        }
+    };
+
+    element.stress = Some(
+        f64::sqrt(f64::powi(stress_tensor[0], 2) + f64::powi(stress_tensor[1], 2)) * sign as f64,
+    );
+    element.stress_tensor = Some(stress_tensor);
+}
+
+/// Computes the von Mises equivalent stress of a plane stress state
+///
+/// # Arguments
+/// * `stress_tensor` - The stresses `[sigma_xx, sigma_yy, tau_xy]`
+pub fn compute_von_mises_stress(stress_tensor: &[f64; 3]) -> f64 {
+    let [sxx, syy, txy] = *stress_tensor;
+    f64::sqrt(sxx * sxx - sxx * syy + syy * syy + 3.0 * txy * txy)
+}
+
+/// Computes the Tresca equivalent stress of a plane stress state; twice the
+/// maximum shear stress, taking the out-of-plane principal stress as zero
+///
+/// # Arguments
+/// * `stress_tensor` - The stresses `[sigma_xx, sigma_yy, tau_xy]`
+pub fn compute_tresca_stress(stress_tensor: &[f64; 3]) -> f64 {
+    let [sxx, syy, txy] = *stress_tensor;
+    let center = (sxx + syy) / 2.0;
+    let radius = f64::sqrt(f64::powi((sxx - syy) / 2.0, 2) + txy * txy);
+    let (s1, s2) = (center + radius, center - radius);
+
+    (s1 - s2).abs().max(s1.abs()).max(s2.abs())
+}
+
+/// The total stiffness matrix of a model, partitioned by its constrained
+/// degrees of freedom and Cholesky-factored. Once factored, it can be solved
+/// for any number of load vectors that share the same constraints.
+pub struct FactoredStiffness {
+    stiffness: CsrMatrix<f64>,
+    constrained: Vec<bool>,
+    free: Vec<usize>,
+    factor: CscCholesky<f64>,
+}
+
+impl FactoredStiffness {
+    /// Partitions and factors a total stiffness matrix
+    ///
+    /// # Arguments
+    /// * `total_stiffness_matrix` - The total stiffness matrix of the model
+    /// * `nodes` - Nodes with boundary conditions applied. Degrees of freedom
+    ///   with a prescribed displacement are constrained.
+    ///
+    /// # Returns
+    /// The factored stiffness matrix
+    pub fn new(
+        total_stiffness_matrix: &DMatrix<f64>,
+        nodes: &[Node],
+    ) -> Result<FactoredStiffness, MagnetiteError> {
+        let partitioning = Timer::start(info_span!("partitioning"));
+        let (_, nodal_displacements) = build_col_vecs(nodes);
+        let constrained: Vec<bool> = nodal_displacements.iter().map(|u| u.is_some()).collect();
+        let free: Vec<usize> = (0..constrained.len())
+            .filter(|i| !constrained[*i])
+            .collect();
+
+        // Index of each degree of freedom within the free partition
+        let mut free_index: Vec<Option<usize>> = vec![None; constrained.len()];
+        for (i, dof) in free.iter().enumerate() {
+            free_index[*dof] = Some(i);
+        }
+
+        let n = total_stiffness_matrix.nrows();
+        let mut stiffness_coo: CooMatrix<f64> = CooMatrix::new(n, n);
+        let mut free_coo: CooMatrix<f64> = CooMatrix::new(free.len(), free.len());
+        for row in 0..n {
+            for col in 0..n {
+                let k = total_stiffness_matrix[(row, col)];
+                if k == 0.0 {
+                    continue;
+                }
+                stiffness_coo.push(row, col, k);
+                if let (Some(r), Some(c)) = (free_index[row], free_index[col]) {
+                    free_coo.push(r, c, k);
+                }
+            }
+        }
+
+        drop(partitioning);
+
+        info!(
+            "factoring stiffness matrix with {} free degrees of freedom...",
+            free.len()
+        );
+        let _timer = Timer::start(info_span!("solve"));
+        let start = std::time::Instant::now();
+        let factor = match CscCholesky::factor(&CscMatrix::from(&free_coo)) {
+            Ok(f) => f,
+            Err(_err) => {
+                return Err(MagnetiteError::Solver(
+                    "Stiffness matrix is singular; check that the model is fully constrained"
+                        .to_owned(),
+                ))
             }
         };
+        let elapsed = (std::time::Instant::now() - start).as_secs_f32();
+        info!("factored stiffness matrix in {:.3} seconds", elapsed);
+
+        Ok(FactoredStiffness {
+            stiffness: CsrMatrix::from(&stiffness_coo),
+            constrained,
+            free,
+            factor,
+        })
+    }
 
-        element.stress =
-            Some(f64::sqrt(f64::powi(stress[0], 2) + f64::powi(stress[1], 2)) * sign as f64);
+    /// Checks whether nodes constrain the same degrees of freedom as the
+    /// factored matrix
+    pub fn matches(&self, nodes: &[Node]) -> bool {
+        let (_, nodal_displacements) = build_col_vecs(nodes);
+        nodal_displacements.len() == self.constrained.len()
+            && std::iter::zip(&nodal_displacements, &self.constrained)
+                .all(|(u, constrained)| u.is_some() == *constrained)
+    }
+
+    /// Solves for the displacements and reaction forces of the nodes. Loads
+    /// the results into the node objects
+    ///
+    /// # Arguments
+    /// * `nodes` - The vector of nodes, which must match the constraints the
+    ///   matrix was factored with
+    pub fn solve(&self, nodes: &mut [Node]) -> Result<(), MagnetiteError> {
+        if !self.matches(nodes) {
+            return Err(MagnetiteError::Solver(
+                "Boundary conditions constrain different degrees of freedom than the factored stiffness matrix".to_owned(),
+            ));
+        }
+
+        let _timer = Timer::start(info_span!("solve"));
+        let (nodal_forces, nodal_displacements) = build_col_vecs(nodes);
+
+        // Move prescribed displacements to the right hand side
+        let prescribed = DVector::from_iterator(
+            nodal_displacements.len(),
+            nodal_displacements.iter().map(|u| u.unwrap_or(0.0)),
+        );
+        let prescribed_forces = &self.stiffness * &prescribed;
+        let rhs = DVector::from_iterator(
+            self.free.len(),
+            self.free.iter().map(|dof| {
+                nodal_forces[*dof].expect("Free degree of freedom without a force")
+                    - prescribed_forces[*dof]
+            }),
+        );
+
+        let solution = self.factor.solve(&rhs);
+
+        let mut displacements = prescribed;
+        for (i, dof) in self.free.iter().enumerate() {
+            displacements[*dof] = solution[(i, 0)];
+        }
+
+        // Reaction forces at constrained degrees of freedom
+        let forces = &self.stiffness * &displacements;
+        let forces: Vec<f64> = (0..forces.len())
+            .map(|dof| match self.constrained[dof] {
+                true => forces[dof],
+                false => nodal_forces[dof].expect("Free degree of freedom without a force"),
+            })
+            .collect();
+
+        for (i, node) in nodes.iter_mut().enumerate() {
+            node.ux = Some(displacements[2 * i]);
+            node.uy = Some(displacements[2 * i + 1]);
+
+            node.fx = Some(forces[2 * i]);
+            node.fy = Some(forces[2 * i + 1]);
+        }
+
+        Ok(())
     }
 }
 
-/// Runs the solver. Updates values on nodes and elements vectors
+/// Builds the total stiffness matrix of a model
 ///
 /// # Arguments
-/// * `elements` - A mutable reference to the vector of elements
-/// * `nodes` - A mutable reference to the vector of nodes
+/// * `nodes` - A reference to the vector of nodes
+/// * `elements` - A reference to the vector of elements
+/// * `materials` - The material of each element
 /// * `model_metadata` - The model metadata
-pub fn run(
-    nodes: &mut Vec<Node>,
-    elements: &mut Vec<Element>,
+///
+/// # Returns
+/// The total stiffness matrix
+pub fn assemble_stiffness(
+    nodes: &[Node],
+    elements: &[Element],
+    materials: &[Material],
     model_metadata: &ModelMetadata,
-) -> Result<(), MagnetiteError> {
+) -> DMatrix<f64> {
+    let _timer = Timer::start(info_span!("assembly"));
+
     // Build element stiffness matrix for each element
     let mut element_stiffness_matrices: Vec<SMatrix<f64, 6, 6>> = Vec::new();
 
-    println!("info: building element stiffness matrices...");
-    let bar = ProgressBar::new(elements.len() as u64);
-    for (i, element) in elements.iter().enumerate() {
-        bar.inc(i as u64);
+    info!("building element stiffness matrices...");
+    let bar = logging::progress_bar(elements.len() as u64);
+    for (element, material) in std::iter::zip(elements, materials) {
+        bar.inc(1);
 
         element_stiffness_matrices.push(compute_element_stiffness_matrix(
-            &element,
-            &nodes,
-            model_metadata.poisson_ratio,
-            model_metadata.youngs_modulus,
+            element,
+            nodes,
+            material.poisson_ratio,
+            material.youngs_modulus,
             model_metadata.part_thickness,
         ));
     }
-    bar.finish_with_message(format!(
-        "info: successfully built {} stiffness matrices\n",
-        elements.len()
-    ));
+    bar.finish_and_clear();
 
     // Compile matrices into total stiffness matrix
-    println!("info: building total stiffness matrix...");
-    let total_stiffness_matrix =
-        build_total_stiffness_matrix(&nodes, &elements, element_stiffness_matrices);
+    info!("building total stiffness matrix...");
+    build_total_stiffness_matrix(nodes, elements, element_stiffness_matrices)
+}
+
+/// Finds the material of each element. An element is in a body if the mesh
+/// puts it in the body's surface physical group; elements outside the
+/// bodies with their own material are made of the model's material.
+///
+/// # Arguments
+/// * `mesh` - The mesh, with a surface physical group for each body
+/// * `model_metadata` - The model metadata
+///
+/// # Returns
+/// The material of each element, in the order of the mesh's elements
+pub fn element_materials(
+    mesh: &Mesh,
+    model_metadata: &ModelMetadata,
+) -> Result<Vec<Material>, MagnetiteError> {
+    let mut materials = vec![model_metadata.material.clone(); mesh.elements.len()];
+
+    for body_material in &model_metadata.body_materials {
+        let Some(group) = mesh
+            .physical_groups
+            .iter()
+            .find(|g| g.dimension == 2 && g.name == body_material.body)
+        else {
+            return Err(MagnetiteError::Input(format!(
+                "A material is given for body '{}', but the mesh has no surface with that name",
+                body_material.body
+            )));
+        };
+        for (element, material) in std::iter::zip(&mesh.elements, &mut materials) {
+            if element.physical_tags.contains(&group.tag) {
+                *material = body_material.material.clone();
+            }
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Runs the solver. Updates values on nodes and elements vectors
+///
+/// # Arguments
+/// * `elements` - A mutable reference to the vector of elements
+/// * `nodes` - A mutable reference to the vector of nodes
+/// * `materials` - The material of each element
+/// * `model_metadata` - The model metadata
+pub fn run(
+    nodes: &mut [Node],
+    elements: &mut [Element],
+    materials: &[Material],
+    model_metadata: &ModelMetadata,
+) -> Result<(), MagnetiteError> {
+    let total_stiffness_matrix = assemble_stiffness(nodes, elements, materials, model_metadata);
 
     // Solve system
     solve(nodes, &total_stiffness_matrix)?;
 
     // Solve for stress
-    compute_stress(
-        elements,
-        nodes,
-        model_metadata.poisson_ratio,
-        model_metadata.youngs_modulus,
-    );
+    compute_stress(elements, nodes, materials);
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_equivalent_stresses() {
+        // Uniaxial tension
+        assert_eq!(compute_von_mises_stress(&[100.0, 0.0, 0.0]), 100.0);
+        assert_eq!(compute_tresca_stress(&[100.0, 0.0, 0.0]), 100.0);
+
+        // Pure shear; principal stresses of +-50
+        assert!((compute_von_mises_stress(&[0.0, 0.0, 50.0]) - 50.0 * 3f64.sqrt()).abs() < 1e-9);
+        assert!((compute_tresca_stress(&[0.0, 0.0, 50.0]) - 100.0).abs() < 1e-9);
+
+        // Equal biaxial tension; the out-of-plane stress governs Tresca
+        assert_eq!(compute_von_mises_stress(&[80.0, 80.0, 0.0]), 80.0);
+        assert_eq!(compute_tresca_stress(&[80.0, 80.0, 0.0]), 80.0);
+    }
+
+    fn material(youngs_modulus: f64) -> Material {
+        Material {
+            youngs_modulus,
+            poisson_ratio: 0.3,
+            density: None,
+            thermal_expansion: None,
+            yield_strength: None,
+            ultimate_strength: None,
+        }
+    }
+
+    /// Two triangles sharing an edge, the second one in the body "pin".
+    /// Every node touches the pin, as at a conforming interface.
+    fn two_bodies() -> (Mesh, ModelMetadata) {
+        let node = |x, y| Node {
+            vertex: crate::datatypes::Vertex { x, y },
+            ux: None,
+            uy: None,
+            fx: None,
+            fy: None,
+        };
+        let element = |nodes, physical_tags| Element {
+            nodes,
+            stress: None,
+            stress_tensor: None,
+            plastic_strain: None,
+            physical_tags,
+        };
+        let mesh = Mesh {
+            nodes: vec![
+                node(0.0, 0.0),
+                node(1.0, 0.0),
+                node(1.0, 1.0),
+                node(0.0, 1.0),
+            ],
+            elements: vec![element([0, 1, 3], vec![2]), element([1, 2, 3], vec![1])],
+            physical_groups: vec![crate::datatypes::PhysicalGroup {
+                name: "pin".to_string(),
+                dimension: 2,
+                tag: 1,
+                nodes: vec![0, 1, 2, 3],
+            }],
+        };
+        let metadata = ModelMetadata {
+            material: material(1000.0),
+            body_materials: vec![crate::datatypes::BodyMaterial {
+                body: "pin".to_string(),
+                material: material(3000.0),
+            }],
+            part_thickness: 1.0,
+            mesh_options: Default::default(),
+            units: None,
+            safety: Default::default(),
+        };
+        (mesh, metadata)
+    }
+
+    #[test]
+    fn assigns_materials_to_bodies() {
+        let (mesh, metadata) = two_bodies();
+        let materials = element_materials(&mesh, &metadata).unwrap();
+        assert_eq!(materials[0].youngs_modulus, 1000.0);
+        assert_eq!(materials[1].youngs_modulus, 3000.0);
+
+        // Only the stiffer element contributes to node 2
+        let single = assemble_stiffness(
+            &mesh.nodes,
+            &mesh.elements,
+            &vec![metadata.material.clone(); 2],
+            &metadata,
+        );
+        let stiffness = assemble_stiffness(&mesh.nodes, &mesh.elements, &materials, &metadata);
+        assert!((stiffness[(4, 4)] - 3.0 * single[(4, 4)]).abs() < 1e-9 * single[(4, 4)]);
+        assert!(stiffness[(0, 0)] == single[(0, 0)]);
+    }
+
+    #[test]
+    fn rejects_materials_of_unknown_bodies() {
+        let (mesh, mut metadata) = two_bodies();
+        metadata.body_materials[0].body = "bolt".to_string();
+        let message = element_materials(&mesh, &metadata).unwrap_err().to_string();
+        assert!(message.contains("body 'bolt'"), "{message}");
+    }
+}
@@ -0,0 +1,505 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+};
+
+use crate::{
+    datatypes::{FailureCriterion, MeshOptions, Results},
+    error::MagnetiteError,
+    input::{self, InputFile, SweepInput, SweepMode},
+    mesher::{self, ScratchDir},
+    model::Model,
+    units::{label, Units},
+};
+use tracing::{info, warn};
+
+/// Prefix of parameters that are substituted into geometry files
+const GEOMETRY_PREFIX: &str = "geometry.";
+
+/// One case of a sweep; a value for each swept parameter
+#[derive(Debug, Clone)]
+pub struct SweepCase {
+    pub parameters: Vec<(String, serde_json::Value)>,
+}
+
+/// A sweep case and its outcome
+#[derive(Debug)]
+pub struct SweepResult {
+    pub case: SweepCase,
+    pub outcome: Result<CaseSummary, MagnetiteError>,
+}
+
+/// A summary of a solved sweep case. Values are in SI units.
+#[derive(Debug, Clone)]
+pub struct CaseSummary {
+    /// The case's declared units, for converting the summary back
+    pub units: Option<Units>,
+    pub max_displacement: f64,
+    pub max_stress: f64,
+    pub min_safety_factor: Option<f64>,
+    /// Whether the case passed its factor of safety check, if it has one
+    pub passed: Option<bool>,
+}
+
+/// Expands the sweep section into cases
+///
+/// # Arguments
+/// * `sweep` - The parsed sweep section
+///
+/// # Returns
+/// The cases to run, in order
+pub fn cases(sweep: &SweepInput) -> Result<Vec<SweepCase>, MagnetiteError> {
+    if sweep.parameters.is_empty() {
+        return Err(MagnetiteError::Input(
+            "sweep.parameters is empty".to_owned(),
+        ));
+    }
+    if let Some((path, _)) = sweep
+        .parameters
+        .iter()
+        .find(|(_, values)| values.is_empty())
+    {
+        return Err(MagnetiteError::Input(format!(
+            "Sweep parameter '{path}' has no values"
+        )));
+    }
+
+    let parameters: Vec<(&String, &Vec<serde_json::Value>)> = sweep.parameters.iter().collect();
+
+    match sweep.mode {
+        SweepMode::List => {
+            let count = parameters[0].1.len();
+            if let Some((path, values)) = parameters.iter().find(|(_, v)| v.len() != count) {
+                return Err(MagnetiteError::Input(format!(
+                    "Sweep parameter '{path}' has {} values, but '{}' has {count}; list sweeps need the same number of values for every parameter",
+                    values.len(),
+                    parameters[0].0
+                )));
+            }
+
+            Ok((0..count)
+                .map(|i| SweepCase {
+                    parameters: parameters
+                        .iter()
+                        .map(|(path, values)| (path.to_string(), values[i].clone()))
+                        .collect(),
+                })
+                .collect())
+        }
+        SweepMode::Grid => {
+            // Count through the grid, with the last parameter varying fastest
+            let count: usize = parameters.iter().map(|(_, v)| v.len()).product();
+            Ok((0..count)
+                .map(|mut index| {
+                    let mut values: Vec<(String, serde_json::Value)> = parameters
+                        .iter()
+                        .rev()
+                        .map(|(path, values)| {
+                            let value = values[index % values.len()].clone();
+                            index /= values.len();
+                            (path.to_string(), value)
+                        })
+                        .collect();
+                    values.reverse();
+                    SweepCase { parameters: values }
+                })
+                .collect())
+        }
+    }
+}
+
+/// Writes copies of the geometry files with `${name}` placeholders replaced
+/// by the case's `geometry.<name>` parameters. Files are only copied if the
+/// case has geometry parameters. Each copy goes in its own directory, so
+/// files of the same name from different directories don't overwrite each
+/// other, and csv bodies keep the names of their files.
+///
+/// # Arguments
+/// * `geometry_files` - The geometry files
+/// * `case` - The sweep case
+/// * `scratch` - The directory to write the copies in
+///
+/// # Returns
+/// The geometry files to use for the case
+fn substitute_geometry(
+    geometry_files: &[&str],
+    case: &SweepCase,
+    scratch: &ScratchDir,
+) -> Result<Vec<String>, MagnetiteError> {
+    let substitutions: Vec<(String, String)> = case
+        .parameters
+        .iter()
+        .filter_map(|(path, value)| {
+            let name = path.strip_prefix(GEOMETRY_PREFIX)?;
+            Some((format!("${{{name}}}"), format_parameter(value)))
+        })
+        .collect();
+
+    if substitutions.is_empty() {
+        return Ok(geometry_files.iter().map(|f| f.to_string()).collect());
+    }
+
+    let mut files = Vec::new();
+    for (i, geometry_file) in geometry_files.iter().enumerate() {
+        let mut contents = match std::fs::read_to_string(geometry_file) {
+            Ok(f) => f,
+            Err(_err) => {
+                return Err(MagnetiteError::Input(format!(
+                    "Unable to open geometry file {geometry_file}"
+                )))
+            }
+        };
+        for (placeholder, value) in &substitutions {
+            contents = contents.replace(placeholder, value);
+        }
+
+        // Keep the file name so the geometry type is still recognized
+        let name = std::path::Path::new(geometry_file)
+            .file_name()
+            .map_or("geometry".into(), |n| n.to_string_lossy());
+        let directory = scratch.file(&format!("geometry_{i}"));
+        let path = std::path::Path::new(&directory)
+            .join(&*name)
+            .to_string_lossy()
+            .into_owned();
+        let written =
+            std::fs::create_dir_all(&directory).and_then(|_| std::fs::write(&path, contents));
+        if let Err(err) = written {
+            return Err(MagnetiteError::Input(format!(
+                "Failed to write {path}: {err}"
+            )));
+        }
+        files.push(path);
+    }
+
+    Ok(files)
+}
+
+/// Summarizes the results of a solved case
+///
+/// # Returns
+/// The maximum displacement, maximum equivalent stress and minimum factor of
+/// safety, in that order
+fn summarize(results: &Results, criterion: FailureCriterion) -> (f64, f64, Option<f64>) {
+    let max_displacement = results
+        .nodes
+        .iter()
+        .map(|n| n.ux.hypot(n.uy))
+        .fold(0.0, f64::max);
+    let max_stress = results
+        .elements
+        .iter()
+        .map(|e| match criterion {
+            FailureCriterion::VonMises => e.von_mises,
+            FailureCriterion::Tresca => e.tresca,
+        })
+        .fold(0.0, f64::max);
+    let min_safety_factor = results
+        .elements
+        .iter()
+        .filter_map(|e| e.safety_factor)
+        .reduce(f64::min);
+
+    (max_displacement, max_stress, min_safety_factor)
+}
+
+/// Loads, meshes and solves a single sweep case. Models with load cases are
+/// summarized by their envelope over every load case and combination.
+///
+/// # Arguments
+/// * `input_file` - The path to the input file
+/// * `geometry_files` - The geometry files
+/// * `case` - The sweep case
+/// * `index` - The index of the case, which numbers its Gmsh log
+/// * `settings` - The Gmsh and intermediate file settings of every case
+///
+/// # Returns
+/// The CaseSummary of the case
+fn run_case(
+    input_file: &str,
+    geometry_files: &[&str],
+    case: &SweepCase,
+    index: usize,
+    settings: &MeshOptions,
+) -> Result<CaseSummary, MagnetiteError> {
+    let parameters: Vec<(String, serde_json::Value)> = case
+        .parameters
+        .iter()
+        .filter(|(path, _)| !path.starts_with(GEOMETRY_PREFIX))
+        .cloned()
+        .collect();
+    let mut model_input = input::load_with_parameters(input_file, &parameters)?;
+    let mesh_options = &mut model_input.metadata.mesh_options;
+    mesh_options.gmsh_path = settings.gmsh_path.clone();
+    mesh_options.gmsh_log = settings
+        .gmsh_log
+        .as_ref()
+        .map(|log| match log.rsplit_once('.') {
+            Some((stem, extension)) => format!("{stem}_{index}.{extension}"),
+            None => format!("{log}_{index}"),
+        });
+    mesh_options.keep_intermediates = settings.keep_intermediates;
+
+    let mut scratch = ScratchDir::new(settings.keep_intermediates)?;
+    let files = substitute_geometry(geometry_files, case, &scratch)?;
+    let file_refs: Vec<&str> = files.iter().map(|f| f.as_str()).collect();
+    let geometry = mesher::load_geometry(&file_refs, &model_input.metadata)?;
+    scratch.succeed();
+    drop(scratch);
+
+    let mut model = Model::from_input(model_input, geometry);
+
+    let criterion = model.metadata.safety.criterion;
+    let summaries: Vec<(f64, f64, Option<f64>)> = if model.load_cases.is_empty() {
+        vec![summarize(model.solve()?, criterion)]
+    } else {
+        model
+            .solve_load_cases()?
+            .iter()
+            .map(|c| summarize(&c.results, criterion))
+            .collect()
+    };
+
+    let min_safety_factor = summaries.iter().filter_map(|s| s.2).reduce(f64::min);
+    Ok(CaseSummary {
+        units: model.metadata.units,
+        max_displacement: summaries.iter().map(|s| s.0).fold(0.0, f64::max),
+        max_stress: summaries.iter().map(|s| s.1).fold(0.0, f64::max),
+        min_safety_factor,
+        passed: model
+            .metadata
+            .safety
+            .minimum_factor
+            .zip(min_safety_factor)
+            .map(|(minimum, factor)| factor >= minimum),
+    })
+}
+
+/// Runs every case of an input file's sweep section, in parallel
+///
+/// # Arguments
+/// * `input_file` - The path to the input file
+/// * `geometry_files` - The geometry files. Placeholders like `${radius}`
+///   are replaced by `geometry.radius` parameters.
+/// * `jobs` - The number of cases to run at once
+/// * `settings` - The Gmsh executable, Gmsh log and whether to keep
+///   intermediate files, which override the input file's mesh options. Each
+///   case's log gets the case index inserted before the extension, e.g.
+///   `gmsh_3.log`.
+///
+/// # Returns
+/// The result of each case, in order
+pub fn run(
+    input_file: &str,
+    geometry_files: &[&str],
+    jobs: usize,
+    settings: &MeshOptions,
+) -> Result<Vec<SweepResult>, MagnetiteError> {
+    let input: InputFile = input::read(input_file)?;
+    let Some(sweep) = &input.sweep else {
+        return Err(MagnetiteError::Input(format!(
+            "{input_file} has no sweep section"
+        )));
+    };
+    let cases = cases(sweep)?;
+    let jobs = jobs.clamp(1, cases.len());
+
+    info!("running {} sweep cases, {jobs} at a time", cases.len());
+
+    let next_case = AtomicUsize::new(0);
+    let outcomes: Mutex<Vec<Option<Result<CaseSummary, MagnetiteError>>>> =
+        Mutex::new(cases.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let index = next_case.fetch_add(1, Ordering::Relaxed);
+                let Some(case) = cases.get(index) else {
+                    break;
+                };
+
+                let outcome = run_case(input_file, geometry_files, case, index, settings);
+
+                // Report progress while holding the lock so counts print in order
+                let mut outcomes = outcomes.lock().expect("Sweep worker panicked");
+                let count = outcomes.iter().filter(|o| o.is_some()).count() + 1;
+                match &outcome {
+                    Ok(_) => info!("finished sweep case {index} ({count}/{})", cases.len()),
+                    Err(err) => warn!("sweep case {index} failed: {err}"),
+                }
+                outcomes[index] = Some(outcome);
+            });
+        }
+    });
+
+    Ok(
+        std::iter::zip(cases, outcomes.into_inner().expect("Sweep worker panicked"))
+            .map(|(case, outcome)| SweepResult {
+                case,
+                outcome: outcome.expect("Sweep case was not run"),
+            })
+            .collect(),
+    )
+}
+
+/// Formats a parameter value for the summary table
+fn format_parameter(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Writes a csv table with a row for each sweep case: its parameter values,
+/// maximum displacement, maximum stress and minimum factor of safety.
+/// Values are written in the units of the input file.
+///
+/// # Arguments
+/// * `results` - The results of each case, in order
+/// * `output` - The filepath of the csv file
+pub fn write_summary(results: &[SweepResult], output: &str) -> Result<(), MagnetiteError> {
+    let Some(first) = results.first() else {
+        return Ok(());
+    };
+    let units = results.iter().find_map(|r| r.outcome.as_ref().ok()?.units);
+
+    let mut headers = vec!["case".to_string()];
+    headers.extend(first.case.parameters.iter().map(|(path, _)| path.clone()));
+    headers.push(label("max_displacement", units.map(|u| u.length.name())));
+    headers.push(label("max_stress", units.map(|u| u.stress.name())));
+    headers.push("min_safety_factor".to_string());
+    headers.push("status".to_string());
+    let mut table = headers.join(",") + "\n";
+
+    for (index, result) in results.iter().enumerate() {
+        let mut row = vec![index.to_string()];
+        row.extend(
+            result
+                .case
+                .parameters
+                .iter()
+                .map(|(_, value)| format_parameter(value)),
+        );
+
+        match &result.outcome {
+            Ok(summary) => {
+                let length = summary.units.map_or(1.0, |u| u.length.factor());
+                let stress = summary.units.map_or(1.0, |u| u.stress.factor());
+                row.push((summary.max_displacement / length).to_string());
+                row.push((summary.max_stress / stress).to_string());
+                row.push(
+                    summary
+                        .min_safety_factor
+                        .map_or(String::new(), |f| f.to_string()),
+                );
+                row.push(
+                    match summary.passed {
+                        Some(true) => "pass",
+                        Some(false) => "fail",
+                        None => "ok",
+                    }
+                    .to_string(),
+                );
+            }
+            Err(err) => {
+                row.extend([String::new(), String::new(), String::new()]);
+                row.push(format!(
+                    "error: {}",
+                    err.to_string().replace([',', '\n'], " ")
+                ));
+            }
+        }
+
+        table += &(row.join(",") + "\n");
+    }
+
+    if let Err(err) = std::fs::write(output, table) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    info!("wrote sweep summary to {output}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sweep(mode: SweepMode) -> SweepInput {
+        let parameters = serde_json::json!({
+            "metadata.part_thickness": [1.0, 2.0],
+            "geometry.radius": [5, 10]
+        });
+        SweepInput {
+            mode,
+            parameters: serde_json::from_value(parameters).unwrap(),
+        }
+    }
+
+    fn values(cases: &[SweepCase]) -> Vec<Vec<String>> {
+        cases
+            .iter()
+            .map(|c| {
+                c.parameters
+                    .iter()
+                    .map(|(_, v)| format_parameter(v))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expands_grid_and_list_sweeps() {
+        let grid = cases(&sweep(SweepMode::Grid)).unwrap();
+        assert_eq!(
+            values(&grid),
+            [["1.0", "5"], ["1.0", "10"], ["2.0", "5"], ["2.0", "10"]]
+        );
+        assert_eq!(grid[0].parameters[0].0, "metadata.part_thickness");
+
+        let list = cases(&sweep(SweepMode::List)).unwrap();
+        assert_eq!(values(&list), [["1.0", "5"], ["2.0", "10"]]);
+
+        let mut uneven = sweep(SweepMode::List);
+        uneven.parameters["geometry.radius"].push(serde_json::json!(15));
+        assert!(cases(&uneven).is_err());
+    }
+
+    #[test]
+    fn substitutes_geometry_parameters() {
+        let mut templates = ScratchDir::new(false).unwrap();
+        let source = templates.file("template.csv");
+        std::fs::write(&source, "0,0\n${width},0\n${width},${width}\n").unwrap();
+
+        let case = SweepCase {
+            parameters: vec![
+                ("geometry.width".to_string(), serde_json::json!(2.5)),
+                ("metadata.part_thickness".to_string(), serde_json::json!(1)),
+            ],
+        };
+        let mut scratch = ScratchDir::new(false).unwrap();
+        let files = substitute_geometry(&[&source], &case, &scratch).unwrap();
+        let contents = std::fs::read_to_string(&files[0]).unwrap();
+
+        assert_ne!(files[0], source);
+        assert!(files[0].ends_with("template.csv"));
+        assert_eq!(contents, "0,0\n2.5,0\n2.5,2.5\n");
+
+        // Files of the same name from different directories are kept apart
+        let other = templates.file("other");
+        std::fs::create_dir(&other).unwrap();
+        let other = format!("{other}/template.csv");
+        std::fs::write(&other, "${width},1\n").unwrap();
+        let files = substitute_geometry(&[&source, &other], &case, &scratch).unwrap();
+        assert_ne!(files[0], files[1]);
+        assert!(files[1].ends_with("template.csv"));
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), contents);
+        assert_eq!(std::fs::read_to_string(&files[1]).unwrap(), "2.5,1\n");
+
+        templates.succeed();
+        scratch.succeed();
+    }
+}
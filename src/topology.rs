@@ -0,0 +1,850 @@
+use std::collections::HashMap;
+
+use nalgebra::{DVector, SMatrix};
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix};
+
+use crate::{
+    datatypes::{
+        BoundaryRegion, Element, Mesh, ModelMetadata, Node, PhysicalGroup, TopologyOptions, Vertex,
+    },
+    error::MagnetiteError,
+    mesher,
+    solver::{compute_element_area, compute_element_stiffness_matrix, DOF},
+    units::{label, Units},
+};
+use tracing::{info, warn};
+
+/// Densities below this are treated as this when dividing by them
+const FILTER_MINIMUM_DENSITY: f64 = 1e-3;
+/// Relative tolerance of the bisection on the volume constraint multiplier
+const BISECTION_TOLERANCE: f64 = 1e-6;
+
+/// The outcome of a topology optimisation
+#[derive(Debug, Clone)]
+pub struct TopologyResult {
+    /// The density of each element, between the minimum density and 1
+    pub densities: Vec<f64>,
+    /// The compliance of the final design; the work done by the loads
+    pub compliance: f64,
+    /// The fraction of the design domain's area kept
+    pub volume_fraction: f64,
+    pub iterations: usize,
+    pub converged: bool,
+}
+
+/// Returns the centroid of an element
+fn centroid(element: &Element, nodes: &[Node]) -> Vertex {
+    let vertices = element.nodes.map(|n| &nodes[n].vertex);
+    Vertex {
+        x: vertices.iter().map(|v| v.x).sum::<f64>() / 3.0,
+        y: vertices.iter().map(|v| v.y).sum::<f64>() / 3.0,
+    }
+}
+
+/// Checks whether an element is in a region. Elements are in a region if
+/// their centroid is, and, if the region names a physical group, all their
+/// nodes are in the group.
+fn in_region(
+    region: &BoundaryRegion,
+    element: &Element,
+    nodes: &[Node],
+    physical_groups: &[PhysicalGroup],
+) -> bool {
+    let in_group = match &region.physical_group {
+        Some(reference) => physical_groups
+            .iter()
+            .find(|g| reference.matches(g))
+            .is_some_and(|g| {
+                element
+                    .nodes
+                    .iter()
+                    .all(|n| g.nodes.binary_search(n).is_ok())
+            }),
+        None => true,
+    };
+    let c = centroid(element, nodes);
+    in_group && c.x > region.x_min && c.x < region.x_max && c.y > region.y_min && c.y < region.y_max
+}
+
+/// Builds the weights of the sensitivity filter. Each element is filtered
+/// over the elements whose centroids are within the filter radius, weighted
+/// by their area and by how close they are.
+///
+/// # Arguments
+/// * `centroids` - The centroid of each element
+/// * `areas` - The area of each element
+/// * `radius` - The filter radius
+///
+/// # Returns
+/// For each element, its neighbours and their weights
+fn build_filter(centroids: &[Vertex], areas: &[f64], radius: f64) -> Vec<Vec<(usize, f64)>> {
+    // Bucket elements into a grid of cells the size of the filter radius,
+    // so only neighbouring cells need searching
+    let cell = |v: &Vertex| ((v.x / radius).floor() as i64, (v.y / radius).floor() as i64);
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, c) in centroids.iter().enumerate() {
+        grid.entry(cell(c)).or_default().push(i);
+    }
+
+    centroids
+        .iter()
+        .map(|c| {
+            let (cx, cy) = cell(c);
+            let mut neighbours = Vec::new();
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for j in grid.get(&(cx + dx, cy + dy)).into_iter().flatten() {
+                        let distance = (centroids[*j].x - c.x).hypot(centroids[*j].y - c.y);
+                        if distance < radius {
+                            neighbours.push((*j, (radius - distance) * areas[*j]));
+                        }
+                    }
+                }
+            }
+            neighbours
+        })
+        .collect()
+}
+
+/// Runs SIMP topology optimisation, minimizing compliance for a fraction of
+/// the design domain's area. Densities are updated with the optimality
+/// criteria method, and sensitivities are filtered to avoid checkerboards
+/// and mesh dependence.
+///
+/// # Arguments
+/// * `mesh` - The mesh of the design domain
+/// * `nodes` - The mesh nodes, with boundary conditions applied
+/// * `model_metadata` - The model metadata
+/// * `options` - The topology optimisation settings
+///
+/// # Returns
+/// The optimised TopologyResult
+pub fn optimize(
+    mesh: &Mesh,
+    nodes: &[Node],
+    model_metadata: &ModelMetadata,
+    options: &TopologyOptions,
+) -> Result<TopologyResult, MagnetiteError> {
+    let elements = &mesh.elements;
+
+    for region in &options.non_design {
+        if let Some(reference) = &region.region.physical_group {
+            mesher::find_physical_group(
+                reference,
+                &mesh.physical_groups,
+                &format!("Non-design region '{}'", region.name),
+            )?;
+        }
+    }
+
+    // Compliance sensitivities assume the loads are all forces
+    let mut free_index: Vec<Option<usize>> = Vec::with_capacity(DOF * nodes.len());
+    let mut forces: Vec<f64> = Vec::new();
+    for node in nodes {
+        for (u, f) in [(node.ux, node.fx), (node.uy, node.fy)] {
+            match u {
+                Some(u) if u != 0.0 => {
+                    return Err(MagnetiteError::Input(
+                        "Topology optimisation needs loads to be forces; prescribed displacements must be zero".to_owned(),
+                    ))
+                }
+                Some(_) => free_index.push(None),
+                None => {
+                    free_index.push(Some(forces.len()));
+                    forces.push(f.expect("Node has neither a force nor a displacement"));
+                }
+            }
+        }
+    }
+    if forces.iter().all(|f| *f == 0.0) {
+        return Err(MagnetiteError::Input(
+            "Topology optimisation needs at least one non-zero force".to_owned(),
+        ));
+    }
+    let forces = DVector::from_vec(forces);
+
+    let material = &model_metadata.material;
+    let stiffness_matrices: Vec<SMatrix<f64, 6, 6>> = elements
+        .iter()
+        .map(|element| {
+            compute_element_stiffness_matrix(
+                element,
+                nodes,
+                material.poisson_ratio,
+                material.youngs_modulus,
+                model_metadata.part_thickness,
+            )
+        })
+        .collect();
+    let element_dofs: Vec<[Option<usize>; 6]> = elements
+        .iter()
+        .map(|element| {
+            let n = element.nodes;
+            [
+                2 * n[0],
+                2 * n[0] + 1,
+                2 * n[1],
+                2 * n[1] + 1,
+                2 * n[2],
+                2 * n[2] + 1,
+            ]
+            .map(|dof| free_index[dof])
+        })
+        .collect();
+
+    let areas: Vec<f64> = elements
+        .iter()
+        .map(|e| compute_element_area(e, nodes))
+        .collect();
+    let centroids: Vec<Vertex> = elements.iter().map(|e| centroid(e, nodes)).collect();
+    let filter = build_filter(&centroids, &areas, options.filter_radius);
+
+    // Non-design elements keep a fixed density; later regions take precedence
+    let fixed: Vec<Option<f64>> = elements
+        .iter()
+        .map(|element| {
+            options
+                .non_design
+                .iter()
+                .rev()
+                .find(|r| in_region(&r.region, element, nodes, &mesh.physical_groups))
+                .map(|r| {
+                    if r.solid {
+                        1.0
+                    } else {
+                        options.minimum_density
+                    }
+                })
+        })
+        .collect();
+    let design_area: f64 = std::iter::zip(&areas, &fixed)
+        .filter(|(_, f)| f.is_none())
+        .map(|(a, _)| a)
+        .sum();
+    if design_area <= 0.0 {
+        return Err(MagnetiteError::Input(
+            "Non-design regions cover the whole part; nothing to optimise".to_owned(),
+        ));
+    }
+    let target_volume = options.volume_fraction * design_area;
+
+    info!(
+        "optimising {} design elements to a volume fraction of {}",
+        fixed.iter().filter(|f| f.is_none()).count(),
+        options.volume_fraction
+    );
+
+    let mut densities: Vec<f64> = fixed
+        .iter()
+        .map(|f| f.unwrap_or(options.volume_fraction))
+        .collect();
+    let mut compliance = 0.0;
+    let mut converged = false;
+    let mut iterations = 0;
+
+    while iterations < options.max_iterations {
+        iterations += 1;
+
+        // Assemble and solve the penalized stiffness matrix
+        let mut stiffness_coo: CooMatrix<f64> = CooMatrix::new(forces.len(), forces.len());
+        for ((stiffness, dofs), density) in
+            stiffness_matrices.iter().zip(&element_dofs).zip(&densities)
+        {
+            let scale = density.powf(options.penalty);
+            for (a, row) in dofs.iter().enumerate() {
+                for (b, col) in dofs.iter().enumerate() {
+                    if let (Some(row), Some(col)) = (row, col) {
+                        stiffness_coo.push(*row, *col, scale * stiffness[(a, b)]);
+                    }
+                }
+            }
+        }
+        let factor = match CscCholesky::factor(&CscMatrix::from(&stiffness_coo)) {
+            Ok(f) => f,
+            Err(_err) => {
+                return Err(MagnetiteError::Solver(
+                    "Stiffness matrix is singular; check that the model is fully constrained"
+                        .to_owned(),
+                ))
+            }
+        };
+        let displacements = factor.solve(&forces);
+        compliance = forces.dot(&displacements.column(0));
+
+        // Sensitivity of the compliance to each density, per unit area
+        let sensitivities: Vec<f64> = (0..elements.len())
+            .map(|e| {
+                let u = SMatrix::<f64, 6, 1>::from_iterator(
+                    element_dofs[e].map(|dof| dof.map_or(0.0, |d| displacements[(d, 0)])),
+                );
+                let strain_energy = (u.transpose() * stiffness_matrices[e] * u)[(0, 0)];
+                -options.penalty * densities[e].powf(options.penalty - 1.0) * strain_energy
+                    / areas[e]
+            })
+            .collect();
+
+        let filtered: Vec<f64> = filter
+            .iter()
+            .enumerate()
+            .map(|(i, neighbours)| {
+                let weighted: f64 = neighbours
+                    .iter()
+                    .map(|(j, w)| w * densities[*j] * sensitivities[*j])
+                    .sum();
+                let total: f64 = neighbours.iter().map(|(_, w)| w).sum();
+                weighted / (densities[i].max(FILTER_MINIMUM_DENSITY) * total)
+            })
+            .collect();
+
+        // Scale sensitivities so the multiplier's bracket doesn't depend on
+        // the model's units
+        let scale = filtered.iter().map(|s| s.abs()).sum::<f64>() / filtered.len() as f64;
+
+        // Optimality criteria update, bisecting for the volume multiplier
+        let update = |multiplier: f64| -> Vec<f64> {
+            (0..elements.len())
+                .map(|e| match fixed[e] {
+                    Some(density) => density,
+                    None => {
+                        let x = densities[e];
+                        let target = x * (-filtered[e] / scale / multiplier).max(0.0).sqrt();
+                        target
+                            .clamp(x - options.move_limit, x + options.move_limit)
+                            .clamp(options.minimum_density, 1.0)
+                    }
+                })
+                .collect()
+        };
+        let volume = |densities: &[f64]| -> f64 {
+            (0..elements.len())
+                .filter(|e| fixed[*e].is_none())
+                .map(|e| areas[e] * densities[e])
+                .sum()
+        };
+
+        let (mut lower, mut upper) = (0.0, 1e9);
+        let mut updated = update(upper);
+        while (upper - lower) / (upper + lower) > BISECTION_TOLERANCE {
+            let middle = 0.5 * (lower + upper);
+            updated = update(middle);
+            if volume(&updated) > target_volume {
+                lower = middle;
+            } else {
+                upper = middle;
+            }
+        }
+
+        let change = std::iter::zip(&updated, &densities)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        densities = updated;
+
+        info!(
+            "iteration {iterations}: compliance {compliance:.4e}, volume fraction {:.3}, change {change:.3}",
+            volume(&densities) / design_area
+        );
+
+        if change < options.tolerance {
+            converged = true;
+            break;
+        }
+    }
+
+    if converged {
+        info!("topology converged in {iterations} iterations");
+    } else {
+        warn!(
+            "topology did not converge in {} iterations",
+            options.max_iterations
+        );
+    }
+
+    let volume_fraction = (0..elements.len())
+        .filter(|e| fixed[*e].is_none())
+        .map(|e| areas[e] * densities[e])
+        .sum::<f64>()
+        / design_area;
+
+    Ok(TopologyResult {
+        densities,
+        compliance,
+        volume_fraction,
+        iterations,
+        converged,
+    })
+}
+
+/// Writes the density of each element to a csv file, with its centroid
+///
+/// # Arguments
+/// * `mesh` - The optimised mesh
+/// * `densities` - The density of each element
+/// * `units` - The units to write lengths in, if declared
+/// * `output` - The filepath of the csv file
+pub fn write_densities(
+    mesh: &Mesh,
+    densities: &[f64],
+    units: Option<&Units>,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let length_unit = units.map(|u| u.length.name());
+
+    let mut table = format!(
+        "n0,n1,n2,{},{},density\n",
+        label("x", length_unit),
+        label("y", length_unit)
+    );
+    for (element, density) in std::iter::zip(&mesh.elements, densities) {
+        let c = centroid(element, &mesh.nodes);
+        table += &format!(
+            "{},{},{},{},{},{density}\n",
+            element.nodes[0],
+            element.nodes[1],
+            element.nodes[2],
+            c.x / length,
+            c.y / length
+        );
+    }
+
+    if let Err(err) = std::fs::write(output, table) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    info!("wrote element densities to {output}");
+
+    Ok(())
+}
+
+/// A point on the extracted boundary; either a mesh node, or where the
+/// density crosses the threshold along a mesh edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum BoundaryPoint {
+    Node(usize),
+    Crossing(usize, usize),
+}
+
+impl BoundaryPoint {
+    fn crossing(a: usize, b: usize) -> BoundaryPoint {
+        BoundaryPoint::Crossing(a.min(b), a.max(b))
+    }
+
+    /// Whether the point lies on the mesh edge between nodes a and b
+    fn on_edge(&self, a: usize, b: usize) -> bool {
+        match *self {
+            BoundaryPoint::Node(n) => n == a || n == b,
+            BoundaryPoint::Crossing(p, q) => (p, q) == (a.min(b), a.max(b)),
+        }
+    }
+}
+
+/// Extracts the boundary of the material, where the density is at least
+/// `threshold`, as closed loops. Element densities are averaged onto nodes
+/// and interpolated linearly over each element.
+///
+/// # Arguments
+/// * `mesh` - The optimised mesh
+/// * `densities` - The density of each element
+/// * `threshold` - The density at the boundary
+///
+/// # Returns
+/// Counter-clockwise outer loops and clockwise holes
+pub fn extract_boundary(mesh: &Mesh, densities: &[f64], threshold: f64) -> Vec<Vec<Vertex>> {
+    let nodes = &mesh.nodes;
+
+    // Area-weighted average of the densities of the elements around each node
+    let mut nodal = vec![(0.0, 0.0); nodes.len()];
+    for (element, density) in std::iter::zip(&mesh.elements, densities) {
+        let area = compute_element_area(element, nodes);
+        for n in element.nodes {
+            nodal[n].0 += area * density;
+            nodal[n].1 += area;
+        }
+    }
+    let nodal: Vec<f64> = nodal
+        .iter()
+        .map(|(weighted, area)| if *area > 0.0 { weighted / area } else { 0.0 })
+        .collect();
+    let inside = |n: usize| nodal[n] >= threshold;
+
+    let position = |point: &BoundaryPoint| -> Vertex {
+        match *point {
+            BoundaryPoint::Node(n) => nodes[n].vertex.clone(),
+            BoundaryPoint::Crossing(a, b) => {
+                let t = (threshold - nodal[a]) / (nodal[b] - nodal[a]);
+                let (va, vb) = (&nodes[a].vertex, &nodes[b].vertex);
+                Vertex {
+                    x: va.x + t * (vb.x - va.x),
+                    y: va.y + t * (vb.y - va.y),
+                }
+            }
+        }
+    };
+
+    // Mesh edges used by only one element are on the outside of the part
+    let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+    for element in &mesh.elements {
+        for i in 0..3 {
+            let (a, b) = (element.nodes[i], element.nodes[(i + 1) % 3]);
+            *edge_count.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+
+    // Clip each element to its material, and keep the edges of the clipped
+    // polygons that aren't shared with a neighbouring element
+    let mut next: HashMap<BoundaryPoint, Vec<BoundaryPoint>> = HashMap::new();
+    for element in &mesh.elements {
+        // Walk each element counter-clockwise, so loops are consistently
+        // oriented whatever order the mesher wrote the nodes in
+        let [n0, n1, n2] = element.nodes;
+        let (v0, v1, v2) = (&nodes[n0].vertex, &nodes[n1].vertex, &nodes[n2].vertex);
+        let ccw = (v1.x - v0.x) * (v2.y - v0.y) - (v2.x - v0.x) * (v1.y - v0.y) > 0.0;
+        let element_nodes = if ccw { [n0, n1, n2] } else { [n0, n2, n1] };
+
+        let mut polygon = Vec::new();
+        for i in 0..3 {
+            let (a, b) = (element_nodes[i], element_nodes[(i + 1) % 3]);
+            if inside(a) {
+                polygon.push(BoundaryPoint::Node(a));
+            }
+            if inside(a) != inside(b) {
+                polygon.push(BoundaryPoint::crossing(a, b));
+            }
+        }
+        if polygon.len() < 3 {
+            continue;
+        }
+
+        for i in 0..polygon.len() {
+            let (p, q) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            let along_edge = (0..3).find_map(|k| {
+                let (a, b) = (element.nodes[k], element.nodes[(k + 1) % 3]);
+                (p.on_edge(a, b) && q.on_edge(a, b)).then_some((a.min(b), a.max(b)))
+            });
+            let on_boundary = match along_edge {
+                Some(edge) => edge_count[&edge] == 1,
+                None => true,
+            };
+            if on_boundary {
+                next.entry(p).or_default().push(q);
+            }
+        }
+    }
+
+    // Chain the edges into loops
+    let mut loops = Vec::new();
+    while let Some(start) = next.keys().next().copied() {
+        let mut points = vec![start];
+        let mut current = start;
+        while let Some(following) = next.get_mut(&current).and_then(|n| n.pop()) {
+            if next.get(&current).is_some_and(|n| n.is_empty()) {
+                next.remove(&current);
+            }
+            if following == start {
+                break;
+            }
+            points.push(following);
+            current = following;
+        }
+        if points.len() >= 3 {
+            loops.push(points.iter().map(position).collect());
+        }
+    }
+
+    loops
+}
+
+/// Returns the signed area of a loop; positive if it is counter-clockwise
+fn signed_area(points: &[Vertex]) -> f64 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (&points[i], &points[(i + 1) % points.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+/// Writes the extracted boundary of an optimised part to an svg file that
+/// can be meshed again. The largest outer loop is the OUTER polygon and
+/// holes are INNER polygons.
+///
+/// # Arguments
+/// * `loops` - The boundary loops, from [`extract_boundary`]
+/// * `units` - The units to write lengths in, if declared
+/// * `output` - The filepath of the svg file
+pub fn write_boundary_svg(
+    loops: &[Vec<Vertex>],
+    units: Option<&Units>,
+    output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+
+    let mut outers: Vec<&Vec<Vertex>> = loops.iter().filter(|l| signed_area(l) > 0.0).collect();
+    let holes: Vec<&Vec<Vertex>> = loops.iter().filter(|l| signed_area(l) < 0.0).collect();
+    outers.sort_by(|a, b| signed_area(b).total_cmp(&signed_area(a)));
+
+    if outers.is_empty() {
+        return Err(MagnetiteError::PostProcessor(
+            "Optimised part has no material above the density threshold".to_owned(),
+        ));
+    }
+    if outers.len() > 1 {
+        warn!(
+            "optimised part has {} disconnected bodies; only the largest is the OUTER polygon",
+            outers.len()
+        );
+    }
+
+    // SVG y points down, and lengths are written in the input's units
+    let points = |l: &Vec<Vertex>| -> String {
+        l.iter()
+            .map(|v| format!("{} {}", v.x / length, -v.y / length))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    let (mut x_min, mut x_max, mut y_min, mut y_max) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for v in loops.iter().flatten() {
+        x_min = x_min.min(v.x / length);
+        x_max = x_max.max(v.x / length);
+        y_min = y_min.min(-v.y / length);
+        y_max = y_max.max(-v.y / length);
+    }
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{x_min} {y_min} {} {}\">\n",
+        x_max - x_min,
+        y_max - y_min
+    );
+    for (i, outer) in outers.iter().enumerate() {
+        let id = if i == 0 {
+            "OUTER".to_string()
+        } else {
+            format!("BODY-{}", i + 1)
+        };
+        svg += &format!(
+            "  <polygon id=\"{id}\" points=\"{}\" fill=\"#4c4c4c\"/>\n",
+            points(outer)
+        );
+    }
+    for (i, hole) in holes.iter().enumerate() {
+        svg += &format!(
+            "  <polygon id=\"INNER-{}\" points=\"{}\" fill=\"#ffffff\"/>\n",
+            i + 1,
+            points(hole)
+        );
+    }
+    svg += "</svg>\n";
+
+    if let Err(err) = std::fs::write(output, svg) {
+        return Err(MagnetiteError::PostProcessor(format!(
+            "Failed to write {output}: {err}"
+        )));
+    }
+
+    info!(
+        "wrote optimised boundary with {} holes to {output}",
+        holes.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datatypes::{Material, MeshOptions, NonDesignRegion};
+
+    /// A structured mesh of a `width` by `height` rectangle, with two
+    /// triangles per unit square
+    fn grid(width: usize, height: usize) -> Mesh {
+        let mut nodes = Vec::new();
+        for j in 0..=height {
+            for i in 0..=width {
+                nodes.push(Node {
+                    vertex: Vertex {
+                        x: i as f64,
+                        y: j as f64,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                });
+            }
+        }
+        let index = |i: usize, j: usize| j * (width + 1) + i;
+        let mut elements = Vec::new();
+        for j in 0..height {
+            for i in 0..width {
+                for nodes in [
+                    [index(i, j), index(i + 1, j), index(i + 1, j + 1)],
+                    [index(i, j), index(i + 1, j + 1), index(i, j + 1)],
+                ] {
+                    elements.push(Element {
+                        nodes,
+                        stress: None,
+                        stress_tensor: None,
+                        plastic_strain: None,
+                        physical_tags: Vec::new(),
+                    });
+                }
+            }
+        }
+        Mesh {
+            nodes,
+            elements,
+            physical_groups: Vec::new(),
+        }
+    }
+
+    /// A cantilever fixed on its left edge with a downward load at the
+    /// middle of its right edge
+    fn cantilever(mesh: &Mesh) -> Vec<Node> {
+        let width = mesh.nodes.iter().map(|n| n.vertex.x).fold(0.0, f64::max);
+        let height = mesh.nodes.iter().map(|n| n.vertex.y).fold(0.0, f64::max);
+        let mut nodes = mesh.nodes.clone();
+        for node in nodes.iter_mut() {
+            if node.vertex.x == 0.0 {
+                (node.ux, node.uy, node.fx, node.fy) = (Some(0.0), Some(0.0), None, None);
+            }
+            if node.vertex.x == width && node.vertex.y == (height / 2.0).floor() {
+                node.fy = Some(-1.0);
+            }
+        }
+        nodes
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: Material {
+                youngs_modulus: 1.0,
+                poisson_ratio: 0.3,
+                density: None,
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        }
+    }
+
+    fn options(volume_fraction: f64, max_iterations: usize) -> TopologyOptions {
+        TopologyOptions {
+            volume_fraction,
+            penalty: 3.0,
+            filter_radius: 1.5,
+            minimum_density: 1e-3,
+            move_limit: 0.2,
+            max_iterations,
+            tolerance: 0.01,
+            non_design: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn optimisation_meets_volume_and_lowers_compliance() {
+        let mesh = grid(12, 4);
+        let nodes = cantilever(&mesh);
+
+        let first = optimize(&mesh, &nodes, &metadata(), &options(0.5, 1)).unwrap();
+        let result = optimize(&mesh, &nodes, &metadata(), &options(0.5, 40)).unwrap();
+
+        assert!((result.volume_fraction - 0.5).abs() < 1e-3);
+        assert!(result.compliance < first.compliance);
+        assert!(result.densities.iter().all(|d| (1e-3..=1.0).contains(d)));
+    }
+
+    #[test]
+    fn non_design_regions_keep_their_density() {
+        let mesh = grid(12, 4);
+        let nodes = cantilever(&mesh);
+        let mut options = options(0.4, 10);
+        options.non_design = vec![
+            NonDesignRegion {
+                name: "mount".to_string(),
+                region: BoundaryRegion {
+                    x_min: f64::MIN,
+                    x_max: 2.0,
+                    y_min: f64::MIN,
+                    y_max: f64::MAX,
+                    physical_group: None,
+                },
+                solid: true,
+            },
+            NonDesignRegion {
+                name: "keepout".to_string(),
+                region: BoundaryRegion {
+                    x_min: 5.0,
+                    x_max: 7.0,
+                    y_min: 1.0,
+                    y_max: 3.0,
+                    physical_group: None,
+                },
+                solid: false,
+            },
+        ];
+
+        let result = optimize(&mesh, &nodes, &metadata(), &options).unwrap();
+
+        for (element, density) in std::iter::zip(&mesh.elements, &result.densities) {
+            let c = centroid(element, &mesh.nodes);
+            if c.x < 2.0 {
+                assert_eq!(*density, 1.0);
+            }
+            if c.x > 5.0 && c.x < 7.0 && c.y > 1.0 && c.y < 3.0 {
+                assert_eq!(*density, 1e-3);
+            }
+        }
+        assert!((result.volume_fraction - 0.4).abs() < 1e-3);
+    }
+
+    #[test]
+    fn extracts_outer_boundary_and_holes() {
+        let mesh = grid(6, 6);
+        let solid = vec![1.0; mesh.elements.len()];
+
+        let loops = extract_boundary(&mesh, &solid, 0.5);
+        assert_eq!(loops.len(), 1);
+        assert!((signed_area(&loops[0]) - 36.0).abs() < 1e-9);
+
+        // Voiding the middle two by two square leaves a hole
+        let holed: Vec<f64> = mesh
+            .elements
+            .iter()
+            .map(|e| {
+                let c = centroid(e, &mesh.nodes);
+                if (2.0..4.0).contains(&c.x) && (2.0..4.0).contains(&c.y) {
+                    0.0
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let mut loops = extract_boundary(&mesh, &holed, 0.5);
+        loops.sort_by(|a, b| signed_area(b).total_cmp(&signed_area(a)));
+        assert_eq!(loops.len(), 2);
+        assert!((signed_area(&loops[0]) - 36.0).abs() < 1e-9);
+        assert!(signed_area(&loops[1]) < 0.0);
+    }
+
+    #[test]
+    fn rejects_prescribed_displacements() {
+        let mesh = grid(4, 2);
+        let mut nodes = cantilever(&mesh);
+        nodes[0].ux = Some(0.1);
+
+        assert!(optimize(&mesh, &nodes, &metadata(), &options(0.5, 5)).is_err());
+    }
+}
@@ -0,0 +1,507 @@
+use nalgebra::{DVector, SMatrix};
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix, CsrMatrix};
+
+use crate::{
+    datatypes::{Integrator, Mesh, ModelMetadata, Node, Results, TimeFunction, TransientOptions},
+    error::MagnetiteError,
+    model::collect_results,
+    solver::{
+        compute_element_mass_matrix, compute_element_stiffness_matrix, compute_stress,
+        element_dofs, DOF,
+    },
+    units::{label, Units},
+};
+use tracing::info;
+
+/// The results of a transient solve at one time
+#[derive(Debug, Clone)]
+pub struct TransientFrame {
+    /// The time, in seconds
+    pub time: f64,
+    pub results: Results,
+}
+
+/// The loading of one degree of freedom, as a function of time
+struct DofLoad<'a> {
+    /// The prescribed displacement, if constrained
+    displacement: Option<f64>,
+    /// The applied force, if free
+    force: f64,
+    time_function: Option<&'a TimeFunction>,
+}
+
+impl DofLoad<'_> {
+    fn factor(&self, time: f64) -> f64 {
+        self.time_function.map_or(1.0, |f| f.value(time))
+    }
+}
+
+/// Returns the Newmark `beta` and `gamma` and the HHT `alpha` of an
+/// integrator
+fn integration_parameters(integrator: &Integrator) -> (f64, f64, f64) {
+    match *integrator {
+        Integrator::Newmark { beta, gamma } => (beta, gamma, 0.0),
+        Integrator::Hht { alpha } => ((1.0 - alpha).powi(2) / 4.0, 0.5 - alpha, alpha),
+    }
+}
+
+/// Factors a sparse matrix, which must be positive definite
+fn factor(coo: &CooMatrix<f64>, name: &str) -> Result<CscCholesky<f64>, MagnetiteError> {
+    match CscCholesky::factor(&CscMatrix::from(coo)) {
+        Ok(factor) => Ok(factor),
+        Err(_err) => Err(MagnetiteError::Solver(format!(
+            "{name} matrix is singular; check that the model is fully constrained"
+        ))),
+    }
+}
+
+/// Integrates the equations of motion `M a + C v + K u = F(t)` through
+/// time, starting at rest, with Rayleigh damping `C = a M + b K`. Forces
+/// and prescribed displacements are scaled by their time functions.
+///
+/// # Arguments
+/// * `mesh` - The mesh
+/// * `nodes` - The mesh nodes, with boundary conditions applied
+/// * `time_functions` - The time function of each node's boundary rule, if any
+/// * `model_metadata` - The model metadata; the material needs a density
+/// * `options` - The transient settings
+///
+/// # Returns
+/// The results at each output time, starting at time zero
+pub fn run(
+    mesh: &Mesh,
+    nodes: &[Node],
+    time_functions: &[Option<&TimeFunction>],
+    model_metadata: &ModelMetadata,
+    options: &TransientOptions,
+) -> Result<Vec<TransientFrame>, MagnetiteError> {
+    let Some(density) = model_metadata.material.density else {
+        return Err(MagnetiteError::Input(
+            "Transient analysis needs a material density; set metadata.density or use a library material"
+                .to_owned(),
+        ));
+    };
+    let elements = &mesh.elements;
+    let dof_count = DOF * nodes.len();
+
+    let loads: Vec<DofLoad> = std::iter::zip(nodes, time_functions)
+        .flat_map(|(node, time_function)| {
+            [(node.ux, node.fx), (node.uy, node.fy)].map(|(u, f)| DofLoad {
+                displacement: u,
+                force: match u {
+                    Some(_) => 0.0,
+                    None => f.expect("Free degree of freedom without a force"),
+                },
+                time_function: *time_function,
+            })
+        })
+        .collect();
+    let forces_at = |time: f64| -> DVector<f64> {
+        DVector::from_iterator(dof_count, loads.iter().map(|l| l.force * l.factor(time)))
+    };
+    let displacement_at = |dof: usize, time: f64| -> Option<f64> {
+        loads[dof].displacement.map(|u| u * loads[dof].factor(time))
+    };
+
+    // Index of each degree of freedom within the free partition
+    let mut free_index: Vec<Option<usize>> = vec![None; dof_count];
+    let mut free_count = 0;
+    for (dof, load) in loads.iter().enumerate() {
+        if load.displacement.is_none() {
+            free_index[dof] = Some(free_count);
+            free_count += 1;
+        }
+    }
+
+    let dt = options.time_step;
+    let (beta, gamma, alpha) = integration_parameters(&options.integrator);
+    let a0 = 1.0 / (beta * dt * dt);
+    let a1 = gamma / (beta * dt);
+    let (mass_damping, stiffness_damping) = (options.mass_damping, options.stiffness_damping);
+
+    // Assemble the full stiffness, mass and effective stiffness matrices,
+    // and the free partitions of the mass and effective stiffness
+    let mut stiffness_coo = CooMatrix::new(dof_count, dof_count);
+    let mut mass_coo = CooMatrix::new(dof_count, dof_count);
+    let mut effective_coo = CooMatrix::new(dof_count, dof_count);
+    let mut free_mass_coo = CooMatrix::new(free_count, free_count);
+    let mut free_effective_coo = CooMatrix::new(free_count, free_count);
+    for element in elements {
+        let stiffness_mat = compute_element_stiffness_matrix(
+            element,
+            nodes,
+            model_metadata.material.poisson_ratio,
+            model_metadata.material.youngs_modulus,
+            model_metadata.part_thickness,
+        );
+        let mass_mat =
+            compute_element_mass_matrix(element, nodes, density, model_metadata.part_thickness);
+        let damping_mat: SMatrix<f64, 6, 6> =
+            mass_damping * mass_mat + stiffness_damping * stiffness_mat;
+        let effective_mat = a0 * mass_mat + (1.0 + alpha) * (a1 * damping_mat + stiffness_mat);
+
+        let dofs = element_dofs(element);
+        for (a, row) in dofs.iter().enumerate() {
+            for (b, col) in dofs.iter().enumerate() {
+                stiffness_coo.push(*row, *col, stiffness_mat[(a, b)]);
+                mass_coo.push(*row, *col, mass_mat[(a, b)]);
+                effective_coo.push(*row, *col, effective_mat[(a, b)]);
+                if let (Some(r), Some(c)) = (free_index[*row], free_index[*col]) {
+                    free_mass_coo.push(r, c, mass_mat[(a, b)]);
+                    free_effective_coo.push(r, c, effective_mat[(a, b)]);
+                }
+            }
+        }
+    }
+    let stiffness = CsrMatrix::from(&stiffness_coo);
+    let mass = CsrMatrix::from(&mass_coo);
+    let effective = CsrMatrix::from(&effective_coo);
+    let free_mass = factor(&free_mass_coo, "Mass")?;
+    let free_effective = factor(&free_effective_coo, "Effective stiffness")?;
+
+    let damping_times = |v: &DVector<f64>| -> DVector<f64> {
+        mass_damping * (&mass * v) + stiffness_damping * (&stiffness * v)
+    };
+    let restrict = |full: &DVector<f64>| -> DVector<f64> {
+        let mut free = DVector::zeros(free_count);
+        for (dof, index) in free_index.iter().enumerate() {
+            if let Some(r) = index {
+                free[*r] = full[dof];
+            }
+        }
+        free
+    };
+
+    let steps = (options.duration / dt).round() as usize;
+    let output_every = ((options.output_interval / dt).round() as usize).max(1);
+    info!(
+        "integrating {steps} time steps of {dt:.3e} s with {}, {free_count} free degrees of freedom",
+        match options.integrator {
+            Integrator::Newmark { .. } => "Newmark",
+            Integrator::Hht { .. } => "HHT",
+        }
+    );
+
+    // Start at rest, with the accelerations that balance the initial loads
+    let mut displacements = DVector::from_iterator(
+        dof_count,
+        (0..dof_count).map(|dof| displacement_at(dof, 0.0).unwrap_or(0.0)),
+    );
+    let mut velocities = DVector::<f64>::zeros(dof_count);
+    let mut accelerations = DVector::<f64>::zeros(dof_count);
+    let mut forces = forces_at(0.0);
+    let free_accelerations = free_mass.solve(&restrict(&(&forces - &stiffness * &displacements)));
+    for (dof, index) in free_index.iter().enumerate() {
+        if let Some(r) = index {
+            accelerations[dof] = free_accelerations[(*r, 0)];
+        }
+    }
+
+    let materials = vec![model_metadata.material.clone(); elements.len()];
+    let frame = |time: f64, u: &DVector<f64>, v: &DVector<f64>, a: &DVector<f64>| {
+        // Reactions balance the inertial, damping and elastic forces at
+        // constrained degrees of freedom
+        let internal = &mass * a + damping_times(v) + &stiffness * u;
+        let applied = forces_at(time);
+        let mut frame_nodes = nodes.to_vec();
+        for (i, node) in frame_nodes.iter_mut().enumerate() {
+            let force = |dof: usize| match loads[dof].displacement {
+                Some(_) => internal[dof],
+                None => applied[dof],
+            };
+            node.ux = Some(u[DOF * i]);
+            node.uy = Some(u[DOF * i + 1]);
+            node.fx = Some(force(DOF * i));
+            node.fy = Some(force(DOF * i + 1));
+        }
+        let mut frame_elements = elements.clone();
+        compute_stress(&mut frame_elements, &frame_nodes, &materials);
+        TransientFrame {
+            time,
+            results: collect_results(frame_nodes, frame_elements, &materials, model_metadata),
+        }
+    };
+
+    let mut frames = vec![frame(0.0, &displacements, &velocities, &accelerations)];
+
+    for step in 1..=steps {
+        let time = step as f64 * dt;
+        let next_forces = forces_at(time);
+
+        // Newmark predictors, from the last step alone
+        let predicted_u =
+            &displacements + dt * &velocities + (dt * dt * (0.5 - beta)) * &accelerations;
+        let predicted_v = &velocities + ((1.0 - gamma) * dt) * &accelerations;
+
+        let mut rhs = (1.0 + alpha) * &next_forces - alpha * &forces
+            + &mass * (a0 * &predicted_u)
+            + (1.0 + alpha) * damping_times(&(a1 * &predicted_u - &predicted_v))
+            + alpha * (damping_times(&velocities) + &stiffness * &displacements);
+
+        // Move the prescribed displacements to the right hand side
+        let mut next_u = DVector::<f64>::zeros(dof_count);
+        for dof in 0..dof_count {
+            if let Some(u) = displacement_at(dof, time) {
+                next_u[dof] = u;
+            }
+        }
+        rhs -= &effective * &next_u;
+
+        let free_u = free_effective.solve(&restrict(&rhs));
+        for (dof, index) in free_index.iter().enumerate() {
+            if let Some(r) = index {
+                next_u[dof] = free_u[(*r, 0)];
+            }
+        }
+
+        accelerations = a0 * (&next_u - &predicted_u);
+        velocities = predicted_v + (gamma * dt) * &accelerations;
+        displacements = next_u;
+        forces = next_forces;
+
+        if !displacements.iter().all(|u| u.is_finite()) {
+            return Err(MagnetiteError::Solver(format!(
+                "Transient solve diverged at t = {time:.4e} s; try a smaller time step"
+            )));
+        }
+
+        if step % output_every == 0 || step == steps {
+            frames.push(frame(time, &displacements, &velocities, &accelerations));
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Writes displacement and stress time histories to csv files, one row per
+/// node or element and output time
+///
+/// # Arguments
+/// * `frames` - The results at each output time
+/// * `units` - The units to write results in, if declared
+/// * `nodes_output` - The filepath of the displacement history
+/// * `elements_output` - The filepath of the stress history
+pub fn write_history(
+    frames: &[TransientFrame],
+    units: Option<&Units>,
+    nodes_output: &str,
+    elements_output: &str,
+) -> Result<(), MagnetiteError> {
+    let length = units.map_or(1.0, |u| u.length.factor());
+    let stress = units.map_or(1.0, |u| u.stress.factor());
+    let length_unit = units.map(|u| u.length.name());
+    let stress_unit = units.map(|u| u.stress.name());
+
+    let mut node_table = format!(
+        "time [s],node,{},{}\n",
+        label("ux", length_unit),
+        label("uy", length_unit)
+    );
+    let mut element_table = format!(
+        "time [s],element,{},{},{}\n",
+        label("stress", stress_unit),
+        label("von_mises", stress_unit),
+        label("tresca", stress_unit)
+    );
+    for frame in frames {
+        for (i, node) in frame.results.nodes.iter().enumerate() {
+            node_table += &format!(
+                "{},{i},{},{}\n",
+                frame.time,
+                node.ux / length,
+                node.uy / length
+            );
+        }
+        for (i, element) in frame.results.elements.iter().enumerate() {
+            element_table += &format!(
+                "{},{i},{},{},{}\n",
+                frame.time,
+                element.stress / stress,
+                element.von_mises / stress,
+                element.tresca / stress
+            );
+        }
+    }
+
+    for (output, table) in [(nodes_output, node_table), (elements_output, element_table)] {
+        if let Err(err) = std::fs::write(output, table) {
+            return Err(MagnetiteError::PostProcessor(format!(
+                "Failed to write {output}: {err}"
+            )));
+        }
+    }
+
+    info!(
+        "wrote {} output times to {nodes_output} and {elements_output}",
+        frames.len()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        datatypes::{Element, Material, MeshOptions, Vertex},
+        expression::Expression,
+        solver,
+    };
+
+    /// A 4 by 1 strip of eight elements, fixed at x = 0 with a downwards
+    /// force on the two nodes at x = 4
+    fn cantilever() -> (Mesh, Vec<Node>) {
+        let mut nodes = Vec::new();
+        for j in 0..=1 {
+            for i in 0..=4 {
+                nodes.push(Node {
+                    vertex: Vertex {
+                        x: i as f64,
+                        y: j as f64,
+                    },
+                    ux: None,
+                    uy: None,
+                    fx: Some(0.0),
+                    fy: Some(0.0),
+                });
+            }
+        }
+        let elements = (0..4)
+            .flat_map(|i| [[i, i + 1, i + 6], [i, i + 6, i + 5]])
+            .map(|nodes| Element {
+                nodes,
+                stress: None,
+                stress_tensor: None,
+                plastic_strain: None,
+                physical_tags: Vec::new(),
+            })
+            .collect();
+        let mesh = Mesh {
+            nodes: nodes.clone(),
+            elements,
+            physical_groups: Vec::new(),
+        };
+
+        for node in nodes.iter_mut() {
+            if node.vertex.x == 0.0 {
+                (node.ux, node.uy, node.fx, node.fy) = (Some(0.0), Some(0.0), None, None);
+            }
+            if node.vertex.x == 4.0 {
+                node.fy = Some(-0.1);
+            }
+        }
+        (mesh, nodes)
+    }
+
+    fn metadata() -> ModelMetadata {
+        ModelMetadata {
+            material: Material {
+                youngs_modulus: 1000.0,
+                poisson_ratio: 0.3,
+                density: Some(1.0),
+                thermal_expansion: None,
+                yield_strength: None,
+                ultimate_strength: None,
+            },
+            body_materials: Vec::new(),
+            part_thickness: 1.0,
+            mesh_options: MeshOptions {
+                characteristic_length_min: 0.0,
+                characteristic_length_max: 1.0,
+                ..Default::default()
+            },
+            units: None,
+            safety: Default::default(),
+        }
+    }
+
+    fn static_tip(mesh: &Mesh, nodes: &[Node]) -> f64 {
+        let mut nodes = nodes.to_vec();
+        let stiffness = solver::assemble_stiffness(
+            &nodes,
+            &mesh.elements,
+            &vec![metadata().material; mesh.elements.len()],
+            &metadata(),
+        );
+        solver::FactoredStiffness::new(&stiffness, &nodes)
+            .unwrap()
+            .solve(&mut nodes)
+            .unwrap();
+        nodes[4].uy.unwrap()
+    }
+
+    #[test]
+    fn step_load_overshoots_to_twice_the_static_displacement() {
+        let (mesh, nodes) = cantilever();
+        let options = TransientOptions {
+            duration: 4.0,
+            time_step: 0.005,
+            output_interval: 0.005,
+            integrator: Integrator::Newmark {
+                beta: 0.25,
+                gamma: 0.5,
+            },
+            mass_damping: 0.0,
+            stiffness_damping: 0.0,
+        };
+
+        let frames = run(
+            &mesh,
+            &nodes,
+            &vec![None; nodes.len()],
+            &metadata(),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(frames.len(), 801);
+        assert_eq!(frames[0].results.nodes[4].uy, 0.0);
+        let peak = frames
+            .iter()
+            .map(|f| f.results.nodes[4].uy)
+            .fold(0.0, f64::min);
+        let ratio = peak / static_tip(&mesh, &nodes);
+        assert!((ratio - 2.0).abs() < 0.05, "{ratio}");
+    }
+
+    #[test]
+    fn damped_ramp_settles_to_the_static_solution() {
+        let (mesh, mut nodes) = cantilever();
+        let ramp = TimeFunction::Table(vec![(0.0, 0.0), (1.0, 1.0)]);
+        let shake = TimeFunction::Expression(Expression::parse("0.01 * sin(t)").unwrap());
+        let mut time_functions: Vec<Option<&TimeFunction>> = vec![None; nodes.len()];
+        for (i, node) in nodes.iter().enumerate() {
+            if node.vertex.x == 4.0 {
+                time_functions[i] = Some(&ramp);
+            }
+        }
+        let options = TransientOptions {
+            duration: 30.0,
+            time_step: 0.05,
+            output_interval: 1.0,
+            integrator: Integrator::Hht { alpha: -0.1 },
+            mass_damping: 5.0,
+            stiffness_damping: 0.0,
+        };
+
+        let frames = run(&mesh, &nodes, &time_functions, &metadata(), &options).unwrap();
+        let last = &frames.last().unwrap().results;
+        assert_eq!(frames.len(), 31);
+        assert!((last.nodes[4].uy / static_tip(&mesh, &nodes) - 1.0).abs() < 1e-3);
+
+        // Reactions balance the applied load once the part is at rest
+        let reaction: f64 = [0, 5].iter().map(|i| last.nodes[*i].fy).sum();
+        assert!((reaction - 0.2).abs() < 1e-3, "{reaction}");
+
+        // Shake the fixed end sideways; prescribed displacements follow
+        // their time functions
+        (nodes[0].ux, nodes[5].ux) = (Some(1.0), Some(1.0));
+        time_functions[0] = Some(&shake);
+        time_functions[5] = Some(&shake);
+        let frames = run(&mesh, &nodes, &time_functions, &metadata(), &options).unwrap();
+        for frame in &frames {
+            let expected = 0.01 * frame.time.sin();
+            assert!((frame.results.nodes[5].ux - expected).abs() < 1e-12);
+        }
+    }
+}
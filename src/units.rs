@@ -0,0 +1,188 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Relative tolerance used when checking that units are consistent
+const CONSISTENCY_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum LengthUnit {
+    #[default]
+    #[serde(rename = "m")]
+    Meter,
+    #[serde(rename = "cm")]
+    Centimeter,
+    #[serde(rename = "mm")]
+    Millimeter,
+    #[serde(rename = "um")]
+    Micrometer,
+    #[serde(rename = "in")]
+    Inch,
+    #[serde(rename = "ft")]
+    Foot,
+}
+
+impl LengthUnit {
+    /// The number of meters in one of this unit
+    pub fn factor(&self) -> f64 {
+        match self {
+            LengthUnit::Meter => 1.0,
+            LengthUnit::Centimeter => 1e-2,
+            LengthUnit::Millimeter => 1e-3,
+            LengthUnit::Micrometer => 1e-6,
+            LengthUnit::Inch => 0.0254,
+            LengthUnit::Foot => 0.3048,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LengthUnit::Meter => "m",
+            LengthUnit::Centimeter => "cm",
+            LengthUnit::Millimeter => "mm",
+            LengthUnit::Micrometer => "um",
+            LengthUnit::Inch => "in",
+            LengthUnit::Foot => "ft",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum ForceUnit {
+    #[default]
+    #[serde(rename = "N")]
+    Newton,
+    #[serde(rename = "kN")]
+    Kilonewton,
+    #[serde(rename = "MN")]
+    Meganewton,
+    #[serde(rename = "lbf")]
+    PoundForce,
+    #[serde(rename = "kip")]
+    Kip,
+}
+
+impl ForceUnit {
+    /// The number of newtons in one of this unit
+    pub fn factor(&self) -> f64 {
+        match self {
+            ForceUnit::Newton => 1.0,
+            ForceUnit::Kilonewton => 1e3,
+            ForceUnit::Meganewton => 1e6,
+            ForceUnit::PoundForce => 4.448_221_615_260_5,
+            ForceUnit::Kip => 4_448.221_615_260_5,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForceUnit::Newton => "N",
+            ForceUnit::Kilonewton => "kN",
+            ForceUnit::Meganewton => "MN",
+            ForceUnit::PoundForce => "lbf",
+            ForceUnit::Kip => "kip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+pub enum StressUnit {
+    #[default]
+    #[serde(rename = "Pa")]
+    Pascal,
+    #[serde(rename = "kPa")]
+    Kilopascal,
+    #[serde(rename = "MPa")]
+    Megapascal,
+    #[serde(rename = "GPa")]
+    Gigapascal,
+    #[serde(rename = "psi")]
+    Psi,
+    #[serde(rename = "ksi")]
+    Ksi,
+}
+
+impl StressUnit {
+    /// The number of pascals in one of this unit
+    pub fn factor(&self) -> f64 {
+        match self {
+            StressUnit::Pascal => 1.0,
+            StressUnit::Kilopascal => 1e3,
+            StressUnit::Megapascal => 1e6,
+            StressUnit::Gigapascal => 1e9,
+            StressUnit::Psi => 6_894.757_293_168,
+            StressUnit::Ksi => 6_894_757.293_168,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StressUnit::Pascal => "Pa",
+            StressUnit::Kilopascal => "kPa",
+            StressUnit::Megapascal => "MPa",
+            StressUnit::Gigapascal => "GPa",
+            StressUnit::Psi => "psi",
+            StressUnit::Ksi => "ksi",
+        }
+    }
+}
+
+/// The units an input file is written in. Values are converted to SI (m, N,
+/// Pa) internally, and converted back when writing outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Units {
+    /// Unit of geometry, boundary regions, displacements, thickness and mesh
+    /// sizes
+    #[serde(default)]
+    pub length: LengthUnit,
+    /// Unit of forces
+    #[serde(default)]
+    pub force: ForceUnit,
+    /// Unit of material constants and stresses
+    #[serde(default)]
+    pub stress: StressUnit,
+}
+
+impl Units {
+    /// Returns a description of the mismatch if the stress unit is not the
+    /// force unit over the length unit squared, e.g. mm with Pa instead of
+    /// mm with MPa
+    pub fn inconsistency(&self) -> Option<String> {
+        let implied = self.force.factor() / self.length.factor().powi(2);
+        let relative_error = (self.stress.factor() - implied).abs() / implied;
+
+        if relative_error < CONSISTENCY_TOLERANCE {
+            return None;
+        }
+
+        let expected = [
+            StressUnit::Pascal,
+            StressUnit::Kilopascal,
+            StressUnit::Megapascal,
+            StressUnit::Gigapascal,
+            StressUnit::Psi,
+            StressUnit::Ksi,
+        ]
+        .into_iter()
+        .find(|unit| (unit.factor() - implied).abs() / implied < 1e-6)
+        .map_or(format!("{implied:e} Pa"), |unit| unit.name().to_string());
+
+        Some(format!(
+            "units mix {} and {} with stresses in {}; {} over {}^2 is {}",
+            self.length.name(),
+            self.force.name(),
+            self.stress.name(),
+            self.force.name(),
+            self.length.name(),
+            expected
+        ))
+    }
+}
+
+/// Appends a unit label to a name, e.g. `x [mm]`, if units are declared
+pub fn label(name: &str, unit: Option<&str>) -> String {
+    match unit {
+        Some(unit) => format!("{name} [{unit}]"),
+        None => name.to_string(),
+    }
+}